@@ -1,3 +1,14 @@
 C:/VulkanSDK/1.1.85.0/Bin32/glslangValidator.exe -V shader.vert
 c:/VulkanSDK/1.1.85.0/Bin32/glslangValidator.exe -V shader.frag
+C:/VulkanSDK/1.1.85.0/Bin32/glslangValidator.exe -V mesh.vert -o mesh.vert.spv
+C:/VulkanSDK/1.1.85.0/Bin32/glslangValidator.exe -V mesh.frag -o mesh.frag.spv
+C:/VulkanSDK/1.1.85.0/Bin32/glslangValidator.exe -V shadow.vert -o shadow.vert.spv
+C:/VulkanSDK/1.1.85.0/Bin32/glslangValidator.exe -V gbuffer.frag -o gbuffer.frag.spv
+C:/VulkanSDK/1.1.85.0/Bin32/glslangValidator.exe -V deferred_lighting.frag -o deferred_lighting.frag.spv
+C:/VulkanSDK/1.1.85.0/Bin32/glslangValidator.exe -V post/fullscreen.vert -o post/fullscreen.vert.spv
+C:/VulkanSDK/1.1.85.0/Bin32/glslangValidator.exe -V post/bloom_prefilter.frag -o post/bloom_prefilter.frag.spv
+C:/VulkanSDK/1.1.85.0/Bin32/glslangValidator.exe -V post/bloom_downsample.frag -o post/bloom_downsample.frag.spv
+C:/VulkanSDK/1.1.85.0/Bin32/glslangValidator.exe -V post/bloom_upsample.frag -o post/bloom_upsample.frag.spv
+C:/VulkanSDK/1.1.85.0/Bin32/glslangValidator.exe -V post/composite.frag -o post/composite.frag.spv
+C:/VulkanSDK/1.1.85.0/Bin32/glslangValidator.exe -V post/fxaa.frag -o post/fxaa.frag.spv
 pause
@@ -0,0 +1,15 @@
+#!/bin/sh
+# Rebuilds the SPIR-V next to each shader, run from this directory.
+set -e
+GLSLANG=${GLSLANG:-glslangValidator}
+$GLSLANG -V mesh.vert -o mesh.vert.spv
+$GLSLANG -V mesh.frag -o mesh.frag.spv
+$GLSLANG -V post/fullscreen.vert -o post/fullscreen.vert.spv
+$GLSLANG -V post/composite.frag -o post/composite.frag.spv
+$GLSLANG -V post/bloom_prefilter.frag -o post/bloom_prefilter.frag.spv
+$GLSLANG -V post/bloom_downsample.frag -o post/bloom_downsample.frag.spv
+$GLSLANG -V post/bloom_upsample.frag -o post/bloom_upsample.frag.spv
+$GLSLANG -V post/fxaa.frag -o post/fxaa.frag.spv
+$GLSLANG -V shadow.vert -o shadow.vert.spv
+$GLSLANG -V gbuffer.frag -o gbuffer.frag.spv
+$GLSLANG -V deferred_lighting.frag -o deferred_lighting.frag.spv
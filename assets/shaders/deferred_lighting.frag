@@ -0,0 +1,48 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : enable
+
+#include "lighting.glsl"
+
+// The G-buffer written by `gbuffer.frag`, matches
+// `deferred::gbuffer_set_layout`.
+layout(input_attachment_index = 0, set = 1, binding = 0) uniform subpassInput albedo;
+layout(input_attachment_index = 1, set = 1, binding = 1) uniform subpassInput normal;
+layout(input_attachment_index = 2, set = 1, binding = 2) uniform subpassInput material;
+layout(input_attachment_index = 3, set = 1, binding = 3) uniform subpassInput depth;
+
+// Matches `DeferredPushConstants` in src/engine/deferred.rs.
+layout(push_constant) uniform PushConstants {
+  // What the depth is cleared to, where nothing was drawn.
+  float clearDepth;
+} push;
+
+layout(location = 0) in vec2 fragUv;
+
+// Added to the emitted light already in the output.
+layout(location = 0) out vec4 outColor;
+
+void main() {
+  float fragDepth = subpassLoad(depth).r;
+  if (fragDepth == push.clearDepth) {
+    discard;
+  }
+
+  vec4 clip = vec4(fragUv * 2.0 - 1.0, fragDepth, 1.0);
+  vec4 world = camera.inverseViewProjection * clip;
+  vec3 position = world.xyz / world.w;
+
+  vec4 albedoOcclusion = subpassLoad(albedo);
+  vec2 metallicRoughness = subpassLoad(material).rg;
+
+  vec3 color = shade(
+    position,
+    normalize(subpassLoad(normal).xyz),
+    albedoOcclusion.rgb,
+    metallicRoughness.r,
+    metallicRoughness.g,
+    albedoOcclusion.a
+  );
+
+  outColor = vec4(color, 0.0);
+}
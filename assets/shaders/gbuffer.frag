@@ -0,0 +1,31 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : enable
+
+#include "material.glsl"
+
+layout(location = 0) in vec3 fragPosition;
+layout(location = 1) in vec3 fragNormal;
+layout(location = 2) in vec2 fragUv;
+
+// The lit output, which starts out with the emitted light and gets the
+// rest from the lighting subpass.
+layout(location = 0) out vec4 outColor;
+// Base color with the occlusion in alpha.
+layout(location = 1) out vec4 outAlbedo;
+// World space normal, `w` is unused.
+layout(location = 2) out vec4 outNormal;
+// Metallic and roughness.
+layout(location = 3) out vec4 outMaterial;
+
+void main() {
+  Surface surface = materialSurface(fragPosition, fragNormal, fragUv);
+  if (surface.baseColor.a < material.alphaCutoff) {
+    discard;
+  }
+
+  outColor = vec4(surface.emissive, 1.0);
+  outAlbedo = vec4(surface.baseColor.rgb, surface.occlusion);
+  outNormal = vec4(surface.normal, 0.0);
+  outMaterial = vec4(surface.metallic, surface.roughness, 0.0, 0.0);
+}
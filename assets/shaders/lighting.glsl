@@ -0,0 +1,190 @@
+// The per frame bindings and the lighting shared by the forward and the
+// deferred shaders, matches `pbr::frame_set_layout`.
+
+layout(set = 0, binding = 0) uniform Camera {
+  mat4 view;
+  mat4 projection;
+  mat4 viewProjection;
+  vec4 position;
+  mat4 inverseViewProjection;
+} camera;
+
+struct Light {
+  vec4 positionRange;
+  vec4 direction;
+  vec4 color;
+  vec2 spotScaleOffset;
+  uint kind;
+  int shadowLayer;
+};
+
+const uint LIGHT_DIRECTIONAL = 0;
+const uint LIGHT_POINT = 1;
+const uint LIGHT_SPOT = 2;
+
+layout(std430, set = 0, binding = 1) readonly buffer Lights {
+  uint count;
+  float ambientIntensity;
+  Light lights[];
+} lights;
+
+layout(set = 0, binding = 2) uniform samplerCube environment;
+
+const int CASCADES = 4;
+const int SHADOW_LAYERS = 8;
+
+layout(set = 0, binding = 3) uniform Shadows {
+  mat4 viewProjections[SHADOW_LAYERS];
+  vec4 cascadeSplits;
+  float texelSize;
+} shadows;
+
+layout(set = 0, binding = 4) uniform sampler2DArrayShadow shadowMaps;
+
+const float PI = 3.14159265359;
+
+float distributionGgx(float nDotH, float alpha) {
+  float alpha2 = alpha * alpha;
+  float d = nDotH * nDotH * (alpha2 - 1.0) + 1.0;
+  return alpha2 / (PI * d * d);
+}
+
+// Height correlated Smith, already divided by 4 n.l n.v.
+float visibilitySmithGgx(float nDotL, float nDotV, float alpha) {
+  float alpha2 = alpha * alpha;
+  float ggxV = nDotL * sqrt(nDotV * nDotV * (1.0 - alpha2) + alpha2);
+  float ggxL = nDotV * sqrt(nDotL * nDotL * (1.0 - alpha2) + alpha2);
+  return 0.5 / max(ggxV + ggxL, 1e-5);
+}
+
+vec3 fresnelSchlick(vec3 f0, float vDotH) {
+  return f0 + (1.0 - f0) * pow(1.0 - vDotH, 5.0);
+}
+
+// Karis' analytic fit of the split sum environment BRDF.
+vec3 environmentBrdf(vec3 f0, float roughness, float nDotV) {
+  const vec4 c0 = vec4(-1.0, -0.0275, -0.572, 0.022);
+  const vec4 c1 = vec4(1.0, 0.0425, 1.04, -0.04);
+  vec4 r = roughness * c0 + c1;
+  float a004 = min(r.x * r.x, exp2(-9.28 * nDotV)) * r.x + r.y;
+  vec2 scaleBias = vec2(-1.04, 1.04) * a004 + r.zw;
+  return f0 * scaleBias.x + scaleBias.y;
+}
+
+// Inverse square falloff, windowed to reach zero at the range when there is
+// one.
+float distanceAttenuation(float distance, float range) {
+  float attenuation = 1.0 / max(distance * distance, 1e-4);
+  if (range > 0.0) {
+    float ratio = distance / range;
+    float window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    attenuation *= window * window;
+  }
+  return attenuation;
+}
+
+// How much of the light reaches `position`, filtered over 3x3 shadow map
+// texels.
+float shadowFactor(Light light, vec3 position) {
+  if (light.shadowLayer < 0) {
+    return 1.0;
+  }
+
+  int layer = light.shadowLayer;
+  if (light.kind == LIGHT_DIRECTIONAL) {
+    float depth = -(camera.view * vec4(position, 1.0)).z;
+    if (depth > shadows.cascadeSplits[CASCADES - 1]) {
+      return 1.0;
+    }
+
+    int cascade = 0;
+    while (cascade < CASCADES - 1 && depth > shadows.cascadeSplits[cascade]) {
+      cascade++;
+    }
+    layer += cascade;
+  }
+
+  vec4 projected = shadows.viewProjections[layer] * vec4(position, 1.0);
+  if (projected.w <= 0.0) {
+    return 1.0;
+  }
+  projected.xyz /= projected.w;
+  vec2 uv = projected.xy * 0.5 + 0.5;
+
+  float lit = 0.0;
+  for (int y = -1; y <= 1; y++) {
+    for (int x = -1; x <= 1; x++) {
+      vec2 offset = vec2(x, y) * shadows.texelSize;
+      lit += texture(shadowMaps, vec4(uv + offset, float(layer), projected.z));
+    }
+  }
+  return lit / 9.0;
+}
+
+// Light reflected towards the camera from `position` by every light and
+// the environment.
+vec3 shade(
+  vec3 position,
+  vec3 normal,
+  vec3 baseColor,
+  float metallic,
+  float roughness,
+  float occlusion
+) {
+  float alpha = roughness * roughness;
+  vec3 view = normalize(camera.position.xyz - position);
+  float nDotV = max(dot(normal, view), 1e-4);
+
+  vec3 f0 = mix(vec3(0.04), baseColor, metallic);
+  vec3 diffuseColor = baseColor * (1.0 - metallic);
+
+  vec3 color = vec3(0.0);
+  for (uint i = 0; i < lights.count; i++) {
+    Light light = lights.lights[i];
+
+    vec3 toLight;
+    float attenuation = 1.0;
+    if (light.kind == LIGHT_DIRECTIONAL) {
+      toLight = -light.direction.xyz;
+    } else {
+      vec3 offset = light.positionRange.xyz - position;
+      float distance = length(offset);
+      toLight = offset / max(distance, 1e-4);
+      attenuation = distanceAttenuation(distance, light.positionRange.w);
+
+      if (light.kind == LIGHT_SPOT) {
+        float cd = dot(light.direction.xyz, -toLight);
+        float cone = clamp(cd * light.spotScaleOffset.x + light.spotScaleOffset.y, 0.0, 1.0);
+        attenuation *= cone * cone;
+      }
+    }
+
+    float nDotL = dot(normal, toLight);
+    if (nDotL <= 0.0 || attenuation <= 0.0) {
+      continue;
+    }
+
+    attenuation *= shadowFactor(light, position);
+
+    vec3 halfway = normalize(toLight + view);
+    float nDotH = max(dot(normal, halfway), 0.0);
+    float vDotH = max(dot(view, halfway), 0.0);
+
+    vec3 fresnel = fresnelSchlick(f0, vDotH);
+    vec3 specular = fresnel * distributionGgx(nDotH, alpha)
+      * visibilitySmithGgx(nDotL, nDotV, alpha);
+    vec3 diffuse = (1.0 - fresnel) * diffuseColor / PI;
+
+    color += (diffuse + specular) * light.color.rgb * attenuation * nDotL;
+  }
+
+  // Smaller mip levels of the environment stand in for blurrier lighting,
+  // the smallest one for the irradiance.
+  float lastLevel = float(textureQueryLevels(environment) - 1);
+  vec3 irradiance = textureLod(environment, normal, lastLevel).rgb;
+  vec3 reflected = reflect(-view, normal);
+  vec3 radiance = textureLod(environment, reflected, roughness * lastLevel).rgb;
+  vec3 ambient = diffuseColor * irradiance + environmentBrdf(f0, roughness, nDotV) * radiance;
+  color += ambient * lights.ambientIntensity * occlusion;
+  return color;
+}
@@ -0,0 +1,67 @@
+// The material bindings and how surfaces are read from them, matches
+// `pbr::material_set_layout`.
+
+layout(set = 1, binding = 0) uniform Material {
+  vec4 baseColorFactor;
+  vec4 emissiveFactor;
+  float metallicFactor;
+  float roughnessFactor;
+  float alphaCutoff;
+  uint doubleSided;
+} material;
+
+layout(set = 1, binding = 1) uniform sampler2D baseColorTexture;
+layout(set = 1, binding = 2) uniform sampler2D metallicRoughnessTexture;
+layout(set = 1, binding = 3) uniform sampler2D normalTexture;
+layout(set = 1, binding = 4) uniform sampler2D occlusionTexture;
+layout(set = 1, binding = 5) uniform sampler2D emissiveTexture;
+
+struct Surface {
+  vec4 baseColor;
+  vec3 normal;
+  float metallic;
+  float roughness;
+  float occlusion;
+  vec3 emissive;
+};
+
+// Tangent frame from screen space derivatives, as meshes carry no tangents.
+vec3 perturbNormal(vec3 normal, vec3 position, vec2 uv) {
+  vec3 dp1 = dFdx(position);
+  vec3 dp2 = dFdy(position);
+  vec2 duv1 = dFdx(uv);
+  vec2 duv2 = dFdy(uv);
+
+  vec3 dp2perp = cross(dp2, normal);
+  vec3 dp1perp = cross(normal, dp1);
+  vec3 tangent = dp2perp * duv1.x + dp1perp * duv2.x;
+  vec3 bitangent = dp2perp * duv1.y + dp1perp * duv2.y;
+  float invmax = inversesqrt(max(dot(tangent, tangent), dot(bitangent, bitangent)));
+  if (isinf(invmax) || isnan(invmax)) {
+    return normal;
+  }
+
+  vec3 mapped = texture(normalTexture, uv).xyz * 2.0 - 1.0;
+  return normalize(mat3(tangent * invmax, bitangent * invmax, normal) * mapped);
+}
+
+// The material at `uv` of a fragment at `position` with the interpolated
+// vertex `normal`.
+Surface materialSurface(vec3 position, vec3 normal, vec2 uv) {
+  Surface surface;
+  surface.baseColor = material.baseColorFactor * texture(baseColorTexture, uv);
+
+  vec4 metallicRoughness = texture(metallicRoughnessTexture, uv);
+  surface.metallic = clamp(material.metallicFactor * metallicRoughness.b, 0.0, 1.0);
+  surface.roughness = clamp(material.roughnessFactor * metallicRoughness.g, 0.04, 1.0);
+
+  normal = normalize(normal);
+  if (material.doubleSided != 0 && !gl_FrontFacing) {
+    normal = -normal;
+  }
+  surface.normal = perturbNormal(normal, position, uv);
+
+  surface.occlusion = texture(occlusionTexture, uv).r;
+  surface.emissive = material.emissiveFactor.rgb * texture(emissiveTexture, uv).rgb;
+  return surface;
+}
@@ -0,0 +1,31 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : enable
+
+#include "lighting.glsl"
+#include "material.glsl"
+
+layout(location = 0) in vec3 fragPosition;
+layout(location = 1) in vec3 fragNormal;
+layout(location = 2) in vec2 fragUv;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+  Surface surface = materialSurface(fragPosition, fragNormal, fragUv);
+  if (surface.baseColor.a < material.alphaCutoff) {
+    discard;
+  }
+
+  vec3 color = shade(
+    fragPosition,
+    surface.normal,
+    surface.baseColor.rgb,
+    surface.metallic,
+    surface.roughness,
+    surface.occlusion
+  );
+  color += surface.emissive;
+
+  outColor = vec4(color, surface.baseColor.a);
+}
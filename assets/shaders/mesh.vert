@@ -0,0 +1,33 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform Camera {
+  mat4 view;
+  mat4 projection;
+  mat4 viewProjection;
+  vec4 position;
+} camera;
+
+layout(push_constant) uniform PushConstants {
+  mat4 transform;
+} push;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+layout(location = 2) in vec2 inUv;
+
+out gl_PerVertex {
+  vec4 gl_Position;
+};
+
+layout(location = 0) out vec3 fragPosition;
+layout(location = 1) out vec3 fragNormal;
+layout(location = 2) out vec2 fragUv;
+
+void main() {
+  vec4 position = push.transform * vec4(inPosition, 1.0);
+  gl_Position = camera.viewProjection * position;
+  fragPosition = position.xyz;
+  fragNormal = mat3(push.transform) * inNormal;
+  fragUv = inUv;
+}
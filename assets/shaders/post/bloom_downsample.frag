@@ -0,0 +1,23 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : enable
+
+#include "constants.glsl"
+
+// The level above, twice the size of this one
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(location = 0) in vec2 fragUv;
+
+layout(location = 0) out vec4 outColor;
+
+// Four bilinear taps averaging a 4x4 block of the level above.
+void main() {
+  vec2 offset = post.texelSize;
+  vec3 color = texture(source, fragUv + vec2(-offset.x, -offset.y)).rgb
+    + texture(source, fragUv + vec2(offset.x, -offset.y)).rgb
+    + texture(source, fragUv + vec2(-offset.x, offset.y)).rgb
+    + texture(source, fragUv + vec2(offset.x, offset.y)).rgb;
+
+  outColor = vec4(color * 0.25, 1.0);
+}
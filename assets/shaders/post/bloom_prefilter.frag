@@ -0,0 +1,25 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : enable
+
+#include "constants.glsl"
+
+// The scene
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(location = 0) in vec2 fragUv;
+
+layout(location = 0) out vec4 outColor;
+
+// Keep what is brighter than the threshold, with a soft knee so the bloom
+// fades in instead of popping.
+void main() {
+  vec3 color = texture(source, fragUv).rgb * post.exposure;
+  float brightness = max(color.r, max(color.g, color.b));
+  float knee = post.bloomThreshold * 0.5;
+  float soft = clamp(brightness - post.bloomThreshold + knee, 0.0, 2.0 * knee);
+  soft = soft * soft / (4.0 * knee + 0.00001);
+  float contribution = max(soft, brightness - post.bloomThreshold) / max(brightness, 0.00001);
+
+  outColor = vec4(color * contribution, 1.0);
+}
@@ -0,0 +1,30 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : enable
+
+#include "constants.glsl"
+
+// The upsampled level below, half the size of this one
+layout(set = 0, binding = 0) uniform sampler2D source;
+// The downsampled level of the same size
+layout(set = 0, binding = 1) uniform sampler2D downsampled;
+
+layout(location = 0) in vec2 fragUv;
+
+layout(location = 0) out vec4 outColor;
+
+// A 3x3 tent filter over the level below, added to this level.
+void main() {
+  vec2 offset = post.texelSize;
+  vec3 color = texture(source, fragUv).rgb * 4.0
+    + (texture(source, fragUv + vec2(-offset.x, 0.0)).rgb
+      + texture(source, fragUv + vec2(offset.x, 0.0)).rgb
+      + texture(source, fragUv + vec2(0.0, -offset.y)).rgb
+      + texture(source, fragUv + vec2(0.0, offset.y)).rgb) * 2.0
+    + texture(source, fragUv + vec2(-offset.x, -offset.y)).rgb
+    + texture(source, fragUv + vec2(offset.x, -offset.y)).rgb
+    + texture(source, fragUv + vec2(-offset.x, offset.y)).rgb
+    + texture(source, fragUv + vec2(offset.x, offset.y)).rgb;
+
+  outColor = vec4(color / 16.0 + texture(downsampled, fragUv).rgb, 1.0);
+}
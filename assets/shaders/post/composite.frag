@@ -0,0 +1,49 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : enable
+
+#include "constants.glsl"
+
+// The scene
+layout(set = 0, binding = 0) uniform sampler2D scene;
+// The top of the upsampled bloom chain
+layout(set = 0, binding = 1) uniform sampler2D bloom;
+
+layout(location = 0) in vec2 fragUv;
+
+layout(location = 0) out vec4 outColor;
+
+vec3 reinhard(vec3 color) {
+  return color / (color + vec3(1.0));
+}
+
+// Narkowicz's fit of the ACES filmic curve
+vec3 aces(vec3 color) {
+  const float a = 2.51;
+  const float b = 0.03;
+  const float c = 2.43;
+  const float d = 0.59;
+  const float e = 0.14;
+  return clamp((color * (a * color + b)) / (color * (c * color + d) + e), 0.0, 1.0);
+}
+
+void main() {
+  vec3 color = texture(scene, fragUv).rgb * post.exposure;
+  color += texture(bloom, fragUv).rgb * post.bloomIntensity;
+
+  if (post.tonemap == 1) {
+    color = reinhard(color);
+  } else if (post.tonemap == 2) {
+    color = aces(color);
+  }
+
+  vec2 centered = fragUv - vec2(0.5);
+  float falloff = smoothstep(post.vignetteRadius, post.vignetteRadius - 0.5, length(centered));
+  color *= mix(1.0, falloff, post.vignetteStrength);
+
+  color = pow(clamp(color, 0.0, 1.0), vec3(post.inverseGamma));
+
+  // FXAA finds edges by the luma in alpha
+  float luma = dot(color, vec3(0.299, 0.587, 0.114));
+  outColor = vec4(color, luma);
+}
@@ -0,0 +1,17 @@
+// Push constants shared by every post-processing pass, matches
+// `PostConstants` in src/engine/post.rs.
+layout(push_constant) uniform PostConstants {
+  // Size of a texel of the input at binding 0
+  vec2 texelSize;
+  float bloomThreshold;
+  // 0 with bloom turned off
+  float bloomIntensity;
+  float exposure;
+  // 0 turns tonemapping off, 1 is Reinhard, 2 ACES
+  uint tonemap;
+  // 0 with the vignette turned off
+  float vignetteStrength;
+  float vignetteRadius;
+  // 1 with gamma correction turned off
+  float inverseGamma;
+} post;
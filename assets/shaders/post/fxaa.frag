@@ -0,0 +1,47 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : enable
+
+#include "constants.glsl"
+
+// Tonemapped color with luma in alpha
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(location = 0) in vec2 fragUv;
+
+layout(location = 0) out vec4 outColor;
+
+const float SPAN_MAX = 8.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float REDUCE_MIN = 1.0 / 128.0;
+
+// The FXAA 2 console variant, blurring along the edge through the pixel.
+void main() {
+  vec2 texel = post.texelSize;
+  float lumaNw = texture(source, fragUv + vec2(-1.0, -1.0) * texel).a;
+  float lumaNe = texture(source, fragUv + vec2(1.0, -1.0) * texel).a;
+  float lumaSw = texture(source, fragUv + vec2(-1.0, 1.0) * texel).a;
+  float lumaSe = texture(source, fragUv + vec2(1.0, 1.0) * texel).a;
+  vec4 center = texture(source, fragUv);
+  float lumaM = center.a;
+
+  float lumaMin = min(lumaM, min(min(lumaNw, lumaNe), min(lumaSw, lumaSe)));
+  float lumaMax = max(lumaM, max(max(lumaNw, lumaNe), max(lumaSw, lumaSe)));
+
+  vec2 direction = vec2(
+    -((lumaNw + lumaNe) - (lumaSw + lumaSe)),
+    (lumaNw + lumaSw) - (lumaNe + lumaSe));
+  float reduce = max((lumaNw + lumaNe + lumaSw + lumaSe) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+  float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+  direction = clamp(direction * scale, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * texel;
+
+  vec3 colorA = 0.5 * (
+    texture(source, fragUv + direction * (1.0 / 3.0 - 0.5)).rgb
+    + texture(source, fragUv + direction * (2.0 / 3.0 - 0.5)).rgb);
+  vec3 colorB = colorA * 0.5 + 0.25 * (
+    texture(source, fragUv - direction * 0.5).rgb
+    + texture(source, fragUv + direction * 0.5).rgb);
+
+  float lumaB = dot(colorB, vec3(0.299, 0.587, 0.114));
+  outColor = vec4((lumaB < lumaMin || lumaB > lumaMax) ? colorA : colorB, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(push_constant) uniform PushConstants {
+  mat4 viewProjection;
+  mat4 transform;
+} push;
+
+layout(location = 0) in vec3 inPosition;
+
+out gl_PerVertex {
+  vec4 gl_Position;
+};
+
+void main() {
+  gl_Position = push.viewProjection * push.transform * vec4(inPosition, 1.0);
+}
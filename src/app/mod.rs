@@ -0,0 +1,295 @@
+mod runner;
+mod target;
+pub mod window;
+
+use ash::version::{DeviceV1_0, V1_0};
+use ash::vk;
+use ash::{Device, Entry, Instance};
+use std::fmt;
+use winit::WindowEvent;
+
+use config::{Config, ConfigError, WindowConfig};
+use engine::command_pool::CommandPool;
+use engine::pipeline::{RenderPass, Viewport};
+use engine::surface::SurfaceFormat;
+use engine::upload::Uploader;
+use input::bindings::Bindings;
+use input::Input;
+use time::Time;
+
+use self::runner::Runner;
+
+pub use self::target::WindowTarget;
+
+/// Something to run on the engine.
+///
+/// The runner owns the windows and all of the Vulkan setup, an app only
+/// creates what it draws with. Every method gets the `Context` holding the
+/// device and the main render pass to create those resources for.
+///
+/// ```text
+/// init
+/// loop {
+///     on_event for every window event
+///     update zero or more times, on a fixed timestep
+///     render_offscreen, then render for every window
+/// }
+/// shutdown
+/// ```
+pub trait App {
+    /// Called once before the first frame, after the device and the main
+    /// render pass have been created.
+    fn init(&mut self, ctx: &mut Context);
+
+    /// Advance the simulation by `time.fixed_delta` seconds.
+    ///
+    /// `input` covers everything that happened since the previous update,
+    /// later updates of the same frame see no presses or cursor movement.
+    fn update(&mut self, ctx: &mut Context, input: &Input, time: &Time);
+
+    /// Record the frame's draws into the window `frame.target`. The main
+    /// render pass has been begun on the frame's command buffer and is ended
+    /// once this returns.
+    ///
+    /// Called once per open window, every window is submitted before the
+    /// next one is recorded, so per frame resources have to be kept per
+    /// window as well.
+    fn render(&mut self, ctx: &Context, frame: &Frame);
+
+    /// Record the passes `render` depends on into the frame's command
+    /// buffer, like rendering into a `RenderTarget` that `render` samples.
+    /// Called for every window right before its main render pass begins.
+    fn render_offscreen(&mut self, _ctx: &Context, _frame: &Frame) {}
+
+    /// Called for every event of the window `target`, after the input state
+    /// saw it.
+    fn on_event(&mut self, _ctx: &mut Context, _target: TargetId, _event: &WindowEvent) {}
+
+    /// Called once after the last frame, when the device is idle. Everything
+    /// created in `init` has to be destroyed here.
+    fn shutdown(&mut self, ctx: &mut Context);
+}
+
+/// How the runner sets up the window and the main loop.
+pub struct Settings {
+    /// Window, device and swapchain setup.
+    pub config: Config,
+    /// Rate of the fixed timestep updates.
+    pub updates_per_second: u32,
+    /// Upper limit on rendered frames per second, `None` renders as fast as
+    /// the present mode allows.
+    pub frame_cap: Option<u32>,
+    pub bindings: Bindings,
+}
+
+impl Settings {
+    pub fn new(title: &str) -> Self {
+        let mut settings = Settings::default();
+        settings.config.window.title = title.to_owned();
+        settings
+    }
+
+    /// Replaces the title and size set with `new` and `with_size`.
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.config.window.width = width;
+        self.config.window.height = height;
+        self
+    }
+
+    pub fn with_updates_per_second(mut self, updates_per_second: u32) -> Self {
+        self.updates_per_second = updates_per_second;
+        self
+    }
+
+    pub fn with_frame_cap(mut self, frame_cap: Option<u32>) -> Self {
+        self.frame_cap = frame_cap;
+        self
+    }
+
+    pub fn with_bindings(mut self, bindings: Bindings) -> Self {
+        self.bindings = bindings;
+        self
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            config: Config::default(),
+            updates_per_second: 60,
+            frame_cap: None,
+            bindings: Bindings::new(),
+        }
+    }
+}
+
+/// Identifies a window for as long as it is open.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TargetId(usize);
+
+impl TargetId {
+    /// The window opened with the `Settings`, closing it ends the main loop.
+    pub const MAIN: TargetId = TargetId(0);
+}
+
+/// The Vulkan objects shared between the runner and the app.
+pub struct Context {
+    pub entry: Entry<V1_0>,
+    pub instance: Instance<V1_0>,
+    pub device: Device<V1_0>,
+    pub pdevice: vk::PhysicalDevice,
+    pub memory_properties: vk::PhysicalDeviceMemoryProperties,
+    pub device_properties: vk::PhysicalDeviceProperties,
+    /// Used for graphics and presentation, and for uploads without a
+    /// transfer queue.
+    pub queue: vk::Queue,
+    pub queue_family_index: u32,
+    /// For uploads, every window records its frames into its own pool.
+    pub command_pool: CommandPool,
+    /// Batches uploads onto the transfer queue, what is recorded during
+    /// `App::update` is submitted before the frame is drawn.
+    pub uploader: Uploader,
+    /// The swapchain format of every window, render targets resolved or
+    /// blitted into a swapchain have to match it.
+    pub surface_format: SurfaceFormat,
+    /// Renders into the swapchain image, with a depth attachment following
+    /// the color attachment. Pipelines for it have to use `samples`.
+    pub render_pass: RenderPass,
+    pub samples: vk::SampleCountFlags,
+    /// Frames that can be recorded while the device is still working on
+    /// earlier ones. Per frame resources are indexed by
+    /// `Frame::frame_index`.
+    pub frames_in_flight: usize,
+    /// The open windows, the main one first.
+    pub windows: Vec<WindowTarget>,
+    /// The color attachment is cleared to this at the start of every frame.
+    pub clear_color: [f32; 4],
+    /// The depth attachment is cleared to this at the start of every frame,
+    /// 0 for reversed depth.
+    pub clear_depth: f32,
+    requested_windows: Vec<(TargetId, WindowConfig)>,
+    closing_windows: Vec<TargetId>,
+    next_target_id: usize,
+    exit_requested: bool,
+}
+
+impl Context {
+    /// Stop the main loop after the current frame.
+    pub fn exit(&mut self) {
+        self.exit_requested = true;
+    }
+
+    /// Open another window before the next frame, presenting with the same
+    /// device and render pass as the main window.
+    ///
+    /// `window(id)` is `None` until the window has been opened.
+    pub fn open_window(&mut self, config: WindowConfig) -> TargetId {
+        let id = TargetId(self.next_target_id);
+        self.next_target_id += 1;
+        self.requested_windows.push((id, config));
+        id
+    }
+
+    /// Close the window `id` before the next frame, closing the main window
+    /// exits.
+    pub fn close_window(&mut self, id: TargetId) {
+        if id == TargetId::MAIN {
+            self.exit();
+        } else {
+            self.requested_windows.retain(|&(requested, _)| requested != id);
+            self.closing_windows.push(id);
+        }
+    }
+
+    pub fn window(&self, id: TargetId) -> Option<&WindowTarget> {
+        self.windows.iter().find(|target| target.id == id)
+    }
+
+    pub fn window_mut(&mut self, id: TargetId) -> Option<&mut WindowTarget> {
+        self.windows.iter_mut().find(|target| target.id == id)
+    }
+
+    pub fn main_window(&self) -> &WindowTarget {
+        &self.windows[0]
+    }
+
+    pub fn main_window_mut(&mut self) -> &mut WindowTarget {
+        &mut self.windows[0]
+    }
+
+    /// Aspect ratio of the main window.
+    pub fn aspect_ratio(&self) -> f32 {
+        self.main_window().aspect_ratio()
+    }
+}
+
+/// The frame being recorded.
+pub struct Frame {
+    /// In the recording state, inside the main render pass during
+    /// `App::render` and outside of any during `App::render_offscreen`.
+    pub command_buffer: vk::CommandBuffer,
+    /// The window rendered to.
+    pub target: TargetId,
+    /// Which of the `Context::frames_in_flight` frames this is.
+    pub frame_index: usize,
+    /// The swapchain image of the window rendered to.
+    pub image_index: u32,
+    pub extent: vk::Extent2D,
+    pub time: Time,
+}
+
+impl Frame {
+    pub fn aspect_ratio(&self) -> f32 {
+        self.extent.width as f32 / self.extent.height as f32
+    }
+
+    /// Cover the whole swapchain image, for pipelines with a dynamic viewport
+    /// and scissor.
+    pub fn set_viewport(&self, device: &Device<V1_0>) {
+        let viewport = Viewport::new(self.extent);
+
+        unsafe {
+            device.cmd_set_viewport(self.command_buffer, 0, &[viewport.viewport]);
+            device.cmd_set_scissor(self.command_buffer, 0, &[viewport.scissor]);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RunError {
+    /// The device can not handle the settings.
+    Config(ConfigError),
+    Vulkan(vk::Result),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RunError::Config(ref error) => write!(f, "unsupported configuration, {}", error),
+            RunError::Vulkan(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Open a window and run `app` until the window is closed or the app asks
+/// to exit.
+pub fn run<A: App>(settings: Settings, mut app: A) -> Result<(), RunError> {
+    let mut runner = Runner::new(&settings).map_err(RunError::Config)?;
+
+    app.init(&mut runner.context);
+    runner.main_loop(&mut app);
+
+    runner
+        .context
+        .device
+        .device_wait_idle()
+        .map_err(RunError::Vulkan)?;
+    app.shutdown(&mut runner.context);
+
+    Ok(())
+}
@@ -0,0 +1,707 @@
+use ash::version::{DeviceV1_0, EntryV1_0, InstanceV1_0, V1_0};
+use ash::vk;
+use ash::{Entry, Instance};
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::mem;
+use std::ptr;
+use winit::{Event, EventsLoop, WindowEvent};
+
+use config::{Config, ConfigError};
+use input::Input;
+use time::FrameTimer;
+
+use engine::depth;
+use engine::device::{find_transfer_queue_family, Device as EngineDevice};
+use engine::full_screen_exclusive;
+use engine::multisample;
+use engine::upload::{self, Uploader};
+use engine::{command_pool, instance, pipeline, surface, swapchain};
+
+use super::target::{Presenter, WindowTarget};
+use super::window::{self, WindowMode};
+use super::{App, Context, Settings, TargetId};
+
+#[cfg(target_os = "windows")]
+use ash::extensions::Win32Surface;
+
+#[cfg(target_os = "macos")]
+use ash::extensions::MacOSSurface;
+
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+use ash::extensions::XlibSurface;
+
+use ash::extensions::{DebugReport, Surface, Swapchain};
+
+/// Owns the windows, the device and everything else needed to get frames
+/// on screen, and drives an `App` from the main loop.
+pub struct Runner {
+    events_loop: EventsLoop,
+    pub context: Context,
+    /// Only set up with validation turned on.
+    debug_report: Option<(DebugReport, vk::DebugReportCallbackEXT)>,
+    presenter: Presenter,
+    input: Input,
+    timer: FrameTimer,
+}
+
+impl Runner {
+    /// Fails when the device does not support the configured settings.
+    pub fn new(settings: &Settings) -> Result<Self, ConfigError> {
+        let config = &settings.config;
+
+        // Create the main window and get the event_loop
+        let events_loop = EventsLoop::new();
+        let window = window::create_window(&events_loop, &config.window)?;
+
+        // Create new entry
+        let entry = Entry::new().unwrap();
+
+        // Set of validation layers to request and load
+        let validation_layers = if config.debug.validation {
+            config
+                .debug
+                .layers
+                .iter()
+                .map(|layer| CString::new(layer.as_str()).expect("Invalid layer name"))
+                .collect::<Vec<_>>()
+        } else {
+            Vec::new()
+        };
+
+        // Check that we have access to the validation layers
+        if config.debug.validation
+            && !Runner::check_validation_layer_support(&entry, &validation_layers)
+        {
+            panic!(
+                "Requested validation layers {:?} not available, install them or set \
+                 debug.validation to false",
+                config.debug.layers
+            );
+        }
+
+        // Names
+        let app_name = CString::new(config.app.name.as_str()).expect("Invalid app name");
+        let engine_name =
+            CString::new(config.app.engine_name.as_str()).expect("Invalid engine name");
+
+        // Translate valiation layers to a value that can be passed
+        // to ash
+        let layer_names_raw: Vec<*const i8> = validation_layers
+            .iter()
+            .map(|raw_name| raw_name.as_ptr())
+            .collect();
+
+        // Load extensions from the sdk
+        let extensions = entry
+            .enumerate_instance_extension_properties()
+            .expect("unable to extract extensions");
+
+        // Print extensions
+        let extension_names_available = extensions
+            .iter()
+            .map(|extension| {
+                CString::new(
+                    extension
+                        .extension_name
+                        .iter()
+                        .map(|char| *char as u8)
+                        .filter(|char| *char != 0)
+                        .collect::<Vec<_>>(),
+                ).unwrap()
+            }).collect::<Vec<_>>();
+        for name in &extension_names_available {
+            println!("Extension: {:?}", name);
+        }
+
+        // Get requested extension names
+        let mut extension_names_raw = extension_names();
+        if config.debug.validation {
+            extension_names_raw.push(DebugReport::name().as_ptr());
+        }
+
+        // The HDR color spaces need an instance extension, without it only
+        // sRGB is left
+        let colorspace_extension = CString::new(surface::SWAPCHAIN_COLORSPACE_EXTENSION).unwrap();
+        let mut color_space = config.graphics.color_space;
+        if color_space.is_hdr() {
+            if extension_names_available.contains(&colorspace_extension) {
+                extension_names_raw.push(colorspace_extension.as_ptr());
+            } else {
+                println!(
+                    "{} not available, falling back to sRGB",
+                    surface::SWAPCHAIN_COLORSPACE_EXTENSION
+                );
+                color_space = surface::ColorSpacePreference::Srgb;
+            }
+        }
+
+        // Exclusive fullscreen needs a device extension, which in turn needs
+        // these instance extensions
+        let full_screen_exclusive_instance_extensions = full_screen_exclusive::INSTANCE_EXTENSIONS
+            .iter()
+            .map(|name| CString::new(*name).unwrap())
+            .collect::<Vec<_>>();
+        let full_screen_exclusive_available = full_screen_exclusive_instance_extensions
+            .iter()
+            .all(|name| extension_names_available.contains(name));
+        if full_screen_exclusive_available {
+            extension_names_raw.extend(
+                full_screen_exclusive_instance_extensions
+                    .iter()
+                    .map(|name| name.as_ptr()),
+            );
+        }
+
+        // Create a vulkan instance
+        let instance = instance::create_instance(
+            &entry,
+            app_name,
+            engine_name,
+            &layer_names_raw,
+            &extension_names_raw,
+        ).unwrap();
+
+        // Load the debug report and its callback
+        let debug_report = if config.debug.validation {
+            Some(Runner::setup_debug_callback(&entry, &instance))
+        } else {
+            None
+        };
+
+        // Create surface
+        let surface = surface::create_surface(&entry, &instance, &window).unwrap();
+        let surface_loader =
+            Surface::new(&entry, &instance).expect("Unable to load the Surface extension");
+
+        // Pick our suitable physical device, and its queue index
+        let (pdevice, queue_index) =
+            Runner::pick_physical_device(&instance, surface, &surface_loader, config);
+
+        let device_properties = instance.get_physical_device_properties(pdevice);
+        let samples = config.msaa_samples().unwrap();
+        let supported_samples = multisample::supported_sample_counts(&device_properties);
+        if !supported_samples.subset(samples) {
+            unsafe {
+                surface_loader.destroy_surface_khr(surface, None);
+                if let Some((ref debug_report_loader, debug_callback)) = debug_report {
+                    debug_report_loader.destroy_debug_report_callback_ext(debug_callback, None);
+                }
+                instance.destroy_instance(None);
+            }
+
+            return Err(ConfigError::Unsupported {
+                key: "graphics.msaa".to_owned(),
+                value: config.graphics.msaa.to_string(),
+                supported: multisample::sample_counts(supported_samples)
+                    .iter()
+                    .map(|samples| samples.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            });
+        }
+
+        // Device extensions besides the swapchain
+        let full_screen_exclusive_extension =
+            CString::new(full_screen_exclusive::EXTENSION).unwrap();
+        let full_screen_exclusive = full_screen_exclusive_available
+            && full_screen_exclusive::is_supported(&instance, pdevice);
+        let mut device_extension_names_raw = Vec::new();
+        if full_screen_exclusive {
+            device_extension_names_raw.push(full_screen_exclusive_extension.as_ptr());
+        } else if config.window.mode == WindowMode::Exclusive {
+            println!(
+                "{} not available, exclusive fullscreen is left to the driver",
+                full_screen_exclusive::EXTENSION
+            );
+        }
+
+        // A dedicated transfer queue lets uploads run alongside rendering
+        let transfer_queue_index = find_transfer_queue_family(&instance, pdevice);
+
+        // Create device, we will use this to interact with the physical device
+        //
+        // This connects the physical device to the instance, connecting all the
+        // validation layers and extensions.
+        let device = {
+            let device = EngineDevice::new(
+                &instance,
+                layer_names_raw,
+                device_extension_names_raw,
+                queue_index as u32,
+                transfer_queue_index,
+                pdevice,
+            ).unwrap();
+            device.device
+        };
+
+        // Create the presentation queue from the device, with the queue index
+        let present_queue = unsafe { device.get_device_queue(queue_index as u32, 0) };
+        let transfer_queue = transfer_queue_index.map(|family_index| upload::Queue {
+            queue: unsafe { device.get_device_queue(family_index, 0) },
+            family_index,
+        });
+
+        // Pick the swapchain format, the render pass and the image views all
+        // follow it
+        let surface_formats =
+            surface::surface_formats(&entry, &instance, pdevice, surface).unwrap();
+        let surface_format = surface::select_surface_format(&surface_formats, color_space)
+            .expect("Unable to find suitable surface format.");
+
+        let memory_properties = instance.get_physical_device_memory_properties(pdevice);
+
+        // Every app gets a depth attachment, pipelines that have no use for
+        // it can disable the depth test.
+        let depth_format =
+            depth::select_depth_format(&instance, pdevice).expect("No supported depth format");
+
+        let render_pass = pipeline::RenderPass::multisampled(
+            &device,
+            surface_format.format,
+            Some(depth_format),
+            samples,
+        );
+
+        // Only used for uploads, every window has its own pool for frames
+        let command_pool = command_pool::CommandPool::new(&device, 1, queue_index as u32);
+
+        let uploader = Uploader::new(
+            &device,
+            &memory_properties,
+            upload::Queue {
+                queue: present_queue,
+                family_index: queue_index as u32,
+            },
+            transfer_queue,
+            upload::DEFAULT_STAGING_SIZE,
+        ).expect("Unable to create the staging buffer");
+
+        let swapchain_loader =
+            Swapchain::new(&instance, &device).expect("Unable to load swapchain");
+
+        let create_swapchain = swapchain::load_create_swapchain(&entry, &instance)
+            .expect("Unable to load vkCreateSwapchainKHR");
+
+        let presenter = Presenter {
+            pdevice,
+            surface_loader,
+            swapchain_loader,
+            create_swapchain,
+            full_screen_exclusive,
+            image_count: config.graphics.image_count,
+        };
+
+        let mut context = Context {
+            entry,
+            instance,
+            device,
+            pdevice,
+            memory_properties,
+            device_properties,
+            queue: present_queue,
+            queue_family_index: queue_index as u32,
+            command_pool,
+            uploader,
+            surface_format,
+            render_pass,
+            samples,
+            frames_in_flight: config.graphics.frames_in_flight as usize,
+            windows: Vec::new(),
+            clear_color: [0.0, 0.0, 0.0, 0.0],
+            clear_depth: 1.0,
+            requested_windows: Vec::new(),
+            closing_windows: Vec::new(),
+            next_target_id: 1,
+            exit_requested: false,
+        };
+
+        let main_window = WindowTarget::new(
+            &context,
+            &presenter,
+            TargetId::MAIN,
+            &config.window,
+            window,
+            surface,
+            config.graphics.vsync,
+        );
+        context.windows.push(main_window);
+
+        Ok(Runner {
+            events_loop,
+            context,
+            debug_report,
+            presenter,
+            input: Input::new(settings.bindings.clone()),
+            timer: FrameTimer::new(settings.updates_per_second)
+                .with_frame_cap(settings.frame_cap),
+        })
+    }
+
+    pub fn main_loop<A: App>(&mut self, app: &mut A) {
+        let mut current_frame = 0;
+        let mut frames_this_second = 0;
+        let mut next_title_update = 1.0;
+
+        loop {
+            {
+                let context = &mut self.context;
+                let input = &mut self.input;
+
+                self.events_loop.poll_events(|event| {
+                    if let Event::WindowEvent { window_id, event } = event {
+                        let target = match context
+                            .windows
+                            .iter()
+                            .find(|target| target.window.id() == window_id)
+                        {
+                            Some(target) => target.id,
+                            None => return,
+                        };
+
+                        input.handle_event(&event);
+                        app.on_event(context, target, &event);
+
+                        if let WindowEvent::CloseRequested = event {
+                            context.close_window(target);
+                        }
+                        if let Some(target) = context.window_mut(target) {
+                            target.handle_event(&event);
+                        }
+                    }
+                });
+            }
+
+            if self.context.exit_requested {
+                break;
+            }
+
+            self.close_windows();
+            self.open_windows();
+
+            for target in self.context.windows.iter_mut() {
+                target.update_swapchain(
+                    &self.context.device,
+                    &self.context.memory_properties,
+                    &self.presenter,
+                );
+            }
+
+            let updates = self.timer.begin_frame();
+            let time = *self.timer.time();
+
+            self.input.poll_gamepads();
+            for _ in 0..updates {
+                app.update(&mut self.context, &self.input, &time);
+
+                // Presses and cursor movement only count for the first
+                // update, the rest of the frame's updates see none.
+                self.input.end_frame();
+            }
+
+            // Uploads recorded during the updates go out ahead of the frame
+            // that uses them.
+            self.context.uploader.flush(&self.context.device, &[]);
+
+            // Every window gets the same frame index, each of them has its
+            // own fences and semaphores for it.
+            current_frame = (current_frame + 1) % self.context.frames_in_flight;
+            let mut out_of_date = Vec::new();
+            for target in self.context.windows.iter() {
+                if !target.is_minimized()
+                    && !target.draw(&self.context, &self.presenter, app, current_frame, &time)
+                {
+                    out_of_date.push(target.id);
+                }
+            }
+            self.context.uploader.collect(&self.context.device);
+
+            for target in self.context.windows.iter_mut() {
+                if out_of_date.contains(&target.id) {
+                    target.recreate_swapchain(
+                        &self.context.device,
+                        &self.context.memory_properties,
+                        &self.presenter,
+                    );
+                }
+            }
+
+            frames_this_second += 1;
+            if time.elapsed >= next_title_update {
+                for target in self.context.windows.iter() {
+                    target.window.set_title(&format!(
+                        "{} - {} fps ({:?})",
+                        target.title, frames_this_second, target.present_mode
+                    ));
+                }
+                frames_this_second = 0;
+                next_title_update = time.elapsed.floor() + 1.0;
+            }
+
+            // Without a window to present to nothing paces the loop, so it
+            // only wakes up for the next update.
+            if self.context.windows.iter().all(|target| target.is_minimized()) {
+                self.timer.wait_for_update();
+            } else {
+                self.timer.end_frame();
+            }
+        }
+    }
+
+    /// Destroy the windows the app or the user closed.
+    fn close_windows(&mut self) {
+        let closing = mem::take(&mut self.context.closing_windows);
+        if closing.is_empty() {
+            return;
+        }
+
+        self.context.device.device_wait_idle().unwrap();
+
+        for id in closing {
+            if let Some(index) = self.context.windows.iter().position(|target| target.id == id) {
+                let target = self.context.windows.remove(index);
+                target.destroy(&self.context.device, &self.presenter);
+            }
+        }
+    }
+
+    /// Open the windows the app asked for, with the present policy of the
+    /// main window.
+    fn open_windows(&mut self) {
+        let requested = mem::take(&mut self.context.requested_windows);
+
+        for (id, config) in requested {
+            let window = window::create_window(&self.events_loop, &config).unwrap_or_else(|error| {
+                panic!("Unable to open window {:?}, {}", config.title, error)
+            });
+            let surface =
+                surface::create_surface(&self.context.entry, &self.context.instance, &window)
+                    .unwrap();
+
+            // Every window shares the queue and the render pass
+            let supports_present = self
+                .presenter
+                .surface_loader
+                .get_physical_device_surface_support_khr(
+                    self.context.pdevice,
+                    self.context.queue_family_index,
+                    surface,
+                );
+            if !supports_present {
+                panic!("Unable to present to window {:?} from the queue", config.title);
+            }
+
+            let formats = surface::surface_formats(
+                &self.context.entry,
+                &self.context.instance,
+                self.context.pdevice,
+                surface,
+            ).unwrap();
+            let surface_format = self.context.surface_format;
+            if !formats.iter().any(|format| {
+                *format == surface_format || format.format == vk::Format::Undefined
+            }) {
+                panic!(
+                    "Window {:?} does not support the surface format {:?} of the main window",
+                    config.title, surface_format
+                );
+            }
+
+            let present_policy = self.context.main_window().present_policy;
+            let target = WindowTarget::new(
+                &self.context,
+                &self.presenter,
+                id,
+                &config,
+                window,
+                surface,
+                present_policy,
+            );
+            self.context.windows.push(target);
+        }
+    }
+
+    /// The first device with a queue family that supports both graphics and
+    /// presenting to `surface`, out of the devices matching `graphics.gpu`.
+    fn pick_physical_device(
+        instance: &Instance<V1_0>,
+        surface: vk::SurfaceKHR,
+        surface_loader: &Surface,
+        config: &Config,
+    ) -> (vk::PhysicalDevice, usize) {
+        let pdevices = instance
+            .enumerate_physical_devices()
+            .expect("Unable to enumerate physical devices");
+
+        let names = pdevices
+            .iter()
+            .map(|&pdevice| {
+                let properties = instance.get_physical_device_properties(pdevice);
+                unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }
+                    .to_string_lossy()
+                    .into_owned()
+            }).collect::<Vec<_>>();
+
+        pdevices
+            .iter()
+            .zip(names.iter())
+            .enumerate()
+            .filter(|&(index, (_, name))| match config.graphics.gpu {
+                Some(ref gpu) => match gpu.parse::<usize>() {
+                    Ok(gpu_index) => gpu_index == index,
+                    Err(_) => name.to_lowercase().contains(&gpu.to_lowercase()),
+                },
+                None => true,
+            }).filter_map(|(_, (pdevice, _))| {
+                instance
+                    .get_physical_device_queue_family_properties(*pdevice)
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, info)| {
+                        let supports_graphic_and_surface =
+                            info.queue_flags.subset(vk::QUEUE_GRAPHICS_BIT) && surface_loader
+                                .get_physical_device_surface_support_khr(
+                                    *pdevice,
+                                    index as u32,
+                                    surface,
+                                );
+                        match supports_graphic_and_surface {
+                            true => Some((*pdevice, index)),
+                            _ => None,
+                        }
+                    }).nth(0)
+            }).nth(0)
+            .unwrap_or_else(|| match config.graphics.gpu {
+                Some(ref gpu) => panic!(
+                    "No suitable GPU matches graphics.gpu = {:?}, available GPUs: {:?}",
+                    gpu, names
+                ),
+                None => panic!("Couldn't find suitable device."),
+            })
+    }
+
+    fn check_validation_layer_support(
+        entry: &Entry<V1_0>,
+        validation_layers: &[CString],
+    ) -> bool {
+        let layers = entry
+            .enumerate_instance_layer_properties()
+            .expect("unable to fetch layer properties");
+
+        let mut layer_found = false;
+        for layer in layers {
+            let name = CString::new(
+                layer
+                    .layer_name
+                    .iter()
+                    .map(|char| *char as u8)
+                    .filter(|char| *char != 0)
+                    .collect::<Vec<_>>(),
+            ).unwrap();
+
+            println!("Layer: {:?}", name);
+
+            for validation_layer in validation_layers.iter() {
+                if validation_layer == &name {
+                    println!("Validation: Found matching validation layer: {:?}", name);
+                    layer_found = true
+                }
+            }
+        }
+
+        layer_found
+    }
+
+    fn setup_debug_callback(
+        entry: &Entry<V1_0>,
+        instance: &Instance<V1_0>,
+    ) -> (DebugReport, vk::DebugReportCallbackEXT) {
+        let debug_info = vk::DebugReportCallbackCreateInfoEXT {
+            s_type: vk::StructureType::DebugReportCallbackCreateInfoExt,
+            p_next: ptr::null(),
+            flags: vk::DEBUG_REPORT_ERROR_BIT_EXT
+                | vk::DEBUG_REPORT_WARNING_BIT_EXT
+                | vk::DEBUG_REPORT_PERFORMANCE_WARNING_BIT_EXT,
+            pfn_callback: vulkan_debug_callback,
+            p_user_data: ptr::null_mut(),
+        };
+
+        let debug_report_loader =
+            DebugReport::new(entry, instance).expect("Unable to load debug report");
+
+        let debug_callback;
+        unsafe {
+            debug_callback = debug_report_loader
+                .create_debug_report_callback_ext(&debug_info, None)
+                .unwrap();
+        }
+
+        (debug_report_loader, debug_callback)
+    }
+
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        let device = &self.context.device;
+
+        unsafe {
+            self.context
+                .windows
+                .iter()
+                .for_each(|target| target.destroy(device, &self.presenter));
+
+            self.context.uploader.destroy(device);
+            device.destroy_command_pool(self.context.command_pool.command_pool, None);
+            self.context.render_pass.destroy(device);
+
+            device.destroy_device(None);
+
+            if let Some((ref debug_report_loader, debug_callback)) = self.debug_report {
+                debug_report_loader.destroy_debug_report_callback_ext(debug_callback, None);
+            }
+
+            self.context.instance.destroy_instance(None);
+        };
+    }
+}
+
+unsafe extern "system" fn vulkan_debug_callback(
+    _: vk::DebugReportFlagsEXT,
+    _: vk::DebugReportObjectTypeEXT,
+    _: vk::uint64_t,
+    _: vk::size_t,
+    _: vk::int32_t,
+    _: *const vk::c_char,
+    p_message: *const vk::c_char,
+    _: *mut vk::c_void,
+) -> u32 {
+    println!("{:?}", CStr::from_ptr(p_message));
+    vk::VK_FALSE
+}
+
+/// Instance extensions needed to present to a window, `DebugReport` is added
+/// with validation turned on.
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+fn extension_names() -> Vec<*const i8> {
+    vec![
+        Surface::name().as_ptr(),
+        XlibSurface::name().as_ptr(),
+    ]
+}
+
+#[cfg(target_os = "macos")]
+fn extension_names() -> Vec<*const i8> {
+    vec![
+        Surface::name().as_ptr(),
+        MacOSSurface::name().as_ptr(),
+    ]
+}
+
+#[cfg(windows)]
+fn extension_names() -> Vec<*const i8> {
+    vec![
+        Surface::name().as_ptr(),
+        Win32Surface::name().as_ptr(),
+    ]
+}
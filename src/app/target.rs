@@ -0,0 +1,531 @@
+use ash::extensions::{Surface, Swapchain};
+use ash::version::{DeviceV1_0, V1_0};
+use ash::vk;
+use ash::Device;
+use std::ptr;
+use winit::{ElementState, KeyboardInput, VirtualKeyCode, Window, WindowEvent};
+
+use config::WindowConfig;
+use engine::command_pool::{self, CommandPool};
+use engine::depth::DepthBuffer;
+use engine::fence::Fence;
+use engine::full_screen_exclusive::FullScreenExclusive;
+use engine::multisample::ColorBuffer;
+use engine::pipeline::RenderPass;
+use engine::semaphore::Semaphore;
+use engine::surface::{self, PresentModePolicy, SurfaceFormat};
+use engine::{framebuffer, image, swapchain};
+use time::Time;
+
+use super::window::{self, WindowMode};
+use super::{App, Context, Frame, TargetId};
+
+/// The device side of presenting, shared by every window target.
+pub struct Presenter {
+    pub pdevice: vk::PhysicalDevice,
+    pub surface_loader: Surface,
+    pub swapchain_loader: Swapchain,
+    /// Used instead of `swapchain_loader` to create swapchains, it can pass
+    /// on the HDR color spaces.
+    pub create_swapchain: swapchain::CreateSwapchain,
+    /// Whether `VK_EXT_full_screen_exclusive` is enabled on the device.
+    pub full_screen_exclusive: bool,
+    /// Swapchain images asked for, `None` leaves it to the surface.
+    pub image_count: Option<u32>,
+}
+
+/// A window with its own surface, swapchain and frame synchronization, all
+/// rendered with the main render pass of the `Context`.
+pub struct WindowTarget {
+    pub id: TargetId,
+    pub window: Window,
+    pub title: String,
+    /// Size of the swapchain images, changes when the window is resized.
+    pub extent: vk::Extent2D,
+    /// The policy the swapchain was created with, change it with
+    /// `set_present_policy`.
+    pub present_policy: PresentModePolicy,
+    /// The present mode the policy ended up with on this surface.
+    pub present_mode: vk::PresentModeKHR,
+    /// How the window covers the screen, change it with `set_window_mode`
+    /// or Alt+Enter.
+    pub window_mode: WindowMode,
+    /// Pixels per logical pixel on the monitor the window is on, `extent`
+    /// is in pixels.
+    pub hidpi_factor: f64,
+    requested_present_policy: Option<PresentModePolicy>,
+    requested_window_mode: Option<WindowMode>,
+    /// The fullscreen mode Alt+Enter switches to from a window.
+    fullscreen_mode: WindowMode,
+    /// Set when the window changed size in pixels.
+    resized: bool,
+    surface: vk::SurfaceKHR,
+    surface_format: SurfaceFormat,
+    render_pass: RenderPass,
+    samples: vk::SampleCountFlags,
+    swapchain: vk::SwapchainKHR,
+    image_views: Vec<vk::ImageView>,
+    /// The multisampled color attachment, with MSAA turned on.
+    color_buffer: Option<ColorBuffer>,
+    depth_buffer: Option<DepthBuffer>,
+    framebuffers: Vec<vk::Framebuffer>,
+    /// One command buffer per frame in flight.
+    command_pool: CommandPool,
+    image_available_semaphores: Vec<Semaphore>,
+    render_finished_semaphores: Vec<Semaphore>,
+    in_flight_fences: Vec<Fence>,
+}
+
+impl WindowTarget {
+    /// Create the swapchain for `window`, which presents to `surface`.
+    ///
+    /// The surface has to support the `Context`'s surface format and be
+    /// presentable from its queue.
+    pub fn new(
+        ctx: &Context,
+        presenter: &Presenter,
+        id: TargetId,
+        config: &WindowConfig,
+        window: Window,
+        surface: vk::SurfaceKHR,
+        present_policy: PresentModePolicy,
+    ) -> Self {
+        let device = &ctx.device;
+        let hidpi_factor = window.get_hidpi_factor();
+
+        let mut target = WindowTarget {
+            id,
+            window,
+            title: config.title.clone(),
+            extent: vk::Extent2D {
+                width: 0,
+                height: 0,
+            },
+            present_policy,
+            present_mode: vk::PresentModeKHR::Fifo,
+            window_mode: config.mode,
+            hidpi_factor,
+            requested_present_policy: None,
+            requested_window_mode: None,
+            fullscreen_mode: if config.mode.is_fullscreen() {
+                config.mode
+            } else {
+                WindowMode::Borderless
+            },
+            resized: false,
+            surface,
+            surface_format: ctx.surface_format,
+            render_pass: ctx.render_pass,
+            samples: ctx.samples,
+            swapchain: vk::SwapchainKHR::null(),
+            image_views: Vec::new(),
+            color_buffer: None,
+            depth_buffer: None,
+            framebuffers: Vec::new(),
+            command_pool: CommandPool::new(
+                device,
+                ctx.frames_in_flight as u32,
+                ctx.queue_family_index,
+            ),
+            image_available_semaphores: (0..ctx.frames_in_flight)
+                .map(|_| Semaphore::new(device))
+                .collect(),
+            render_finished_semaphores: (0..ctx.frames_in_flight)
+                .map(|_| Semaphore::new(device))
+                .collect(),
+            in_flight_fences: (0..ctx.frames_in_flight)
+                .map(|_| Fence::new(device))
+                .collect(),
+        };
+
+        target.create_swapchain(device, &ctx.memory_properties, presenter);
+
+        target
+    }
+
+    /// Rebuild the swapchain with `policy` before the next frame.
+    /// `present_mode` tells which mode it ended up with afterwards.
+    pub fn set_present_policy(&mut self, policy: PresentModePolicy) {
+        self.requested_present_policy = Some(policy);
+    }
+
+    /// Switch the window to `mode` before the next frame. Fullscreen modes
+    /// cover the monitor the window is on.
+    pub fn set_window_mode(&mut self, mode: WindowMode) {
+        self.requested_window_mode = Some(mode);
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.extent.width as f32 / self.extent.height as f32
+    }
+
+    /// There is nothing to present to while minimized.
+    pub fn is_minimized(&self) -> bool {
+        let (width, height) = window::physical_size(&self.window);
+        width == 0 || height == 0
+    }
+
+    /// Keep track of the size of the window, and switch modes on Alt+Enter.
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        match *event {
+            WindowEvent::Resized(_) => self.resized = true,
+            // The logical size stays the same, the size in pixels does not.
+            WindowEvent::HiDpiFactorChanged(hidpi_factor) => {
+                self.hidpi_factor = hidpi_factor;
+                self.resized = true;
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::Return),
+                        modifiers,
+                        ..
+                    },
+                ..
+            }
+                if modifiers.alt =>
+            {
+                let mode = self.window_mode.toggled(self.fullscreen_mode);
+                self.set_window_mode(mode);
+            }
+            _ => {}
+        }
+    }
+
+    /// Apply the requested window mode and present policy, and rebuild the
+    /// swapchain if either of them or the size of the window changed.
+    pub fn update_swapchain(
+        &mut self,
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        presenter: &Presenter,
+    ) {
+        // The swapchain follows in the resize, exclusive fullscreen needs a
+        // new one either way.
+        if let Some(mode) = self.requested_window_mode.take() {
+            if mode != self.window_mode {
+                window::set_window_mode(&self.window, mode);
+                if mode.is_fullscreen() {
+                    self.fullscreen_mode = mode;
+                }
+                self.window_mode = mode;
+                self.resized = true;
+            }
+        }
+
+        // Switching the present mode needs a new swapchain, the window
+        // itself stays as it is.
+        if let Some(policy) = self.requested_present_policy.take() {
+            self.present_policy = policy;
+            self.resized = true;
+        }
+
+        if self.resized {
+            self.recreate_swapchain(device, memory_properties, presenter);
+        }
+    }
+
+    /// Rebuild the swapchain and everything that depends on its images.
+    ///
+    /// The render pass only depends on the formats, so it is kept as is.
+    pub fn recreate_swapchain(
+        &mut self,
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        presenter: &Presenter,
+    ) {
+        // A minimized window has no area to present to, try again once it
+        // is restored.
+        if self.is_minimized() {
+            return;
+        }
+
+        device.device_wait_idle().unwrap();
+        self.destroy_framebuffers(device);
+
+        self.create_swapchain(device, memory_properties, presenter);
+    }
+
+    /// Record and present a frame, calling `App::render` inside the main
+    /// render pass.
+    ///
+    /// Returns `false` when the swapchain no longer matches the surface and
+    /// has to be recreated before the next frame can be drawn.
+    pub fn draw<A: App>(
+        &self,
+        ctx: &Context,
+        presenter: &Presenter,
+        app: &mut A,
+        frame_index: usize,
+        time: &Time,
+    ) -> bool {
+        let device = &ctx.device;
+
+        let image_available_semaphore = &self.image_available_semaphores[frame_index];
+        let render_finished_semaphore = &self.render_finished_semaphores[frame_index];
+        let in_flight_fence = &self.in_flight_fences[frame_index];
+
+        unsafe {
+            device
+                .wait_for_fences(&[in_flight_fence.fence], false, u64::MAX)
+                .unwrap();
+        };
+
+        let image_index = unsafe {
+            match presenter.swapchain_loader.acquire_next_image_khr(
+                self.swapchain,
+                u64::MAX,
+                image_available_semaphore.semaphore,
+                vk::Fence::null(),
+            ) {
+                Ok(image_index) => image_index,
+                Err(vk::Result::ErrorOutOfDateKhr) => return false,
+                Err(e) => panic!("Unable to acquire next image: {:?}", e),
+            }
+        };
+
+        // Only reset once we know work will be submitted, otherwise the
+        // next wait on this fence would never return.
+        unsafe {
+            device.reset_fences(&[in_flight_fence.fence]).unwrap();
+        };
+
+        let command_buffer = self
+            .command_pool
+            .begin_command_buffer(device, frame_index as u32)
+            .unwrap();
+
+        let frame = Frame {
+            command_buffer,
+            target: self.id,
+            frame_index,
+            image_index,
+            extent: self.extent,
+            time: *time,
+        };
+
+        app.render_offscreen(ctx, &frame);
+
+        command_pool::begin_render_pass(
+            device,
+            command_buffer,
+            &self.render_pass,
+            self.framebuffers[image_index as usize],
+            self.extent,
+            ctx.clear_color,
+            ctx.clear_depth,
+        );
+
+        app.render(ctx, &frame);
+
+        unsafe {
+            device.cmd_end_render_pass(command_buffer);
+            device
+                .end_command_buffer(command_buffer)
+                .expect("Unable to record command buffer");
+        }
+
+        let submit_info = vk::SubmitInfo {
+            s_type: vk::StructureType::SubmitInfo,
+            p_next: ptr::null(),
+            p_wait_semaphores: [image_available_semaphore.semaphore].as_ptr(),
+            wait_semaphore_count: 1,
+            p_wait_dst_stage_mask: [vk::PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT].as_ptr(),
+            command_buffer_count: 1,
+            p_command_buffers: &command_buffer,
+            signal_semaphore_count: 1,
+            p_signal_semaphores: [render_finished_semaphore.semaphore].as_ptr(),
+        };
+
+        unsafe {
+            device
+                .queue_submit(ctx.queue, &[submit_info], in_flight_fence.fence)
+                .expect("Unable to submit queue");
+        };
+
+        let present_info = vk::PresentInfoKHR {
+            s_type: vk::StructureType::PresentInfoKhr,
+            p_next: ptr::null(),
+            wait_semaphore_count: 1,
+            p_wait_semaphores: [render_finished_semaphore.semaphore].as_ptr(),
+            swapchain_count: 1,
+            p_swapchains: [self.swapchain].as_ptr(),
+            p_image_indices: [image_index].as_ptr(),
+            p_results: ptr::null_mut(),
+        };
+
+        let present_result = unsafe {
+            presenter
+                .swapchain_loader
+                .queue_present_khr(ctx.queue, &present_info)
+        };
+
+        match present_result {
+            Ok(()) => true,
+            Err(vk::Result::ErrorOutOfDateKhr) | Err(vk::Result::SuboptimalKhr) => false,
+            Err(e) => panic!("Unable to queue present: {:?}", e),
+        }
+    }
+
+    /// Destroy everything but the window, which closes when the target is
+    /// dropped. The device has to be idle.
+    pub fn destroy(&self, device: &Device<V1_0>, presenter: &Presenter) {
+        self.destroy_framebuffers(device);
+
+        unsafe {
+            self.in_flight_fences
+                .iter()
+                .for_each(|fence| device.destroy_fence(fence.fence, None));
+            self.render_finished_semaphores
+                .iter()
+                .chain(self.image_available_semaphores.iter())
+                .for_each(|semaphore| device.destroy_semaphore(semaphore.semaphore, None));
+            device.destroy_command_pool(self.command_pool.command_pool, None);
+
+            presenter
+                .swapchain_loader
+                .destroy_swapchain_khr(self.swapchain, None);
+            presenter
+                .surface_loader
+                .destroy_surface_khr(self.surface, None);
+        }
+    }
+
+    /// Create a swapchain for the current size of the window, replacing the
+    /// old one, with image views and framebuffers for its images.
+    fn create_swapchain(
+        &mut self,
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        presenter: &Presenter,
+    ) {
+        let (width, height) = window::physical_size(&self.window);
+        let pdevice = presenter.pdevice;
+
+        let surface_capabilities = presenter
+            .surface_loader
+            .get_physical_device_surface_capabilities_khr(pdevice, self.surface)
+            .unwrap();
+
+        let desired_image_count = surface::select_desired_image_count(
+            &surface_capabilities,
+            presenter.image_count,
+        );
+        let surface_resolution =
+            surface::select_surface_extent(&surface_capabilities, width, height);
+        let pre_transform = surface::select_pre_transform(&surface_capabilities);
+
+        let present_mode = surface::select_present_mode(
+            &presenter.surface_loader,
+            pdevice,
+            self.surface,
+            self.present_policy,
+        ).unwrap();
+
+        let old_swapchain = self.swapchain;
+        self.swapchain = swapchain::create_swapchain(
+            presenter.create_swapchain,
+            device,
+            self.surface,
+            desired_image_count,
+            &self.surface_format,
+            surface_resolution,
+            pre_transform,
+            present_mode,
+            old_swapchain,
+            exclusive_mode(presenter.full_screen_exclusive, self.window_mode),
+        ).unwrap();
+
+        if old_swapchain != vk::SwapchainKHR::null() {
+            unsafe {
+                presenter
+                    .swapchain_loader
+                    .destroy_swapchain_khr(old_swapchain, None);
+            }
+        }
+
+        let images = presenter
+            .swapchain_loader
+            .get_swapchain_images_khr(self.swapchain)
+            .unwrap();
+
+        self.image_views =
+            image::create_image_views(device, images, self.surface_format.format).unwrap();
+
+        self.color_buffer = if self.samples != vk::SAMPLE_COUNT_1_BIT {
+            Some(
+                ColorBuffer::new(
+                    device,
+                    memory_properties,
+                    self.surface_format.format,
+                    self.samples,
+                    surface_resolution,
+                ).expect("Unable to create multisampled color buffer"),
+            )
+        } else {
+            None
+        };
+
+        self.depth_buffer = self.render_pass.depth_format.map(|depth_format| {
+            DepthBuffer::new(
+                device,
+                memory_properties,
+                depth_format,
+                self.samples,
+                surface_resolution,
+            ).expect("Unable to create depth buffer")
+        });
+
+        self.framebuffers = framebuffer::create_framebuffers(
+            device,
+            self.render_pass.render_pass,
+            &self.image_views,
+            self.color_buffer
+                .as_ref()
+                .map(|color_buffer| color_buffer.view),
+            self.depth_buffer
+                .as_ref()
+                .map(|depth_buffer| depth_buffer.view),
+            surface_resolution,
+        ).unwrap();
+
+        self.extent = surface_resolution;
+        self.present_mode = present_mode;
+        self.resized = false;
+    }
+
+    /// Destroy what `create_swapchain` creates, except for the swapchain
+    /// that the next one replaces.
+    fn destroy_framebuffers(&self, device: &Device<V1_0>) {
+        unsafe {
+            self.framebuffers.iter().for_each(|&framebuffer| {
+                device.destroy_framebuffer(framebuffer, None);
+            });
+
+            self.image_views.iter().for_each(|&image_view| {
+                device.destroy_image_view(image_view, None);
+            });
+        }
+
+        if let Some(ref color_buffer) = self.color_buffer {
+            color_buffer.destroy(device);
+        }
+        if let Some(ref depth_buffer) = self.depth_buffer {
+            depth_buffer.destroy(device);
+        }
+    }
+}
+
+/// What to tell `VK_EXT_full_screen_exclusive` about a swapchain for `mode`,
+/// nothing when the extension is not enabled.
+fn exclusive_mode(supported: bool, mode: WindowMode) -> Option<FullScreenExclusive> {
+    if !supported {
+        return None;
+    }
+
+    match mode {
+        WindowMode::Exclusive => Some(FullScreenExclusive::Allowed),
+        _ => Some(FullScreenExclusive::Disallowed),
+    }
+}
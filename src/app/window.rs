@@ -0,0 +1,176 @@
+use std::str::FromStr;
+use winit::dpi::{LogicalPosition, LogicalSize};
+use winit::{EventsLoop, MonitorId, Window, WindowBuilder};
+
+use config::{ConfigError, WindowConfig};
+
+/// How the window covers the screen.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum WindowMode {
+    /// A decorated window of the configured size.
+    #[serde(rename = "windowed")]
+    Windowed,
+    /// An undecorated window covering the monitor, the compositor keeps
+    /// running so switching away is instant.
+    #[serde(rename = "borderless")]
+    Borderless,
+    /// Covers the monitor like `Borderless`, and lets the driver take
+    /// exclusive control of the display where `VK_EXT_full_screen_exclusive`
+    /// is available.
+    #[serde(rename = "exclusive")]
+    Exclusive,
+}
+
+impl WindowMode {
+    pub fn is_fullscreen(&self) -> bool {
+        *self != WindowMode::Windowed
+    }
+
+    /// What Alt+Enter switches to, `fullscreen` is used when leaving the
+    /// windowed mode.
+    pub fn toggled(&self, fullscreen: WindowMode) -> WindowMode {
+        match *self {
+            WindowMode::Windowed if fullscreen.is_fullscreen() => fullscreen,
+            WindowMode::Windowed => WindowMode::Borderless,
+            _ => WindowMode::Windowed,
+        }
+    }
+}
+
+impl FromStr for WindowMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "windowed" => Ok(WindowMode::Windowed),
+            "borderless" => Ok(WindowMode::Borderless),
+            "exclusive" => Ok(WindowMode::Exclusive),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The monitor at `selection`, an index or a case insensitive part of its
+/// name, or the primary monitor when there is no selection.
+///
+/// Fails when no monitor matches, listing the available ones.
+pub fn select_monitor(
+    events_loop: &EventsLoop,
+    selection: Option<&str>,
+) -> Result<MonitorId, ConfigError> {
+    let selection = match selection {
+        Some(selection) => selection,
+        None => return Ok(events_loop.get_primary_monitor()),
+    };
+
+    let monitors = events_loop.get_available_monitors().collect::<Vec<_>>();
+    let names = monitors
+        .iter()
+        .map(|monitor| monitor.get_name().unwrap_or_default())
+        .collect::<Vec<_>>();
+
+    match find_monitor(&names, selection) {
+        Some(index) => Ok(monitors[index].clone()),
+        None => Err(ConfigError::Unsupported {
+            key: "window.monitor".to_owned(),
+            value: selection.to_owned(),
+            supported: names
+                .iter()
+                .enumerate()
+                .map(|(index, name)| format!("{} ({})", index, name))
+                .collect::<Vec<_>>()
+                .join(", "),
+        }),
+    }
+}
+
+/// Index of the monitor `selection` refers to out of the monitor `names`.
+fn find_monitor(names: &[String], selection: &str) -> Option<usize> {
+    match selection.parse::<usize>() {
+        Ok(index) if index < names.len() => Some(index),
+        Ok(_) => None,
+        Err(_) => names
+            .iter()
+            .position(|name| name.to_lowercase().contains(&selection.to_lowercase())),
+    }
+}
+
+/// Open a window in the configured mode on the configured monitor.
+pub fn create_window(
+    events_loop: &EventsLoop,
+    config: &WindowConfig,
+) -> Result<Window, ConfigError> {
+    let selection = config.monitor.as_deref();
+    let monitor = select_monitor(events_loop, selection)?;
+
+    let fullscreen = if config.mode.is_fullscreen() {
+        Some(monitor.clone())
+    } else {
+        None
+    };
+
+    let window = WindowBuilder::new()
+        .with_title(config.title.clone())
+        .with_dimensions(LogicalSize::new(config.width as f64, config.height as f64))
+        .with_fullscreen(fullscreen)
+        .build(events_loop)
+        .unwrap();
+
+    if !config.mode.is_fullscreen() {
+        center_on(&window, &monitor);
+    }
+
+    Ok(window)
+}
+
+/// Switch `window` to `mode`, going fullscreen on the monitor the window is
+/// on.
+///
+/// winit only has one kind of fullscreen, which is borderless on every
+/// platform. What makes `Exclusive` exclusive is the swapchain.
+pub fn set_window_mode(window: &Window, mode: WindowMode) {
+    if mode.is_fullscreen() {
+        window.set_fullscreen(Some(window.get_current_monitor()));
+    } else {
+        window.set_fullscreen(None);
+    }
+}
+
+/// Size of the inside of `window` in pixels, which is what the swapchain
+/// has to match.
+pub fn physical_size(window: &Window) -> (u32, u32) {
+    window
+        .get_inner_size()
+        .unwrap()
+        .to_physical(window.get_hidpi_factor())
+        .into()
+}
+
+/// Move `window` to the middle of `monitor`.
+fn center_on(window: &Window, monitor: &MonitorId) {
+    let scale = monitor.get_hidpi_factor();
+    let position = monitor.get_position().to_logical(scale);
+    let size = monitor.get_dimensions().to_logical(scale);
+
+    if let Some(window_size) = window.get_outer_size() {
+        window.set_position(LogicalPosition::new(
+            position.x + ((size.width - window_size.width) / 2.0).max(0.0),
+            position.y + ((size.height - window_size.height) / 2.0).max(0.0),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monitors_are_found_by_index_or_name() {
+        let names = vec!["DELL U2718Q".to_string(), "Built-in Retina Display".to_string()];
+        assert_eq!(find_monitor(&names, "1"), Some(1));
+        assert_eq!(find_monitor(&names, "2"), None);
+        assert_eq!(find_monitor(&names, "dell"), Some(0));
+        assert_eq!(find_monitor(&names, "RETINA"), Some(1));
+        assert_eq!(find_monitor(&names, "hdmi"), None);
+    }
+}
@@ -0,0 +1,400 @@
+use ash::version::V1_0;
+use ash::vk;
+use ash::Device;
+use gltf;
+use gltf::image::Format;
+use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+use std::path::Path;
+
+use engine::command_pool::CommandPool;
+use engine::material::{AlphaMode, Material};
+use engine::mesh::{Mesh, Primitive, PrimitiveData, Vertex};
+use engine::model::{Model, Node};
+use engine::texture::{SamplerDesc, Texture};
+
+#[derive(Debug)]
+pub enum LoadError {
+    Gltf(gltf::Error),
+    /// Only triangle lists can be drawn with the mesh pipeline.
+    UnsupportedMode(gltf::mesh::Mode),
+    MissingPositions,
+    /// Creating a buffer or image on the device failed.
+    Upload(vk::Result),
+}
+
+/// Import a `.gltf` (with its `.bin` and image files) or a `.glb` file and
+/// upload everything it contains.
+pub fn load<P: AsRef<Path>>(
+    device: &Device<V1_0>,
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    command_pool: &CommandPool,
+    queue: vk::Queue,
+    path: P,
+) -> Result<Model, LoadError> {
+    let (document, buffers, images) = gltf::import(path).map_err(LoadError::Gltf)?;
+
+    let materials = document.materials().map(load_material).collect::<Vec<_>>();
+
+    // Colors are stored in sRGB, everything else is linear data.
+    let srgb_textures = materials
+        .iter()
+        .flat_map(|material| {
+            material
+                .base_color_texture
+                .into_iter()
+                .chain(material.emissive_texture)
+        }).collect::<Vec<_>>();
+
+    let nodes = document
+        .nodes()
+        .map(|node| Node {
+            name: node.name().map(String::from),
+            local_transform: node.transform().matrix(),
+            mesh: node.mesh().map(|mesh| mesh.index()),
+            children: node.children().map(|child| child.index()).collect(),
+        }).collect::<Vec<_>>();
+
+    let root_nodes = match document.default_scene().or_else(|| document.scenes().next()) {
+        Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+        // Without a scene every node that is nobody's child is a root.
+        None => (0..nodes.len())
+            .filter(|index| !nodes.iter().any(|node| node.children.contains(index)))
+            .collect(),
+    };
+
+    // Filled in as things are uploaded, so whatever was uploaded before a
+    // failure can be destroyed.
+    let mut model = Model {
+        meshes: Vec::new(),
+        materials,
+        textures: Vec::new(),
+        nodes,
+        root_nodes,
+    };
+
+    for texture in document.textures() {
+        let image = &images[texture.source().index()];
+        let format = if srgb_textures.contains(&texture.index()) {
+            vk::Format::R8g8b8a8Srgb
+        } else {
+            vk::Format::R8g8b8a8Unorm
+        };
+
+        match Texture::from_rgba8(
+            device,
+            memory_properties,
+            command_pool,
+            queue,
+            image.width,
+            image.height,
+            &to_rgba8(image),
+            format,
+            sampler_desc(&texture.sampler()),
+        ) {
+            Ok(texture) => model.textures.push(texture),
+            Err(error) => {
+                model.destroy(device);
+                return Err(LoadError::Upload(error));
+            }
+        }
+    }
+
+    for mesh in document.meshes() {
+        model.meshes.push(Mesh {
+            name: mesh.name().map(String::from),
+            primitives: Vec::new(),
+        });
+
+        for primitive in mesh.primitives() {
+            let uploaded = load_primitive(&primitive, &buffers).and_then(|data| {
+                Primitive::upload(device, memory_properties, &data).map_err(LoadError::Upload)
+            });
+            match uploaded {
+                Ok(primitive) => model.meshes[mesh.index()].primitives.push(primitive),
+                Err(error) => {
+                    model.destroy(device);
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    Ok(model)
+}
+
+fn load_primitive(
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data],
+) -> Result<PrimitiveData, LoadError> {
+    if primitive.mode() != gltf::mesh::Mode::Triangles {
+        return Err(LoadError::UnsupportedMode(primitive.mode()));
+    }
+
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()][..]));
+
+    let positions = reader.read_positions().ok_or(LoadError::MissingPositions)?;
+    let mut vertices = positions
+        .map(|position| Vertex {
+            position,
+            ..Default::default()
+        }).collect::<Vec<_>>();
+
+    let has_normals = match reader.read_normals() {
+        Some(normals) => {
+            vertices
+                .iter_mut()
+                .zip(normals)
+                .for_each(|(vertex, normal)| vertex.normal = normal);
+            true
+        }
+        None => false,
+    };
+
+    if let Some(uvs) = reader.read_tex_coords(0) {
+        vertices
+            .iter_mut()
+            .zip(uvs.into_f32())
+            .for_each(|(vertex, uv)| vertex.uv = uv);
+    }
+
+    let indices = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertices.len() as u32).collect(),
+    };
+
+    let mut data = PrimitiveData {
+        vertices,
+        indices,
+        material: primitive.material().index(),
+    };
+
+    // The spec asks for flat normals when a primitive has none.
+    if !has_normals {
+        data.compute_flat_normals();
+    }
+
+    Ok(data)
+}
+
+fn load_material(material: gltf::Material) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+
+    Material {
+        name: material.name().map(String::from),
+        base_color_factor: pbr.base_color_factor(),
+        base_color_texture: pbr.base_color_texture().map(|info| info.texture().index()),
+        metallic_factor: pbr.metallic_factor(),
+        roughness_factor: pbr.roughness_factor(),
+        metallic_roughness_texture: pbr
+            .metallic_roughness_texture()
+            .map(|info| info.texture().index()),
+        normal_texture: material
+            .normal_texture()
+            .map(|normal| normal.texture().index()),
+        occlusion_texture: material
+            .occlusion_texture()
+            .map(|occlusion| occlusion.texture().index()),
+        emissive_factor: material.emissive_factor(),
+        emissive_texture: material
+            .emissive_texture()
+            .map(|info| info.texture().index()),
+        alpha_mode: match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask(material.alpha_cutoff()),
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        },
+        double_sided: material.double_sided(),
+    }
+}
+
+fn sampler_desc(sampler: &gltf::texture::Sampler) -> SamplerDesc {
+    let address_mode = |mode: WrappingMode| match mode {
+        WrappingMode::ClampToEdge => vk::SamplerAddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => vk::SamplerAddressMode::MirroredRepeat,
+        WrappingMode::Repeat => vk::SamplerAddressMode::Repeat,
+    };
+
+    SamplerDesc {
+        mag_filter: match sampler.mag_filter() {
+            Some(MagFilter::Nearest) => vk::Filter::Nearest,
+            _ => vk::Filter::Linear,
+        },
+        min_filter: match sampler.min_filter() {
+            Some(MinFilter::Nearest)
+            | Some(MinFilter::NearestMipmapNearest)
+            | Some(MinFilter::NearestMipmapLinear) => vk::Filter::Nearest,
+            _ => vk::Filter::Linear,
+        },
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+        ..SamplerDesc::default()
+    }
+}
+
+/// Expand any of the image formats to 8 bit RGBA, 16 bit channels keep their
+/// most significant byte.
+fn to_rgba8(image: &gltf::image::Data) -> Vec<u8> {
+    let (channels, channel_size, bgr) = match image.format {
+        Format::R8 => (1, 1, false),
+        Format::R8G8 => (2, 1, false),
+        Format::R8G8B8 => (3, 1, false),
+        Format::R8G8B8A8 => (4, 1, false),
+        Format::B8G8R8 => (3, 1, true),
+        Format::B8G8R8A8 => (4, 1, true),
+        Format::R16 => (1, 2, false),
+        Format::R16G16 => (2, 2, false),
+        Format::R16G16B16 => (3, 2, false),
+        Format::R16G16B16A16 => (4, 2, false),
+    };
+
+    image
+        .pixels
+        .chunks(channels * channel_size)
+        .flat_map(|pixel| {
+            // 16 bit channels are in native byte order.
+            let channel = |index: usize| match channel_size {
+                1 => pixel[index],
+                _ => (u16::from_ne_bytes([pixel[index * 2], pixel[index * 2 + 1]]) >> 8) as u8,
+            };
+            let (r, g, b) = match channels {
+                1 => (channel(0), channel(0), channel(0)),
+                2 => (channel(0), channel(1), 0),
+                _ if bgr => (channel(2), channel(1), channel(0)),
+                _ => (channel(0), channel(1), channel(2)),
+            };
+            let a = if channels == 4 { channel(3) } else { 255 };
+            vec![r, g, b, a]
+        }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A triangle without normals or indices, the same triangle as lines and
+    /// a masked material with a sampled texture.
+    const DOCUMENT: &str = r#"{
+        "asset": { "version": "2.0" },
+        "buffers": [{ "uri": "triangle.bin", "byteLength": 36 }],
+        "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+        "accessors": [{
+            "bufferView": 0,
+            "componentType": 5126,
+            "count": 3,
+            "type": "VEC3",
+            "min": [0.0, 0.0, 0.0],
+            "max": [1.0, 1.0, 0.0]
+        }],
+        "meshes": [
+            { "primitives": [{ "attributes": { "POSITION": 0 }, "material": 0 }] },
+            { "primitives": [{ "attributes": { "POSITION": 0 }, "mode": 1 }] }
+        ],
+        "materials": [{
+            "name": "masked",
+            "pbrMetallicRoughness": {
+                "baseColorFactor": [1.0, 0.5, 0.25, 1.0],
+                "baseColorTexture": { "index": 0 },
+                "roughnessFactor": 0.5
+            },
+            "alphaMode": "MASK",
+            "alphaCutoff": 0.25,
+            "doubleSided": true
+        }],
+        "samplers": [{ "magFilter": 9728, "minFilter": 9986, "wrapS": 33071 }],
+        "images": [{ "uri": "texture.png" }],
+        "textures": [{ "source": 0, "sampler": 0 }]
+    }"#;
+
+    fn document() -> (gltf::Document, Vec<gltf::buffer::Data>) {
+        let document = gltf::Gltf::from_slice(DOCUMENT.as_bytes()).unwrap().document;
+        let positions = [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let bytes = positions
+            .iter()
+            .flat_map(|position| position.to_bits().to_le_bytes().to_vec())
+            .collect();
+        (document, vec![gltf::buffer::Data(bytes)])
+    }
+
+    #[test]
+    fn primitives_get_indices_and_flat_normals() {
+        let (document, buffers) = document();
+        let mesh = document.meshes().next().unwrap();
+        let data = load_primitive(&mesh.primitives().next().unwrap(), &buffers).unwrap();
+
+        assert_eq!(data.indices, vec![0, 1, 2]);
+        assert_eq!(data.material, Some(0));
+        assert_eq!(data.vertices[1].position, [1.0, 0.0, 0.0]);
+        for vertex in &data.vertices {
+            assert_eq!(vertex.normal, [0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn lines_are_unsupported() {
+        let (document, buffers) = document();
+        let mesh = document.meshes().nth(1).unwrap();
+        match load_primitive(&mesh.primitives().next().unwrap(), &buffers) {
+            Err(LoadError::UnsupportedMode(gltf::mesh::Mode::Lines)) => {}
+            result => panic!("{:?}", result.map(|data| data.indices)),
+        }
+    }
+
+    #[test]
+    fn materials() {
+        let (document, _) = document();
+        let material = load_material(document.materials().next().unwrap());
+
+        assert_eq!(material.name, Some("masked".to_owned()));
+        assert_eq!(material.base_color_factor, [1.0, 0.5, 0.25, 1.0]);
+        assert_eq!(material.base_color_texture, Some(0));
+        assert_eq!(material.metallic_factor, 1.0);
+        assert_eq!(material.roughness_factor, 0.5);
+        assert_eq!(material.normal_texture, None);
+        assert_eq!(material.alpha_mode, AlphaMode::Mask(0.25));
+        assert!(material.double_sided);
+    }
+
+    #[test]
+    fn samplers() {
+        let (document, _) = document();
+        let desc = sampler_desc(&document.textures().next().unwrap().sampler());
+
+        assert_eq!(desc.mag_filter, vk::Filter::Nearest);
+        assert_eq!(desc.min_filter, vk::Filter::Nearest);
+        assert_eq!(desc.address_mode_u, vk::SamplerAddressMode::ClampToEdge);
+        assert_eq!(desc.address_mode_v, vk::SamplerAddressMode::Repeat);
+    }
+
+    fn image(format: Format, pixels: Vec<u8>) -> gltf::image::Data {
+        gltf::image::Data {
+            pixels,
+            format,
+            width: 1,
+            height: 1,
+        }
+    }
+
+    #[test]
+    fn images_expand_to_rgba8() {
+        let table = [
+            (Format::R8, vec![7], [7, 7, 7, 255]),
+            (Format::R8G8, vec![1, 2], [1, 2, 0, 255]),
+            (Format::R8G8B8, vec![1, 2, 3], [1, 2, 3, 255]),
+            (Format::R8G8B8A8, vec![1, 2, 3, 4], [1, 2, 3, 4]),
+            (Format::B8G8R8, vec![1, 2, 3], [3, 2, 1, 255]),
+            (Format::B8G8R8A8, vec![1, 2, 3, 4], [3, 2, 1, 4]),
+        ];
+        for &(format, ref pixels, rgba) in table.iter() {
+            assert_eq!(to_rgba8(&image(format, pixels.clone())), rgba.to_vec());
+        }
+
+        let pixels = [0x1234u16, 0xabcd, 0xff00]
+            .iter()
+            .flat_map(|channel| channel.to_ne_bytes().to_vec())
+            .collect();
+        assert_eq!(
+            to_rgba8(&image(Format::R16G16B16, pixels)),
+            vec![0x12, 0xab, 0xff, 255]
+        );
+    }
+}
@@ -0,0 +1,42 @@
+use ash::version::V1_0;
+use ash::vk;
+use ash::Device;
+use std::path::Path;
+
+use engine::command_pool::CommandPool;
+use engine::model::Model;
+
+pub mod gltf;
+pub mod obj;
+
+#[derive(Debug)]
+pub enum ModelError {
+    Gltf(gltf::LoadError),
+    Obj(obj::ObjError),
+}
+
+/// Load and upload a model, picking the importer from the file extension.
+///
+/// OBJ files without normals get smooth ones generated.
+pub fn load_model<P: AsRef<Path>>(
+    device: &Device<V1_0>,
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    command_pool: &CommandPool,
+    queue: vk::Queue,
+    path: P,
+) -> Result<Model, ModelError> {
+    let path = path.as_ref();
+    let is_obj = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.eq_ignore_ascii_case("obj"))
+        .unwrap_or(false);
+
+    if is_obj {
+        obj::load(path, obj::NormalGeneration::Smooth)
+            .and_then(|model| model.upload(device, memory_properties, command_pool, queue))
+            .map_err(ModelError::Obj)
+    } else {
+        gltf::load(device, memory_properties, command_pool, queue, path).map_err(ModelError::Gltf)
+    }
+}
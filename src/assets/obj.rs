@@ -0,0 +1,755 @@
+use ash::version::V1_0;
+use ash::vk;
+use ash::Device;
+use image;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use engine::command_pool::CommandPool;
+use engine::material::{AlphaMode, Material};
+use engine::mesh::{Mesh, Primitive, PrimitiveData, Vertex};
+use engine::model::{Model, Node, IDENTITY};
+use engine::texture::{SamplerDesc, Texture};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(PathBuf, io::Error),
+    Parse { line: usize, message: String },
+    Image(PathBuf, image::ImageError),
+    /// Creating a buffer or image on the device failed.
+    Upload(vk::Result),
+}
+
+/// How normals are generated for faces that do not reference any.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NormalGeneration {
+    /// One normal per face, vertices are not shared between faces.
+    Flat,
+    /// Face normals averaged over every face sharing a position.
+    Smooth,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ObjMesh {
+    pub name: Option<String>,
+    /// One primitive per material used by the mesh.
+    pub primitives: Vec<PrimitiveData>,
+}
+
+/// The contents of an OBJ file and its material libraries, still on the CPU.
+#[derive(Clone, Debug, Default)]
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+    pub materials: Vec<Material>,
+    /// Images referenced by the materials, indexed by their texture fields.
+    pub texture_paths: Vec<PathBuf>,
+}
+
+/// Parse an OBJ file and the MTL files it references, relative to its
+/// directory.
+pub fn load<P: AsRef<Path>>(path: P, normals: NormalGeneration) -> Result<ObjModel, ObjError> {
+    let path = path.as_ref();
+    let source = read_to_string(path)?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    let mut model = parse(&source, normals)?;
+
+    let mut material_names = HashMap::new();
+    for library in material_libraries(&source) {
+        let library_path = directory.join(&library);
+        let library_source = read_to_string(&library_path)?;
+
+        for (name, mut material, textures) in parse_mtl(&library_source)? {
+            let mut texture_index = |texture: Option<String>| {
+                texture.map(|texture| {
+                    let texture_path = library_path
+                        .parent()
+                        .unwrap_or(Path::new(""))
+                        .join(texture);
+                    match model.texture_paths.iter().position(|p| *p == texture_path) {
+                        Some(index) => index,
+                        None => {
+                            model.texture_paths.push(texture_path);
+                            model.texture_paths.len() - 1
+                        }
+                    }
+                })
+            };
+
+            material.base_color_texture = texture_index(textures.base_color);
+            material.normal_texture = texture_index(textures.normal);
+            material.emissive_texture = texture_index(textures.emissive);
+
+            material_names.insert(name, model.materials.len());
+            model.materials.push(material);
+        }
+    }
+
+    // `parse` numbers materials in order of first use, map those to the
+    // order they were loaded in.
+    let used_materials = used_materials(&source);
+    for mesh in &mut model.meshes {
+        for primitive in &mut mesh.primitives {
+            primitive.material = primitive
+                .material
+                .and_then(|index| material_names.get(&used_materials[index]).cloned());
+        }
+    }
+
+    Ok(model)
+}
+
+/// Parse the geometry of an OBJ file.
+///
+/// Faces are triangulated as fans and vertices are deduplicated on their
+/// position, uv and normal indices. The `material` of each primitive is the
+/// index of its `usemtl` name in order of first use, since the libraries are
+/// not read here.
+pub fn parse(source: &str, normals: NormalGeneration) -> Result<ObjModel, ObjError> {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut source_normals: Vec<[f32; 3]> = Vec::new();
+
+    let mut builder = MeshBuilder::default();
+    let mut meshes = Vec::new();
+    let mut materials: Vec<String> = Vec::new();
+
+    for (line_number, keyword, arguments) in lines(source) {
+
+        match keyword {
+            "v" => positions.push(parse_floats(&arguments, line_number)?),
+            "vt" => {
+                let [u]: [f32; 1] = parse_floats(&arguments, line_number)?;
+                // `v` is optional and defaults to 0.
+                let [v]: [f32; 1] = match arguments.get(1) {
+                    Some(_) => parse_floats(&arguments[1..], line_number)?,
+                    None => [0.0],
+                };
+                // OBJ puts the origin at the bottom left, Vulkan at the top left.
+                uvs.push([u, 1.0 - v]);
+            }
+            "vn" => source_normals.push(parse_floats(&arguments, line_number)?),
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(parse_error(line_number, "a face needs at least 3 vertices"));
+                }
+
+                let corners = arguments
+                    .iter()
+                    .map(|corner| {
+                        parse_corner(
+                            corner,
+                            positions.len(),
+                            uvs.len(),
+                            source_normals.len(),
+                            line_number,
+                        )
+                    }).collect::<Result<Vec<_>, _>>()?;
+
+                for i in 1..corners.len() - 1 {
+                    builder.push_triangle(
+                        [corners[0], corners[i], corners[i + 1]],
+                        &positions,
+                        &uvs,
+                        &source_normals,
+                    );
+                }
+            }
+            "o" => {
+                // The material in use carries over into the next object.
+                let material = builder.current.material;
+                builder.finish_primitive();
+                if !builder.is_empty() {
+                    meshes.push(builder.finish(normals));
+                }
+                builder = MeshBuilder::default();
+                builder.mesh.name = arguments.first().map(|name| name.to_string());
+                builder.current.material = material;
+            }
+            "usemtl" => {
+                let name = arguments.join(" ");
+                let index = match materials.iter().position(|material| *material == name) {
+                    Some(index) => index,
+                    None => {
+                        materials.push(name);
+                        materials.len() - 1
+                    }
+                };
+                builder.use_material(index);
+            }
+            // Groups, smoothing groups, material libraries and anything
+            // unknown do not change the geometry.
+            _ => {}
+        }
+    }
+
+    builder.finish_primitive();
+    if !builder.is_empty() {
+        meshes.push(builder.finish(normals));
+    }
+
+    Ok(ObjModel {
+        meshes,
+        materials: Vec::new(),
+        texture_paths: Vec::new(),
+    })
+}
+
+/// Texture file names referenced by a material, relative to its library.
+#[derive(Clone, Debug, Default)]
+pub struct MtlTextures {
+    pub base_color: Option<String>,
+    pub normal: Option<String>,
+    pub emissive: Option<String>,
+}
+
+/// Parse a material library into named materials and the textures they use.
+pub fn parse_mtl(source: &str) -> Result<Vec<(String, Material, MtlTextures)>, ObjError> {
+    let mut materials: Vec<(String, Material, MtlTextures)> = Vec::new();
+
+    for (line_number, keyword, arguments) in lines(source) {
+
+        if keyword == "newmtl" {
+            let name = arguments.join(" ");
+            let material = Material {
+                name: Some(name.clone()),
+                // OBJ materials are not metals unless they say so.
+                metallic_factor: 0.0,
+                ..Material::default()
+            };
+            materials.push((name, material, MtlTextures::default()));
+            continue;
+        }
+
+        let &mut (_, ref mut material, ref mut textures) = match materials.last_mut() {
+            Some(current) => current,
+            None => return Err(parse_error(line_number, "material data before newmtl")),
+        };
+
+        match keyword {
+            "Kd" => {
+                let color: [f32; 3] = parse_floats(&arguments, line_number)?;
+                material.base_color_factor[..3].copy_from_slice(&color);
+            }
+            "d" => {
+                let [alpha]: [f32; 1] = parse_floats(&arguments, line_number)?;
+                material.base_color_factor[3] = alpha;
+            }
+            "Tr" => {
+                let [transparency]: [f32; 1] = parse_floats(&arguments, line_number)?;
+                material.base_color_factor[3] = 1.0 - transparency;
+            }
+            "Ke" => material.emissive_factor = parse_floats(&arguments, line_number)?,
+            "Pr" => {
+                let [roughness]: [f32; 1] = parse_floats(&arguments, line_number)?;
+                material.roughness_factor = roughness;
+            }
+            "Pm" => {
+                let [metallic]: [f32; 1] = parse_floats(&arguments, line_number)?;
+                material.metallic_factor = metallic;
+            }
+            // The file name is the last argument, after any options.
+            "map_Kd" => textures.base_color = arguments.last().map(|s| s.to_string()),
+            "map_Bump" | "bump" | "norm" => {
+                textures.normal = arguments.last().map(|s| s.to_string())
+            }
+            "map_Ke" => textures.emissive = arguments.last().map(|s| s.to_string()),
+            _ => {}
+        }
+
+        if material.base_color_factor[3] < 1.0 {
+            material.alpha_mode = AlphaMode::Blend;
+        }
+    }
+
+    Ok(materials)
+}
+
+impl ObjModel {
+    /// Upload the meshes and textures, with one root node per mesh.
+    pub fn upload(
+        &self,
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        command_pool: &CommandPool,
+        queue: vk::Queue,
+    ) -> Result<Model, ObjError> {
+        let nodes = self
+            .meshes
+            .iter()
+            .enumerate()
+            .map(|(index, mesh)| Node {
+                name: mesh.name.clone(),
+                local_transform: IDENTITY,
+                mesh: Some(index),
+                children: Vec::new(),
+            }).collect::<Vec<_>>();
+
+        // Filled in as things are uploaded, so whatever was uploaded before a
+        // failure can be destroyed.
+        let mut model = Model {
+            meshes: Vec::new(),
+            materials: self.materials.clone(),
+            textures: Vec::new(),
+            root_nodes: (0..nodes.len()).collect(),
+            nodes,
+        };
+
+        for (index, path) in self.texture_paths.iter().enumerate() {
+            let is_color = self.materials.iter().any(|material| {
+                material.base_color_texture == Some(index)
+                    || material.emissive_texture == Some(index)
+            });
+            let format = if is_color {
+                vk::Format::R8g8b8a8Srgb
+            } else {
+                vk::Format::R8g8b8a8Unorm
+            };
+
+            let texture = image::open(path)
+                .map_err(|e| ObjError::Image(path.clone(), e))
+                .and_then(|image| {
+                    let image = image.to_rgba();
+                    let (width, height) = image.dimensions();
+                    Texture::from_rgba8(
+                        device,
+                        memory_properties,
+                        command_pool,
+                        queue,
+                        width,
+                        height,
+                        &image.into_raw(),
+                        format,
+                        SamplerDesc::default(),
+                    ).map_err(ObjError::Upload)
+                });
+            match texture {
+                Ok(texture) => model.textures.push(texture),
+                Err(error) => {
+                    model.destroy(device);
+                    return Err(error);
+                }
+            }
+        }
+
+        for mesh in &self.meshes {
+            model.meshes.push(Mesh {
+                name: mesh.name.clone(),
+                primitives: Vec::new(),
+            });
+
+            for data in &mesh.primitives {
+                match Primitive::upload(device, memory_properties, data) {
+                    Ok(primitive) => model.meshes.last_mut().unwrap().primitives.push(primitive),
+                    Err(error) => {
+                        model.destroy(device);
+                        return Err(ObjError::Upload(error));
+                    }
+                }
+            }
+        }
+
+        Ok(model)
+    }
+}
+
+/// Position, uv and normal indices of a face corner, zero based.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+#[derive(Default)]
+struct MeshBuilder {
+    mesh: ObjMesh,
+    current: PrimitiveData,
+    /// Whether every face of `current` referenced normals.
+    current_has_normals: bool,
+    /// Primitives that still need normals generated once finished.
+    missing_normals: Vec<bool>,
+    vertex_cache: HashMap<Corner, u32>,
+}
+
+impl MeshBuilder {
+    fn is_empty(&self) -> bool {
+        self.mesh.primitives.is_empty()
+    }
+
+    fn use_material(&mut self, material: usize) {
+        if self.current.material != Some(material) {
+            self.finish_primitive();
+            self.current.material = Some(material);
+        }
+    }
+
+    fn push_triangle(
+        &mut self,
+        corners: [Corner; 3],
+        positions: &[[f32; 3]],
+        uvs: &[[f32; 2]],
+        normals: &[[f32; 3]],
+    ) {
+        if self.current.indices.is_empty() {
+            self.current_has_normals = true;
+        }
+
+        for &corner in &corners {
+            let (position, uv, normal) = corner;
+            if normal.is_none() {
+                self.current_has_normals = false;
+            }
+
+            let vertices = &mut self.current.vertices;
+            let index = *self.vertex_cache.entry(corner).or_insert_with(|| {
+                vertices.push(Vertex {
+                    position: positions[position],
+                    normal: normal.map(|n| normals[n]).unwrap_or([0.0; 3]),
+                    uv: uv.map(|t| uvs[t]).unwrap_or([0.0; 2]),
+                });
+                vertices.len() as u32 - 1
+            });
+            self.current.indices.push(index);
+        }
+    }
+
+    fn finish_primitive(&mut self) {
+        let material = self.current.material;
+        if !self.current.indices.is_empty() {
+            let primitive = ::std::mem::take(&mut self.current);
+            self.mesh.primitives.push(primitive);
+            self.missing_normals.push(!self.current_has_normals);
+        }
+
+        self.current.material = material;
+        self.vertex_cache.clear();
+    }
+
+    fn finish(mut self, normals: NormalGeneration) -> ObjMesh {
+        for (primitive, &missing) in self.mesh.primitives.iter_mut().zip(&self.missing_normals) {
+            if missing {
+                match normals {
+                    NormalGeneration::Flat => primitive.compute_flat_normals(),
+                    NormalGeneration::Smooth => primitive.compute_smooth_normals(),
+                }
+            }
+        }
+
+        self.mesh
+    }
+}
+
+fn parse_corner(
+    corner: &str,
+    position_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+    line_number: usize,
+) -> Result<Corner, ObjError> {
+    let mut parts = corner.split('/');
+
+    let position = match parts.next() {
+        Some(index) => resolve_index(index, position_count, line_number)?,
+        None => return Err(parse_error(line_number, "face corner without a position")),
+    };
+
+    let uv = match parts.next() {
+        Some("") | None => None,
+        Some(index) => Some(resolve_index(index, uv_count, line_number)?),
+    };
+
+    let normal = match parts.next() {
+        Some("") | None => None,
+        Some(index) => Some(resolve_index(index, normal_count, line_number)?),
+    };
+
+    Ok((position, uv, normal))
+}
+
+/// Turn a one based or negative (relative to the end) index into a zero
+/// based one.
+fn resolve_index(index: &str, count: usize, line_number: usize) -> Result<usize, ObjError> {
+    let index: i64 = index
+        .parse()
+        .map_err(|_| parse_error(line_number, &format!("invalid index {:?}", index)))?;
+
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(parse_error(
+            line_number,
+            &format!("index {} out of range", index),
+        ));
+    }
+
+    Ok(resolved as usize)
+}
+
+/// Parse the first `N` arguments as floats, extra arguments (like the
+/// optional `w` of a position) are ignored.
+fn parse_floats<T: Default + AsMut<[f32]>>(
+    arguments: &[&str],
+    line_number: usize,
+) -> Result<T, ObjError> {
+    let mut values = T::default();
+    {
+        let slice = values.as_mut();
+        if arguments.len() < slice.len() {
+            return Err(parse_error(
+                line_number,
+                &format!("expected {} numbers", slice.len()),
+            ));
+        }
+
+        for (value, argument) in slice.iter_mut().zip(arguments) {
+            *value = argument
+                .parse()
+                .map_err(|_| parse_error(line_number, &format!("invalid number {:?}", argument)))?;
+        }
+    }
+
+    Ok(values)
+}
+
+fn parse_error(line: usize, message: &str) -> ObjError {
+    ObjError::Parse {
+        line,
+        message: message.to_string(),
+    }
+}
+
+/// The non-empty lines of an OBJ or MTL source with comments removed, as
+/// their line number, keyword and arguments.
+fn lines(source: &str) -> impl Iterator<Item = (usize, &str, Vec<&str>)> {
+    source.lines().enumerate().filter_map(|(line_index, line)| {
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+
+        let mut words = line.split_whitespace();
+        words
+            .next()
+            .map(|keyword| (line_index + 1, keyword, words.collect()))
+    })
+}
+
+fn material_libraries(source: &str) -> Vec<String> {
+    lines(source)
+        .filter(|&(_, keyword, _)| keyword == "mtllib")
+        .map(|(_, _, arguments)| arguments.join(" "))
+        .collect()
+}
+
+fn used_materials(source: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (_, keyword, arguments) in lines(source) {
+        if keyword == "usemtl" {
+            let name = arguments.join(" ");
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+fn read_to_string(path: &Path) -> Result<String, ObjError> {
+    let mut source = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut source))
+        .map_err(|e| ObjError::Io(path.to_path_buf(), e))?;
+    Ok(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUAD: &str = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+";
+
+    fn parse_one(source: &str) -> PrimitiveData {
+        let model = parse(source, NormalGeneration::Smooth).unwrap();
+        assert_eq!(model.meshes.len(), 1);
+        assert_eq!(model.meshes[0].primitives.len(), 1);
+        model.meshes[0].primitives[0].clone()
+    }
+
+    fn parse_error_line(source: &str) -> usize {
+        match parse(source, NormalGeneration::Smooth) {
+            Err(ObjError::Parse { line, .. }) => line,
+            result => panic!("expected a parse error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn shared_corners_are_deduplicated() {
+        let source = format!("{}f 1/1/1 2/2/1 3/3/1\nf 1/1/1 3/3/1 4/4/1\n", QUAD);
+        let primitive = parse_one(&source);
+        assert_eq!(primitive.vertices.len(), 4);
+        assert_eq!(primitive.indices, vec![0, 1, 2, 0, 2, 3]);
+
+        // The same position with another uv is a different vertex.
+        let source = format!("{}f 1/1/1 2/2/1 3/3/1\nf 1/2/1 3/3/1 4/4/1\n", QUAD);
+        assert_eq!(parse_one(&source).vertices.len(), 5);
+    }
+
+    #[test]
+    fn polygons_are_triangulated_as_fans() {
+        let source = format!("{}v 0.5 1.5 0\nf 1 2 3 5 4\n", QUAD);
+        let primitive = parse_one(&source);
+        assert_eq!(primitive.indices, vec![0, 1, 2, 0, 2, 3, 0, 3, 4]);
+        assert_eq!(primitive.vertices[3].position, [0.5, 1.5, 0.0]);
+    }
+
+    #[test]
+    fn negative_indices_count_from_the_end() {
+        let absolute = parse_one(&format!("{}f 2/2/1 3/3/1 4/4/1\n", QUAD));
+        let relative = parse_one(&format!("{}f -3/-3/-1 -2/-2/-1 -1/-1/-1\n", QUAD));
+        assert_eq!(absolute.indices, relative.indices);
+        for (a, b) in absolute.vertices.iter().zip(&relative.vertices) {
+            assert_eq!(a.position, b.position);
+            assert_eq!(a.uv, b.uv);
+            assert_eq!(a.normal, b.normal);
+        }
+
+        // Relative indices refer to the vertices declared so far.
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\nv 5 5 5\nf -4 -3 -2\n";
+        let primitive = parse_one(source);
+        assert_eq!(primitive.vertices.len(), 3);
+        assert_eq!(primitive.indices, vec![0, 1, 2, 0, 1, 2]);
+    }
+
+    #[test]
+    fn uvs_are_flipped_and_optional() {
+        let primitive = parse_one(&format!("{}f 1/1 2/2 3/3\n", QUAD));
+        assert_eq!(primitive.vertices[0].uv, [0.0, 1.0]);
+        assert_eq!(primitive.vertices[2].uv, [1.0, 0.0]);
+
+        let primitive = parse_one(&format!("{}f 1//1 2//1 3//1\n", QUAD));
+        assert!(primitive.vertices.iter().all(|v| v.uv == [0.0, 0.0]));
+        assert!(primitive.vertices.iter().all(|v| v.normal == [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn missing_normals_are_generated() {
+        let source = format!("{}f 1 2 3\nf 1 3 4\n", QUAD);
+
+        let smooth = parse_one(&source);
+        assert_eq!(smooth.vertices.len(), 4);
+        for vertex in &smooth.vertices {
+            assert!(vertex.normal[2].abs() > 0.99, "{:?}", vertex.normal);
+        }
+
+        let model = parse(&source, NormalGeneration::Flat).unwrap();
+        let flat = &model.meshes[0].primitives[0];
+        assert_eq!(flat.vertices.len(), 6);
+        for vertex in &flat.vertices {
+            assert!(vertex.normal[2].abs() > 0.99, "{:?}", vertex.normal);
+        }
+    }
+
+    #[test]
+    fn materials_split_primitives_and_objects_split_meshes() {
+        let source = format!(
+            "{}usemtl red\nf 1 2 3\nusemtl blue\nf 1 3 4\no second\nf 1 2 4\n",
+            QUAD
+        );
+        let model = parse(&source, NormalGeneration::Smooth).unwrap();
+        assert_eq!(model.meshes.len(), 2);
+        assert_eq!(model.meshes[0].primitives.len(), 2);
+        assert_eq!(model.meshes[0].primitives[0].material, Some(0));
+        assert_eq!(model.meshes[0].primitives[1].material, Some(1));
+        assert_eq!(model.meshes[1].name, Some("second".to_string()));
+        // The material in use carries over into the next object.
+        assert_eq!(model.meshes[1].primitives[0].material, Some(1));
+    }
+
+    #[test]
+    fn malformed_lines_are_errors() {
+        assert_eq!(parse_error_line("v 0 0\n"), 1);
+        assert_eq!(parse_error_line("v 0 0 0\nv 0 x 0\n"), 2);
+        assert_eq!(parse_error_line(&format!("{}f 1 2\n", QUAD)), 11);
+        assert_eq!(parse_error_line(&format!("{}f 1 2 5\n", QUAD)), 11);
+        assert_eq!(parse_error_line(&format!("{}f 1 2 -5\n", QUAD)), 11);
+        assert_eq!(parse_error_line(&format!("{}f 1 2 0\n", QUAD)), 11);
+        assert_eq!(parse_error_line(&format!("{}f 1/5 2 3\n", QUAD)), 11);
+        assert_eq!(parse_error_line(&format!("{}f 1//2 2 3\n", QUAD)), 11);
+        assert_eq!(parse_error_line(&format!("{}f a 2 3\n", QUAD)), 11);
+    }
+
+    #[test]
+    fn comments_and_unknown_keywords_are_ignored() {
+        let source = format!("# header\n{}g group\ns 1\nf 1 2 3 # trailing\n", QUAD);
+        assert_eq!(parse_one(&source).indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn uvs_may_omit_v() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0.5\nf 1/1 2/1 3/1\n";
+        let primitive = parse_one(source);
+        assert!(primitive.vertices.iter().all(|v| v.uv == [0.5, 1.0]));
+    }
+
+    #[test]
+    fn material_names_ignore_comments() {
+        let source = "mtllib a.mtl # library\nusemtl red # first\nusemtl red\n# usemtl blue\n";
+        assert_eq!(material_libraries(source), vec!["a.mtl".to_string()]);
+        assert_eq!(used_materials(source), vec!["red".to_string()]);
+    }
+
+    #[test]
+    fn mtl_materials_are_parsed() {
+        let source = "
+newmtl glass
+Kd 0.5 0.25 1
+d 0.5
+Pr 0.3
+map_Kd -bm 1 glass.png
+newmtl metal plate
+Pm 1
+Ke 1 2 3
+bump normal.png
+";
+        let materials = parse_mtl(source).unwrap();
+        assert_eq!(materials.len(), 2);
+
+        let (ref name, ref material, ref textures) = materials[0];
+        assert_eq!(name, "glass");
+        assert_eq!(material.base_color_factor, [0.5, 0.25, 1.0, 0.5]);
+        assert_eq!(material.alpha_mode, AlphaMode::Blend);
+        assert_eq!(material.roughness_factor, 0.3);
+        assert_eq!(material.metallic_factor, 0.0);
+        assert_eq!(textures.base_color, Some("glass.png".to_string()));
+
+        let (ref name, ref material, ref textures) = materials[1];
+        assert_eq!(name, "metal plate");
+        assert_eq!(material.metallic_factor, 1.0);
+        assert_eq!(material.emissive_factor, [1.0, 2.0, 3.0]);
+        assert_eq!(textures.normal, Some("normal.png".to_string()));
+    }
+
+    #[test]
+    fn malformed_mtl_lines_are_errors() {
+        match parse_mtl("Kd 1 1 1\n") {
+            Err(ObjError::Parse { line: 1, .. }) => {}
+            result => panic!("{:?}", result),
+        }
+        match parse_mtl("newmtl a\nKd 1 1\n") {
+            Err(ObjError::Parse { line: 2, .. }) => {}
+            result => panic!("{:?}", result),
+        }
+    }
+}
@@ -0,0 +1,560 @@
+//! Renders the post-processing stack headless and compares the results with
+//! the images in `assets/golden`.
+//!
+//! ```text
+//! cargo run --bin golden                  # compare every case
+//! cargo run --bin golden -- bloom fxaa    # compare some of them
+//! cargo run --bin golden -- --update      # write the images instead
+//! ```
+//!
+//! Software drivers like lavapipe or SwiftShader are picked over GPUs, so the
+//! images come out the same on every machine. `--gpu` picks a device by
+//! index or by part of its name instead. The images in the repository are
+//! written with lavapipe, on a machine with other drivers installed the
+//! loader can be limited to it:
+//!
+//! ```text
+//! VK_ICD_FILENAMES=/usr/share/vulkan/icd.d/lvp_icd.x86_64.json \
+//!     cargo run --bin golden -- --update
+//! ```
+//!
+//! A case without an image fails, the output of failed cases is written to
+//! `target/golden` to look at.
+//!
+//! `cargo test --test golden -- --ignored` runs the comparison as a test.
+extern crate ash;
+extern crate ash_toy_engine;
+extern crate image;
+
+use ash::version::{DeviceV1_0, InstanceV1_0, V1_0};
+use ash::vk;
+use ash::{Device, Entry, Instance};
+use std::env;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::slice;
+
+use ash_toy_engine::engine::barrier::{AccessType, Barrier};
+use ash_toy_engine::engine::buffer::Buffer;
+use ash_toy_engine::engine::command_pool::CommandPool;
+use ash_toy_engine::engine::device::Device as EngineDevice;
+use ash_toy_engine::engine::image::{self as engine_image, Image, ImageDesc};
+use ash_toy_engine::engine::instance;
+use ash_toy_engine::engine::pipeline::{self, RenderPass};
+use ash_toy_engine::engine::post::{self, PostSettings, PostStack, PostTargets, TonemapOperator};
+use ash_toy_engine::engine::render_target::RenderTarget;
+use ash_toy_engine::engine::texture::{self, SamplerDesc};
+use ash_toy_engine::engine::upload::{self, UploadDst, Uploader};
+
+const GOLDEN_DIR: &str = "assets/golden";
+
+/// Where the output of failed cases is written, next to the build output.
+const FAILED_DIR: &str = "target/golden";
+
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 256;
+
+/// Linear 8 bit output, so the images hold what the shaders wrote.
+const OUTPUT_FORMAT: vk::Format = vk::Format::R8g8b8a8Unorm;
+
+/// Largest difference allowed per channel, drivers may round and filter a
+/// little differently.
+const TOLERANCE: u8 = 2;
+
+const CASES: &[&str] = &[
+    "reinhard", "aces", "bloom", "fxaa", "vignette", "gamma", "all",
+];
+
+/// The settings of `case`, every other effect is off.
+fn case_settings(case: &str) -> PostSettings {
+    let mut settings = PostSettings::default();
+    settings.bloom.enabled = false;
+    settings.fxaa.enabled = false;
+    settings.vignette.enabled = false;
+    settings.gamma.enabled = false;
+    settings.tonemap.operator = TonemapOperator::Aces;
+
+    match case {
+        "reinhard" => settings.tonemap.operator = TonemapOperator::Reinhard,
+        "aces" => {}
+        "bloom" => {
+            settings.bloom.enabled = true;
+            settings.bloom.intensity = 0.2;
+        }
+        "fxaa" => settings.fxaa.enabled = true,
+        "vignette" => settings.vignette.enabled = true,
+        "gamma" => settings.gamma.enabled = true,
+        "all" => {
+            settings = PostSettings::default();
+            settings.gamma.enabled = true;
+        }
+        _ => unreachable!(),
+    }
+
+    settings
+}
+
+fn main() {
+    let mut update = false;
+    let mut gpu = None;
+    let mut cases = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--update" => update = true,
+            "--gpu" => {
+                gpu = Some(args.next().unwrap_or_else(|| usage("missing value for --gpu")))
+            }
+            case if CASES.contains(&case) => cases.push(arg.clone()),
+            _ => usage(&format!("unknown case or option `{}`", arg)),
+        }
+    }
+    if cases.is_empty() {
+        cases = CASES.iter().map(|&case| case.to_owned()).collect();
+    }
+
+    let mut renderer = Renderer::new(gpu.as_deref());
+    let mut failed = 0;
+
+    for case in &cases {
+        let pixels = renderer.render(case_settings(case));
+        let path = Path::new(GOLDEN_DIR).join(format!("{}.png", case));
+
+        if update {
+            save(&path, &pixels);
+            println!("{}: written to {}", case, path.display());
+            continue;
+        }
+
+        match compare(&path, &pixels) {
+            Ok(()) => println!("{}: ok", case),
+            Err(error) => {
+                let failed_path = Path::new(FAILED_DIR).join(format!("{}.png", case));
+                save(&failed_path, &pixels);
+                println!(
+                    "{}: FAILED, {}, the output is in {}",
+                    case,
+                    error,
+                    failed_path.display()
+                );
+                failed += 1;
+            }
+        }
+    }
+
+    renderer.destroy();
+
+    if failed > 0 {
+        println!("{} of {} cases failed", failed, cases.len());
+        process::exit(1);
+    }
+}
+
+fn usage(error: &str) -> ! {
+    eprintln!("{}", error);
+    eprintln!(
+        "usage: golden [--update] [--gpu <index or name>] [{}]...",
+        CASES.join("|")
+    );
+    process::exit(2);
+}
+
+/// Compare tightly packed RGBA pixels with the image at `path`.
+fn compare(path: &Path, pixels: &[u8]) -> Result<(), String> {
+    let golden = image::open(path)
+        .map_err(|error| format!("unable to read {}: {}", path.display(), error))?
+        .to_rgba();
+    if golden.width() != WIDTH || golden.height() != HEIGHT {
+        return Err(format!(
+            "{} is {}x{}, expected {}x{}",
+            path.display(),
+            golden.width(),
+            golden.height(),
+            WIDTH,
+            HEIGHT
+        ));
+    }
+
+    let (mismatches, max_difference) = golden
+        .into_raw()
+        .iter()
+        .zip(pixels.iter())
+        .map(|(&a, &b)| a.abs_diff(b))
+        .filter(|&difference| difference > TOLERANCE)
+        .fold((0, 0), |(count, max), difference| {
+            (count + 1, max.max(difference))
+        });
+
+    if mismatches > 0 {
+        return Err(format!(
+            "{} channels differ by more than {}, by up to {}",
+            mismatches, TOLERANCE, max_difference
+        ));
+    }
+
+    Ok(())
+}
+
+fn save(path: &Path, pixels: &[u8]) {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).expect("Unable to create the output directory");
+    }
+    image::save_buffer(path, pixels, WIDTH, HEIGHT, image::ColorType::RGBA(8))
+        .unwrap_or_else(|error| panic!("Unable to write {}: {}", path.display(), error));
+}
+
+/// A device without a surface, rendering the stack from a procedural scene
+/// into an offscreen target.
+struct Renderer {
+    _entry: Entry<V1_0>,
+    instance: Instance<V1_0>,
+    device: Device<V1_0>,
+    queue: vk::Queue,
+    command_pool: CommandPool,
+    uploader: Uploader,
+    scene: Image,
+    scene_view: vk::ImageView,
+    scene_sampler: vk::Sampler,
+    output_pass: RenderPass,
+    output: RenderTarget,
+    stack: PostStack,
+    targets: PostTargets,
+    readback: Buffer,
+}
+
+impl Renderer {
+    fn new(gpu: Option<&str>) -> Self {
+        let entry = Entry::new().unwrap();
+        let instance = instance::create_instance(
+            &entry,
+            CString::new("golden").unwrap(),
+            CString::new("golden").unwrap(),
+            &Vec::new(),
+            &Vec::new(),
+        ).unwrap();
+
+        let (pdevice, queue_index) = pick_physical_device(&instance, gpu);
+        let device =
+            EngineDevice::new(&instance, Vec::new(), Vec::new(), queue_index, None, pdevice)
+                .expect("Unable to create device")
+                .device;
+        let queue = unsafe { device.get_device_queue(queue_index, 0) };
+
+        let memory_properties = instance.get_physical_device_memory_properties(pdevice);
+        let device_properties = instance.get_physical_device_properties(pdevice);
+        let extent = vk::Extent2D {
+            width: WIDTH,
+            height: HEIGHT,
+        };
+
+        let command_pool = CommandPool::new(&device, 1, queue_index);
+        let mut uploader = Uploader::new(
+            &device,
+            &memory_properties,
+            upload::Queue {
+                queue,
+                family_index: queue_index,
+            },
+            None,
+            upload::DEFAULT_STAGING_SIZE,
+        ).expect("Unable to create the staging buffer");
+
+        let scene = Image::create(
+            &instance,
+            pdevice,
+            &device,
+            &memory_properties,
+            &ImageDesc::new_2d(post::HDR_FORMAT, WIDTH, HEIGHT)
+                .with_usage(vk::IMAGE_USAGE_TRANSFER_DST_BIT | vk::IMAGE_USAGE_SAMPLED_BIT),
+        ).unwrap_or_else(|error| panic!("Unable to create the scene, {}", error));
+        uploader
+            .upload_image(
+                &device,
+                &memory_properties,
+                scene.image,
+                vk::ImageSubresourceLayers {
+                    aspect_mask: vk::IMAGE_ASPECT_COLOR_BIT,
+                    mip_level: 0,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                },
+                scene.extent,
+                &scene_texels(),
+                vk::ImageLayout::ShaderReadOnlyOptimal,
+                UploadDst::fragment_shader_read(),
+            ).expect("Unable to upload the scene");
+        let ticket = uploader.flush(&device, &[]);
+        uploader.wait(&device, ticket);
+
+        let scene_view = scene.create_view(&device, &scene.view_desc());
+        let scene_sampler = texture::create_sampler(
+            &device,
+            &SamplerDesc {
+                address_mode_u: vk::SamplerAddressMode::ClampToEdge,
+                address_mode_v: vk::SamplerAddressMode::ClampToEdge,
+                ..SamplerDesc::default()
+            },
+        );
+
+        let output_pass =
+            RenderPass::offscreen(&device, OUTPUT_FORMAT, None, vk::SAMPLE_COUNT_1_BIT);
+        let output = RenderTarget::new(
+            &instance,
+            pdevice,
+            &device,
+            &memory_properties,
+            output_pass,
+            extent,
+        ).unwrap_or_else(|error| panic!("Unable to create the output, {}", error));
+
+        let stack = PostStack::new(
+            &device,
+            &device_properties.limits,
+            output_pass,
+            PostSettings::default(),
+        );
+        let targets = PostTargets::new(
+            &instance,
+            pdevice,
+            &device,
+            &memory_properties,
+            &stack,
+            scene_view,
+            scene_sampler,
+            extent,
+        ).unwrap_or_else(|error| {
+            panic!("Unable to create the post-processing targets, {}", error)
+        });
+
+        let readback = Buffer::new(
+            &device,
+            &memory_properties,
+            (WIDTH * HEIGHT * 4) as vk::DeviceSize,
+            vk::BUFFER_USAGE_TRANSFER_DST_BIT,
+            vk::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vk::MEMORY_PROPERTY_HOST_COHERENT_BIT,
+        ).expect("Unable to create the readback buffer");
+
+        Renderer {
+            _entry: entry,
+            instance,
+            device,
+            queue,
+            command_pool,
+            uploader,
+            scene,
+            scene_view,
+            scene_sampler,
+            output_pass,
+            output,
+            stack,
+            targets,
+            readback,
+        }
+    }
+
+    /// Run the stack with `settings` and read the output back as tightly
+    /// packed RGBA pixels.
+    fn render(&mut self, settings: PostSettings) -> Vec<u8> {
+        let device = &self.device;
+        let stack = &mut self.stack;
+        let targets = &self.targets;
+        let output = &mut self.output;
+        let readback = &self.readback;
+
+        stack.settings = settings;
+        // Where the render pass of the last run left it
+        output
+            .color
+            .assume_access(&[AccessType::FragmentShaderSampledRead]);
+
+        self.command_pool
+            .one_time_submit(device, self.queue, |command_buffer| {
+                stack.render_offscreen(device, command_buffer, targets);
+
+                output.begin(device, command_buffer, [0.0; 4], 0.0);
+                let viewport = pipeline::Viewport::new(output.extent);
+                unsafe {
+                    device.cmd_set_viewport(command_buffer, 0, &[viewport.viewport]);
+                    device.cmd_set_scissor(command_buffer, 0, &[viewport.scissor]);
+                }
+                stack.render(device, command_buffer, targets);
+                output.end(device, command_buffer);
+
+                Barrier::new()
+                    .with_image(&mut output.color, &[AccessType::TransferSrc])
+                    .record(device, command_buffer);
+
+                let region = vk::BufferImageCopy {
+                    buffer_offset: 0,
+                    buffer_row_length: 0,
+                    buffer_image_height: 0,
+                    image_subresource: vk::ImageSubresourceLayers {
+                        aspect_mask: vk::IMAGE_ASPECT_COLOR_BIT,
+                        mip_level: 0,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    },
+                    image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+                    image_extent: output.color.extent,
+                };
+                unsafe {
+                    device.cmd_copy_image_to_buffer(
+                        command_buffer,
+                        output.color.image,
+                        vk::ImageLayout::TransferSrcOptimal,
+                        readback.buffer,
+                        &[region],
+                    );
+                }
+
+                Barrier::new()
+                    .with_buffer(
+                        readback.buffer,
+                        &[AccessType::TransferDst],
+                        &[AccessType::HostRead],
+                    ).record(device, command_buffer);
+            });
+
+        unsafe {
+            let mapped = device
+                .map_memory(
+                    readback.memory,
+                    0,
+                    readback.size,
+                    vk::MemoryMapFlags::empty(),
+                ).expect("Unable to map the readback buffer");
+            let pixels =
+                slice::from_raw_parts(mapped as *const u8, readback.size as usize).to_vec();
+            device.unmap_memory(readback.memory);
+            pixels
+        }
+    }
+
+    fn destroy(mut self) {
+        let device = &self.device;
+
+        device.device_wait_idle().unwrap();
+        self.uploader.destroy(device);
+        self.readback.destroy(device);
+        self.targets.destroy(device);
+        self.stack.destroy(device);
+        self.output.destroy(device);
+        self.output_pass.destroy(device);
+        unsafe {
+            device.destroy_sampler(self.scene_sampler, None);
+            device.destroy_image_view(self.scene_view, None);
+        }
+        self.scene.destroy(device);
+        unsafe {
+            device.destroy_command_pool(self.command_pool.command_pool, None);
+            device.destroy_device(None);
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+/// The device matching `gpu`, otherwise the first CPU device, otherwise the
+/// first device, with a graphics queue family.
+fn pick_physical_device(
+    instance: &Instance<V1_0>,
+    gpu: Option<&str>,
+) -> (vk::PhysicalDevice, u32) {
+    let pdevices = instance
+        .enumerate_physical_devices()
+        .expect("Unable to enumerate physical devices");
+
+    let mut candidates = pdevices
+        .iter()
+        .enumerate()
+        .filter_map(|(index, &pdevice)| {
+            let properties = instance.get_physical_device_properties(pdevice);
+            let name = unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }
+                .to_string_lossy()
+                .into_owned();
+            let queue_index = instance
+                .get_physical_device_queue_family_properties(pdevice)
+                .iter()
+                .position(|info| {
+                    info.queue_count > 0 && info.queue_flags.subset(vk::QUEUE_GRAPHICS_BIT)
+                })?;
+            let matches = match gpu {
+                Some(gpu) => match gpu.parse::<usize>() {
+                    Ok(gpu_index) => gpu_index == index,
+                    Err(_) => name.to_lowercase().contains(&gpu.to_lowercase()),
+                },
+                None => true,
+            };
+
+            if matches {
+                Some((pdevice, queue_index as u32, name, properties.device_type))
+            } else {
+                None
+            }
+        }).collect::<Vec<_>>();
+
+    // Stable, so devices of the same type keep their order
+    candidates
+        .sort_by_key(|&(_, _, _, device_type)| device_type != vk::PhysicalDeviceType::Cpu);
+
+    match candidates.into_iter().next() {
+        Some((pdevice, queue_index, name, _)) => {
+            println!("Rendering with {}", name);
+            (pdevice, queue_index)
+        }
+        None => {
+            eprintln!("No device with a graphics queue matches {:?}", gpu);
+            process::exit(1);
+        }
+    }
+}
+
+/// An HDR test scene in `HDR_FORMAT`: a brightness ramp from 0 to 4 across,
+/// colored bands down, a hard diagonal edge for FXAA and bright spots for
+/// bloom.
+fn scene_texels() -> Vec<u8> {
+    let spots = [(0.25, 0.25), (0.75, 0.3), (0.5, 0.75)];
+
+    let mut texels = Vec::with_capacity((WIDTH * HEIGHT * 8) as usize);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let u = (x as f32 + 0.5) / WIDTH as f32;
+            let v = (y as f32 + 0.5) / HEIGHT as f32;
+
+            let brightness = u * 4.0;
+            let band = [
+                [1.0, 0.2, 0.2],
+                [0.2, 1.0, 0.2],
+                [0.2, 0.2, 1.0],
+                [1.0, 1.0, 1.0],
+            ][((v * 4.0) as usize).min(3)];
+            let mut color = [
+                band[0] * brightness,
+                band[1] * brightness,
+                band[2] * brightness,
+            ];
+
+            // Darken below the diagonal, a single pixel step
+            if x * 3 < y * 2 {
+                color = [color[0] * 0.1, color[1] * 0.1, color[2] * 0.1];
+            }
+
+            let spot = spots
+                .iter()
+                .any(|&(sx, sy): &(f32, f32)| (u - sx).hypot(v - sy) < 0.02);
+            if spot {
+                color = [20.0, 18.0, 12.0];
+            }
+
+            for &channel in color.iter().chain(Some(&1.0)) {
+                let bits = engine_image::f16_bits(channel);
+                texels.push(bits as u8);
+                texels.push((bits >> 8) as u8);
+            }
+        }
+    }
+
+    texels
+}
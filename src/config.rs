@@ -0,0 +1,713 @@
+use ash::vk;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use toml;
+
+use app::window::WindowMode;
+use engine::deferred::Shading;
+use engine::multisample;
+use engine::post::{PostSettings, MAX_BLOOM_LEVELS};
+use engine::surface::{ColorSpacePreference, PresentModePolicy};
+
+/// Environment variable naming the config file, `--config` takes precedence.
+const CONFIG_ENV: &str = "ASH_CONFIG";
+
+/// Prefix of the environment variables overriding single settings, the rest
+/// is the key in upper case with dots replaced by underscores, e.g.
+/// `ASH_WINDOW_WIDTH`.
+const ENV_PREFIX: &str = "ASH_";
+
+/// Every setting that can be overridden, with its command line flag and
+/// whether the flag can be given without a value.
+const OPTIONS: &[(&str, &str, bool)] = &[
+    ("app.name", "app-name", false),
+    ("app.engine_name", "engine-name", false),
+    ("window.title", "title", false),
+    ("window.width", "width", false),
+    ("window.height", "height", false),
+    ("window.mode", "window-mode", false),
+    ("window.monitor", "monitor", false),
+    ("graphics.vsync", "vsync", false),
+    ("graphics.frames_in_flight", "frames-in-flight", false),
+    ("graphics.image_count", "image-count", false),
+    ("graphics.gpu", "gpu", false),
+    ("graphics.msaa", "msaa", false),
+    ("graphics.shading", "shading", false),
+    ("graphics.color_space", "color-space", false),
+    ("post.tonemap.enabled", "tonemap", true),
+    ("post.tonemap.operator", "tonemap-operator", false),
+    ("post.tonemap.exposure", "exposure", false),
+    ("post.bloom.enabled", "bloom", true),
+    ("post.bloom.threshold", "bloom-threshold", false),
+    ("post.bloom.intensity", "bloom-intensity", false),
+    ("post.bloom.levels", "bloom-levels", false),
+    ("post.fxaa.enabled", "fxaa", true),
+    ("post.vignette.enabled", "vignette", true),
+    ("post.vignette.strength", "vignette-strength", false),
+    ("post.vignette.radius", "vignette-radius", false),
+    ("post.gamma.enabled", "gamma", true),
+    ("post.gamma.value", "gamma-value", false),
+    ("debug.validation", "validation", true),
+    ("debug.layers", "layers", false),
+];
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    /// `source` is the file, environment variable or flag the value came from.
+    Invalid {
+        source: String,
+        key: String,
+        value: String,
+        expected: &'static str,
+    },
+    UnknownOption(String),
+    MissingValue(String),
+    /// A valid value the device or the displays can not handle.
+    Unsupported {
+        key: String,
+        value: String,
+        supported: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io(ref path, ref error) => {
+                write!(f, "unable to read {}: {}", path.display(), error)
+            }
+            ConfigError::Parse(ref path, ref error) => write!(f, "{}: {}", path.display(), error),
+            ConfigError::Invalid {
+                ref source,
+                ref key,
+                ref value,
+                expected,
+            } => write!(
+                f,
+                "{}: invalid value `{}` for {}, expected {}",
+                source, value, key, expected
+            ),
+            ConfigError::UnknownOption(ref option) => write!(f, "unknown option `{}`", option),
+            ConfigError::MissingValue(ref option) => write!(f, "missing value for `{}`", option),
+            ConfigError::Unsupported {
+                ref key,
+                ref value,
+                ref supported,
+            } => write!(
+                f,
+                "{} = {} is not supported on this system, supported: {}",
+                key, value, supported
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    /// Reported to the driver, which may use it to pick per-application
+    /// settings.
+    pub name: String,
+    pub engine_name: String,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            name: "test".to_owned(),
+            engine_name: "test".to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    pub mode: WindowMode,
+    /// Index of the monitor, or a case insensitive part of its name. The
+    /// primary monitor when unset.
+    pub monitor: Option<String>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            title: "Ash test".to_owned(),
+            width: 1024,
+            height: 768,
+            mode: WindowMode::Windowed,
+            monitor: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GraphicsConfig {
+    pub vsync: PresentModePolicy,
+    /// Frames that can be recorded while the device is still working on
+    /// earlier ones.
+    pub frames_in_flight: u32,
+    /// Swapchain images to ask for, clamped to what the surface supports.
+    /// One more than the surface minimum when unset.
+    pub image_count: Option<u32>,
+    /// Index of the physical device, or a case insensitive part of its name.
+    /// The first suitable device when unset.
+    pub gpu: Option<String>,
+    /// Samples per pixel of the main render pass, 1 disables multisampling.
+    pub msaa: u32,
+    /// Forward or deferred, deferred shading needs `msaa` at 1.
+    pub shading: Shading,
+    /// Color space of the swapchain, the HDR ones fall back to sRGB when
+    /// the surface does not support them.
+    pub color_space: ColorSpacePreference,
+}
+
+impl Default for GraphicsConfig {
+    fn default() -> Self {
+        GraphicsConfig {
+            vsync: PresentModePolicy::LowLatency,
+            frames_in_flight: 2,
+            image_count: None,
+            gpu: None,
+            msaa: 1,
+            shading: Shading::Forward,
+            color_space: ColorSpacePreference::Srgb,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DebugConfig {
+    /// Load the validation layers and print what they report.
+    pub validation: bool,
+    pub layers: Vec<String>,
+}
+
+impl Default for DebugConfig {
+    fn default() -> Self {
+        DebugConfig {
+            validation: true,
+            layers: vec!["VK_LAYER_LUNARG_standard_validation".to_owned()],
+        }
+    }
+}
+
+/// Engine settings, read from a TOML file and overridden from the
+/// environment and the command line, in that order.
+///
+/// ```text
+/// [window]
+/// width = 1280
+/// height = 720
+///
+/// [graphics]
+/// vsync = "on"
+/// msaa = 4
+///
+/// [post.bloom]
+/// enabled = false
+/// ```
+///
+/// Every setting can be left out to keep its default.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub app: AppConfig,
+    pub window: WindowConfig,
+    pub graphics: GraphicsConfig,
+    pub post: PostSettings,
+    pub debug: DebugConfig,
+    /// The environment variable or flag each overridden setting came from,
+    /// to name it in validation errors.
+    #[serde(skip)]
+    sources: HashMap<String, String>,
+}
+
+impl Config {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, ConfigError> {
+        let path = path.as_ref();
+        let config = Config::read(path)?;
+        config.validate(&path.display().to_string())?;
+        Ok(config)
+    }
+
+    /// Parse a file without validating it, overrides may still fix it.
+    fn read(path: &Path) -> Result<Config, ConfigError> {
+        let source =
+            fs::read_to_string(path).map_err(|error| ConfigError::Io(path.to_owned(), error))?;
+        toml::from_str(&source).map_err(|error| ConfigError::Parse(path.to_owned(), error))
+    }
+
+    /// Load the file given with `--config` or `ASH_CONFIG`, otherwise
+    /// `default_path` if it exists, and apply the overrides from the
+    /// environment and from `args`.
+    ///
+    /// Returns the config and the arguments that are not options.
+    pub fn load_with_overrides<P: AsRef<Path>>(
+        default_path: P,
+        args: Vec<String>,
+    ) -> Result<(Config, Vec<String>), ConfigError> {
+        let (path, args) = take_config_path(args)?;
+
+        let default_path = default_path.as_ref();
+        let path = path
+            .or_else(|| env::var(CONFIG_ENV).ok().map(PathBuf::from))
+            .or_else(|| Some(default_path.to_owned()).filter(|path| path.exists()));
+
+        let (mut config, source) = match path {
+            Some(path) => (Config::read(&path)?, path.display().to_string()),
+            None => (Config::default(), "defaults".to_owned()),
+        };
+
+        config.apply_env()?;
+        let rest = config.apply_args(args)?;
+
+        // Only the end result has to be valid, an override may depend on
+        // another one, like `--no-validation` on an empty `--layers`.
+        config.validate(&source)?;
+
+        Ok((config, rest))
+    }
+
+    /// Override settings from `ASH_<KEY>` environment variables.
+    ///
+    /// Like every override, this is not validated until `validate` is
+    /// called.
+    pub fn apply_env(&mut self) -> Result<(), ConfigError> {
+        for &(key, _, _) in OPTIONS {
+            let name = format!("{}{}", ENV_PREFIX, key.replace('.', "_").to_uppercase());
+            if let Ok(value) = env::var(&name) {
+                self.set_from(&name, key, &value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Override settings from `--flag value` or `--flag=value` arguments,
+    /// flags of on/off settings can be given without a value or with a
+    /// `no-` prefix.
+    ///
+    /// Returns the arguments that are not options.
+    pub fn apply_args<I: IntoIterator<Item = String>>(
+        &mut self,
+        args: I,
+    ) -> Result<Vec<String>, ConfigError> {
+        let mut rest = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                rest.push(arg);
+                continue;
+            }
+
+            let (flag, value) = match arg.find('=') {
+                Some(index) => (arg[2..index].to_owned(), Some(arg[index + 1..].to_owned())),
+                None => (arg[2..].to_owned(), None),
+            };
+
+            let negated = flag.starts_with("no-");
+            let option = OPTIONS.iter().find(|&&(_, name, is_switch)| {
+                name == flag || (is_switch && negated && name == &flag[3..])
+            });
+            let &(key, _, is_switch) = option.ok_or_else(|| ConfigError::UnknownOption(arg.clone()))?;
+
+            let value = match value {
+                Some(value) => value,
+                None if is_switch => (!negated).to_string(),
+                None => args
+                    .next()
+                    .ok_or_else(|| ConfigError::MissingValue(arg.clone()))?,
+            };
+
+            self.set_from(&format!("--{}", flag), key, &value)?;
+        }
+
+        Ok(rest)
+    }
+
+    /// Set a single setting by its key, e.g. `window.width`, call `validate`
+    /// once every change is made.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        self.set_from(key, key, value)
+    }
+
+    fn set_from(&mut self, source: &str, key: &str, value: &str) -> Result<(), ConfigError> {
+        let invalid = |expected| ConfigError::Invalid {
+            source: source.to_owned(),
+            key: key.to_owned(),
+            value: value.to_owned(),
+            expected,
+        };
+
+        match key {
+            "app.name" => self.app.name = value.to_owned(),
+            "app.engine_name" => self.app.engine_name = value.to_owned(),
+            "window.title" => self.window.title = value.to_owned(),
+            "window.width" => self.window.width = value.parse().map_err(|_| invalid(POSITIVE))?,
+            "window.height" => {
+                self.window.height = value.parse().map_err(|_| invalid(POSITIVE))?
+            }
+            "window.mode" => {
+                self.window.mode = value.parse().map_err(|_| invalid(WINDOW_MODE))?
+            }
+            "window.monitor" => {
+                self.window.monitor = match value {
+                    "" | "auto" => None,
+                    value => Some(value.to_owned()),
+                }
+            }
+            "graphics.vsync" => self.graphics.vsync = value.parse().map_err(|_| invalid(VSYNC))?,
+            "graphics.frames_in_flight" => {
+                self.graphics.frames_in_flight =
+                    value.parse().map_err(|_| invalid(FRAMES_IN_FLIGHT))?
+            }
+            "graphics.image_count" => {
+                self.graphics.image_count = match value {
+                    "" | "auto" => None,
+                    value => Some(value.parse().map_err(|_| invalid(POSITIVE))?),
+                }
+            }
+            "graphics.gpu" => {
+                self.graphics.gpu = match value {
+                    "" | "auto" => None,
+                    value => Some(value.to_owned()),
+                }
+            }
+            "graphics.msaa" => self.graphics.msaa = value.parse().map_err(|_| invalid(MSAA))?,
+            "graphics.shading" => {
+                self.graphics.shading = value.parse().map_err(|_| invalid(SHADING))?
+            }
+            "graphics.color_space" => {
+                self.graphics.color_space = value.parse().map_err(|_| invalid(COLOR_SPACE))?
+            }
+            "post.tonemap.enabled" => {
+                self.post.tonemap.enabled = value.parse().map_err(|_| invalid(BOOLEAN))?
+            }
+            "post.tonemap.operator" => {
+                self.post.tonemap.operator = value.parse().map_err(|_| invalid(TONEMAP))?
+            }
+            "post.tonemap.exposure" => {
+                self.post.tonemap.exposure = value.parse().map_err(|_| invalid(POSITIVE_NUMBER))?
+            }
+            "post.bloom.enabled" => {
+                self.post.bloom.enabled = value.parse().map_err(|_| invalid(BOOLEAN))?
+            }
+            "post.bloom.threshold" => {
+                self.post.bloom.threshold = value.parse().map_err(|_| invalid(NUMBER))?
+            }
+            "post.bloom.intensity" => {
+                self.post.bloom.intensity = value.parse().map_err(|_| invalid(NUMBER))?
+            }
+            "post.bloom.levels" => {
+                self.post.bloom.levels = value.parse().map_err(|_| invalid(BLOOM_LEVELS))?
+            }
+            "post.fxaa.enabled" => {
+                self.post.fxaa.enabled = value.parse().map_err(|_| invalid(BOOLEAN))?
+            }
+            "post.vignette.enabled" => {
+                self.post.vignette.enabled = value.parse().map_err(|_| invalid(BOOLEAN))?
+            }
+            "post.vignette.strength" => {
+                self.post.vignette.strength = value.parse().map_err(|_| invalid(UNIT))?
+            }
+            "post.vignette.radius" => {
+                self.post.vignette.radius = value.parse().map_err(|_| invalid(NUMBER))?
+            }
+            "post.gamma.enabled" => {
+                self.post.gamma.enabled = value.parse().map_err(|_| invalid(BOOLEAN))?
+            }
+            "post.gamma.value" => {
+                self.post.gamma.value = value.parse().map_err(|_| invalid(POSITIVE_NUMBER))?
+            }
+            "debug.validation" => {
+                self.debug.validation = value.parse().map_err(|_| invalid(BOOLEAN))?
+            }
+            "debug.layers" => {
+                self.debug.layers = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|layer| !layer.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            }
+            _ => return Err(ConfigError::UnknownOption(key.to_owned())),
+        }
+
+        self.sources.insert(key.to_owned(), source.to_owned());
+        Ok(())
+    }
+
+    /// Check the values serde can not. Errors name where the value was
+    /// overridden, or `source` for values that were not.
+    pub fn validate(&self, source: &str) -> Result<(), ConfigError> {
+        let invalid = |key: &str, value: &dyn fmt::Display, expected| {
+            Err(ConfigError::Invalid {
+                source: self
+                    .sources
+                    .get(key)
+                    .map(String::as_str)
+                    .unwrap_or(source)
+                    .to_owned(),
+                key: key.to_owned(),
+                value: value.to_string(),
+                expected,
+            })
+        };
+
+        if self.window.width == 0 {
+            return invalid("window.width", &self.window.width, POSITIVE);
+        }
+        if self.window.height == 0 {
+            return invalid("window.height", &self.window.height, POSITIVE);
+        }
+        if self.graphics.frames_in_flight == 0 || self.graphics.frames_in_flight > 8 {
+            return invalid(
+                "graphics.frames_in_flight",
+                &self.graphics.frames_in_flight,
+                FRAMES_IN_FLIGHT,
+            );
+        }
+        if let Some(image_count) = self.graphics.image_count {
+            if image_count == 0 {
+                return invalid("graphics.image_count", &image_count, POSITIVE);
+            }
+        }
+        if self.msaa_samples().is_none() {
+            return invalid("graphics.msaa", &self.graphics.msaa, MSAA);
+        }
+        if self.graphics.shading == Shading::Deferred && self.graphics.msaa != 1 {
+            return invalid("graphics.msaa", &self.graphics.msaa, "1 with deferred shading");
+        }
+        let post = &self.post;
+        if post.tonemap.exposure <= 0.0 {
+            return invalid("post.tonemap.exposure", &post.tonemap.exposure, POSITIVE_NUMBER);
+        }
+        if post.bloom.threshold < 0.0 {
+            return invalid("post.bloom.threshold", &post.bloom.threshold, NUMBER);
+        }
+        if post.bloom.intensity < 0.0 {
+            return invalid("post.bloom.intensity", &post.bloom.intensity, NUMBER);
+        }
+        if post.bloom.levels == 0 || post.bloom.levels > MAX_BLOOM_LEVELS {
+            return invalid("post.bloom.levels", &post.bloom.levels, BLOOM_LEVELS);
+        }
+        if post.vignette.strength < 0.0 || post.vignette.strength > 1.0 {
+            return invalid("post.vignette.strength", &post.vignette.strength, UNIT);
+        }
+        if post.vignette.radius < 0.0 {
+            return invalid("post.vignette.radius", &post.vignette.radius, NUMBER);
+        }
+        if post.gamma.value <= 0.0 {
+            return invalid("post.gamma.value", &post.gamma.value, POSITIVE_NUMBER);
+        }
+        if self.debug.validation && self.debug.layers.is_empty() {
+            return invalid("debug.layers", &"", "at least one layer with validation on");
+        }
+
+        Ok(())
+    }
+
+    /// The sample count flag for `graphics.msaa`.
+    pub fn msaa_samples(&self) -> Option<vk::SampleCountFlags> {
+        multisample::sample_count_flag(self.graphics.msaa)
+    }
+}
+
+const POSITIVE: &str = "a positive integer";
+const BOOLEAN: &str = "true or false";
+const WINDOW_MODE: &str = "windowed, borderless or exclusive";
+const VSYNC: &str = "off, mailbox, on or adaptive";
+const FRAMES_IN_FLIGHT: &str = "an integer from 1 to 8";
+const MSAA: &str = "1, 2, 4, 8, 16, 32 or 64";
+const SHADING: &str = "forward or deferred";
+const COLOR_SPACE: &str = "srgb, hdr10 or scrgb";
+const TONEMAP: &str = "reinhard or aces";
+const NUMBER: &str = "a number of at least 0";
+const POSITIVE_NUMBER: &str = "a number above 0";
+const UNIT: &str = "a number from 0 to 1";
+const BLOOM_LEVELS: &str = "an integer from 1 to 8";
+
+/// Remove `--config <path>` or `--config=<path>` from `args`.
+fn take_config_path(args: Vec<String>) -> Result<(Option<PathBuf>, Vec<String>), ConfigError> {
+    let mut path = None;
+    let mut rest = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            let value = args
+                .next()
+                .ok_or_else(|| ConfigError::MissingValue(arg.clone()))?;
+            path = Some(PathBuf::from(value));
+        } else if let Some(value) = arg.strip_prefix("--config=") {
+            path = Some(PathBuf::from(value));
+        } else {
+            rest.push(arg);
+        }
+    }
+
+    Ok((path, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn parse(source: &str) -> Config {
+        toml::from_str(source).unwrap()
+    }
+
+    fn invalid_key(result: Result<(), ConfigError>) -> (String, String) {
+        match result {
+            Err(ConfigError::Invalid { source, key, .. }) => (source, key),
+            result => panic!("expected an invalid value, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn parse_files() {
+        let config = parse(
+            "
+[window]
+width = 1280
+mode = \"borderless\"
+
+[graphics]
+msaa = 4
+image_count = 3
+
+[debug]
+validation = false
+",
+        );
+        assert_eq!(config.window.width, 1280);
+        assert_eq!(config.window.height, 768);
+        assert_eq!(config.window.mode, WindowMode::Borderless);
+        assert_eq!(config.graphics.msaa, 4);
+        assert_eq!(config.graphics.image_count, Some(3));
+        assert!(!config.debug.validation);
+        assert!(config.validate("file").is_ok());
+
+        assert!(toml::from_str::<Config>("[window]\nwidht = 1280\n").is_err());
+        assert!(toml::from_str::<Config>("[window]\nwidth = \"wide\"\n").is_err());
+    }
+
+    #[test]
+    fn override_from_args() {
+        let mut config = Config::default();
+        let rest = config
+            .apply_args(args(&[
+                "--width=1920",
+                "model.gltf",
+                "--height",
+                "1080",
+                "--no-bloom",
+                "--fxaa",
+                "--gpu",
+                "auto",
+                "--layers",
+                "A, B,",
+            ])).unwrap();
+
+        assert_eq!(rest, args(&["model.gltf"]));
+        assert_eq!(config.window.width, 1920);
+        assert_eq!(config.window.height, 1080);
+        assert!(!config.post.bloom.enabled);
+        assert!(config.post.fxaa.enabled);
+        assert_eq!(config.graphics.gpu, None);
+        assert_eq!(config.debug.layers, args(&["A", "B"]));
+        assert!(config.validate("defaults").is_ok());
+    }
+
+    #[test]
+    fn invalid_args() {
+        let mut config = Config::default();
+        match config.apply_args(args(&["--depth", "24"])) {
+            Err(ConfigError::UnknownOption(ref option)) if option == "--depth" => {}
+            result => panic!("{:?}", result),
+        }
+        match config.apply_args(args(&["--width"])) {
+            Err(ConfigError::MissingValue(ref option)) if option == "--width" => {}
+            result => panic!("{:?}", result),
+        }
+        // Only on/off settings can be negated.
+        assert!(config.apply_args(args(&["--no-width"])).is_err());
+
+        match config.apply_args(args(&["--width", "wide"])) {
+            Err(ConfigError::Invalid { ref key, .. }) if key == "window.width" => {}
+            result => panic!("{:?}", result),
+        }
+    }
+
+    #[test]
+    fn validate_once_every_override_is_applied() {
+        // Empty layers are only valid with validation off, in either order.
+        let mut config = Config::default();
+        config
+            .apply_args(args(&["--layers", "", "--no-validation"]))
+            .unwrap();
+        assert!(config.validate("defaults").is_ok());
+
+        let mut config = Config::default();
+        config.apply_args(args(&["--layers", ""])).unwrap();
+        let (source, key) = invalid_key(config.validate("defaults"));
+        assert_eq!(source, "--layers");
+        assert_eq!(key, "debug.layers");
+    }
+
+    #[test]
+    fn validate_names_the_source() {
+        let mut config = parse("[graphics]\nmsaa = 3\n");
+        let (source, key) = invalid_key(config.validate("config.toml"));
+        assert_eq!(source, "config.toml");
+        assert_eq!(key, "graphics.msaa");
+
+        config.set("graphics.msaa", "2").unwrap();
+        assert!(config.validate("config.toml").is_ok());
+
+        config.set("window.width", "0").unwrap();
+        let (source, key) = invalid_key(config.validate("config.toml"));
+        assert_eq!(source, "window.width");
+        assert_eq!(key, "window.width");
+
+        let mut config = Config::default();
+        config.apply_args(args(&["--bloom-levels=9"])).unwrap();
+        assert_eq!(
+            invalid_key(config.validate("defaults")),
+            ("--bloom-levels".to_owned(), "post.bloom.levels".to_owned())
+        );
+    }
+
+    #[test]
+    fn deferred_shading_overrides_msaa_from_the_file() {
+        let mut config = parse("[graphics]\nmsaa = 4\n");
+        config
+            .apply_args(args(&["--shading", "deferred", "--msaa", "1"]))
+            .unwrap();
+        assert_eq!(config.graphics.shading, Shading::Deferred);
+        assert!(config.validate("config.toml").is_ok());
+
+        let mut config = parse("[graphics]\nmsaa = 4\n");
+        config.apply_args(args(&["--shading", "deferred"])).unwrap();
+        let (source, key) = invalid_key(config.validate("config.toml"));
+        assert_eq!(source, "config.toml");
+        assert_eq!(key, "graphics.msaa");
+    }
+}
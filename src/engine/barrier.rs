@@ -0,0 +1,729 @@
+use ash::version::{DeviceV1_0, V1_0};
+use ash::vk;
+use ash::Device;
+use std::ptr;
+
+use super::image::Image;
+
+/// How a resource is used, each use implies the pipeline stage, the access
+/// and for images the layout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessType {
+    /// Not used, as a previous access the contents are discarded.
+    Nothing,
+    IndirectBuffer,
+    IndexBuffer,
+    VertexBuffer,
+    VertexShaderUniformRead,
+    VertexShaderSampledRead,
+    FragmentShaderUniformRead,
+    FragmentShaderSampledRead,
+    FragmentShaderInputAttachmentRead,
+    ComputeShaderUniformRead,
+    ComputeShaderSampledRead,
+    ComputeShaderStorageRead,
+    ColorAttachmentRead,
+    DepthStencilAttachmentRead,
+    TransferSrc,
+    HostRead,
+    Present,
+    ColorAttachmentWrite,
+    DepthStencilAttachmentWrite,
+    ComputeShaderStorageWrite,
+    TransferDst,
+    HostWrite,
+    /// Any access in any stage, in the general layout.
+    General,
+}
+
+/// What an `AccessType` maps to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AccessInfo {
+    pub stage: vk::PipelineStageFlags,
+    pub access: vk::AccessFlags,
+    pub layout: vk::ImageLayout,
+}
+
+impl AccessType {
+    pub fn info(&self) -> AccessInfo {
+        let (stage, access, layout) = match *self {
+            AccessType::Nothing => (
+                vk::PipelineStageFlags::empty(),
+                vk::AccessFlags::empty(),
+                vk::ImageLayout::Undefined,
+            ),
+            AccessType::IndirectBuffer => (
+                vk::PIPELINE_STAGE_DRAW_INDIRECT_BIT,
+                vk::ACCESS_INDIRECT_COMMAND_READ_BIT,
+                vk::ImageLayout::Undefined,
+            ),
+            AccessType::IndexBuffer => (
+                vk::PIPELINE_STAGE_VERTEX_INPUT_BIT,
+                vk::ACCESS_INDEX_READ_BIT,
+                vk::ImageLayout::Undefined,
+            ),
+            AccessType::VertexBuffer => (
+                vk::PIPELINE_STAGE_VERTEX_INPUT_BIT,
+                vk::ACCESS_VERTEX_ATTRIBUTE_READ_BIT,
+                vk::ImageLayout::Undefined,
+            ),
+            AccessType::VertexShaderUniformRead => (
+                vk::PIPELINE_STAGE_VERTEX_SHADER_BIT,
+                vk::ACCESS_UNIFORM_READ_BIT,
+                vk::ImageLayout::Undefined,
+            ),
+            AccessType::VertexShaderSampledRead => (
+                vk::PIPELINE_STAGE_VERTEX_SHADER_BIT,
+                vk::ACCESS_SHADER_READ_BIT,
+                vk::ImageLayout::ShaderReadOnlyOptimal,
+            ),
+            AccessType::FragmentShaderUniformRead => (
+                vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT,
+                vk::ACCESS_UNIFORM_READ_BIT,
+                vk::ImageLayout::Undefined,
+            ),
+            AccessType::FragmentShaderSampledRead => (
+                vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT,
+                vk::ACCESS_SHADER_READ_BIT,
+                vk::ImageLayout::ShaderReadOnlyOptimal,
+            ),
+            AccessType::FragmentShaderInputAttachmentRead => (
+                vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT,
+                vk::ACCESS_INPUT_ATTACHMENT_READ_BIT,
+                vk::ImageLayout::ShaderReadOnlyOptimal,
+            ),
+            AccessType::ComputeShaderUniformRead => (
+                vk::PIPELINE_STAGE_COMPUTE_SHADER_BIT,
+                vk::ACCESS_UNIFORM_READ_BIT,
+                vk::ImageLayout::Undefined,
+            ),
+            AccessType::ComputeShaderSampledRead => (
+                vk::PIPELINE_STAGE_COMPUTE_SHADER_BIT,
+                vk::ACCESS_SHADER_READ_BIT,
+                vk::ImageLayout::ShaderReadOnlyOptimal,
+            ),
+            AccessType::ComputeShaderStorageRead => (
+                vk::PIPELINE_STAGE_COMPUTE_SHADER_BIT,
+                vk::ACCESS_SHADER_READ_BIT,
+                vk::ImageLayout::General,
+            ),
+            AccessType::ColorAttachmentRead => (
+                vk::PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT,
+                vk::ACCESS_COLOR_ATTACHMENT_READ_BIT,
+                vk::ImageLayout::ColorAttachmentOptimal,
+            ),
+            AccessType::DepthStencilAttachmentRead => (
+                vk::PIPELINE_STAGE_EARLY_FRAGMENT_TESTS_BIT
+                    | vk::PIPELINE_STAGE_LATE_FRAGMENT_TESTS_BIT,
+                vk::ACCESS_DEPTH_STENCIL_ATTACHMENT_READ_BIT,
+                vk::ImageLayout::DepthStencilReadOnlyOptimal,
+            ),
+            AccessType::TransferSrc => (
+                vk::PIPELINE_STAGE_TRANSFER_BIT,
+                vk::ACCESS_TRANSFER_READ_BIT,
+                vk::ImageLayout::TransferSrcOptimal,
+            ),
+            AccessType::HostRead => (
+                vk::PIPELINE_STAGE_HOST_BIT,
+                vk::ACCESS_HOST_READ_BIT,
+                vk::ImageLayout::General,
+            ),
+            // Presenting is ordered by semaphores, the barrier only has to
+            // change the layout.
+            AccessType::Present => (
+                vk::PipelineStageFlags::empty(),
+                vk::AccessFlags::empty(),
+                vk::ImageLayout::PresentSrcKhr,
+            ),
+            AccessType::ColorAttachmentWrite => (
+                vk::PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT,
+                vk::ACCESS_COLOR_ATTACHMENT_WRITE_BIT,
+                vk::ImageLayout::ColorAttachmentOptimal,
+            ),
+            AccessType::DepthStencilAttachmentWrite => (
+                vk::PIPELINE_STAGE_EARLY_FRAGMENT_TESTS_BIT
+                    | vk::PIPELINE_STAGE_LATE_FRAGMENT_TESTS_BIT,
+                vk::ACCESS_DEPTH_STENCIL_ATTACHMENT_WRITE_BIT,
+                vk::ImageLayout::DepthStencilAttachmentOptimal,
+            ),
+            AccessType::ComputeShaderStorageWrite => (
+                vk::PIPELINE_STAGE_COMPUTE_SHADER_BIT,
+                vk::ACCESS_SHADER_WRITE_BIT,
+                vk::ImageLayout::General,
+            ),
+            AccessType::TransferDst => (
+                vk::PIPELINE_STAGE_TRANSFER_BIT,
+                vk::ACCESS_TRANSFER_WRITE_BIT,
+                vk::ImageLayout::TransferDstOptimal,
+            ),
+            AccessType::HostWrite => (
+                vk::PIPELINE_STAGE_HOST_BIT,
+                vk::ACCESS_HOST_WRITE_BIT,
+                vk::ImageLayout::General,
+            ),
+            AccessType::General => (
+                vk::PIPELINE_STAGE_ALL_COMMANDS_BIT,
+                vk::ACCESS_MEMORY_READ_BIT | vk::ACCESS_MEMORY_WRITE_BIT,
+                vk::ImageLayout::General,
+            ),
+        };
+
+        AccessInfo {
+            stage,
+            access,
+            layout,
+        }
+    }
+
+    pub fn is_write(&self) -> bool {
+        matches!(
+            *self,
+            AccessType::ColorAttachmentWrite
+                | AccessType::DepthStencilAttachmentWrite
+                | AccessType::ComputeShaderStorageWrite
+                | AccessType::TransferDst
+                | AccessType::HostWrite
+                | AccessType::General
+        )
+    }
+}
+
+/// The layout an image has to be in for all of `accesses` at once, the
+/// general layout when they disagree.
+pub fn image_layout(accesses: &[AccessType]) -> vk::ImageLayout {
+    let mut layouts = accesses
+        .iter()
+        .map(|access| access.info().layout)
+        .filter(|&layout| layout != vk::ImageLayout::Undefined);
+
+    let first = match layouts.next() {
+        Some(first) => first,
+        None => return vk::ImageLayout::Undefined,
+    };
+
+    if layouts.all(|layout| layout == first) {
+        first
+    } else {
+        vk::ImageLayout::General
+    }
+}
+
+/// Stages and accesses to wait for after `prev`, only writes have to be made
+/// available.
+pub fn src_masks(prev: &[AccessType]) -> (vk::PipelineStageFlags, vk::AccessFlags) {
+    prev.iter().fold(
+        (vk::PipelineStageFlags::empty(), vk::AccessFlags::empty()),
+        |(stage, access), prev| {
+            let info = prev.info();
+            let access = if prev.is_write() {
+                access | info.access
+            } else {
+                access
+            };
+            (stage | info.stage, access)
+        },
+    )
+}
+
+/// Stages and accesses that wait for a barrier before `next`.
+pub fn dst_masks(next: &[AccessType]) -> (vk::PipelineStageFlags, vk::AccessFlags) {
+    next.iter().fold(
+        (vk::PipelineStageFlags::empty(), vk::AccessFlags::empty()),
+        |(stage, access), next| {
+            let info = next.info();
+            (stage | info.stage, access | info.access)
+        },
+    )
+}
+
+/// Collects barriers between accesses and records them as one
+/// `vkCmdPipelineBarrier`.
+///
+/// ```ignore
+/// Barrier::new()
+///     .with_image(&mut image, &[AccessType::FragmentShaderSampledRead])
+///     .record(device, command_buffer);
+/// ```
+pub struct Barrier {
+    pub src_stage: vk::PipelineStageFlags,
+    pub dst_stage: vk::PipelineStageFlags,
+    pub memory_barriers: Vec<vk::MemoryBarrier>,
+    pub buffer_barriers: Vec<vk::BufferMemoryBarrier>,
+    pub image_barriers: Vec<vk::ImageMemoryBarrier>,
+}
+
+impl Barrier {
+    pub fn new() -> Self {
+        Barrier {
+            src_stage: vk::PipelineStageFlags::empty(),
+            dst_stage: vk::PipelineStageFlags::empty(),
+            memory_barriers: Vec::new(),
+            buffer_barriers: Vec::new(),
+            image_barriers: Vec::new(),
+        }
+    }
+
+    /// Order every resource used by `prev` before `next`.
+    pub fn with_global(mut self, prev: &[AccessType], next: &[AccessType]) -> Self {
+        let (src_access_mask, dst_access_mask) = self.add_stages(prev, next);
+        self.memory_barriers.push(vk::MemoryBarrier {
+            s_type: vk::StructureType::MemoryBarrier,
+            p_next: ptr::null(),
+            src_access_mask,
+            dst_access_mask,
+        });
+        self
+    }
+
+    /// Order all of `buffer` used by `prev` before `next`.
+    pub fn with_buffer(
+        mut self,
+        buffer: vk::Buffer,
+        prev: &[AccessType],
+        next: &[AccessType],
+    ) -> Self {
+        let (src_access_mask, dst_access_mask) = self.add_stages(prev, next);
+        self.buffer_barriers.push(vk::BufferMemoryBarrier {
+            s_type: vk::StructureType::BufferMemoryBarrier,
+            p_next: ptr::null(),
+            src_access_mask,
+            dst_access_mask,
+            src_queue_family_index: vk::VK_QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::VK_QUEUE_FAMILY_IGNORED,
+            buffer,
+            offset: 0,
+            size: vk::VK_WHOLE_SIZE,
+        });
+        self
+    }
+
+    /// Order `subresource_range` of `image` used by `prev` before `next`,
+    /// moving it to the layout of `next`. With `AccessType::Nothing` as the
+    /// previous access the contents are discarded.
+    pub fn with_image_range(
+        mut self,
+        image: vk::Image,
+        subresource_range: vk::ImageSubresourceRange,
+        prev: &[AccessType],
+        next: &[AccessType],
+    ) -> Self {
+        let (src_access_mask, dst_access_mask) = self.add_stages(prev, next);
+        let old_layout = image_layout(prev);
+        let new_layout = image_layout(next);
+
+        // A layout change writes the image, so the next accesses always have
+        // to wait for it even after reads.
+        let dst_access_mask = if old_layout != new_layout {
+            dst_masks(next).1
+        } else {
+            dst_access_mask
+        };
+
+        self.image_barriers.push(vk::ImageMemoryBarrier {
+            s_type: vk::StructureType::ImageMemoryBarrier,
+            p_next: ptr::null(),
+            src_access_mask,
+            dst_access_mask,
+            old_layout,
+            new_layout,
+            src_queue_family_index: vk::VK_QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::VK_QUEUE_FAMILY_IGNORED,
+            image,
+            subresource_range,
+        });
+        self
+    }
+
+    /// Move all of `image` from its last access to `next`, and remember
+    /// `next` as its last access.
+    pub fn with_image(self, image: &mut Image, next: &[AccessType]) -> Self {
+        let barrier = self.with_image_range(
+            image.image,
+            image.subresource_range.clone(),
+            image.access(),
+            next,
+        );
+        image.assume_access(next);
+        barrier
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.memory_barriers.is_empty()
+            && self.buffer_barriers.is_empty()
+            && self.image_barriers.is_empty()
+    }
+
+    /// Record the barriers into `command_buffer`, nothing is recorded
+    /// without any.
+    pub fn record(&self, device: &Device<V1_0>, command_buffer: vk::CommandBuffer) {
+        if self.is_empty() {
+            return;
+        }
+
+        // Stage masks can't be empty, nothing before is the top of the
+        // pipe and nothing after the bottom.
+        let src_stage = if self.src_stage.is_empty() {
+            vk::PIPELINE_STAGE_TOP_OF_PIPE_BIT
+        } else {
+            self.src_stage
+        };
+        let dst_stage = if self.dst_stage.is_empty() {
+            vk::PIPELINE_STAGE_BOTTOM_OF_PIPE_BIT
+        } else {
+            self.dst_stage
+        };
+
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stage,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                &self.memory_barriers,
+                &self.buffer_barriers,
+                &self.image_barriers,
+            );
+        }
+    }
+
+    /// Add the stages of `prev` and `next`, and return the access masks
+    /// for a barrier between them.
+    fn add_stages(
+        &mut self,
+        prev: &[AccessType],
+        next: &[AccessType],
+    ) -> (vk::AccessFlags, vk::AccessFlags) {
+        let (src_stage, src_access) = src_masks(prev);
+        let (dst_stage, dst_access) = dst_masks(next);
+        self.src_stage |= src_stage;
+        self.dst_stage |= dst_stage;
+
+        // Reads after reads only need the execution dependency.
+        let dst_access = if src_access.is_empty() {
+            vk::AccessFlags::empty()
+        } else {
+            dst_access
+        };
+
+        (src_access, dst_access)
+    }
+}
+
+impl Default for Barrier {
+    fn default() -> Self {
+        Barrier::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_attachment_write() {
+        assert_eq!(
+            AccessType::ColorAttachmentWrite.info(),
+            AccessInfo {
+                stage: vk::PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT,
+                access: vk::ACCESS_COLOR_ATTACHMENT_WRITE_BIT,
+                layout: vk::ImageLayout::ColorAttachmentOptimal,
+            }
+        );
+    }
+
+    #[test]
+    fn fragment_shader_sampled_read() {
+        assert_eq!(
+            AccessType::FragmentShaderSampledRead.info(),
+            AccessInfo {
+                stage: vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT,
+                access: vk::ACCESS_SHADER_READ_BIT,
+                layout: vk::ImageLayout::ShaderReadOnlyOptimal,
+            }
+        );
+    }
+
+    #[test]
+    fn transfer_dst() {
+        assert_eq!(
+            AccessType::TransferDst.info(),
+            AccessInfo {
+                stage: vk::PIPELINE_STAGE_TRANSFER_BIT,
+                access: vk::ACCESS_TRANSFER_WRITE_BIT,
+                layout: vk::ImageLayout::TransferDstOptimal,
+            }
+        );
+    }
+
+    #[test]
+    fn depth_attachment_layouts() {
+        assert_eq!(
+            AccessType::DepthStencilAttachmentWrite.info().layout,
+            vk::ImageLayout::DepthStencilAttachmentOptimal
+        );
+        assert_eq!(
+            AccessType::DepthStencilAttachmentRead.info().layout,
+            vk::ImageLayout::DepthStencilReadOnlyOptimal
+        );
+    }
+
+    #[test]
+    fn nothing_discards() {
+        let info = AccessType::Nothing.info();
+        assert!(info.stage.is_empty());
+        assert!(info.access.is_empty());
+        assert_eq!(info.layout, vk::ImageLayout::Undefined);
+    }
+
+    #[test]
+    fn writes() {
+        assert!(AccessType::ColorAttachmentWrite.is_write());
+        assert!(AccessType::TransferDst.is_write());
+        assert!(AccessType::General.is_write());
+        assert!(!AccessType::FragmentShaderSampledRead.is_write());
+        assert!(!AccessType::TransferSrc.is_write());
+        assert!(!AccessType::Present.is_write());
+    }
+
+    #[test]
+    fn layout_of_several_accesses() {
+        assert_eq!(image_layout(&[]), vk::ImageLayout::Undefined);
+        assert_eq!(
+            image_layout(&[
+                AccessType::FragmentShaderSampledRead,
+                AccessType::VertexShaderSampledRead,
+            ]),
+            vk::ImageLayout::ShaderReadOnlyOptimal
+        );
+        assert_eq!(
+            image_layout(&[AccessType::FragmentShaderSampledRead, AccessType::TransferSrc]),
+            vk::ImageLayout::General
+        );
+    }
+
+    #[test]
+    fn src_masks_skip_reads() {
+        let (stage, access) =
+            src_masks(&[AccessType::TransferDst, AccessType::FragmentShaderSampledRead]);
+        assert_eq!(
+            stage,
+            vk::PIPELINE_STAGE_TRANSFER_BIT | vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT
+        );
+        assert_eq!(access, vk::ACCESS_TRANSFER_WRITE_BIT);
+    }
+
+    #[test]
+    fn image_transition() {
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::IMAGE_ASPECT_COLOR_BIT,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let barrier = Barrier::new().with_image_range(
+            vk::Image::null(),
+            range,
+            &[AccessType::TransferDst],
+            &[AccessType::FragmentShaderSampledRead],
+        );
+
+        assert_eq!(barrier.src_stage, vk::PIPELINE_STAGE_TRANSFER_BIT);
+        assert_eq!(barrier.dst_stage, vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT);
+        let image_barrier = &barrier.image_barriers[0];
+        assert_eq!(image_barrier.src_access_mask, vk::ACCESS_TRANSFER_WRITE_BIT);
+        assert_eq!(image_barrier.dst_access_mask, vk::ACCESS_SHADER_READ_BIT);
+        assert_eq!(image_barrier.old_layout, vk::ImageLayout::TransferDstOptimal);
+        assert_eq!(image_barrier.new_layout, vk::ImageLayout::ShaderReadOnlyOptimal);
+    }
+
+    #[test]
+    fn read_after_read_is_execution_only() {
+        let barrier = Barrier::new().with_buffer(
+            vk::Buffer::null(),
+            &[AccessType::VertexBuffer],
+            &[AccessType::TransferSrc],
+        );
+
+        assert!(barrier.buffer_barriers[0].src_access_mask.is_empty());
+        assert!(barrier.buffer_barriers[0].dst_access_mask.is_empty());
+    }
+
+    #[test]
+    fn every_access_type() {
+        let table = [
+            (
+                AccessType::Nothing,
+                vk::PipelineStageFlags::empty(),
+                vk::AccessFlags::empty(),
+                vk::ImageLayout::Undefined,
+                false,
+            ),
+            (
+                AccessType::IndirectBuffer,
+                vk::PIPELINE_STAGE_DRAW_INDIRECT_BIT,
+                vk::ACCESS_INDIRECT_COMMAND_READ_BIT,
+                vk::ImageLayout::Undefined,
+                false,
+            ),
+            (
+                AccessType::IndexBuffer,
+                vk::PIPELINE_STAGE_VERTEX_INPUT_BIT,
+                vk::ACCESS_INDEX_READ_BIT,
+                vk::ImageLayout::Undefined,
+                false,
+            ),
+            (
+                AccessType::VertexBuffer,
+                vk::PIPELINE_STAGE_VERTEX_INPUT_BIT,
+                vk::ACCESS_VERTEX_ATTRIBUTE_READ_BIT,
+                vk::ImageLayout::Undefined,
+                false,
+            ),
+            (
+                AccessType::VertexShaderUniformRead,
+                vk::PIPELINE_STAGE_VERTEX_SHADER_BIT,
+                vk::ACCESS_UNIFORM_READ_BIT,
+                vk::ImageLayout::Undefined,
+                false,
+            ),
+            (
+                AccessType::VertexShaderSampledRead,
+                vk::PIPELINE_STAGE_VERTEX_SHADER_BIT,
+                vk::ACCESS_SHADER_READ_BIT,
+                vk::ImageLayout::ShaderReadOnlyOptimal,
+                false,
+            ),
+            (
+                AccessType::FragmentShaderUniformRead,
+                vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT,
+                vk::ACCESS_UNIFORM_READ_BIT,
+                vk::ImageLayout::Undefined,
+                false,
+            ),
+            (
+                AccessType::FragmentShaderSampledRead,
+                vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT,
+                vk::ACCESS_SHADER_READ_BIT,
+                vk::ImageLayout::ShaderReadOnlyOptimal,
+                false,
+            ),
+            (
+                AccessType::FragmentShaderInputAttachmentRead,
+                vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT,
+                vk::ACCESS_INPUT_ATTACHMENT_READ_BIT,
+                vk::ImageLayout::ShaderReadOnlyOptimal,
+                false,
+            ),
+            (
+                AccessType::ComputeShaderUniformRead,
+                vk::PIPELINE_STAGE_COMPUTE_SHADER_BIT,
+                vk::ACCESS_UNIFORM_READ_BIT,
+                vk::ImageLayout::Undefined,
+                false,
+            ),
+            (
+                AccessType::ComputeShaderSampledRead,
+                vk::PIPELINE_STAGE_COMPUTE_SHADER_BIT,
+                vk::ACCESS_SHADER_READ_BIT,
+                vk::ImageLayout::ShaderReadOnlyOptimal,
+                false,
+            ),
+            (
+                AccessType::ComputeShaderStorageRead,
+                vk::PIPELINE_STAGE_COMPUTE_SHADER_BIT,
+                vk::ACCESS_SHADER_READ_BIT,
+                vk::ImageLayout::General,
+                false,
+            ),
+            (
+                AccessType::ColorAttachmentRead,
+                vk::PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT,
+                vk::ACCESS_COLOR_ATTACHMENT_READ_BIT,
+                vk::ImageLayout::ColorAttachmentOptimal,
+                false,
+            ),
+            (
+                AccessType::DepthStencilAttachmentRead,
+                vk::PIPELINE_STAGE_EARLY_FRAGMENT_TESTS_BIT
+                    | vk::PIPELINE_STAGE_LATE_FRAGMENT_TESTS_BIT,
+                vk::ACCESS_DEPTH_STENCIL_ATTACHMENT_READ_BIT,
+                vk::ImageLayout::DepthStencilReadOnlyOptimal,
+                false,
+            ),
+            (
+                AccessType::TransferSrc,
+                vk::PIPELINE_STAGE_TRANSFER_BIT,
+                vk::ACCESS_TRANSFER_READ_BIT,
+                vk::ImageLayout::TransferSrcOptimal,
+                false,
+            ),
+            (
+                AccessType::HostRead,
+                vk::PIPELINE_STAGE_HOST_BIT,
+                vk::ACCESS_HOST_READ_BIT,
+                vk::ImageLayout::General,
+                false,
+            ),
+            (
+                AccessType::Present,
+                vk::PipelineStageFlags::empty(),
+                vk::AccessFlags::empty(),
+                vk::ImageLayout::PresentSrcKhr,
+                false,
+            ),
+            (
+                AccessType::ColorAttachmentWrite,
+                vk::PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT,
+                vk::ACCESS_COLOR_ATTACHMENT_WRITE_BIT,
+                vk::ImageLayout::ColorAttachmentOptimal,
+                true,
+            ),
+            (
+                AccessType::DepthStencilAttachmentWrite,
+                vk::PIPELINE_STAGE_EARLY_FRAGMENT_TESTS_BIT
+                    | vk::PIPELINE_STAGE_LATE_FRAGMENT_TESTS_BIT,
+                vk::ACCESS_DEPTH_STENCIL_ATTACHMENT_WRITE_BIT,
+                vk::ImageLayout::DepthStencilAttachmentOptimal,
+                true,
+            ),
+            (
+                AccessType::ComputeShaderStorageWrite,
+                vk::PIPELINE_STAGE_COMPUTE_SHADER_BIT,
+                vk::ACCESS_SHADER_WRITE_BIT,
+                vk::ImageLayout::General,
+                true,
+            ),
+            (
+                AccessType::TransferDst,
+                vk::PIPELINE_STAGE_TRANSFER_BIT,
+                vk::ACCESS_TRANSFER_WRITE_BIT,
+                vk::ImageLayout::TransferDstOptimal,
+                true,
+            ),
+            (
+                AccessType::HostWrite,
+                vk::PIPELINE_STAGE_HOST_BIT,
+                vk::ACCESS_HOST_WRITE_BIT,
+                vk::ImageLayout::General,
+                true,
+            ),
+            (
+                AccessType::General,
+                vk::PIPELINE_STAGE_ALL_COMMANDS_BIT,
+                vk::ACCESS_MEMORY_READ_BIT | vk::ACCESS_MEMORY_WRITE_BIT,
+                vk::ImageLayout::General,
+                true,
+            ),
+        ];
+        assert_eq!(table.len(), 23);
+
+        for &(access_type, stage, access, layout, is_write) in table.iter() {
+            let info = access_type.info();
+            assert_eq!(info.stage, stage, "{:?}", access_type);
+            assert_eq!(info.access, access, "{:?}", access_type);
+            assert_eq!(info.layout, layout, "{:?}", access_type);
+            assert_eq!(access_type.is_write(), is_write, "{:?}", access_type);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use ash::version::{DeviceV1_0, V1_0};
+use ash::vk;
+use ash::Device;
+use std::mem;
+use std::ptr;
+
+use super::memory;
+
+pub struct Buffer {
+    pub buffer: vk::Buffer,
+    pub memory: vk::DeviceMemory,
+    pub size: vk::DeviceSize,
+}
+
+impl Buffer {
+    pub fn new(
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        flags: vk::MemoryPropertyFlags,
+    ) -> Result<Buffer, vk::Result> {
+        let buffer_info = vk::BufferCreateInfo {
+            s_type: vk::StructureType::BufferCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            size,
+            usage,
+            sharing_mode: vk::SharingMode::Exclusive,
+            queue_family_index_count: 0,
+            p_queue_family_indices: ptr::null(),
+        };
+
+        let buffer = unsafe { device.create_buffer(&buffer_info, None)? };
+
+        let memory_requirements = device.get_buffer_memory_requirements(buffer);
+        let memory_type_index =
+            match memory::find_memory_type_index(&memory_requirements, memory_properties, flags) {
+                Some(index) => index,
+                None => {
+                    unsafe { device.destroy_buffer(buffer, None) };
+                    return Err(vk::Result::ErrorOutOfDeviceMemory);
+                }
+            };
+
+        let allocate_info = vk::MemoryAllocateInfo {
+            s_type: vk::StructureType::MemoryAllocateInfo,
+            p_next: ptr::null(),
+            allocation_size: memory_requirements.size,
+            memory_type_index,
+        };
+
+        let memory = unsafe {
+            let memory = match device.allocate_memory(&allocate_info, None) {
+                Ok(memory) => memory,
+                Err(error) => {
+                    device.destroy_buffer(buffer, None);
+                    return Err(error);
+                }
+            };
+            if let Err(error) = device.bind_buffer_memory(buffer, memory, 0) {
+                device.destroy_buffer(buffer, None);
+                device.free_memory(memory, None);
+                return Err(error);
+            }
+            memory
+        };
+
+        Ok(Buffer {
+            buffer,
+            memory,
+            size,
+        })
+    }
+
+    /// Create a host visible buffer holding a copy of `data`, which must not
+    /// be empty. Empty data fails with `ErrorInitializationFailed`.
+    pub fn with_data<T: Copy>(
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        usage: vk::BufferUsageFlags,
+        data: &[T],
+    ) -> Result<Buffer, vk::Result> {
+        let size = mem::size_of_val(data) as vk::DeviceSize;
+        // Vulkan doesn't allow buffers without any bytes.
+        if size == 0 {
+            return Err(vk::Result::ErrorInitializationFailed);
+        }
+
+        let buffer = Buffer::new(
+            device,
+            memory_properties,
+            size,
+            usage,
+            vk::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vk::MEMORY_PROPERTY_HOST_COHERENT_BIT,
+        )?;
+
+        buffer.write(device, 0, data);
+
+        Ok(buffer)
+    }
+
+    /// Copy `data` into the buffer at `offset`, the buffer has to be host
+    /// visible and coherent.
+    pub fn write<T: Copy>(&self, device: &Device<V1_0>, offset: vk::DeviceSize, data: &[T]) {
+        let size = mem::size_of_val(data) as vk::DeviceSize;
+        assert!(offset + size <= self.size, "write past the end of the buffer");
+
+        if size == 0 {
+            return;
+        }
+
+        unsafe {
+            let mapped = device
+                .map_memory(self.memory, offset, size, vk::MemoryMapFlags::empty())
+                .expect("Unable to map buffer memory");
+            ptr::copy_nonoverlapping(data.as_ptr() as *const u8, mapped as *mut u8, size as usize);
+            device.unmap_memory(self.memory);
+        }
+    }
+
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        unsafe {
+            device.destroy_buffer(self.buffer, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
@@ -1,9 +1,11 @@
 use ash::version::{DeviceV1_0, V1_0};
 use ash::vk;
 use ash::Device;
+use std::mem;
 use std::ptr;
+use std::slice;
 
-use super::pipeline::Pipeline;
+use super::pipeline::{PipelineLayout, RenderPass};
 
 pub struct CommandPool {
     pub command_pool: vk::CommandPool,
@@ -11,12 +13,14 @@ pub struct CommandPool {
 }
 
 impl CommandPool {
-    pub fn new(device: &Device<V1_0>, swapchain_buffers_len: u32, queue_index: u32) -> Self {
+    pub fn new(device: &Device<V1_0>, command_buffer_count: u32, queue_index: u32) -> Self {
+        // Buffers are re-recorded every frame, so they need to be
+        // individually resettable.
         let command_pool_info = vk::CommandPoolCreateInfo {
             s_type: vk::StructureType::CommandPoolCreateInfo,
             p_next: ptr::null(),
-            flags: Default::default(),
-            queue_family_index: queue_index as u32,
+            flags: vk::COMMAND_POOL_CREATE_RESET_COMMAND_BUFFER_BIT,
+            queue_family_index: queue_index,
         };
 
         let command_pool = unsafe {
@@ -29,8 +33,8 @@ impl CommandPool {
             s_type: vk::StructureType::CommandBufferAllocateInfo,
             p_next: ptr::null(),
             level: vk::CommandBufferLevel::Primary,
-            command_pool: command_pool,
-            command_buffer_count: swapchain_buffers_len,
+            command_pool,
+            command_buffer_count,
         };
 
         let command_buffers = unsafe {
@@ -45,80 +49,187 @@ impl CommandPool {
         }
     }
 
-    pub fn setup_command_buffers(
+    /// Record commands into a fresh buffer, submit them to `queue` and wait
+    /// for the queue to finish executing them.
+    ///
+    /// Meant for setup work like uploads, not for anything done per frame.
+    pub fn one_time_submit<F>(&self, device: &Device<V1_0>, queue: vk::Queue, record: F)
+    where
+        F: FnOnce(vk::CommandBuffer),
+    {
+        let command_buffer_alloc_info = vk::CommandBufferAllocateInfo {
+            s_type: vk::StructureType::CommandBufferAllocateInfo,
+            p_next: ptr::null(),
+            level: vk::CommandBufferLevel::Primary,
+            command_pool: self.command_pool,
+            command_buffer_count: 1,
+        };
+
+        let buffer = unsafe {
+            device
+                .allocate_command_buffers(&command_buffer_alloc_info)
+                .expect("Unable to allocate command buffers")[0]
+        };
+
+        let begin_info = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::CommandBufferBeginInfo,
+            p_next: ptr::null(),
+            flags: vk::COMMAND_BUFFER_USAGE_ONE_TIME_SUBMIT_BIT,
+            p_inheritance_info: ptr::null(),
+        };
+
+        unsafe {
+            device
+                .begin_command_buffer(buffer, &begin_info)
+                .expect("Unable to begin buffer");
+        }
+
+        record(buffer);
+
+        let submit_info = vk::SubmitInfo {
+            s_type: vk::StructureType::SubmitInfo,
+            p_next: ptr::null(),
+            wait_semaphore_count: 0,
+            p_wait_semaphores: ptr::null(),
+            p_wait_dst_stage_mask: ptr::null(),
+            command_buffer_count: 1,
+            p_command_buffers: &buffer,
+            signal_semaphore_count: 0,
+            p_signal_semaphores: ptr::null(),
+        };
+
+        unsafe {
+            device
+                .end_command_buffer(buffer)
+                .expect("Unable to record command buffer");
+            device
+                .queue_submit(queue, &[submit_info], vk::Fence::null())
+                .expect("Unable to submit queue");
+            device.queue_wait_idle(queue).expect("Unable to wait for queue");
+            device.free_command_buffers(self.command_pool, &[buffer]);
+        }
+    }
+
+    /// Reset the buffer at `index` and begin recording the commands for a
+    /// single frame into it.
+    ///
+    /// The buffer must not be in use by the device anymore. Panics when
+    /// `index` is not below the number of buffers.
+    pub fn begin_command_buffer(
         &self,
         device: &Device<V1_0>,
-        swapchain_buffers: &Vec<vk::Framebuffer>,
-        graphics_pipelines: &Pipeline,
-        surface_resolution: vk::Extent2D,
-    ) -> Result<(), ()> {
-        // let command_buffers = self
-        //     .create_command_buffers(device, swapchain_buffers.len())
-        //     .unwrap();
-
-        self.command_buffers.iter().for_each(|&buffer| {
-            let begin_info = vk::CommandBufferBeginInfo {
-                s_type: vk::StructureType::CommandBufferBeginInfo,
-                p_next: ptr::null(),
-                flags: vk::COMMAND_BUFFER_USAGE_SIMULTANEOUS_USE_BIT,
-                p_inheritance_info: ptr::null(),
-            };
-
-            unsafe {
-                device
-                    .begin_command_buffer(buffer, &begin_info)
-                    .expect("Unable to begin buffer");
-            };
-
-            let clear_values = [vk::ClearValue {
-                color: vk::ClearColorValue {
-                    float32: [0.0, 0.0, 0.0, 0.0],
-                },
-            }];
-
-            swapchain_buffers.iter().for_each(|&framebuffer| {
-                let render_pass_info = vk::RenderPassBeginInfo {
-                    s_type: vk::StructureType::RenderPassBeginInfo,
-                    p_next: ptr::null(),
-                    render_pass: graphics_pipelines.render_pass.render_pass,
-                    framebuffer: framebuffer,
-                    render_area: vk::Rect2D {
-                        offset: vk::Offset2D { x: 0, y: 0 },
-                        extent: surface_resolution.clone(),
-                    },
-                    clear_value_count: 1,
-                    p_clear_values: clear_values.as_ptr(),
-                };
-
-                let graphics_pipeline = graphics_pipelines.graphics_pipelines.get(0).unwrap();
-
-                unsafe {
-                    device.cmd_begin_render_pass(
-                        buffer,
-                        &render_pass_info,
-                        vk::SubpassContents::Inline,
-                    );
-
-                    device.cmd_bind_pipeline(
-                        buffer,
-                        vk::PipelineBindPoint::Graphics,
-                        *graphics_pipeline,
-                    );
-
-                    // TODO: implement other commands
-                    device.cmd_draw(buffer, 3, 1, 0, 0);
-
-                    device.cmd_end_render_pass(buffer);
-                }
-            });
-
-            unsafe {
-                device
-                    .end_command_buffer(buffer)
-                    .expect("Unable to record command buffer");
-            }
+        index: u32,
+    ) -> Result<vk::CommandBuffer, vk::Result> {
+        let buffer = self.command_buffers[index as usize];
+
+        let begin_info = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::CommandBufferBeginInfo,
+            p_next: ptr::null(),
+            flags: vk::COMMAND_BUFFER_USAGE_ONE_TIME_SUBMIT_BIT,
+            p_inheritance_info: ptr::null(),
+        };
+
+        unsafe {
+            device.begin_command_buffer(buffer, &begin_info)?;
+        };
+
+        Ok(buffer)
+    }
+}
+
+/// Begin `render_pass` on `framebuffer`, clearing the color attachment, if
+/// the render pass has one, to `clear_color` and the depth attachment, if
+/// the render pass has one, to `clear_depth`.
+pub fn begin_render_pass(
+    device: &Device<V1_0>,
+    command_buffer: vk::CommandBuffer,
+    render_pass: &RenderPass,
+    framebuffer: vk::Framebuffer,
+    extent: vk::Extent2D,
+    clear_color: [f32; 4],
+    clear_depth: f32,
+) {
+    let mut clear_values = Vec::new();
+    if render_pass.color_format != vk::Format::Undefined {
+        clear_values.push(vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: clear_color,
+            },
         });
+    }
+    if render_pass.depth_format.is_some() {
+        clear_values.push(vk::ClearValue {
+            depth: vk::ClearDepthStencilValue {
+                depth: clear_depth,
+                stencil: 0,
+            },
+        });
+    }
+
+    begin_render_pass_with_clear_values(
+        device,
+        command_buffer,
+        render_pass,
+        framebuffer,
+        extent,
+        &clear_values,
+    );
+}
+
+/// Begin `render_pass` on `framebuffer` with a clear value for every
+/// attachment up to the last one that is cleared, in attachment order.
+pub fn begin_render_pass_with_clear_values(
+    device: &Device<V1_0>,
+    command_buffer: vk::CommandBuffer,
+    render_pass: &RenderPass,
+    framebuffer: vk::Framebuffer,
+    extent: vk::Extent2D,
+    clear_values: &[vk::ClearValue],
+) {
+    let render_pass_info = vk::RenderPassBeginInfo {
+        s_type: vk::StructureType::RenderPassBeginInfo,
+        p_next: ptr::null(),
+        render_pass: render_pass.render_pass,
+        framebuffer,
+        render_area: vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        },
+        clear_value_count: clear_values.len() as u32,
+        p_clear_values: clear_values.as_ptr(),
+    };
+
+    unsafe {
+        device.cmd_begin_render_pass(
+            command_buffer,
+            &render_pass_info,
+            vk::SubpassContents::Inline,
+        );
+    }
+}
 
-        Ok(())
+/// Record a push of `constants` into the layout's push constant block at
+/// `offset`.
+///
+/// `T` is expected to be `#[repr(C)]` and to match the shader's
+/// `push_constant` block, the range has to be declared on `layout` for all of
+/// `stage_flags`.
+pub fn push_constants<T: Copy>(
+    device: &Device<V1_0>,
+    command_buffer: vk::CommandBuffer,
+    layout: &PipelineLayout,
+    stage_flags: vk::ShaderStageFlags,
+    offset: u32,
+    constants: &T,
+) {
+    let size = mem::size_of::<T>();
+    debug_assert!(
+        layout.covers_push_constants(stage_flags, offset, size as u32),
+        "push constants are outside the ranges declared on the layout"
+    );
+
+    unsafe {
+        let bytes = slice::from_raw_parts(constants as *const T as *const u8, size);
+        device.cmd_push_constants(command_buffer, layout.layout, stage_flags, offset, bytes);
     }
 }
@@ -0,0 +1,550 @@
+use ash::version::{DeviceV1_0, V1_0};
+use ash::vk;
+use ash::{Device, Instance};
+use std::ptr;
+use std::str::FromStr;
+
+use super::command_pool;
+use super::descriptor::{self, DescriptorPool, DescriptorSetLayout};
+use super::image::{Image, ImageDesc, ImageError};
+use super::mesh;
+use super::model::{DrawItem, MeshPushConstants, Model};
+use super::pbr::{PbrMaterials, FRAME_SET};
+use super::pipeline::{
+    self, BlendAttachment, ColorBlend, Pipeline, RenderPass, SubpassDesc, Viewport,
+};
+use super::post;
+use super::texture::{self, SamplerDesc};
+use shader::Shader;
+
+const MESH_VERT: &str = "assets/shaders/mesh.vert.spv";
+const GBUFFER_FRAG: &str = "assets/shaders/gbuffer.frag.spv";
+const FULLSCREEN_VERT: &str = "assets/shaders/post/fullscreen.vert.spv";
+const LIGHTING_FRAG: &str = "assets/shaders/deferred_lighting.frag.spv";
+
+/// Set of the G-buffer input attachments in `deferred_lighting.frag`, after
+/// the per frame set.
+pub const GBUFFER_SET: u32 = 1;
+
+/// Base color with the occlusion in alpha.
+pub const ALBEDO_FORMAT: vk::Format = vk::Format::R8g8b8a8Unorm;
+/// World space normals.
+pub const NORMAL_FORMAT: vk::Format = vk::Format::R16g16b16a16Sfloat;
+/// Metallic and roughness.
+pub const MATERIAL_FORMAT: vk::Format = vk::Format::R8g8b8a8Unorm;
+
+/// Attachments of the render pass, the lit output comes first so that it is
+/// the color format of the render pass.
+const OUTPUT: u32 = 0;
+const ALBEDO: u32 = 1;
+const NORMAL: u32 = 2;
+const MATERIAL: u32 = 3;
+const DEPTH: u32 = 4;
+
+/// Subpasses of the render pass.
+const GEOMETRY_SUBPASS: u32 = 0;
+const LIGHTING_SUBPASS: u32 = 1;
+
+/// Push constants of `deferred_lighting.frag`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct DeferredPushConstants {
+    /// What depth is cleared to, the lighting subpass skips those pixels.
+    pub clear_depth: f32,
+}
+
+/// How the scene is lit.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum Shading {
+    /// Every fragment is lit as it is drawn, with multisampling.
+    #[serde(rename = "forward")]
+    Forward,
+    /// Surfaces are drawn into a G-buffer and lit once per pixel afterwards,
+    /// which keeps many lights cheap. Single sampled only.
+    #[serde(rename = "deferred")]
+    Deferred,
+}
+
+impl FromStr for Shading {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "forward" => Ok(Shading::Forward),
+            "deferred" => Ok(Shading::Deferred),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The input attachments read by the lighting subpass: albedo, normal,
+/// material and depth, in that order.
+pub fn gbuffer_set_layout(device: &Device<V1_0>) -> Result<DescriptorSetLayout, vk::Result> {
+    (0..4)
+        .fold(DescriptorSetLayout::build(), |builder, binding| {
+            builder.with_binding(
+                binding,
+                vk::DescriptorType::InputAttachment,
+                1,
+                vk::SHADER_STAGE_FRAGMENT_BIT,
+            )
+        }).create(device)
+}
+
+/// A single render pass that draws surfaces into a G-buffer in its first
+/// subpass and lights them in the second, reading the G-buffer as input
+/// attachments so it never leaves tile memory on GPUs that have it.
+///
+/// Only the lit output is stored, it is left in the shader read only layout
+/// like the color of a `RenderTarget`.
+pub struct DeferredRenderer {
+    pub render_pass: RenderPass,
+    /// Draws meshes with their materials into the G-buffer, writing emitted
+    /// light into the output.
+    pub geometry_pipeline: Pipeline,
+    /// Adds the light of every light and the environment to the output.
+    pub lighting_pipeline: Pipeline,
+    pub gbuffer_set_layout: DescriptorSetLayout,
+}
+
+impl DeferredRenderer {
+    /// `frame_set_layout` and `material_set_layout` are the ones of
+    /// `pbr`, `depth_format` has to be depth only. With `reversed_z` depth
+    /// is tested with greater instead of less.
+    pub fn new(
+        device: &Device<V1_0>,
+        limits: &vk::PhysicalDeviceLimits,
+        depth_format: vk::Format,
+        frame_set_layout: &DescriptorSetLayout,
+        material_set_layout: &DescriptorSetLayout,
+        reversed_z: bool,
+    ) -> DeferredRenderer {
+        let render_pass = create_render_pass(device, depth_format);
+        let gbuffer_set_layout =
+            gbuffer_set_layout(device).expect("Unable to create G-buffer descriptor set layout");
+
+        let geometry_layout = pipeline::PipelineLayout::build()
+            .with_descriptor_set_layout(frame_set_layout)
+            .with_descriptor_set_layout(material_set_layout)
+            .with_push_constants::<MeshPushConstants>(vk::SHADER_STAGE_VERTEX_BIT)
+            .create(device, limits)
+            .expect("Unable to create G-buffer pipeline layout");
+        let depth_stencil = if reversed_z {
+            pipeline::DepthStencil::greater()
+        } else {
+            pipeline::DepthStencil::less()
+        };
+
+        // Materials may be double sided, so draw both faces like the forward
+        // pipeline does.
+        let geometry_pipeline = Pipeline::build()
+            .with_shader_stage(Shader::load(
+                device,
+                MESH_VERT,
+                pipeline::ShaderType::Vertex,
+            )).with_shader_stage(Shader::load(
+                device,
+                GBUFFER_FRAG,
+                pipeline::ShaderType::Fragment,
+            )).with_vertex_input_state(mesh::Vertex::vertex_input())
+            .with_input_assembly_state(pipeline::InputAssembly::triangle_list())
+            .with_dynamic_state(pipeline::DynamicState::viewport_and_scissor())
+            .with_rasterizer(pipeline::Rasterizer::no_cull())
+            .with_multisample(pipeline::Multisample::disabled())
+            .with_color_blend(ColorBlend::new(&[BlendAttachment::opaque(); 4]))
+            .with_depth_stencil(depth_stencil)
+            .with_layout(geometry_layout)
+            .with_render_pass(render_pass)
+            .with_subpass(GEOMETRY_SUBPASS)
+            .create(device)
+            .expect("Unable to create G-buffer pipeline");
+
+        let lighting_layout = pipeline::PipelineLayout::build()
+            .with_descriptor_set_layout(frame_set_layout)
+            .with_descriptor_set_layout(&gbuffer_set_layout)
+            .with_push_constants::<DeferredPushConstants>(vk::SHADER_STAGE_FRAGMENT_BIT)
+            .create(device, limits)
+            .expect("Unable to create deferred lighting pipeline layout");
+
+        let lighting_pipeline = Pipeline::build()
+            .with_shader_stage(Shader::load(
+                device,
+                FULLSCREEN_VERT,
+                pipeline::ShaderType::Vertex,
+            )).with_shader_stage(Shader::load(
+                device,
+                LIGHTING_FRAG,
+                pipeline::ShaderType::Fragment,
+            )).with_vertex_input_state(pipeline::VertexInput::empty())
+            .with_input_assembly_state(pipeline::InputAssembly::triangle_list())
+            .with_dynamic_state(pipeline::DynamicState::viewport_and_scissor())
+            .with_rasterizer(pipeline::Rasterizer::no_cull())
+            .with_multisample(pipeline::Multisample::disabled())
+            .with_color_blend(ColorBlend::additive())
+            .with_depth_stencil(pipeline::DepthStencil::disabled())
+            .with_layout(lighting_layout)
+            .with_render_pass(render_pass)
+            .with_subpass(LIGHTING_SUBPASS)
+            .create(device)
+            .expect("Unable to create deferred lighting pipeline");
+
+        DeferredRenderer {
+            render_pass,
+            geometry_pipeline,
+            lighting_pipeline,
+            gbuffer_set_layout,
+        }
+    }
+
+    /// Record both subpasses into `gbuffer`, lighting `draws` of `model`
+    /// with the lights and shadows of `frame_set`.
+    ///
+    /// `clear_depth` is also what tells the lighting subpass where nothing
+    /// was drawn, those pixels keep `clear_color`.
+    pub fn render(
+        &self,
+        device: &Device<V1_0>,
+        command_buffer: vk::CommandBuffer,
+        gbuffer: &GBuffer,
+        frame_set: vk::DescriptorSet,
+        model: &Model,
+        materials: &PbrMaterials,
+        draws: &[DrawItem],
+        clear_color: [f32; 4],
+        clear_depth: f32,
+    ) {
+        let color = |float32| vk::ClearValue {
+            color: vk::ClearColorValue { float32 },
+        };
+        let clear_values = [
+            color(clear_color),
+            color([0.0; 4]),
+            color([0.0; 4]),
+            color([0.0; 4]),
+            vk::ClearValue {
+                depth: vk::ClearDepthStencilValue {
+                    depth: clear_depth,
+                    stencil: 0,
+                },
+            },
+        ];
+        command_pool::begin_render_pass_with_clear_values(
+            device,
+            command_buffer,
+            &self.render_pass,
+            gbuffer.framebuffer,
+            gbuffer.extent,
+            &clear_values,
+        );
+
+        let viewport = Viewport::new(gbuffer.extent);
+        let geometry = &self.geometry_pipeline;
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::Graphics,
+                geometry.graphics_pipelines[0],
+            );
+            device.cmd_set_viewport(command_buffer, 0, &[viewport.viewport]);
+            device.cmd_set_scissor(command_buffer, 0, &[viewport.scissor]);
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::Graphics,
+                geometry.layout.layout,
+                FRAME_SET,
+                &[frame_set],
+                &[],
+            );
+        }
+        model.draw(device, command_buffer, &geometry.layout, materials, draws);
+
+        // The layouts differ in their push constants, so the frame set has
+        // to be bound again.
+        let lighting = &self.lighting_pipeline;
+        unsafe {
+            device.cmd_next_subpass(command_buffer, vk::SubpassContents::Inline);
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::Graphics,
+                lighting.graphics_pipelines[0],
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::Graphics,
+                lighting.layout.layout,
+                FRAME_SET,
+                &[frame_set],
+                &[],
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::Graphics,
+                lighting.layout.layout,
+                GBUFFER_SET,
+                &[gbuffer.set],
+                &[],
+            );
+        }
+        command_pool::push_constants(
+            device,
+            command_buffer,
+            &lighting.layout,
+            vk::SHADER_STAGE_FRAGMENT_BIT,
+            0,
+            &DeferredPushConstants { clear_depth },
+        );
+        unsafe {
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+            device.cmd_end_render_pass(command_buffer);
+        }
+    }
+
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        self.geometry_pipeline.destroy(device);
+        self.lighting_pipeline.destroy(device);
+        self.gbuffer_set_layout.destroy(device);
+        self.render_pass.destroy(device);
+    }
+}
+
+/// The lit output and the G-buffer of a `DeferredRenderer`, at the size of
+/// what is rendered.
+pub struct GBuffer {
+    /// Sampled by later passes.
+    pub output: Image,
+    pub output_view: vk::ImageView,
+    /// Albedo, normal, material and depth, in the order of the G-buffer set.
+    pub attachments: Vec<(Image, vk::ImageView)>,
+    pub framebuffer: vk::Framebuffer,
+    pub descriptor_pool: DescriptorPool,
+    /// Points the lighting subpass at the attachments.
+    pub set: vk::DescriptorSet,
+    /// Clamps to the edge, so post-processing filters don't wrap around.
+    pub sampler: vk::Sampler,
+    pub extent: vk::Extent2D,
+}
+
+impl GBuffer {
+    pub fn new(
+        instance: &Instance<V1_0>,
+        pdevice: vk::PhysicalDevice,
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        renderer: &DeferredRenderer,
+        extent: vk::Extent2D,
+    ) -> Result<GBuffer, ImageError> {
+        let create_image = |format, usage| -> Result<(Image, vk::ImageView), ImageError> {
+            let desc = ImageDesc::new_2d(format, extent.width, extent.height).with_usage(usage);
+            let image = Image::create(instance, pdevice, device, memory_properties, &desc)?;
+            let view = image.create_view(device, &image.view_desc());
+            Ok((image, view))
+        };
+
+        let (output, output_view) = create_image(
+            post::HDR_FORMAT,
+            vk::IMAGE_USAGE_COLOR_ATTACHMENT_BIT | vk::IMAGE_USAGE_SAMPLED_BIT,
+        )?;
+
+        // Only live while the render pass runs.
+        let gbuffer_usage =
+            vk::IMAGE_USAGE_INPUT_ATTACHMENT_BIT | vk::IMAGE_USAGE_TRANSIENT_ATTACHMENT_BIT;
+        let depth_format = renderer
+            .render_pass
+            .depth_format
+            .expect("The deferred render pass has a depth attachment");
+        let attachments = vec![
+            create_image(
+                ALBEDO_FORMAT,
+                vk::IMAGE_USAGE_COLOR_ATTACHMENT_BIT | gbuffer_usage,
+            )?,
+            create_image(
+                NORMAL_FORMAT,
+                vk::IMAGE_USAGE_COLOR_ATTACHMENT_BIT | gbuffer_usage,
+            )?,
+            create_image(
+                MATERIAL_FORMAT,
+                vk::IMAGE_USAGE_COLOR_ATTACHMENT_BIT | gbuffer_usage,
+            )?,
+            create_image(
+                depth_format,
+                vk::IMAGE_USAGE_DEPTH_STENCIL_ATTACHMENT_BIT | gbuffer_usage,
+            )?,
+        ];
+
+        let views = Some(output_view)
+            .into_iter()
+            .chain(attachments.iter().map(|&(_, view)| view))
+            .collect::<Vec<_>>();
+        let framebuffer_info = vk::FramebufferCreateInfo {
+            s_type: vk::StructureType::FramebufferCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            render_pass: renderer.render_pass.render_pass,
+            attachment_count: views.len() as u32,
+            p_attachments: views.as_ptr(),
+            width: extent.width,
+            height: extent.height,
+            layers: 1,
+        };
+
+        let framebuffer = unsafe {
+            device
+                .create_framebuffer(&framebuffer_info, None)
+                .expect("Unable to create G-buffer framebuffer")
+        };
+
+        let descriptor_pool = DescriptorPool::new(
+            device,
+            &[(vk::DescriptorType::InputAttachment, attachments.len() as u32)],
+            1,
+        ).expect("Unable to create G-buffer descriptor pool");
+        let set = descriptor_pool
+            .allocate(device, &renderer.gbuffer_set_layout, 1)
+            .expect("Unable to allocate G-buffer descriptor set")[0];
+        for (binding, &(_, view)) in attachments.iter().enumerate() {
+            // Bindings follow the attachments from the albedo on.
+            let layout = if binding as u32 + ALBEDO == DEPTH {
+                vk::ImageLayout::DepthStencilReadOnlyOptimal
+            } else {
+                vk::ImageLayout::ShaderReadOnlyOptimal
+            };
+            descriptor::write_input_attachment(device, set, binding as u32, view, layout);
+        }
+
+        let sampler = texture::create_sampler(
+            device,
+            &SamplerDesc {
+                address_mode_u: vk::SamplerAddressMode::ClampToEdge,
+                address_mode_v: vk::SamplerAddressMode::ClampToEdge,
+                ..SamplerDesc::default()
+            },
+        );
+
+        Ok(GBuffer {
+            output,
+            output_view,
+            attachments,
+            framebuffer,
+            descriptor_pool,
+            set,
+            sampler,
+            extent,
+        })
+    }
+
+    /// Destroy the images and the framebuffer. The G-buffer must not be in
+    /// use by the device.
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        self.descriptor_pool.destroy(device);
+        unsafe {
+            device.destroy_sampler(self.sampler, None);
+            device.destroy_framebuffer(self.framebuffer, None);
+            device.destroy_image_view(self.output_view, None);
+        }
+        self.output.destroy(device);
+
+        for &(ref image, view) in &self.attachments {
+            unsafe {
+                device.destroy_image_view(view, None);
+            }
+            image.destroy(device);
+        }
+    }
+}
+
+/// The lit output followed by the G-buffer attachments and depth. The
+/// geometry subpass writes all of them, the lighting subpass reads the
+/// G-buffer and depth and adds to the output.
+fn create_render_pass(device: &Device<V1_0>, depth_format: vk::Format) -> RenderPass {
+    let transient = |format| {
+        (
+            format,
+            vk::AttachmentLoadOp::Clear,
+            vk::AttachmentStoreOp::DontCare,
+            vk::ImageLayout::ShaderReadOnlyOptimal,
+        )
+    };
+    let gbuffer = [
+        transient(ALBEDO_FORMAT),
+        transient(NORMAL_FORMAT),
+        transient(MATERIAL_FORMAT),
+        (
+            depth_format,
+            vk::AttachmentLoadOp::Clear,
+            vk::AttachmentStoreOp::DontCare,
+            vk::ImageLayout::DepthStencilReadOnlyOptimal,
+        ),
+    ];
+
+    let builder = RenderPass::build().with_attachment(
+        post::HDR_FORMAT,
+        vk::AttachmentLoadOp::Clear,
+        vk::AttachmentStoreOp::Store,
+        vk::ImageLayout::ShaderReadOnlyOptimal,
+    );
+    let builder = gbuffer.iter().fold(
+        builder,
+        |builder, &(format, load_op, store_op, final_layout)| {
+            builder.with_attachment(format, load_op, store_op, final_layout)
+        },
+    );
+
+    builder
+        .with_subpass(
+            SubpassDesc::new()
+                .with_color(OUTPUT)
+                .with_color(ALBEDO)
+                .with_color(NORMAL)
+                .with_color(MATERIAL)
+                .with_depth(DEPTH),
+        ).with_subpass(
+            SubpassDesc::new()
+                .with_color(OUTPUT)
+                .with_input(ALBEDO, vk::ImageLayout::ShaderReadOnlyOptimal)
+                .with_input(NORMAL, vk::ImageLayout::ShaderReadOnlyOptimal)
+                .with_input(MATERIAL, vk::ImageLayout::ShaderReadOnlyOptimal)
+                .with_input(DEPTH, vk::ImageLayout::DepthStencilReadOnlyOptimal),
+        )
+        // The previous frame's reads of the output and writes of the depth
+        // have to finish before they are cleared.
+        .with_dependency(vk::SubpassDependency {
+            dependency_flags: Default::default(),
+            src_subpass: vk::VK_SUBPASS_EXTERNAL,
+            dst_subpass: GEOMETRY_SUBPASS,
+            src_stage_mask: vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT
+                | vk::PIPELINE_STAGE_LATE_FRAGMENT_TESTS_BIT,
+            src_access_mask: vk::ACCESS_DEPTH_STENCIL_ATTACHMENT_WRITE_BIT,
+            dst_stage_mask: vk::PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT
+                | vk::PIPELINE_STAGE_EARLY_FRAGMENT_TESTS_BIT,
+            dst_access_mask: vk::ACCESS_COLOR_ATTACHMENT_READ_BIT
+                | vk::ACCESS_COLOR_ATTACHMENT_WRITE_BIT
+                | vk::ACCESS_DEPTH_STENCIL_ATTACHMENT_WRITE_BIT,
+        })
+        // Lighting reads only the pixel it shades, so each region can go
+        // ahead as soon as the geometry subpass is done with it.
+        .with_dependency(vk::SubpassDependency {
+            dependency_flags: vk::DEPENDENCY_BY_REGION_BIT,
+            src_subpass: GEOMETRY_SUBPASS,
+            dst_subpass: LIGHTING_SUBPASS,
+            src_stage_mask: vk::PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT
+                | vk::PIPELINE_STAGE_LATE_FRAGMENT_TESTS_BIT,
+            src_access_mask: vk::ACCESS_COLOR_ATTACHMENT_WRITE_BIT
+                | vk::ACCESS_DEPTH_STENCIL_ATTACHMENT_WRITE_BIT,
+            dst_stage_mask: vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT
+                | vk::PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT,
+            dst_access_mask: vk::ACCESS_INPUT_ATTACHMENT_READ_BIT
+                | vk::ACCESS_COLOR_ATTACHMENT_READ_BIT
+                | vk::ACCESS_COLOR_ATTACHMENT_WRITE_BIT,
+        })
+        // The output has to be written before it is sampled.
+        .with_dependency(vk::SubpassDependency {
+            dependency_flags: Default::default(),
+            src_subpass: LIGHTING_SUBPASS,
+            dst_subpass: vk::VK_SUBPASS_EXTERNAL,
+            src_stage_mask: vk::PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT,
+            src_access_mask: vk::ACCESS_COLOR_ATTACHMENT_WRITE_BIT,
+            dst_stage_mask: vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT,
+            dst_access_mask: vk::ACCESS_SHADER_READ_BIT,
+        }).create(device)
+        .expect("Unable to create deferred render pass")
+}
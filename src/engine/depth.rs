@@ -0,0 +1,178 @@
+use ash::version::{DeviceV1_0, InstanceV1_0, V1_0};
+use ash::vk;
+use ash::{Device, Instance};
+use std::ptr;
+
+use super::memory;
+
+/// Depth formats in order of preference.
+const DEPTH_FORMATS: [vk::Format; 3] = [
+    vk::Format::D32Sfloat,
+    vk::Format::D32SfloatS8Uint,
+    vk::Format::D24UnormS8Uint,
+];
+
+/// The first depth format the device can use as an optimally tiled depth
+/// attachment.
+pub fn select_depth_format(
+    instance: &Instance<V1_0>,
+    pdevice: vk::PhysicalDevice,
+) -> Option<vk::Format> {
+    DEPTH_FORMATS.iter().cloned().find(|&format| {
+        instance
+            .get_physical_device_format_properties(pdevice, format)
+            .optimal_tiling_features
+            .subset(vk::FORMAT_FEATURE_DEPTH_STENCIL_ATTACHMENT_BIT)
+    })
+}
+
+/// Depth only formats, in order of preference.
+const DEPTH_ONLY_FORMATS: [vk::Format; 2] = [vk::Format::D32Sfloat, vk::Format::D16Unorm];
+
+/// The first depth only format the device can render shadow maps into and
+/// sample them from.
+pub fn select_shadow_format(
+    instance: &Instance<V1_0>,
+    pdevice: vk::PhysicalDevice,
+) -> Option<vk::Format> {
+    DEPTH_ONLY_FORMATS.iter().cloned().find(|&format| {
+        instance
+            .get_physical_device_format_properties(pdevice, format)
+            .optimal_tiling_features
+            .subset(
+                vk::FORMAT_FEATURE_DEPTH_STENCIL_ATTACHMENT_BIT
+                    | vk::FORMAT_FEATURE_SAMPLED_IMAGE_BIT,
+            )
+    })
+}
+
+/// The first depth only format the device can render a G-buffer's depth
+/// into, which is read back as an input attachment. Formats with stencil
+/// would need a separate view for that.
+pub fn select_gbuffer_depth_format(
+    instance: &Instance<V1_0>,
+    pdevice: vk::PhysicalDevice,
+) -> Option<vk::Format> {
+    DEPTH_ONLY_FORMATS.iter().cloned().find(|&format| {
+        instance
+            .get_physical_device_format_properties(pdevice, format)
+            .optimal_tiling_features
+            .subset(vk::FORMAT_FEATURE_DEPTH_STENCIL_ATTACHMENT_BIT)
+    })
+}
+
+/// A depth attachment sized to the swapchain, recreated along with it.
+pub struct DepthBuffer {
+    pub image: vk::Image,
+    pub memory: vk::DeviceMemory,
+    pub view: vk::ImageView,
+    pub format: vk::Format,
+    /// Has to match the color attachments it is used with.
+    pub samples: vk::SampleCountFlags,
+}
+
+impl DepthBuffer {
+    pub fn new(
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        format: vk::Format,
+        samples: vk::SampleCountFlags,
+        extent: vk::Extent2D,
+    ) -> Result<DepthBuffer, vk::Result> {
+        let image_info = vk::ImageCreateInfo {
+            s_type: vk::StructureType::ImageCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            image_type: vk::ImageType::Type2d,
+            format,
+            extent: vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            },
+            mip_levels: 1,
+            array_layers: 1,
+            samples,
+            tiling: vk::ImageTiling::Optimal,
+            usage: vk::IMAGE_USAGE_DEPTH_STENCIL_ATTACHMENT_BIT,
+            sharing_mode: vk::SharingMode::Exclusive,
+            queue_family_index_count: 0,
+            p_queue_family_indices: ptr::null(),
+            initial_layout: vk::ImageLayout::Undefined,
+        };
+
+        let image = unsafe {
+            device
+                .create_image(&image_info, None)
+                .expect("Unable to create depth image")
+        };
+
+        let memory_requirements = device.get_image_memory_requirements(image);
+        let memory_type_index = memory::find_memory_type_index(
+            &memory_requirements,
+            memory_properties,
+            vk::MEMORY_PROPERTY_DEVICE_LOCAL_BIT,
+        ).ok_or(vk::Result::ErrorOutOfDeviceMemory)?;
+
+        let allocate_info = vk::MemoryAllocateInfo {
+            s_type: vk::StructureType::MemoryAllocateInfo,
+            p_next: ptr::null(),
+            allocation_size: memory_requirements.size,
+            memory_type_index,
+        };
+
+        let memory = unsafe {
+            let memory = device
+                .allocate_memory(&allocate_info, None)
+                .expect("Unable to allocate depth image memory");
+            device
+                .bind_image_memory(image, memory, 0)
+                .expect("Unable to bind depth image memory");
+            memory
+        };
+
+        let view_info = vk::ImageViewCreateInfo {
+            s_type: vk::StructureType::ImageViewCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            view_type: vk::ImageViewType::Type2d,
+            format,
+            components: vk::ComponentMapping {
+                r: vk::ComponentSwizzle::Identity,
+                g: vk::ComponentSwizzle::Identity,
+                b: vk::ComponentSwizzle::Identity,
+                a: vk::ComponentSwizzle::Identity,
+            },
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: vk::IMAGE_ASPECT_DEPTH_BIT,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image,
+        };
+
+        let view = unsafe {
+            device
+                .create_image_view(&view_info, None)
+                .expect("Unable to create depth image view")
+        };
+
+        Ok(DepthBuffer {
+            image,
+            memory,
+            view,
+            format,
+            samples,
+        })
+    }
+
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
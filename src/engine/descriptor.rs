@@ -0,0 +1,259 @@
+use ash::version::{DeviceV1_0, V1_0};
+use ash::vk;
+use ash::Device;
+use std::ptr;
+
+use super::buffer::Buffer;
+
+pub struct DescriptorSetLayout {
+    pub layout: vk::DescriptorSetLayout,
+    pub bindings: Vec<vk::DescriptorSetLayoutBinding>,
+}
+
+impl DescriptorSetLayout {
+    pub fn build() -> DescriptorSetLayoutBuilder {
+        DescriptorSetLayoutBuilder::new()
+    }
+
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        unsafe {
+            device.destroy_descriptor_set_layout(self.layout, None);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DescriptorSetLayoutBuilder {
+    bindings: Vec<vk::DescriptorSetLayoutBinding>,
+}
+
+impl DescriptorSetLayoutBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_binding(
+        mut self,
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        descriptor_count: u32,
+        stage_flags: vk::ShaderStageFlags,
+    ) -> Self {
+        self.bindings.push(vk::DescriptorSetLayoutBinding {
+            binding,
+            descriptor_type,
+            descriptor_count,
+            stage_flags,
+            p_immutable_samplers: ptr::null(),
+        });
+        self
+    }
+
+    pub fn create(self, device: &Device<V1_0>) -> Result<DescriptorSetLayout, vk::Result> {
+        let layout_info = vk::DescriptorSetLayoutCreateInfo {
+            s_type: vk::StructureType::DescriptorSetLayoutCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            binding_count: self.bindings.len() as u32,
+            p_bindings: self.bindings.as_ptr(),
+        };
+
+        let layout = unsafe {
+            device
+                .create_descriptor_set_layout(&layout_info, None)?
+        };
+
+        Ok(DescriptorSetLayout {
+            layout,
+            bindings: self.bindings,
+        })
+    }
+}
+
+pub struct DescriptorPool {
+    pub pool: vk::DescriptorPool,
+}
+
+impl DescriptorPool {
+    /// A pool with room for `max_sets` sets, drawing from the given number of
+    /// descriptors per type.
+    pub fn new(
+        device: &Device<V1_0>,
+        pool_sizes: &[(vk::DescriptorType, u32)],
+        max_sets: u32,
+    ) -> Result<DescriptorPool, vk::Result> {
+        let pool_sizes = pool_sizes
+            .iter()
+            .map(|&(typ, descriptor_count)| vk::DescriptorPoolSize {
+                typ,
+                descriptor_count,
+            }).collect::<Vec<_>>();
+
+        let pool_info = vk::DescriptorPoolCreateInfo {
+            s_type: vk::StructureType::DescriptorPoolCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            max_sets,
+            pool_size_count: pool_sizes.len() as u32,
+            p_pool_sizes: pool_sizes.as_ptr(),
+        };
+
+        let pool = unsafe {
+            device
+                .create_descriptor_pool(&pool_info, None)?
+        };
+
+        Ok(DescriptorPool { pool })
+    }
+
+    /// Allocate `count` sets with the same layout.
+    pub fn allocate(
+        &self,
+        device: &Device<V1_0>,
+        layout: &DescriptorSetLayout,
+        count: usize,
+    ) -> Result<Vec<vk::DescriptorSet>, vk::Result> {
+        let layouts = vec![layout.layout; count];
+        let allocate_info = vk::DescriptorSetAllocateInfo {
+            s_type: vk::StructureType::DescriptorSetAllocateInfo,
+            p_next: ptr::null(),
+            descriptor_pool: self.pool,
+            descriptor_set_count: layouts.len() as u32,
+            p_set_layouts: layouts.as_ptr(),
+        };
+
+        unsafe { device.allocate_descriptor_sets(&allocate_info) }
+    }
+
+    /// Destroying the pool frees every set allocated from it.
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        unsafe {
+            device.destroy_descriptor_pool(self.pool, None);
+        }
+    }
+}
+
+/// Point `binding` of `set` at the whole of `buffer`.
+pub fn write_uniform_buffer(
+    device: &Device<V1_0>,
+    set: vk::DescriptorSet,
+    binding: u32,
+    buffer: &Buffer,
+) {
+    write_buffer(device, set, binding, vk::DescriptorType::UniformBuffer, buffer);
+}
+
+/// Point `binding` of `set` at the whole of `buffer`, read as a storage
+/// buffer.
+pub fn write_storage_buffer(
+    device: &Device<V1_0>,
+    set: vk::DescriptorSet,
+    binding: u32,
+    buffer: &Buffer,
+) {
+    write_buffer(device, set, binding, vk::DescriptorType::StorageBuffer, buffer);
+}
+
+fn write_buffer(
+    device: &Device<V1_0>,
+    set: vk::DescriptorSet,
+    binding: u32,
+    descriptor_type: vk::DescriptorType,
+    buffer: &Buffer,
+) {
+    let buffer_info = vk::DescriptorBufferInfo {
+        buffer: buffer.buffer,
+        offset: 0,
+        range: buffer.size,
+    };
+
+    let write = vk::WriteDescriptorSet {
+        s_type: vk::StructureType::WriteDescriptorSet,
+        p_next: ptr::null(),
+        dst_set: set,
+        dst_binding: binding,
+        dst_array_element: 0,
+        descriptor_count: 1,
+        descriptor_type,
+        p_image_info: ptr::null(),
+        p_buffer_info: &buffer_info,
+        p_texel_buffer_view: ptr::null(),
+    };
+
+    unsafe {
+        device.update_descriptor_sets(&[write], &[]);
+    }
+}
+
+/// Point `binding` of `set` at `view` sampled with `sampler`, the view is
+/// expected in the shader read only layout.
+pub fn write_combined_image_sampler(
+    device: &Device<V1_0>,
+    set: vk::DescriptorSet,
+    binding: u32,
+    view: vk::ImageView,
+    sampler: vk::Sampler,
+) {
+    let image_info = vk::DescriptorImageInfo {
+        sampler,
+        image_view: view,
+        image_layout: vk::ImageLayout::ShaderReadOnlyOptimal,
+    };
+
+    write_image(
+        device,
+        set,
+        binding,
+        vk::DescriptorType::CombinedImageSampler,
+        &image_info,
+    );
+}
+
+/// Point `binding` of `set` at `view`, read with `subpassLoad` in the
+/// `layout` the subpass references the attachment with.
+pub fn write_input_attachment(
+    device: &Device<V1_0>,
+    set: vk::DescriptorSet,
+    binding: u32,
+    view: vk::ImageView,
+    layout: vk::ImageLayout,
+) {
+    let image_info = vk::DescriptorImageInfo {
+        sampler: vk::Sampler::null(),
+        image_view: view,
+        image_layout: layout,
+    };
+
+    write_image(
+        device,
+        set,
+        binding,
+        vk::DescriptorType::InputAttachment,
+        &image_info,
+    );
+}
+
+fn write_image(
+    device: &Device<V1_0>,
+    set: vk::DescriptorSet,
+    binding: u32,
+    descriptor_type: vk::DescriptorType,
+    image_info: &vk::DescriptorImageInfo,
+) {
+    let write = vk::WriteDescriptorSet {
+        s_type: vk::StructureType::WriteDescriptorSet,
+        p_next: ptr::null(),
+        dst_set: set,
+        dst_binding: binding,
+        dst_array_element: 0,
+        descriptor_count: 1,
+        descriptor_type,
+        p_image_info: image_info,
+        p_buffer_info: ptr::null(),
+        p_texel_buffer_view: ptr::null(),
+    };
+
+    unsafe {
+        device.update_descriptor_sets(&[write], &[]);
+    }
+}
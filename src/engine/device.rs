@@ -2,6 +2,7 @@ use ash;
 use ash::extensions::Swapchain;
 use ash::version::{InstanceV1_0, V1_0};
 use ash::vk;
+use ash::DeviceError;
 use std::ptr;
 
 pub struct Device {
@@ -9,44 +10,67 @@ pub struct Device {
 }
 
 impl Device {
+    /// Create the device with one queue from `queue_index`, and one from
+    /// `transfer_queue_index` when given.
     pub fn new(
         instance: &ash::Instance<V1_0>,
         layer_names_raw: Vec<*const i8>,
         device_extension_names_raw: Vec<*const i8>,
         queue_index: u32,
+        transfer_queue_index: Option<u32>,
         pdevice: vk::PhysicalDevice,
-    ) -> Result<Device, ()> {
+    ) -> Result<Device, vk::Result> {
         let priorities = [1.0];
-        let queue_info = vk::DeviceQueueCreateInfo {
-            s_type: vk::StructureType::DeviceQueueCreateInfo,
-            p_next: ptr::null(),
-            flags: Default::default(),
-            queue_family_index: queue_index,
-            p_queue_priorities: priorities.as_ptr(),
-            queue_count: priorities.len() as u32,
-        };
+        let queue_infos = Some(queue_index)
+            .into_iter()
+            .chain(transfer_queue_index)
+            .map(|queue_family_index| vk::DeviceQueueCreateInfo {
+                s_type: vk::StructureType::DeviceQueueCreateInfo,
+                p_next: ptr::null(),
+                flags: Default::default(),
+                queue_family_index,
+                p_queue_priorities: priorities.as_ptr(),
+                queue_count: priorities.len() as u32,
+            }).collect::<Vec<_>>();
 
         let device_features = instance.get_physical_device_features(pdevice);
-        let device_extension_names_raw = [Swapchain::name().as_ptr()];
+        let mut device_extension_names_raw = device_extension_names_raw;
+        device_extension_names_raw.push(Swapchain::name().as_ptr());
         let device_create_info = vk::DeviceCreateInfo {
             s_type: vk::StructureType::DeviceCreateInfo,
             p_next: ptr::null(),
             flags: Default::default(),
-            queue_create_info_count: 1,
+            queue_create_info_count: queue_infos.len() as u32,
             p_enabled_features: &device_features,
             enabled_layer_count: layer_names_raw.len() as u32,
             pp_enabled_layer_names: layer_names_raw.as_ptr(),
             enabled_extension_count: device_extension_names_raw.len() as u32,
             pp_enabled_extension_names: device_extension_names_raw.as_ptr(),
-            p_queue_create_infos: &queue_info,
+            p_queue_create_infos: queue_infos.as_ptr(),
         };
 
-        let device = unsafe {
-            instance
-                .create_device(pdevice, &device_create_info, None)
-                .expect("Unable to create device")
-        };
+        let device = unsafe { instance.create_device(pdevice, &device_create_info, None) }
+            .map_err(|error| match error {
+                DeviceError::LoadError(_) => vk::Result::ErrorInitializationFailed,
+                DeviceError::VkError(error) => error,
+            })?;
 
         Ok(Device { device })
     }
 }
+
+/// A queue family that only does transfers, which usually means a DMA
+/// engine that copies alongside graphics work.
+pub fn find_transfer_queue_family(
+    instance: &ash::Instance<V1_0>,
+    pdevice: vk::PhysicalDevice,
+) -> Option<u32> {
+    instance
+        .get_physical_device_queue_family_properties(pdevice)
+        .iter()
+        .position(|info| {
+            info.queue_count > 0
+                && info.queue_flags.subset(vk::QUEUE_TRANSFER_BIT)
+                && !info.queue_flags.intersects(vk::QUEUE_GRAPHICS_BIT | vk::QUEUE_COMPUTE_BIT)
+        }).map(|index| index as u32)
+}
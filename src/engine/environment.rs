@@ -0,0 +1,205 @@
+use ash::version::{DeviceV1_0, V1_0};
+use ash::vk;
+use ash::{Device, Instance};
+
+use super::image::{self, Image, ImageDesc, ImageError};
+use super::post;
+use super::texture::{self, SamplerDesc};
+use super::upload::{UploadDst, Uploader};
+
+/// Colors of the procedural sky, linear RGB in the same units as lights.
+#[derive(Clone, Copy, Debug)]
+pub struct SkyDesc {
+    pub zenith: [f32; 3],
+    pub horizon: [f32; 3],
+    pub ground: [f32; 3],
+    /// Towards the sun.
+    pub sun_direction: [f32; 3],
+    pub sun_color: [f32; 3],
+}
+
+impl Default for SkyDesc {
+    fn default() -> Self {
+        SkyDesc {
+            zenith: [0.15, 0.3, 0.65],
+            horizon: [0.7, 0.75, 0.8],
+            ground: [0.2, 0.18, 0.15],
+            sun_direction: [0.3, 0.8, 0.5],
+            sun_color: [40.0, 36.0, 30.0],
+        }
+    }
+}
+
+/// An HDR cube map lighting the scene from all around.
+///
+/// Mip levels stand in for a prefiltered environment: the shaders sample
+/// rougher reflections from smaller levels and diffuse light from the
+/// smallest one.
+pub struct Environment {
+    pub image: Image,
+    pub view: vk::ImageView,
+    pub sampler: vk::Sampler,
+}
+
+impl Environment {
+    /// Render `sky` into a cube with `size` texels a side and upload it with
+    /// every mip level.
+    ///
+    /// The upload is only recorded, it has to be flushed before the
+    /// environment is sampled.
+    pub fn sky(
+        instance: &Instance<V1_0>,
+        pdevice: vk::PhysicalDevice,
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        uploader: &mut Uploader,
+        sky: &SkyDesc,
+        size: u32,
+    ) -> Result<Environment, ImageError> {
+        let image = Image::create(
+            instance,
+            pdevice,
+            device,
+            memory_properties,
+            &ImageDesc::new_cube(post::HDR_FORMAT, size)
+                .with_usage(vk::IMAGE_USAGE_TRANSFER_DST_BIT | vk::IMAGE_USAGE_SAMPLED_BIT)
+                .with_full_mip_chain(),
+        )?;
+        let levels = image.subresource_range.level_count;
+
+        let mut faces = (0..6).map(|face| sky_face(sky, face, size)).collect::<Vec<_>>();
+        let mut level_size = size;
+        for level in 0..levels {
+            let mut texels = Vec::with_capacity(faces.len() * faces[0].len() * 8);
+            for &value in faces.iter().flat_map(|face| face.iter()).flat_map(|texel| texel.iter()) {
+                let bits = image::f16_bits(value);
+                texels.push(bits as u8);
+                texels.push((bits >> 8) as u8);
+            }
+
+            uploader
+                .upload_image(
+                    device,
+                    memory_properties,
+                    image.image,
+                    vk::ImageSubresourceLayers {
+                        aspect_mask: vk::IMAGE_ASPECT_COLOR_BIT,
+                        mip_level: level,
+                        base_array_layer: 0,
+                        layer_count: 6,
+                    },
+                    vk::Extent3D {
+                        width: level_size,
+                        height: level_size,
+                        depth: 1,
+                    },
+                    &texels,
+                    vk::ImageLayout::ShaderReadOnlyOptimal,
+                    UploadDst::fragment_shader_read(),
+                ).expect("Unable to upload the environment");
+
+            faces = faces
+                .iter()
+                .map(|face| downsample(face, level_size))
+                .collect();
+            level_size = (level_size / 2).max(1);
+        }
+
+        let view = image.create_view(device, &image.view_desc());
+        let sampler = texture::create_sampler(
+            device,
+            &SamplerDesc {
+                address_mode_u: vk::SamplerAddressMode::ClampToEdge,
+                address_mode_v: vk::SamplerAddressMode::ClampToEdge,
+                max_lod: levels as f32,
+                ..SamplerDesc::default()
+            },
+        );
+
+        Ok(Environment {
+            image,
+            view,
+            sampler,
+        })
+    }
+
+    pub fn levels(&self) -> u32 {
+        self.image.subresource_range.level_count
+    }
+
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        unsafe {
+            device.destroy_sampler(self.sampler, None);
+            device.destroy_image_view(self.view, None);
+        }
+        self.image.destroy(device);
+    }
+}
+
+/// The texels of `face` in Vulkan's cube face order, +X, -X, +Y, -Y, +Z, -Z.
+fn sky_face(sky: &SkyDesc, face: u32, size: u32) -> Vec<[f32; 4]> {
+    let sun = normalize(sky.sun_direction);
+
+    (0..size * size)
+        .map(|index| {
+            let s = ((index % size) as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+            let t = ((index / size) as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+            let direction = normalize(match face {
+                0 => [1.0, -t, -s],
+                1 => [-1.0, -t, s],
+                2 => [s, 1.0, t],
+                3 => [s, -1.0, -t],
+                4 => [s, -t, 1.0],
+                _ => [-s, -t, -1.0],
+            });
+
+            let up = direction[1];
+            let (from, to, amount) = if up >= 0.0 {
+                (sky.horizon, sky.zenith, up.sqrt())
+            } else {
+                (sky.horizon, sky.ground, (-up * 4.0).min(1.0))
+            };
+            let sun_amount = dot(direction, sun).max(0.0).powf(512.0);
+
+            let channel =
+                |c: usize| from[c] + (to[c] - from[c]) * amount + sky.sun_color[c] * sun_amount;
+            [channel(0), channel(1), channel(2), 1.0]
+        }).collect()
+}
+
+/// Average 2x2 blocks of a face `size` texels a side.
+fn downsample(face: &[[f32; 4]], size: u32) -> Vec<[f32; 4]> {
+    let half = (size / 2).max(1);
+    let last = size - 1;
+
+    (0..half * half)
+        .map(|index| {
+            let x = (index % half) * 2;
+            let y = (index / half) * 2;
+            let corners = [
+                (x, y),
+                ((x + 1).min(last), y),
+                (x, (y + 1).min(last)),
+                ((x + 1).min(last), (y + 1).min(last)),
+            ];
+
+            corners.iter().fold([0.0; 4], |texel, &(x, y)| {
+                let source = face[(y * size + x) as usize];
+                [
+                    texel[0] + source[0] * 0.25,
+                    texel[1] + source[1] * 0.25,
+                    texel[2] + source[2] * 0.25,
+                    texel[3] + source[3] * 0.25,
+                ]
+            })
+        }).collect()
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = dot(v, v).sqrt();
+    [v[0] / length, v[1] / length, v[2] / length]
+}
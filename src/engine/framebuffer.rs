@@ -0,0 +1,57 @@
+use ash::version::{DeviceV1_0, V1_0};
+use ash::vk;
+use ash::Device;
+use std::ptr;
+
+/// Create one framebuffer per image view, each with the view as its color
+/// attachment followed by the shared depth view, if any.
+///
+/// With a shared multisampled `color_view` that one becomes the color
+/// attachment instead, and the image view is attached last to resolve into.
+/// None are left behind on failure.
+pub fn create_framebuffers(
+    device: &Device<V1_0>,
+    render_pass: vk::RenderPass,
+    image_views: &[vk::ImageView],
+    color_view: Option<vk::ImageView>,
+    depth_view: Option<vk::ImageView>,
+    surface_resolution: vk::Extent2D,
+) -> Result<Vec<vk::Framebuffer>, vk::Result> {
+    let mut framebuffers = Vec::with_capacity(image_views.len());
+    for &image_view in image_views {
+        let attachments = match color_view {
+            Some(color_view) => Some(color_view)
+                .into_iter()
+                .chain(depth_view)
+                .chain(Some(image_view))
+                .collect::<Vec<_>>(),
+            None => Some(image_view)
+                .into_iter()
+                .chain(depth_view)
+                .collect::<Vec<_>>(),
+        };
+        let framebuffer_info = vk::FramebufferCreateInfo {
+            s_type: vk::StructureType::FramebufferCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            render_pass,
+            attachment_count: attachments.len() as u32,
+            p_attachments: attachments.as_ptr(),
+            width: surface_resolution.width,
+            height: surface_resolution.height,
+            layers: 1,
+        };
+
+        match unsafe { device.create_framebuffer(&framebuffer_info, None) } {
+            Ok(framebuffer) => framebuffers.push(framebuffer),
+            Err(error) => {
+                for framebuffer in framebuffers {
+                    unsafe { device.destroy_framebuffer(framebuffer, None) };
+                }
+                return Err(error);
+            }
+        }
+    }
+
+    Ok(framebuffers)
+}
@@ -0,0 +1,66 @@
+use ash::version::{InstanceV1_0, V1_0};
+use ash::vk;
+use ash::Instance;
+use std::ffi::CStr;
+use std::ptr;
+
+/// Device extension that controls whether the swapchain may take exclusive
+/// control of the display. Only drivers on Windows offer it.
+pub const EXTENSION: &str = "VK_EXT_full_screen_exclusive";
+
+/// Instance extensions `VK_EXT_full_screen_exclusive` depends on.
+pub const INSTANCE_EXTENSIONS: &[&str] = &[
+    "VK_KHR_get_physical_device_properties2",
+    "VK_KHR_get_surface_capabilities2",
+];
+
+/// `VK_STRUCTURE_TYPE_SURFACE_FULL_SCREEN_EXCLUSIVE_INFO_EXT`
+const SURFACE_FULL_SCREEN_EXCLUSIVE_INFO: i32 = 1000255000;
+
+/// `VkFullScreenExclusiveEXT`
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FullScreenExclusive {
+    /// Left to the driver.
+    Default = 0,
+    /// The driver may go exclusive when the window covers the monitor.
+    Allowed = 1,
+    /// Never exclusive, for windowed and borderless swapchains.
+    Disallowed = 2,
+}
+
+/// `VkSurfaceFullScreenExclusiveInfoEXT`, chained to the swapchain create
+/// info.
+///
+/// ash has no bindings for the extension, the layout follows the Vulkan
+/// headers.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SurfaceFullScreenExclusiveInfo {
+    s_type: i32,
+    p_next: *const vk::c_void,
+    full_screen_exclusive: FullScreenExclusive,
+}
+
+impl SurfaceFullScreenExclusiveInfo {
+    pub fn new(full_screen_exclusive: FullScreenExclusive) -> Self {
+        SurfaceFullScreenExclusiveInfo {
+            s_type: SURFACE_FULL_SCREEN_EXCLUSIVE_INFO,
+            p_next: ptr::null(),
+            full_screen_exclusive,
+        }
+    }
+}
+
+/// Whether `pdevice` has the extension. The instance extensions it depends
+/// on have to be enabled as well.
+pub fn is_supported(instance: &Instance<V1_0>, pdevice: vk::PhysicalDevice) -> bool {
+    instance
+        .enumerate_device_extension_properties(pdevice)
+        .map(|extensions| {
+            extensions.iter().any(|extension| {
+                let name = unsafe { CStr::from_ptr(extension.extension_name.as_ptr()) };
+                name.to_bytes() == EXTENSION.as_bytes()
+            })
+        }).unwrap_or(false)
+}
@@ -1,44 +1,703 @@
-use ash::version::{DeviceV1_0, V1_0};
+use ash::version::{DeviceV1_0, InstanceV1_0, V1_0};
 use ash::vk;
-use ash::Device;
+use ash::{Device, Instance};
+use std::fmt;
 use std::ptr;
 
+use super::barrier::{self, AccessType};
+use super::memory;
+
+#[derive(Debug)]
+pub enum ImageError {
+    /// The format lacks features `usage` needs with `tiling`.
+    UnsupportedFormat {
+        format: vk::Format,
+        tiling: vk::ImageTiling,
+        missing: vk::FormatFeatureFlags,
+    },
+    /// The description contradicts itself, like a multisampled cube.
+    Invalid(&'static str),
+    /// The device cannot use `usage` with `samples` samples.
+    UnsupportedSamples {
+        samples: vk::SampleCountFlags,
+        supported: vk::SampleCountFlags,
+    },
+    /// The image needs a device feature that is not there.
+    MissingFeature(&'static str),
+    NoMemoryType,
+    /// Creating the image or its memory failed.
+    Vulkan(vk::Result),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ImageError::UnsupportedFormat {
+                format,
+                tiling,
+                missing,
+            } => write!(
+                f,
+                "{:?} with {:?} tiling lacks the features {:?}",
+                format, tiling, missing
+            ),
+            ImageError::Invalid(reason) => write!(f, "invalid image: {}", reason),
+            ImageError::UnsupportedSamples { samples, supported } => write!(
+                f,
+                "{:?} are not among the supported sample counts {:?}",
+                samples, supported
+            ),
+            ImageError::MissingFeature(feature) => write!(f, "the device lacks {}", feature),
+            ImageError::NoMemoryType => write!(f, "no device local memory type for the image"),
+            ImageError::Vulkan(result) => write!(f, "{:?}", result),
+        }
+    }
+}
+
+/// The shape of an image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageKind {
+    /// One layer is a plain 2D image, more an array.
+    Type2d { width: u32, height: u32, layers: u32 },
+    Type3d { width: u32, height: u32, depth: u32 },
+    /// `cubes` cube maps of six square layers each, more than one is a cube
+    /// array.
+    Cube { size: u32, cubes: u32 },
+}
+
+impl ImageKind {
+    pub fn extent(&self) -> vk::Extent3D {
+        match *self {
+            ImageKind::Type2d { width, height, .. } => vk::Extent3D {
+                width,
+                height,
+                depth: 1,
+            },
+            ImageKind::Type3d {
+                width,
+                height,
+                depth,
+            } => vk::Extent3D {
+                width,
+                height,
+                depth,
+            },
+            ImageKind::Cube { size, .. } => vk::Extent3D {
+                width: size,
+                height: size,
+                depth: 1,
+            },
+        }
+    }
+
+    pub fn array_layers(&self) -> u32 {
+        match *self {
+            ImageKind::Type2d { layers, .. } => layers,
+            ImageKind::Type3d { .. } => 1,
+            ImageKind::Cube { cubes, .. } => cubes * 6,
+        }
+    }
+
+    fn image_type(&self) -> vk::ImageType {
+        match *self {
+            ImageKind::Type3d { .. } => vk::ImageType::Type3d,
+            _ => vk::ImageType::Type2d,
+        }
+    }
+
+    /// The view type that sees the whole image.
+    pub fn view_type(&self) -> vk::ImageViewType {
+        match *self {
+            ImageKind::Type2d { layers: 1, .. } => vk::ImageViewType::Type2d,
+            ImageKind::Type2d { .. } => vk::ImageViewType::Type2dArray,
+            ImageKind::Type3d { .. } => vk::ImageViewType::Type3d,
+            ImageKind::Cube { cubes: 1, .. } => vk::ImageViewType::Cube,
+            ImageKind::Cube { .. } => vk::ImageViewType::CubeArray,
+        }
+    }
+}
+
+/// Everything `Image::create` needs, a sampled, optimally tiled image with
+/// one mip level unless changed.
+#[derive(Clone, Copy, Debug)]
+pub struct ImageDesc {
+    pub kind: ImageKind,
+    pub format: vk::Format,
+    pub usage: vk::ImageUsageFlags,
+    pub tiling: vk::ImageTiling,
+    pub mip_levels: u32,
+    pub samples: vk::SampleCountFlags,
+}
+
+impl ImageDesc {
+    pub fn new(kind: ImageKind, format: vk::Format) -> Self {
+        ImageDesc {
+            kind,
+            format,
+            usage: vk::IMAGE_USAGE_SAMPLED_BIT,
+            tiling: vk::ImageTiling::Optimal,
+            mip_levels: 1,
+            samples: vk::SAMPLE_COUNT_1_BIT,
+        }
+    }
+
+    pub fn new_2d(format: vk::Format, width: u32, height: u32) -> Self {
+        ImageDesc::new(
+            ImageKind::Type2d {
+                width,
+                height,
+                layers: 1,
+            },
+            format,
+        )
+    }
+
+    pub fn new_cube(format: vk::Format, size: u32) -> Self {
+        ImageDesc::new(ImageKind::Cube { size, cubes: 1 }, format)
+    }
+
+    pub fn with_usage(mut self, usage: vk::ImageUsageFlags) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    pub fn with_tiling(mut self, tiling: vk::ImageTiling) -> Self {
+        self.tiling = tiling;
+        self
+    }
+
+    pub fn with_mip_levels(mut self, mip_levels: u32) -> Self {
+        self.mip_levels = mip_levels;
+        self
+    }
+
+    /// Mip levels all the way down to 1x1.
+    pub fn with_full_mip_chain(self) -> Self {
+        let mip_levels = full_mip_chain(self.kind.extent());
+        self.with_mip_levels(mip_levels)
+    }
+
+    pub fn with_samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// Catch what Vulkan forbids for any format.
+    fn validate(&self) -> Result<(), ImageError> {
+        let extent = self.kind.extent();
+        if extent.width == 0 || extent.height == 0 || extent.depth == 0 {
+            return Err(ImageError::Invalid("the extent is empty"));
+        }
+        if self.kind.array_layers() == 0 {
+            return Err(ImageError::Invalid("there are no layers"));
+        }
+        if self.mip_levels == 0 || self.mip_levels > full_mip_chain(extent) {
+            return Err(ImageError::Invalid("the mip count does not fit the extent"));
+        }
+        if self.samples != vk::SAMPLE_COUNT_1_BIT {
+            match self.kind {
+                ImageKind::Type2d { .. } => {}
+                _ => return Err(ImageError::Invalid("only 2D images can be multisampled")),
+            }
+            if self.mip_levels != 1 || self.tiling != vk::ImageTiling::Optimal {
+                return Err(ImageError::Invalid(
+                    "multisampled images need one mip level and optimal tiling",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The mip count of a full chain for `extent`.
+pub fn full_mip_chain(extent: vk::Extent3D) -> u32 {
+    let largest = extent.width.max(extent.height).max(extent.depth);
+    32 - largest.leading_zeros()
+}
+
+/// `value` as the bits of a half precision float, for filling 16 bit float
+/// images. Rounds toward zero, flushes values too small for a normal half to
+/// zero and clamps values too large to the largest half.
+pub fn f16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = ((bits >> 13) & 0x3ff) as u16;
+
+    if value.is_nan() {
+        sign | 0x7e00
+    } else if exponent <= 0 {
+        sign
+    } else if exponent >= 0x1f {
+        sign | 0x7bff
+    } else {
+        sign | ((exponent as u16) << 10) | mantissa
+    }
+}
+
+/// The aspects `format` has.
+pub fn aspect_mask(format: vk::Format) -> vk::ImageAspectFlags {
+    match format {
+        vk::Format::D16Unorm | vk::Format::X8D24UnormPack32 | vk::Format::D32Sfloat => {
+            vk::IMAGE_ASPECT_DEPTH_BIT
+        }
+        vk::Format::S8Uint => vk::IMAGE_ASPECT_STENCIL_BIT,
+        vk::Format::D16UnormS8Uint
+        | vk::Format::D24UnormS8Uint
+        | vk::Format::D32SfloatS8Uint => vk::IMAGE_ASPECT_DEPTH_BIT | vk::IMAGE_ASPECT_STENCIL_BIT,
+        _ => vk::IMAGE_ASPECT_COLOR_BIT,
+    }
+}
+
+/// Format features images with `usage` need.
+pub fn required_format_features(usage: vk::ImageUsageFlags) -> vk::FormatFeatureFlags {
+    let mut features = vk::FormatFeatureFlags::empty();
+    if usage.intersects(vk::IMAGE_USAGE_SAMPLED_BIT) {
+        features |= vk::FORMAT_FEATURE_SAMPLED_IMAGE_BIT;
+    }
+    if usage.intersects(vk::IMAGE_USAGE_STORAGE_BIT) {
+        features |= vk::FORMAT_FEATURE_STORAGE_IMAGE_BIT;
+    }
+    if usage.intersects(vk::IMAGE_USAGE_COLOR_ATTACHMENT_BIT) {
+        features |= vk::FORMAT_FEATURE_COLOR_ATTACHMENT_BIT;
+    }
+    if usage.intersects(vk::IMAGE_USAGE_DEPTH_STENCIL_ATTACHMENT_BIT) {
+        features |= vk::FORMAT_FEATURE_DEPTH_STENCIL_ATTACHMENT_BIT;
+    }
+    features
+}
+
+/// Check that `format` can be used for `usage` with `tiling` on `pdevice`.
+pub fn check_format_support(
+    instance: &Instance<V1_0>,
+    pdevice: vk::PhysicalDevice,
+    format: vk::Format,
+    tiling: vk::ImageTiling,
+    usage: vk::ImageUsageFlags,
+) -> Result<(), ImageError> {
+    let properties = instance.get_physical_device_format_properties(pdevice, format);
+    let supported = match tiling {
+        vk::ImageTiling::Linear => properties.linear_tiling_features,
+        _ => properties.optimal_tiling_features,
+    };
+
+    let required = required_format_features(usage);
+    if supported.subset(required) {
+        Ok(())
+    } else {
+        Err(ImageError::UnsupportedFormat {
+            format,
+            tiling,
+            missing: required & !supported,
+        })
+    }
+}
+
+/// Sample counts an image with `format` supports for all of `usage`.
+pub fn supported_sample_counts(
+    limits: &vk::PhysicalDeviceLimits,
+    format: vk::Format,
+    usage: vk::ImageUsageFlags,
+) -> vk::SampleCountFlags {
+    let aspects = aspect_mask(format);
+    let mut supported = vk::SAMPLE_COUNT_1_BIT
+        | vk::SAMPLE_COUNT_2_BIT
+        | vk::SAMPLE_COUNT_4_BIT
+        | vk::SAMPLE_COUNT_8_BIT
+        | vk::SAMPLE_COUNT_16_BIT
+        | vk::SAMPLE_COUNT_32_BIT
+        | vk::SAMPLE_COUNT_64_BIT;
+    if usage.intersects(vk::IMAGE_USAGE_COLOR_ATTACHMENT_BIT) {
+        supported &= limits.framebuffer_color_sample_counts;
+    }
+    if usage.intersects(vk::IMAGE_USAGE_DEPTH_STENCIL_ATTACHMENT_BIT) {
+        if aspects.intersects(vk::IMAGE_ASPECT_DEPTH_BIT) {
+            supported &= limits.framebuffer_depth_sample_counts;
+        }
+        if aspects.intersects(vk::IMAGE_ASPECT_STENCIL_BIT) {
+            supported &= limits.framebuffer_stencil_sample_counts;
+        }
+    }
+    if usage.intersects(vk::IMAGE_USAGE_SAMPLED_BIT) {
+        if aspects.intersects(vk::IMAGE_ASPECT_COLOR_BIT) {
+            supported &= limits.sampled_image_color_sample_counts;
+        }
+        if aspects.intersects(vk::IMAGE_ASPECT_DEPTH_BIT) {
+            supported &= limits.sampled_image_depth_sample_counts;
+        }
+        if aspects.intersects(vk::IMAGE_ASPECT_STENCIL_BIT) {
+            supported &= limits.sampled_image_stencil_sample_counts;
+        }
+    }
+    if usage.intersects(vk::IMAGE_USAGE_STORAGE_BIT) {
+        supported &= limits.storage_image_sample_counts;
+    }
+    supported
+}
+
+/// How to view part of an image, the whole image as the image's format with
+/// identity swizzle unless changed.
+#[derive(Clone, Debug)]
+pub struct ViewDesc {
+    pub view_type: vk::ImageViewType,
+    /// Falls back to the image's format, views can reinterpret the image
+    /// only when it was created to allow that.
+    pub format: Option<vk::Format>,
+    pub components: vk::ComponentMapping,
+    pub subresource_range: vk::ImageSubresourceRange,
+}
+
+impl ViewDesc {
+    pub fn new(
+        view_type: vk::ImageViewType,
+        subresource_range: vk::ImageSubresourceRange,
+    ) -> Self {
+        ViewDesc {
+            view_type,
+            format: None,
+            components: vk::ComponentMapping {
+                r: vk::ComponentSwizzle::Identity,
+                g: vk::ComponentSwizzle::Identity,
+                b: vk::ComponentSwizzle::Identity,
+                a: vk::ComponentSwizzle::Identity,
+            },
+            subresource_range,
+        }
+    }
+
+    /// View the image as `view_type` instead, like `Type2dArray` for an
+    /// image with a single layer.
+    pub fn with_view_type(mut self, view_type: vk::ImageViewType) -> Self {
+        self.view_type = view_type;
+        self
+    }
+
+    pub fn with_format(mut self, format: vk::Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    pub fn with_components(mut self, components: vk::ComponentMapping) -> Self {
+        self.components = components;
+        self
+    }
+
+    /// Only `aspect_mask` of the aspects, like the depth of a depth stencil
+    /// image for sampling.
+    pub fn with_aspect_mask(mut self, aspect_mask: vk::ImageAspectFlags) -> Self {
+        self.subresource_range.aspect_mask = aspect_mask;
+        self
+    }
+
+    /// Only `count` mip levels from `base`.
+    pub fn with_mip_levels(mut self, base: u32, count: u32) -> Self {
+        self.subresource_range.base_mip_level = base;
+        self.subresource_range.level_count = count;
+        self
+    }
+
+    /// Only `count` layers from `base`.
+    pub fn with_layers(mut self, base: u32, count: u32) -> Self {
+        self.subresource_range.base_array_layer = base;
+        self.subresource_range.layer_count = count;
+        self
+    }
+}
+
+/// An image that remembers how it was last accessed, so barriers can move
+/// it from the layout it is in.
+///
+/// Images from `Image::create` own their memory and are destroyed with
+/// `destroy`, wrapped images are left to whoever created them.
+pub struct Image {
+    pub image: vk::Image,
+    pub memory: Option<vk::DeviceMemory>,
+    pub format: vk::Format,
+    pub extent: vk::Extent3D,
+    /// The view type that sees the whole image.
+    pub view_type: vk::ImageViewType,
+    /// Every mip level and layer, barriers through `Barrier::with_image`
+    /// cover all of them.
+    pub subresource_range: vk::ImageSubresourceRange,
+    access: Vec<AccessType>,
+}
+
+impl Image {
+    /// Wrap a 2D image that has not been used yet, its contents are
+    /// undefined.
+    pub fn new(
+        image: vk::Image,
+        format: vk::Format,
+        extent: vk::Extent3D,
+        subresource_range: vk::ImageSubresourceRange,
+    ) -> Self {
+        Image {
+            image,
+            memory: None,
+            format,
+            extent,
+            view_type: vk::ImageViewType::Type2d,
+            subresource_range,
+            access: vec![AccessType::Nothing],
+        }
+    }
+
+    /// Create a device local image after checking `pdevice` supports its
+    /// format, sample count and kind for its usage.
+    pub fn create(
+        instance: &Instance<V1_0>,
+        pdevice: vk::PhysicalDevice,
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        desc: &ImageDesc,
+    ) -> Result<Image, ImageError> {
+        desc.validate()?;
+        check_format_support(instance, pdevice, desc.format, desc.tiling, desc.usage)?;
+
+        let properties = instance.get_physical_device_properties(pdevice);
+        let supported = supported_sample_counts(&properties.limits, desc.format, desc.usage);
+        if !supported.subset(desc.samples) {
+            return Err(ImageError::UnsupportedSamples {
+                samples: desc.samples,
+                supported,
+            });
+        }
+        if let ImageKind::Cube { cubes, .. } = desc.kind {
+            if cubes > 1 && instance.get_physical_device_features(pdevice).image_cube_array == 0 {
+                return Err(ImageError::MissingFeature("imageCubeArray"));
+            }
+        }
+
+        let flags = match desc.kind {
+            ImageKind::Cube { .. } => vk::IMAGE_CREATE_CUBE_COMPATIBLE_BIT,
+            _ => vk::ImageCreateFlags::empty(),
+        };
+
+        let image_info = vk::ImageCreateInfo {
+            s_type: vk::StructureType::ImageCreateInfo,
+            p_next: ptr::null(),
+            flags,
+            image_type: desc.kind.image_type(),
+            format: desc.format,
+            extent: desc.kind.extent(),
+            mip_levels: desc.mip_levels,
+            array_layers: desc.kind.array_layers(),
+            samples: desc.samples,
+            tiling: desc.tiling,
+            usage: desc.usage,
+            sharing_mode: vk::SharingMode::Exclusive,
+            queue_family_index_count: 0,
+            p_queue_family_indices: ptr::null(),
+            initial_layout: vk::ImageLayout::Undefined,
+        };
+
+        let image = unsafe {
+            device
+                .create_image(&image_info, None)
+                .map_err(ImageError::Vulkan)?
+        };
+
+        let memory_requirements = device.get_image_memory_requirements(image);
+        let memory_type_index = match memory::find_memory_type_index(
+            &memory_requirements,
+            memory_properties,
+            vk::MEMORY_PROPERTY_DEVICE_LOCAL_BIT,
+        ) {
+            Some(memory_type_index) => memory_type_index,
+            None => {
+                unsafe {
+                    device.destroy_image(image, None);
+                }
+                return Err(ImageError::NoMemoryType);
+            }
+        };
+
+        let allocate_info = vk::MemoryAllocateInfo {
+            s_type: vk::StructureType::MemoryAllocateInfo,
+            p_next: ptr::null(),
+            allocation_size: memory_requirements.size,
+            memory_type_index,
+        };
+
+        let memory = unsafe {
+            let memory = match device.allocate_memory(&allocate_info, None) {
+                Ok(memory) => memory,
+                Err(err) => {
+                    device.destroy_image(image, None);
+                    return Err(ImageError::Vulkan(err));
+                }
+            };
+            if let Err(err) = device.bind_image_memory(image, memory, 0) {
+                device.free_memory(memory, None);
+                device.destroy_image(image, None);
+                return Err(ImageError::Vulkan(err));
+            }
+            memory
+        };
+
+        Ok(Image {
+            image,
+            memory: Some(memory),
+            format: desc.format,
+            extent: image_info.extent,
+            view_type: desc.kind.view_type(),
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: aspect_mask(desc.format),
+                base_mip_level: 0,
+                level_count: desc.mip_levels,
+                base_array_layer: 0,
+                layer_count: image_info.array_layers,
+            },
+            access: vec![AccessType::Nothing],
+        })
+    }
+
+    /// A view of the whole image, to be narrowed down with the `with_*`
+    /// methods.
+    pub fn view_desc(&self) -> ViewDesc {
+        ViewDesc::new(self.view_type, self.subresource_range.clone())
+    }
+
+    pub fn create_view(&self, device: &Device<V1_0>, desc: &ViewDesc) -> vk::ImageView {
+        create_image_view(device, self.image, self.format, desc)
+    }
+
+    /// How the image was last accessed.
+    pub fn access(&self) -> &[AccessType] {
+        &self.access
+    }
+
+    /// The layout the image is in.
+    pub fn layout(&self) -> vk::ImageLayout {
+        barrier::image_layout(&self.access)
+    }
+
+    /// Record that the image was accessed as `access` without a barrier
+    /// from `Barrier::with_image`, like by a render pass moving it to its
+    /// final layout.
+    pub fn assume_access(&mut self, access: &[AccessType]) {
+        self.access = access.to_vec();
+    }
+
+    /// Destroy the image and free its memory if it owns it, views have to be
+    /// destroyed first.
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        if let Some(memory) = self.memory {
+            unsafe {
+                device.destroy_image(self.image, None);
+                device.free_memory(memory, None);
+            }
+        }
+    }
+}
+
+pub fn create_image_view(
+    device: &Device<V1_0>,
+    image: vk::Image,
+    image_format: vk::Format,
+    desc: &ViewDesc,
+) -> vk::ImageView {
+    try_create_image_view(device, image, image_format, desc).expect("Failed to create image view!")
+}
+
+fn try_create_image_view(
+    device: &Device<V1_0>,
+    image: vk::Image,
+    image_format: vk::Format,
+    desc: &ViewDesc,
+) -> Result<vk::ImageView, vk::Result> {
+    let create_view_info = vk::ImageViewCreateInfo {
+        s_type: vk::StructureType::ImageViewCreateInfo,
+        p_next: ptr::null(),
+        flags: Default::default(),
+        view_type: desc.view_type,
+        format: desc.format.unwrap_or(image_format),
+        components: desc.components,
+        subresource_range: desc.subresource_range.clone(),
+        image,
+    };
+
+    unsafe { device.create_image_view(&create_view_info, None) }
+}
+
+/// Views of swapchain images, none are left behind on failure.
 pub fn create_image_views(
     device: &Device<V1_0>,
     images: Vec<vk::Image>,
-    surface_format: &vk::SurfaceFormatKHR,
-) -> Result<Vec<vk::ImageView>, ()> {
-    let image_views = images
-        .iter()
-        .map(|&image| {
-            let create_view_info = vk::ImageViewCreateInfo {
-                s_type: vk::StructureType::ImageViewCreateInfo,
-                p_next: ptr::null(),
-                flags: Default::default(),
-                view_type: vk::ImageViewType::Type2d,
-                format: surface_format.format,
-                components: vk::ComponentMapping {
-                    r: vk::ComponentSwizzle::R,
-                    g: vk::ComponentSwizzle::G,
-                    b: vk::ComponentSwizzle::B,
-                    a: vk::ComponentSwizzle::A,
-                },
-                subresource_range: vk::ImageSubresourceRange {
-                    aspect_mask: vk::IMAGE_ASPECT_COLOR_BIT,
-                    base_mip_level: 0,
-                    level_count: 1,
-                    base_array_layer: 0,
-                    layer_count: 1,
-                },
-                image: image,
-            };
+    format: vk::Format,
+) -> Result<Vec<vk::ImageView>, vk::Result> {
+    let desc = ViewDesc::new(
+        vk::ImageViewType::Type2d,
+        vk::ImageSubresourceRange {
+            aspect_mask: vk::IMAGE_ASPECT_COLOR_BIT,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        },
+    );
 
-            unsafe {
-                device
-                    .create_image_view(&create_view_info, None)
-                    .expect("Failed to create image view!")
+    let mut image_views = Vec::with_capacity(images.len());
+    for image in images {
+        match try_create_image_view(device, image, format, &desc) {
+            Ok(view) => image_views.push(view),
+            Err(error) => {
+                for view in image_views {
+                    unsafe { device.destroy_image_view(view, None) };
+                }
+                return Err(error);
             }
-        }).collect::<Vec<_>>();
+        }
+    }
 
     Ok(image_views)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem;
+
+    fn limits() -> vk::PhysicalDeviceLimits {
+        let mut limits: vk::PhysicalDeviceLimits = unsafe { mem::zeroed() };
+        limits.framebuffer_color_sample_counts =
+            vk::SAMPLE_COUNT_1_BIT | vk::SAMPLE_COUNT_4_BIT | vk::SAMPLE_COUNT_8_BIT;
+        limits.framebuffer_depth_sample_counts = vk::SAMPLE_COUNT_1_BIT | vk::SAMPLE_COUNT_4_BIT;
+        limits.sampled_image_color_sample_counts = vk::SAMPLE_COUNT_1_BIT | vk::SAMPLE_COUNT_8_BIT;
+        limits.sampled_image_depth_sample_counts = vk::SAMPLE_COUNT_1_BIT;
+        limits
+    }
+
+    #[test]
+    fn color_attachment_sample_counts() {
+        assert_eq!(
+            supported_sample_counts(
+                &limits(),
+                vk::Format::R8g8b8a8Unorm,
+                vk::IMAGE_USAGE_COLOR_ATTACHMENT_BIT
+            ),
+            vk::SAMPLE_COUNT_1_BIT | vk::SAMPLE_COUNT_4_BIT | vk::SAMPLE_COUNT_8_BIT
+        );
+    }
+
+    #[test]
+    fn sampled_attachments_support_both() {
+        assert_eq!(
+            supported_sample_counts(
+                &limits(),
+                vk::Format::R8g8b8a8Unorm,
+                vk::IMAGE_USAGE_COLOR_ATTACHMENT_BIT | vk::IMAGE_USAGE_SAMPLED_BIT
+            ),
+            vk::SAMPLE_COUNT_1_BIT | vk::SAMPLE_COUNT_8_BIT
+        );
+        assert_eq!(
+            supported_sample_counts(
+                &limits(),
+                vk::Format::D32Sfloat,
+                vk::IMAGE_USAGE_DEPTH_STENCIL_ATTACHMENT_BIT | vk::IMAGE_USAGE_SAMPLED_BIT
+            ),
+            vk::SAMPLE_COUNT_1_BIT
+        );
+    }
+}
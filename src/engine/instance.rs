@@ -1,6 +1,6 @@
 use ash::version::{EntryV1_0, V1_0};
 use ash::vk;
-use ash::{Entry, Instance};
+use ash::{Entry, Instance, InstanceError};
 use std::ffi::CString;
 use std::ptr;
 
@@ -8,9 +8,9 @@ pub fn create_instance(
     entry: &Entry<V1_0>,
     app_name: CString,
     engine_name: CString,
-    layer_names_raw: &Vec<*const i8>,
-    extension_names_raw: &Vec<*const i8>,
-) -> Result<Instance<V1_0>, ()> {
+    layer_names_raw: &[*const i8],
+    extension_names_raw: &[*const i8],
+) -> Result<Instance<V1_0>, vk::Result> {
     let app_info = vk::ApplicationInfo {
         s_type: vk::StructureType::ApplicationInfo,
         p_next: ptr::null(),
@@ -32,11 +32,8 @@ pub fn create_instance(
         enabled_extension_count: extension_names_raw.len() as u32,
     };
 
-    let instance = unsafe {
-        entry
-            .create_instance(&create_info, None)
-            .expect("failed to create instance!")
-    };
-
-    Ok(instance)
+    unsafe { entry.create_instance(&create_info, None) }.map_err(|error| match error {
+        InstanceError::LoadError(_) => vk::Result::ErrorInitializationFailed,
+        InstanceError::VkError(error) => error,
+    })
 }
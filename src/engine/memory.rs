@@ -0,0 +1,17 @@
+use ash::vk;
+
+/// Find a memory type that is allowed by `memory_requirements` and has all of
+/// the requested property flags.
+pub fn find_memory_type_index(
+    memory_requirements: &vk::MemoryRequirements,
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    flags: vk::MemoryPropertyFlags,
+) -> Option<u32> {
+    memory_properties.memory_types[..memory_properties.memory_type_count as usize]
+        .iter()
+        .enumerate()
+        .find(|&(index, memory_type)| {
+            (memory_requirements.memory_type_bits & (1 << index)) != 0
+                && memory_type.property_flags.subset(flags)
+        }).map(|(index, _)| index as u32)
+}
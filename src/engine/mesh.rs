@@ -0,0 +1,237 @@
+use ash::version::{DeviceV1_0, V1_0};
+use ash::vk;
+use ash::Device;
+use std::collections::HashMap;
+use std::mem;
+
+use super::buffer::Buffer;
+use super::pipeline::VertexInput;
+
+/// The vertex layout shared by every imported mesh.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+impl Vertex {
+    /// Vertex input for a pipeline drawing `Vertex` data from binding 0, at
+    /// locations 0 (position), 1 (normal) and 2 (uv).
+    pub fn vertex_input() -> VertexInput {
+        VertexInput::new()
+            .with_binding(0, mem::size_of::<Vertex>() as u32, vk::VertexInputRate::Vertex)
+            .with_attribute(0, 0, vk::Format::R32g32b32Sfloat, 0)
+            .with_attribute(1, 0, vk::Format::R32g32b32Sfloat, 12)
+            .with_attribute(2, 0, vk::Format::R32g32Sfloat, 24)
+    }
+}
+
+/// Indexed triangle list data on the CPU side, ready to be uploaded.
+#[derive(Clone, Debug, Default)]
+pub struct PrimitiveData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    /// Index into the materials of the model the primitive belongs to.
+    pub material: Option<usize>,
+}
+
+impl PrimitiveData {
+    /// Minimum and maximum vertex position, `None` without vertices.
+    pub fn bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        self.vertices.iter().fold(None, |bounds, vertex| match bounds {
+            Some((min, max)) => Some((
+                component_min(min, vertex.position),
+                component_max(max, vertex.position),
+            )),
+            None => Some((vertex.position, vertex.position)),
+        })
+    }
+
+    /// Give every triangle its own vertices with the face normal, used when
+    /// the source data has no normals.
+    pub fn compute_flat_normals(&mut self) {
+        let mut vertices = Vec::with_capacity(self.indices.len());
+
+        for triangle in self.indices.chunks(3).filter(|triangle| triangle.len() == 3) {
+            let normal = face_normal(
+                self.vertices[triangle[0] as usize].position,
+                self.vertices[triangle[1] as usize].position,
+                self.vertices[triangle[2] as usize].position,
+            );
+
+            for &index in triangle {
+                vertices.push(Vertex {
+                    normal,
+                    ..self.vertices[index as usize]
+                });
+            }
+        }
+
+        self.indices = (0..vertices.len() as u32).collect();
+        self.vertices = vertices;
+    }
+
+    /// Average the normals of all faces around each position, weighted by
+    /// their area.
+    ///
+    /// Vertices that only differ in their uv still get the same normal, so
+    /// texture seams do not show up as shading seams.
+    pub fn compute_smooth_normals(&mut self) {
+        let key = |position: [f32; 3]| {
+            [
+                position[0].to_bits(),
+                position[1].to_bits(),
+                position[2].to_bits(),
+            ]
+        };
+
+        let mut accumulated: HashMap<[u32; 3], [f32; 3]> = HashMap::new();
+        for triangle in self.indices.chunks(3).filter(|triangle| triangle.len() == 3) {
+            let positions = [
+                self.vertices[triangle[0] as usize].position,
+                self.vertices[triangle[1] as usize].position,
+                self.vertices[triangle[2] as usize].position,
+            ];
+            let normal = triangle_normal(positions[0], positions[1], positions[2]);
+
+            for &position in &positions {
+                let sum = accumulated.entry(key(position)).or_insert([0.0; 3]);
+                sum[0] += normal[0];
+                sum[1] += normal[1];
+                sum[2] += normal[2];
+            }
+        }
+
+        for vertex in &mut self.vertices {
+            let sum = accumulated
+                .get(&key(vertex.position))
+                .cloned()
+                .unwrap_or([0.0; 3]);
+            vertex.normal = normalize(sum);
+        }
+    }
+}
+
+/// Unit normal of a counter clockwise triangle, zero for degenerate ones.
+pub fn face_normal(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> [f32; 3] {
+    normalize(triangle_normal(a, b, c))
+}
+
+/// Normal of a counter clockwise triangle with a length of twice its area.
+fn triangle_normal(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> [f32; 3] {
+    let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    [
+        u[1] * v[2] - u[2] * v[1],
+        u[2] * v[0] - u[0] * v[2],
+        u[0] * v[1] - u[1] * v[0],
+    ]
+}
+
+fn component_min(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])]
+}
+
+fn component_max(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])]
+}
+
+pub fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    if length > 0.0 {
+        [v[0] / length, v[1] / length, v[2] / length]
+    } else {
+        [0.0, 0.0, 0.0]
+    }
+}
+
+pub struct Primitive {
+    pub vertex_buffer: Buffer,
+    pub index_buffer: Buffer,
+    pub index_count: u32,
+    pub material: Option<usize>,
+    /// Minimum and maximum vertex position, `None` without vertices.
+    pub bounds: Option<([f32; 3], [f32; 3])>,
+}
+
+impl Primitive {
+    pub fn upload(
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        data: &PrimitiveData,
+    ) -> Result<Primitive, vk::Result> {
+        let vertex_buffer = Buffer::with_data(
+            device,
+            memory_properties,
+            vk::BUFFER_USAGE_VERTEX_BUFFER_BIT,
+            &data.vertices,
+        )?;
+        let index_buffer = match Buffer::with_data(
+            device,
+            memory_properties,
+            vk::BUFFER_USAGE_INDEX_BUFFER_BIT,
+            &data.indices,
+        ) {
+            Ok(index_buffer) => index_buffer,
+            Err(error) => {
+                vertex_buffer.destroy(device);
+                return Err(error);
+            }
+        };
+
+        Ok(Primitive {
+            vertex_buffer,
+            index_buffer,
+            index_count: data.indices.len() as u32,
+            material: data.material,
+            bounds: data.bounds(),
+        })
+    }
+
+    /// Bind the buffers and record an indexed draw.
+    pub fn draw(&self, device: &Device<V1_0>, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer.buffer], &[0]);
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                self.index_buffer.buffer,
+                0,
+                vk::IndexType::Uint32,
+            );
+            device.cmd_draw_indexed(command_buffer, self.index_count, 1, 0, 0, 0);
+        }
+    }
+
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        self.vertex_buffer.destroy(device);
+        self.index_buffer.destroy(device);
+    }
+}
+
+pub struct Mesh {
+    pub name: Option<String>,
+    pub primitives: Vec<Primitive>,
+}
+
+impl Mesh {
+    /// The bounds of all primitives together.
+    pub fn bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        self.primitives
+            .iter()
+            .filter_map(|primitive| primitive.bounds)
+            .fold(None, |bounds, (min, max)| match bounds {
+                Some((bounds_min, bounds_max)) => {
+                    Some((component_min(bounds_min, min), component_max(bounds_max, max)))
+                }
+                None => Some((min, max)),
+            })
+    }
+
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        self.primitives
+            .iter()
+            .for_each(|primitive| primitive.destroy(device));
+    }
+}
@@ -1,9 +1,28 @@
+pub mod barrier;
+pub mod buffer;
 pub mod command_pool;
+pub mod deferred;
+pub mod depth;
+pub mod descriptor;
 pub mod device;
+pub mod environment;
 pub mod fence;
+pub mod framebuffer;
+pub mod full_screen_exclusive;
 pub mod image;
 pub mod instance;
+pub mod material;
+pub mod memory;
+pub mod mesh;
+pub mod model;
+pub mod multisample;
+pub mod pbr;
 pub mod pipeline;
+pub mod post;
+pub mod render_target;
 pub mod semaphore;
+pub mod shadow;
 pub mod surface;
 pub mod swapchain;
+pub mod texture;
+pub mod upload;
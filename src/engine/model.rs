@@ -0,0 +1,153 @@
+use ash::version::{DeviceV1_0, V1_0};
+use ash::vk;
+use ash::Device;
+
+use super::command_pool;
+use super::material::Material;
+use super::mesh::Mesh;
+use super::pbr::{self, PbrMaterials};
+use super::pipeline::PipelineLayout;
+use super::texture::Texture;
+
+pub type Matrix = [[f32; 4]; 4];
+
+pub const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/// Per draw data pushed by `Model::draw`, matching the push constant block of
+/// `mesh.vert`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct MeshPushConstants {
+    pub transform: Matrix,
+}
+
+/// Per draw data pushed by `Model::draw_depth`, matching the push constant
+/// block of `shadow.vert`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct DepthPushConstants {
+    pub view_projection: Matrix,
+    pub transform: Matrix,
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub name: Option<String>,
+    /// Column major transform relative to the parent node.
+    pub local_transform: Matrix,
+    pub mesh: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+/// Meshes, materials and textures uploaded to the device, together with the
+/// node hierarchy that places them.
+pub struct Model {
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+    pub textures: Vec<Texture>,
+    pub nodes: Vec<Node>,
+    /// Nodes without a parent.
+    pub root_nodes: Vec<usize>,
+}
+
+/// A single mesh to draw, produced by culling the scene.
+#[derive(Clone, Copy, Debug)]
+pub struct DrawItem {
+    pub mesh: usize,
+    /// Column major transform from mesh to world space.
+    pub transform: Matrix,
+}
+
+impl Model {
+    /// Record draws for every item of the draw list, with the item transform
+    /// pushed as `MeshPushConstants` and the material of each primitive bound
+    /// from `materials`.
+    pub fn draw(
+        &self,
+        device: &Device<V1_0>,
+        command_buffer: vk::CommandBuffer,
+        layout: &PipelineLayout,
+        materials: &PbrMaterials,
+        draws: &[DrawItem],
+    ) {
+        let mut bound = None;
+
+        for draw in draws {
+            let mesh = &self.meshes[draw.mesh];
+
+            command_pool::push_constants(
+                device,
+                command_buffer,
+                layout,
+                vk::SHADER_STAGE_VERTEX_BIT,
+                0,
+                &MeshPushConstants {
+                    transform: draw.transform,
+                },
+            );
+
+            for primitive in &mesh.primitives {
+                let set = materials.set(primitive.material);
+                if bound != Some(set) {
+                    unsafe {
+                        device.cmd_bind_descriptor_sets(
+                            command_buffer,
+                            vk::PipelineBindPoint::Graphics,
+                            layout.layout,
+                            pbr::MATERIAL_SET,
+                            &[set],
+                            &[],
+                        );
+                    }
+                    bound = Some(set);
+                }
+
+                primitive.draw(device, command_buffer);
+            }
+        }
+    }
+
+    /// Record draws of only the geometry of every item of the draw list,
+    /// seen through `view_projection`, with both pushed as
+    /// `DepthPushConstants`.
+    ///
+    /// Materials are ignored, so alpha masked surfaces are solid.
+    pub fn draw_depth(
+        &self,
+        device: &Device<V1_0>,
+        command_buffer: vk::CommandBuffer,
+        layout: &PipelineLayout,
+        view_projection: Matrix,
+        draws: &[DrawItem],
+    ) {
+        for draw in draws {
+            command_pool::push_constants(
+                device,
+                command_buffer,
+                layout,
+                vk::SHADER_STAGE_VERTEX_BIT,
+                0,
+                &DepthPushConstants {
+                    view_projection,
+                    transform: draw.transform,
+                },
+            );
+
+            for primitive in &self.meshes[draw.mesh].primitives {
+                primitive.draw(device, command_buffer);
+            }
+        }
+    }
+
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        self.meshes.iter().for_each(|mesh| mesh.destroy(device));
+        self.textures
+            .iter()
+            .for_each(|texture| texture.destroy(device));
+    }
+}
@@ -0,0 +1,161 @@
+use ash::version::{DeviceV1_0, V1_0};
+use ash::vk;
+use ash::Device;
+use std::ptr;
+
+use super::memory;
+
+/// The flag for `samples` per pixel, `None` unless it is a power of two up
+/// to 64.
+pub fn sample_count_flag(samples: u32) -> Option<vk::SampleCountFlags> {
+    match samples {
+        1 => Some(vk::SAMPLE_COUNT_1_BIT),
+        2 => Some(vk::SAMPLE_COUNT_2_BIT),
+        4 => Some(vk::SAMPLE_COUNT_4_BIT),
+        8 => Some(vk::SAMPLE_COUNT_8_BIT),
+        16 => Some(vk::SAMPLE_COUNT_16_BIT),
+        32 => Some(vk::SAMPLE_COUNT_32_BIT),
+        64 => Some(vk::SAMPLE_COUNT_64_BIT),
+        _ => None,
+    }
+}
+
+/// The sample counts set in `flags`, lowest first.
+pub fn sample_counts(flags: vk::SampleCountFlags) -> Vec<u32> {
+    (0..7)
+        .map(|shift| 1 << shift)
+        .filter(|&samples| sample_count_flag(samples).is_some_and(|flag| flags.subset(flag)))
+        .collect()
+}
+
+/// Sample counts the device supports for both color and depth attachments.
+pub fn supported_sample_counts(properties: &vk::PhysicalDeviceProperties) -> vk::SampleCountFlags {
+    properties.limits.framebuffer_color_sample_counts
+        & properties.limits.framebuffer_depth_sample_counts
+}
+
+/// A multisampled color attachment that is resolved into the swapchain image
+/// at the end of the render pass, recreated along with the swapchain.
+///
+/// Its contents are never stored, so the memory may be lazily allocated.
+pub struct ColorBuffer {
+    pub image: vk::Image,
+    pub memory: vk::DeviceMemory,
+    pub view: vk::ImageView,
+    pub format: vk::Format,
+    pub samples: vk::SampleCountFlags,
+}
+
+impl ColorBuffer {
+    pub fn new(
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        format: vk::Format,
+        samples: vk::SampleCountFlags,
+        extent: vk::Extent2D,
+    ) -> Result<ColorBuffer, vk::Result> {
+        let image_info = vk::ImageCreateInfo {
+            s_type: vk::StructureType::ImageCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            image_type: vk::ImageType::Type2d,
+            format,
+            extent: vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            },
+            mip_levels: 1,
+            array_layers: 1,
+            samples,
+            tiling: vk::ImageTiling::Optimal,
+            usage: vk::IMAGE_USAGE_COLOR_ATTACHMENT_BIT | vk::IMAGE_USAGE_TRANSIENT_ATTACHMENT_BIT,
+            sharing_mode: vk::SharingMode::Exclusive,
+            queue_family_index_count: 0,
+            p_queue_family_indices: ptr::null(),
+            initial_layout: vk::ImageLayout::Undefined,
+        };
+
+        let image = unsafe {
+            device
+                .create_image(&image_info, None)
+                .expect("Unable to create multisampled image")
+        };
+
+        // Prefer lazily allocated memory, tiled GPUs then never have to back
+        // the samples with real memory.
+        let memory_requirements = device.get_image_memory_requirements(image);
+        let memory_type_index = memory::find_memory_type_index(
+            &memory_requirements,
+            memory_properties,
+            vk::MEMORY_PROPERTY_DEVICE_LOCAL_BIT | vk::MEMORY_PROPERTY_LAZILY_ALLOCATED_BIT,
+        ).or_else(|| {
+            memory::find_memory_type_index(
+                &memory_requirements,
+                memory_properties,
+                vk::MEMORY_PROPERTY_DEVICE_LOCAL_BIT,
+            )
+        }).ok_or(vk::Result::ErrorOutOfDeviceMemory)?;
+
+        let allocate_info = vk::MemoryAllocateInfo {
+            s_type: vk::StructureType::MemoryAllocateInfo,
+            p_next: ptr::null(),
+            allocation_size: memory_requirements.size,
+            memory_type_index,
+        };
+
+        let memory = unsafe {
+            let memory = device
+                .allocate_memory(&allocate_info, None)
+                .expect("Unable to allocate multisampled image memory");
+            device
+                .bind_image_memory(image, memory, 0)
+                .expect("Unable to bind multisampled image memory");
+            memory
+        };
+
+        let view_info = vk::ImageViewCreateInfo {
+            s_type: vk::StructureType::ImageViewCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            view_type: vk::ImageViewType::Type2d,
+            format,
+            components: vk::ComponentMapping {
+                r: vk::ComponentSwizzle::Identity,
+                g: vk::ComponentSwizzle::Identity,
+                b: vk::ComponentSwizzle::Identity,
+                a: vk::ComponentSwizzle::Identity,
+            },
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: vk::IMAGE_ASPECT_COLOR_BIT,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image,
+        };
+
+        let view = unsafe {
+            device
+                .create_image_view(&view_info, None)
+                .expect("Unable to create multisampled image view")
+        };
+
+        Ok(ColorBuffer {
+            image,
+            memory,
+            view,
+            format,
+            samples,
+        })
+    }
+
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
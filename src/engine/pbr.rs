@@ -0,0 +1,266 @@
+use ash::version::V1_0;
+use ash::vk;
+use ash::Device;
+use std::mem;
+
+use super::buffer::Buffer;
+use super::command_pool::CommandPool;
+use super::descriptor::{self, DescriptorPool, DescriptorSetLayout};
+use super::material::{AlphaMode, Material};
+use super::model::Model;
+use super::texture::{SamplerDesc, Texture};
+
+/// Set of the per frame bindings in `mesh.vert`/`mesh.frag`.
+pub const FRAME_SET: u32 = 0;
+/// Set of the material bindings, bound by `Model::draw`.
+pub const MATERIAL_SET: u32 = 1;
+
+/// The camera uniform buffer at binding 0, the `Lights` storage buffer at
+/// binding 1, the environment cube map at binding 2, the `Shadows` uniform
+/// buffer at binding 3 and the shadow map array at binding 4.
+pub fn frame_set_layout(device: &Device<V1_0>) -> Result<DescriptorSetLayout, vk::Result> {
+    DescriptorSetLayout::build()
+        .with_binding(
+            0,
+            vk::DescriptorType::UniformBuffer,
+            1,
+            vk::SHADER_STAGE_VERTEX_BIT | vk::SHADER_STAGE_FRAGMENT_BIT,
+        ).with_binding(
+            1,
+            vk::DescriptorType::StorageBuffer,
+            1,
+            vk::SHADER_STAGE_FRAGMENT_BIT,
+        ).with_binding(
+            2,
+            vk::DescriptorType::CombinedImageSampler,
+            1,
+            vk::SHADER_STAGE_FRAGMENT_BIT,
+        ).with_binding(
+            3,
+            vk::DescriptorType::UniformBuffer,
+            1,
+            vk::SHADER_STAGE_FRAGMENT_BIT,
+        ).with_binding(
+            4,
+            vk::DescriptorType::CombinedImageSampler,
+            1,
+            vk::SHADER_STAGE_FRAGMENT_BIT,
+        ).create(device)
+}
+
+/// The `MaterialUniform` at binding 0 followed by the base color,
+/// metallic-roughness, normal, occlusion and emissive textures.
+pub fn material_set_layout(device: &Device<V1_0>) -> Result<DescriptorSetLayout, vk::Result> {
+    (1..6)
+        .fold(
+            DescriptorSetLayout::build().with_binding(
+                0,
+                vk::DescriptorType::UniformBuffer,
+                1,
+                vk::SHADER_STAGE_FRAGMENT_BIT,
+            ),
+            |builder, binding| {
+                builder.with_binding(
+                    binding,
+                    vk::DescriptorType::CombinedImageSampler,
+                    1,
+                    vk::SHADER_STAGE_FRAGMENT_BIT,
+                )
+            },
+        ).create(device)
+}
+
+/// A material as laid out in the `Material` uniform block of `mesh.frag`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct MaterialUniform {
+    pub base_color_factor: [f32; 4],
+    /// `w` is unused.
+    pub emissive_factor: [f32; 4],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    /// Fragments with less alpha are discarded, 0 keeps every fragment.
+    pub alpha_cutoff: f32,
+    /// 1 to light back faces with a flipped normal.
+    pub double_sided: u32,
+}
+
+impl MaterialUniform {
+    /// Blended materials are drawn opaque until transparent draws are
+    /// sorted.
+    pub fn new(material: &Material) -> Self {
+        let e = material.emissive_factor;
+
+        MaterialUniform {
+            base_color_factor: material.base_color_factor,
+            emissive_factor: [e[0], e[1], e[2], 0.0],
+            metallic_factor: material.metallic_factor,
+            roughness_factor: material.roughness_factor,
+            alpha_cutoff: match material.alpha_mode {
+                AlphaMode::Mask(cutoff) => cutoff,
+                AlphaMode::Opaque | AlphaMode::Blend => 0.0,
+            },
+            double_sided: material.double_sided as u32,
+        }
+    }
+}
+
+/// A descriptor set for every material of a model, plus one for the glTF
+/// default material used by primitives without one.
+///
+/// Missing textures are replaced by 1x1 textures that leave the factors
+/// as they are.
+pub struct PbrMaterials {
+    pool: DescriptorPool,
+    buffers: Vec<Buffer>,
+    sets: Vec<vk::DescriptorSet>,
+    white: Texture,
+    flat_normal: Texture,
+}
+
+impl PbrMaterials {
+    pub fn new(
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        command_pool: &CommandPool,
+        queue: vk::Queue,
+        model: &Model,
+        layout: &DescriptorSetLayout,
+    ) -> Result<PbrMaterials, vk::Result> {
+        let fallback = |pixel: [u8; 4]| {
+            Texture::from_rgba8(
+                device,
+                memory_properties,
+                command_pool,
+                queue,
+                1,
+                1,
+                &pixel,
+                vk::Format::R8g8b8a8Unorm,
+                SamplerDesc::default(),
+            )
+        };
+        let white = fallback([255, 255, 255, 255])?;
+        let flat_normal = fallback([128, 128, 255, 255])?;
+
+        let default_material = Material::default();
+        let materials = model
+            .materials
+            .iter()
+            .chain(Some(&default_material))
+            .collect::<Vec<_>>();
+        let count = materials.len() as u32;
+
+        let pool = DescriptorPool::new(
+            device,
+            &[
+                (vk::DescriptorType::UniformBuffer, count),
+                (vk::DescriptorType::CombinedImageSampler, count * 5),
+            ],
+            count,
+        )?;
+        let sets = pool.allocate(device, layout, materials.len())?;
+
+        let mut buffers = Vec::with_capacity(materials.len());
+        for (&set, material) in sets.iter().zip(materials.iter()) {
+            let buffer = Buffer::new(
+                device,
+                memory_properties,
+                mem::size_of::<MaterialUniform>() as vk::DeviceSize,
+                vk::BUFFER_USAGE_UNIFORM_BUFFER_BIT,
+                vk::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vk::MEMORY_PROPERTY_HOST_COHERENT_BIT,
+            )?;
+            buffer.write(device, 0, &[MaterialUniform::new(material)]);
+            descriptor::write_uniform_buffer(device, set, 0, &buffer);
+            buffers.push(buffer);
+
+            let textures = [
+                (material.base_color_texture, &white),
+                (material.metallic_roughness_texture, &white),
+                (material.normal_texture, &flat_normal),
+                (material.occlusion_texture, &white),
+                (material.emissive_texture, &white),
+            ];
+            for (binding, &(texture, fallback)) in textures.iter().enumerate() {
+                let texture = texture
+                    .and_then(|texture| model.textures.get(texture))
+                    .unwrap_or(fallback);
+                descriptor::write_combined_image_sampler(
+                    device,
+                    set,
+                    binding as u32 + 1,
+                    texture.view,
+                    texture.sampler,
+                );
+            }
+        }
+
+        Ok(PbrMaterials {
+            pool,
+            buffers,
+            sets,
+            white,
+            flat_normal,
+        })
+    }
+
+    /// The set of a material index, or of the default material without one.
+    pub fn set(&self, material: Option<usize>) -> vk::DescriptorSet {
+        let default = self.sets.len() - 1;
+        self.sets[material.map_or(default, |material| material.min(default))]
+    }
+
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        self.pool.destroy(device);
+        self.buffers.iter().for_each(|buffer| buffer.destroy(device));
+        self.white.destroy(device);
+        self.flat_normal.destroy(device);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Byte offset of `field` inside `base`.
+    fn offset<T, F>(base: &T, field: &F) -> usize {
+        field as *const F as usize - base as *const T as usize
+    }
+
+    #[test]
+    fn material_uniform_matches_std140() {
+        let uniform = MaterialUniform::new(&Material::default());
+        assert_eq!(mem::size_of::<MaterialUniform>(), 48);
+        assert_eq!(offset(&uniform, &uniform.base_color_factor), 0);
+        assert_eq!(offset(&uniform, &uniform.emissive_factor), 16);
+        assert_eq!(offset(&uniform, &uniform.metallic_factor), 32);
+        assert_eq!(offset(&uniform, &uniform.roughness_factor), 36);
+        assert_eq!(offset(&uniform, &uniform.alpha_cutoff), 40);
+        assert_eq!(offset(&uniform, &uniform.double_sided), 44);
+    }
+
+    #[test]
+    fn material_uniform_packs_the_material() {
+        let material = Material {
+            base_color_factor: [0.1, 0.2, 0.3, 0.4],
+            metallic_factor: 0.5,
+            roughness_factor: 0.6,
+            emissive_factor: [1.0, 2.0, 3.0],
+            alpha_mode: AlphaMode::Mask(0.25),
+            double_sided: true,
+            ..Material::default()
+        };
+        let uniform = MaterialUniform::new(&material);
+        assert_eq!(uniform.base_color_factor, [0.1, 0.2, 0.3, 0.4]);
+        assert_eq!(uniform.emissive_factor, [1.0, 2.0, 3.0, 0.0]);
+        assert_eq!((uniform.metallic_factor, uniform.roughness_factor), (0.5, 0.6));
+        assert_eq!((uniform.alpha_cutoff, uniform.double_sided), (0.25, 1));
+
+        let blended = Material {
+            alpha_mode: AlphaMode::Blend,
+            ..Material::default()
+        };
+        let uniform = MaterialUniform::new(&blended);
+        assert_eq!((uniform.alpha_cutoff, uniform.double_sided), (0.0, 0));
+    }
+}
@@ -1,35 +1,79 @@
 use ash::version::{DeviceV1_0, V1_0};
 use ash::vk;
 use ash::Device;
-use shader::Shader;
+use shader::{Shader, Specialization};
+use std::collections::hash_map::DefaultHasher;
 use std::default::Default;
+use std::hash::{Hash, Hasher};
+use std::mem;
 use std::ptr;
 
+use super::descriptor::DescriptorSetLayout;
+
 pub struct Pipeline {
+    /// The base pipeline first, followed by one pipeline per variant in the
+    /// order they were added to the builder.
     pub graphics_pipelines: Vec<vk::Pipeline>,
+    /// Identifies the shader code and specialization constants each of the
+    /// `graphics_pipelines` was created with, in the same order.
+    pub cache_keys: Vec<u64>,
     pub render_pass: RenderPass,
     pub layout: PipelineLayout,
     pub shaders: Vec<Shader>,
+    pub dynamic_state: DynamicState,
 }
 
 impl Pipeline {
     pub fn build() -> PipelineBuilder {
         PipelineBuilder::new()
     }
+
+    /// Find the pipeline that was created with the given cache key.
+    pub fn find(&self, cache_key: u64) -> Option<vk::Pipeline> {
+        self.cache_keys
+            .iter()
+            .position(|&key| key == cache_key)
+            .map(|index| self.graphics_pipelines[index])
+    }
+
+    /// Whether viewport and scissor have to be set while recording, instead
+    /// of being baked into the pipeline.
+    pub fn has_dynamic_viewport(&self) -> bool {
+        self.dynamic_state.contains(vk::DynamicState::Viewport)
+            && self.dynamic_state.contains(vk::DynamicState::Scissor)
+    }
+
+    /// Destroy the pipelines, their layout and shader modules. The render
+    /// pass is left alone, it may be shared with other pipelines.
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        unsafe {
+            self.graphics_pipelines
+                .iter()
+                .for_each(|&pipeline| device.destroy_pipeline(pipeline, None));
+
+            device.destroy_pipeline_layout(self.layout.layout, None);
+
+            self.shaders
+                .iter()
+                .for_each(|shader| device.destroy_shader_module(shader.module, None));
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct PipelineBuilder {
     shaders: Vec<Shader>,
-    vertex_input_state: Option<vk::PipelineVertexInputStateCreateInfo>,
-    input_assembly_state: Option<vk::PipelineInputAssemblyStateCreateInfo>,
+    vertex_input_state: Option<VertexInput>,
     viewport_state: Option<Viewport>,
-    rasterizer: Option<vk::PipelineRasterizationStateCreateInfo>,
-    multisample: Option<vk::PipelineMultisampleStateCreateInfo>,
-    color_blend_state: Option<ColorBlend>,
-    dynamic_state: Option<vk::PipelineDynamicStateCreateInfo>,
+    tessellation_patch_control_points: Option<u32>,
+    states: PipelineVariant,
+    variants: Vec<PipelineVariant>,
+    dynamic_state: Option<DynamicState>,
     layout: Option<PipelineLayout>,
     render_pass: Option<RenderPass>,
+    subpass: u32,
+    allow_derivatives: bool,
+    base_pipeline: Option<vk::Pipeline>,
 }
 
 impl PipelineBuilder {
@@ -42,31 +86,30 @@ impl PipelineBuilder {
         self
     }
 
-    pub fn with_vertex_input_state(mut self) -> Self {
-        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo {
-            s_type: vk::StructureType::PipelineVertexInputStateCreateInfo,
-            p_next: ptr::null(),
-            flags: Default::default(),
-            vertex_binding_description_count: 0,
-            p_vertex_binding_descriptions: ptr::null(),
-            vertex_attribute_description_count: 0,
-            p_vertex_attribute_descriptions: ptr::null(),
-        };
+    /// Specialize the shader stage of the given type in the base pipeline.
+    pub fn with_specialization(
+        mut self,
+        shader_type: ShaderType,
+        specialization: Specialization,
+    ) -> Self {
+        self.states.set_specialization(shader_type, specialization);
+        self
+    }
 
-        self.vertex_input_state = Some(vertex_input_state);
+    pub fn with_vertex_input_state(mut self, vertex_input: VertexInput) -> Self {
+        self.vertex_input_state = Some(vertex_input);
         self
     }
 
-    pub fn with_input_assembly_state(mut self) -> Self {
-        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo {
-            s_type: vk::StructureType::PipelineInputAssemblyStateCreateInfo,
-            p_next: ptr::null(),
-            flags: Default::default(),
-            topology: vk::PrimitiveTopology::TriangleList,
-            primitive_restart_enable: 0,
-        };
+    pub fn with_input_assembly_state(mut self, input_assembly: InputAssembly) -> Self {
+        self.states.input_assembly_state = Some(input_assembly);
+        self
+    }
 
-        self.input_assembly_state = Some(input_assembly_state);
+    /// Required when tessellation shader stages are used, together with a
+    /// `PatchList` topology.
+    pub fn with_tessellation_state(mut self, patch_control_points: u32) -> Self {
+        self.tessellation_patch_control_points = Some(patch_control_points);
         self
     }
 
@@ -75,60 +118,30 @@ impl PipelineBuilder {
         self
     }
 
-    pub fn with_rasterizer(mut self) -> Self {
-        let rasterizer = vk::PipelineRasterizationStateCreateInfo {
-            s_type: vk::StructureType::PipelineRasterizationStateCreateInfo,
-            p_next: ptr::null(),
-            flags: Default::default(),
-            depth_clamp_enable: 0,
-            rasterizer_discard_enable: 0,
-            polygon_mode: vk::PolygonMode::Fill,
-            line_width: 1.0,
-            cull_mode: vk::CULL_MODE_BACK_BIT,
-            front_face: vk::FrontFace::Clockwise,
-            depth_bias_enable: 0,
-            depth_bias_constant_factor: 0.0,
-            depth_bias_clamp: 0.0,
-            depth_bias_slope_factor: 0.0,
-        };
-
-        self.rasterizer = Some(rasterizer);
+    pub fn with_rasterizer(mut self, rasterizer: Rasterizer) -> Self {
+        self.states.rasterizer = Some(rasterizer);
         self
     }
 
-    pub fn with_multisample(mut self) -> Self {
-        let multisample = vk::PipelineMultisampleStateCreateInfo {
-            s_type: vk::StructureType::PipelineMultisampleStateCreateInfo,
-            p_next: ptr::null(),
-            flags: Default::default(),
-            sample_shading_enable: 0,
-            rasterization_samples: vk::SAMPLE_COUNT_1_BIT,
-            min_sample_shading: 1.0,
-            p_sample_mask: ptr::null(),
-            alpha_to_coverage_enable: 0,
-            alpha_to_one_enable: 0,
-        };
-
-        self.multisample = Some(multisample);
+    pub fn with_multisample(mut self, multisample: Multisample) -> Self {
+        self.states.multisample = Some(multisample);
         self
     }
 
-    pub fn with_color_blend(mut self) -> Self {
-        self.color_blend_state = Some(ColorBlend::new());
+    pub fn with_color_blend(mut self, color_blend: ColorBlend) -> Self {
+        self.states.color_blend_state = Some(color_blend);
         self
     }
 
-    pub fn with_dynamic_state(mut self) -> Self {
-        let dynamic_state = [vk::DynamicState::Viewport, vk::DynamicState::Scissor];
-        let dynamic_state_info = vk::PipelineDynamicStateCreateInfo {
-            s_type: vk::StructureType::PipelineDynamicStateCreateInfo,
-            p_next: ptr::null(),
-            flags: Default::default(),
-            dynamic_state_count: dynamic_state.len() as u32,
-            p_dynamic_states: dynamic_state.as_ptr(),
-        };
+    /// Without one depth and stencil tests are disabled, the render pass
+    /// needs a depth attachment otherwise.
+    pub fn with_depth_stencil(mut self, depth_stencil: DepthStencil) -> Self {
+        self.states.depth_stencil = Some(depth_stencil);
+        self
+    }
 
-        self.dynamic_state = Some(dynamic_state_info);
+    pub fn with_dynamic_state(mut self, dynamic_state: DynamicState) -> Self {
+        self.dynamic_state = Some(dynamic_state);
         self
     }
 
@@ -142,62 +155,344 @@ impl PipelineBuilder {
         self
     }
 
-    pub fn create(self, device: &Device<V1_0>) -> Result<Pipeline, ()> {
+    /// The index of the subpass of the render pass the pipelines are used
+    /// in, the first one by default.
+    pub fn with_subpass(mut self, subpass: u32) -> Self {
+        self.subpass = subpass;
+        self
+    }
+
+    /// Create an extra pipeline in the same batch, overriding some of the
+    /// states of the base pipeline.
+    ///
+    /// Variants are created as derivatives of the base pipeline.
+    pub fn with_variant(mut self, variant: PipelineVariant) -> Self {
+        self.variants.push(variant);
+        self
+    }
+
+    /// Allow the created pipelines to be used as the base of pipelines built
+    /// later on.
+    pub fn allow_derivatives(mut self) -> Self {
+        self.allow_derivatives = true;
+        self
+    }
+
+    /// Create every pipeline of this builder as a derivative of an existing
+    /// pipeline, which must have been created with `allow_derivatives`.
+    pub fn with_base_pipeline(mut self, base_pipeline: vk::Pipeline) -> Self {
+        self.base_pipeline = Some(base_pipeline);
+        self
+    }
+
+    pub fn create(self, device: &Device<V1_0>) -> Result<Pipeline, vk::Result> {
         let vertex_input_state = self.vertex_input_state.expect("vertex_input_state");
-        let input_assembly_state = self.input_assembly_state.expect("input_assembly_state");
-        let viewport_state = self.viewport_state.expect("viewport");
-        let rasterizer = self.rasterizer.expect("reasterizer");
-        let multisample = self.multisample.expect("multisample");
-        let color_blend_state_create_info = self.color_blend_state.expect("color_blend_state");
+        let dynamic_state = self.dynamic_state.unwrap_or_default();
+        let viewport_state = match self.viewport_state {
+            Some(viewport_state) => viewport_state,
+            None if dynamic_state.contains(vk::DynamicState::Viewport)
+                && dynamic_state.contains(vk::DynamicState::Scissor) =>
+            {
+                Viewport::dynamic()
+            }
+            None => panic!("viewport"),
+        };
         let layout = self.layout.expect("layout");
         let render_pass = self.render_pass.expect("render_pass");
 
-        let shader_stages = self
-            .shaders
+        // The base states first, then every variant with the states it does
+        // not override taken from the base.
+        let mut states = vec![PipelineVariant {
+            input_assembly_state: Some(
+                self.states
+                    .input_assembly_state
+                    .expect("input_assembly_state"),
+            ),
+            rasterizer: Some(self.states.rasterizer.expect("reasterizer")),
+            multisample: Some(self.states.multisample.expect("multisample")),
+            color_blend_state: Some(
+                self.states
+                    .color_blend_state
+                    .clone()
+                    .expect("color_blend_state"),
+            ),
+            depth_stencil: self.states.depth_stencil,
+            specializations: self.states.specializations,
+        }];
+        for variant in self.variants {
+            let resolved = variant.resolve(&states[0]);
+            states.push(resolved);
+        }
+
+        let shaders = self.shaders;
+
+        let uses_tessellation = shaders.iter().any(|shader| {
+            matches!(
+                shader.shader_type,
+                ShaderType::TessellationControl | ShaderType::TessellationEvaluation
+            )
+        });
+
+        if uses_tessellation && self.tessellation_patch_control_points.is_none() {
+            panic!("tessellation_state");
+        }
+
+        // The create infos only hold pointers, so everything they point at
+        // has to stay alive until the pipelines are created.
+        let specialization_infos = states
+            .iter()
+            .map(|state| {
+                shaders
+                    .iter()
+                    .map(|shader| {
+                        state
+                            .specialization(shader.shader_type)
+                            .map(|specialization| specialization.info())
+                    }).collect::<Vec<_>>()
+            }).collect::<Vec<_>>();
+        let shader_stages = specialization_infos
+            .iter()
+            .map(|infos| {
+                shaders
+                    .iter()
+                    .zip(infos.iter())
+                    .map(|(shader, info)| shader.create_stage(info.as_ref()))
+                    .collect::<Vec<_>>()
+            }).collect::<Vec<_>>();
+        let cache_keys = states
             .iter()
-            .map(|ref shader| shader.create_stage())
+            .map(|state| state.cache_key(&shaders))
             .collect::<Vec<_>>();
 
-        let pipeline_info = vk::GraphicsPipelineCreateInfo {
-            s_type: vk::StructureType::GraphicsPipelineCreateInfo,
-            p_next: ptr::null(),
-            flags: Default::default(),
-            stage_count: 2,
-            p_stages: shader_stages.as_ptr(),
-            p_vertex_input_state: &vertex_input_state,
-            p_input_assembly_state: &input_assembly_state,
-            p_viewport_state: &viewport_state.create(),
-            p_rasterization_state: &rasterizer,
-            p_multisample_state: &multisample,
-            p_depth_stencil_state: ptr::null(),
-            p_color_blend_state: &color_blend_state_create_info.create(),
-            p_dynamic_state: ptr::null(),
-            layout: layout.layout,
-            render_pass: render_pass.render_pass,
-            subpass: 0,
-            base_pipeline_handle: vk::Pipeline::null(),
-            base_pipeline_index: 0,
-            p_tessellation_state: ptr::null(),
+        let input_assembly_states = states
+            .iter()
+            .map(|state| state.input_assembly_state.unwrap().create())
+            .collect::<Vec<_>>();
+        let rasterizers = states
+            .iter()
+            .map(|state| state.rasterizer.unwrap().create())
+            .collect::<Vec<_>>();
+        let multisamples = states
+            .iter()
+            .map(|state| state.multisample.unwrap().create())
+            .collect::<Vec<_>>();
+        let color_blend_states = states
+            .iter()
+            .map(|state| state.color_blend_state.as_ref().unwrap().create())
+            .collect::<Vec<_>>();
+        let depth_stencil_states = states
+            .iter()
+            .map(|state| state.depth_stencil.map(|depth_stencil| depth_stencil.create()))
+            .collect::<Vec<_>>();
+        let vertex_input_state = vertex_input_state.create();
+        let viewport_state = viewport_state.create();
+        let dynamic_state_create_info = dynamic_state.create();
+
+        let p_dynamic_state = if dynamic_state.is_empty() {
+            ptr::null()
+        } else {
+            &dynamic_state_create_info as *const _
         };
 
-        let graphics_pipelines = unsafe {
-            device
-                .create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_info], None)
-                .expect("Unable to create graphics pipeline")
+        let tessellation_state =
+            self.tessellation_patch_control_points
+                .map(|patch_control_points| vk::PipelineTessellationStateCreateInfo {
+                    s_type: vk::StructureType::PipelineTessellationStateCreateInfo,
+                    p_next: ptr::null(),
+                    flags: Default::default(),
+                    patch_control_points,
+                });
+
+        let p_tessellation_state = match tessellation_state {
+            Some(ref tessellation_state) if uses_tessellation => tessellation_state as *const _,
+            _ => ptr::null(),
+        };
+
+        let allow_derivatives = self.allow_derivatives;
+        let base_pipeline = self.base_pipeline;
+        let subpass = self.subpass;
+
+        let pipeline_infos = (0..states.len())
+            .map(|index| {
+                let mut flags = vk::PipelineCreateFlags::empty();
+                if allow_derivatives || (index == 0 && states.len() > 1) {
+                    flags |= vk::PIPELINE_CREATE_ALLOW_DERIVATIVES_BIT;
+                }
+
+                // Only one of the handle and index may name the base.
+                let (base_pipeline_handle, base_pipeline_index) = match base_pipeline {
+                    Some(base_pipeline) => {
+                        flags |= vk::PIPELINE_CREATE_DERIVATIVE_BIT;
+                        (base_pipeline, -1)
+                    }
+                    None if index > 0 => {
+                        flags |= vk::PIPELINE_CREATE_DERIVATIVE_BIT;
+                        (vk::Pipeline::null(), 0)
+                    }
+                    None => (vk::Pipeline::null(), -1),
+                };
+
+                vk::GraphicsPipelineCreateInfo {
+                    s_type: vk::StructureType::GraphicsPipelineCreateInfo,
+                    p_next: ptr::null(),
+                    flags,
+                    stage_count: shader_stages[index].len() as u32,
+                    p_stages: shader_stages[index].as_ptr(),
+                    p_vertex_input_state: &vertex_input_state,
+                    p_input_assembly_state: &input_assembly_states[index],
+                    p_viewport_state: &viewport_state,
+                    p_rasterization_state: &rasterizers[index],
+                    p_multisample_state: &multisamples[index],
+                    p_depth_stencil_state: match depth_stencil_states[index] {
+                        Some(ref depth_stencil_state) => depth_stencil_state as *const _,
+                        None => ptr::null(),
+                    },
+                    p_color_blend_state: &color_blend_states[index],
+                    p_dynamic_state,
+                    layout: layout.layout,
+                    render_pass: render_pass.render_pass,
+                    subpass,
+                    base_pipeline_handle,
+                    base_pipeline_index,
+                    p_tessellation_state,
+                }
+            }).collect::<Vec<_>>();
+
+        let result = unsafe {
+            device.create_graphics_pipelines(vk::PipelineCache::null(), &pipeline_infos, None)
+        };
+        let (graphics_pipelines, error) = match result {
+            Ok(graphics_pipelines) => (graphics_pipelines, None),
+            Err((graphics_pipelines, error)) => (graphics_pipelines, Some(error)),
         };
 
-        Ok(Pipeline {
+        let pipeline = Pipeline {
             graphics_pipelines,
-            layout: layout,
-            render_pass: render_pass,
-            shaders: self.shaders,
-        })
+            cache_keys,
+            layout,
+            render_pass,
+            shaders,
+            dynamic_state,
+        };
+
+        match error {
+            None => Ok(pipeline),
+            // The layout and shaders belong to the pipeline, they go along
+            // with the pipelines that were created.
+            Some(error) => {
+                pipeline.destroy(device);
+                Err(error)
+            }
+        }
+    }
+}
+
+/// States that differ between the pipelines created by a single builder.
+///
+/// Anything left unset is taken from the builder's base pipeline.
+#[derive(Clone, Default)]
+pub struct PipelineVariant {
+    input_assembly_state: Option<InputAssembly>,
+    rasterizer: Option<Rasterizer>,
+    multisample: Option<Multisample>,
+    color_blend_state: Option<ColorBlend>,
+    depth_stencil: Option<DepthStencil>,
+    specializations: Vec<(ShaderType, Specialization)>,
+}
+
+impl PipelineVariant {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_input_assembly_state(mut self, input_assembly: InputAssembly) -> Self {
+        self.input_assembly_state = Some(input_assembly);
+        self
+    }
+
+    pub fn with_rasterizer(mut self, rasterizer: Rasterizer) -> Self {
+        self.rasterizer = Some(rasterizer);
+        self
+    }
+
+    pub fn with_multisample(mut self, multisample: Multisample) -> Self {
+        self.multisample = Some(multisample);
+        self
+    }
+
+    pub fn with_color_blend(mut self, color_blend: ColorBlend) -> Self {
+        self.color_blend_state = Some(color_blend);
+        self
+    }
+
+    pub fn with_depth_stencil(mut self, depth_stencil: DepthStencil) -> Self {
+        self.depth_stencil = Some(depth_stencil);
+        self
+    }
+
+    /// Specialize the shader stage of the given type in this variant only.
+    pub fn with_specialization(
+        mut self,
+        shader_type: ShaderType,
+        specialization: Specialization,
+    ) -> Self {
+        self.set_specialization(shader_type, specialization);
+        self
+    }
+
+    fn set_specialization(&mut self, shader_type: ShaderType, specialization: Specialization) {
+        self.specializations.retain(|&(ty, _)| ty != shader_type);
+        self.specializations.push((shader_type, specialization));
+    }
+
+    fn specialization(&self, shader_type: ShaderType) -> Option<&Specialization> {
+        self.specializations
+            .iter()
+            .find(|&&(ty, _)| ty == shader_type)
+            .map(|&(_, ref specialization)| specialization)
+    }
+
+    /// Identifies the shaders, their specialization and every fixed function
+    /// state, so variants differing in any of them get different keys.
+    fn cache_key(&self, shaders: &[Shader]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for shader in shaders {
+            shader.code_hash.hash(&mut hasher);
+            shader.shader_type.hash(&mut hasher);
+            self.specialization(shader.shader_type).hash(&mut hasher);
+        }
+
+        self.input_assembly_state.hash(&mut hasher);
+        self.rasterizer.hash(&mut hasher);
+        self.multisample.hash(&mut hasher);
+        self.color_blend_state.hash(&mut hasher);
+        self.depth_stencil.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn resolve(self, base: &PipelineVariant) -> PipelineVariant {
+        let mut specializations = base.specializations.clone();
+        for (shader_type, specialization) in self.specializations {
+            specializations.retain(|&(ty, _)| ty != shader_type);
+            specializations.push((shader_type, specialization));
+        }
+
+        PipelineVariant {
+            input_assembly_state: self.input_assembly_state.or(base.input_assembly_state),
+            rasterizer: self.rasterizer.or(base.rasterizer),
+            multisample: self.multisample.or(base.multisample),
+            color_blend_state: self
+                .color_blend_state
+                .or_else(|| base.color_blend_state.clone()),
+            depth_stencil: self.depth_stencil.or(base.depth_stencil),
+            specializations,
+        }
     }
 }
 
 pub struct Viewport {
-    viewport: vk::Viewport,
-    scissor: vk::Rect2D,
+    pub viewport: vk::Viewport,
+    pub scissor: vk::Rect2D,
 }
 
 impl Viewport {
@@ -213,13 +508,22 @@ impl Viewport {
 
         let scissor = vk::Rect2D {
             offset: vk::Offset2D { x: 0, y: 0 },
-            extent: surface_resolution.clone(),
+            extent: surface_resolution,
         };
 
         Viewport { viewport, scissor }
     }
 
-    pub fn create(self) -> vk::PipelineViewportStateCreateInfo {
+    /// Placeholder state for pipelines that set viewport and scissor while
+    /// recording, the values here are ignored by the driver.
+    pub fn dynamic() -> Self {
+        Viewport::new(vk::Extent2D {
+            width: 1,
+            height: 1,
+        })
+    }
+
+    pub fn create(&self) -> vk::PipelineViewportStateCreateInfo {
         vk::PipelineViewportStateCreateInfo {
             s_type: vk::StructureType::PipelineViewportStateCreateInfo,
             p_next: ptr::null(),
@@ -232,148 +536,1322 @@ impl Viewport {
     }
 }
 
-pub struct ColorBlend {
-    attachments: [vk::PipelineColorBlendAttachmentState; 1],
+/// Vertex buffer bindings and the attributes read from them.
+#[derive(Clone, Debug, Default)]
+pub struct VertexInput {
+    bindings: Vec<vk::VertexInputBindingDescription>,
+    attributes: Vec<vk::VertexInputAttributeDescription>,
 }
 
-impl ColorBlend {
+impl VertexInput {
     pub fn new() -> Self {
-        ColorBlend {
-            attachments: [vk::PipelineColorBlendAttachmentState {
-                blend_enable: 0,
-                src_color_blend_factor: vk::BlendFactor::SrcColor,
-                dst_color_blend_factor: vk::BlendFactor::OneMinusDstColor,
-                color_blend_op: vk::BlendOp::Add,
-                src_alpha_blend_factor: vk::BlendFactor::Zero,
-                dst_alpha_blend_factor: vk::BlendFactor::Zero,
-                alpha_blend_op: vk::BlendOp::Add,
-                color_write_mask: vk::ColorComponentFlags::all(),
-            }],
-        }
+        Default::default()
     }
 
-    pub fn create(self) -> vk::PipelineColorBlendStateCreateInfo {
-        vk::PipelineColorBlendStateCreateInfo {
-            s_type: vk::StructureType::PipelineColorBlendStateCreateInfo,
+    /// No vertex buffers, for shaders that generate their own vertices.
+    pub fn empty() -> Self {
+        VertexInput::new()
+    }
+
+    pub fn with_binding(mut self, binding: u32, stride: u32, input_rate: vk::VertexInputRate) -> Self {
+        self.bindings.push(vk::VertexInputBindingDescription {
+            binding,
+            stride,
+            input_rate,
+        });
+        self
+    }
+
+    pub fn with_attribute(
+        mut self,
+        location: u32,
+        binding: u32,
+        format: vk::Format,
+        offset: u32,
+    ) -> Self {
+        self.attributes.push(vk::VertexInputAttributeDescription {
+            location,
+            binding,
+            format,
+            offset,
+        });
+        self
+    }
+
+    pub fn create(&self) -> vk::PipelineVertexInputStateCreateInfo {
+        vk::PipelineVertexInputStateCreateInfo {
+            s_type: vk::StructureType::PipelineVertexInputStateCreateInfo,
             p_next: ptr::null(),
             flags: Default::default(),
-            logic_op_enable: 0,
-            logic_op: vk::LogicOp::Copy,
-            attachment_count: self.attachments.len() as u32,
-            p_attachments: self.attachments.as_ptr(),
-            blend_constants: [0.0, 0.0, 0.0, 0.0],
+            vertex_binding_description_count: self.bindings.len() as u32,
+            p_vertex_binding_descriptions: self.bindings.as_ptr(),
+            vertex_attribute_description_count: self.attributes.len() as u32,
+            p_vertex_attribute_descriptions: self.attributes.as_ptr(),
         }
     }
 }
 
-pub struct RenderPass {
-    pub render_pass: vk::RenderPass,
+/// Pipeline state that is supplied while recording commands instead of at
+/// pipeline creation.
+#[derive(Clone, Debug, Default)]
+pub struct DynamicState {
+    states: Vec<vk::DynamicState>,
 }
 
-impl RenderPass {
-    pub fn new(device: &Device<V1_0>, surface_format: vk::SurfaceFormatKHR) -> Self {
-        let color_attachment = vk::AttachmentDescription {
-            format: surface_format.format,
-            flags: vk::AttachmentDescriptionFlags::empty(),
-            samples: vk::SAMPLE_COUNT_1_BIT,
-            load_op: vk::AttachmentLoadOp::Clear,
-            store_op: vk::AttachmentStoreOp::Store,
-            stencil_load_op: vk::AttachmentLoadOp::DontCare,
-            stencil_store_op: vk::AttachmentStoreOp::DontCare,
-            initial_layout: vk::ImageLayout::Undefined,
-            final_layout: vk::ImageLayout::PresentSrcKhr,
-        };
+impl DynamicState {
+    pub fn new(states: &[vk::DynamicState]) -> Self {
+        let mut dynamic_state = DynamicState::default();
+        for &state in states {
+            if !dynamic_state.contains(state) {
+                dynamic_state.states.push(state);
+            }
+        }
 
-        let color_attachment_ref = vk::AttachmentReference {
-            attachment: 0,
-            layout: vk::ImageLayout::ColorAttachmentOptimal,
-        };
+        dynamic_state
+    }
 
-        let subpass_description = vk::SubpassDescription {
-            flags: Default::default(),
-            pipeline_bind_point: vk::PipelineBindPoint::Graphics,
-            color_attachment_count: 1,
-            p_color_attachments: &color_attachment_ref,
-            input_attachment_count: 0,
-            p_input_attachments: ptr::null(),
-            p_resolve_attachments: ptr::null(),
-            p_depth_stencil_attachment: ptr::null(),
-            preserve_attachment_count: 0,
-            p_preserve_attachments: ptr::null(),
-        };
+    /// Lets a pipeline be reused across swapchain resizes.
+    pub fn viewport_and_scissor() -> Self {
+        DynamicState::new(&[vk::DynamicState::Viewport, vk::DynamicState::Scissor])
+    }
 
-        let dependency = vk::SubpassDependency {
-            dependency_flags: Default::default(),
-            src_subpass: vk::VK_SUBPASS_EXTERNAL,
-            dst_subpass: 0,
-            src_stage_mask: vk::PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT,
-            src_access_mask: Default::default(),
-            dst_stage_mask: vk::PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT,
-            dst_access_mask: vk::ACCESS_COLOR_ATTACHMENT_READ_BIT
-                | vk::ACCESS_COLOR_ATTACHMENT_WRITE_BIT,
-        };
+    pub fn contains(&self, state: vk::DynamicState) -> bool {
+        self.states.contains(&state)
+    }
 
-        let render_pass = vk::RenderPassCreateInfo {
-            s_type: vk::StructureType::RenderPassCreateInfo,
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    pub fn create(&self) -> vk::PipelineDynamicStateCreateInfo {
+        vk::PipelineDynamicStateCreateInfo {
+            s_type: vk::StructureType::PipelineDynamicStateCreateInfo,
             p_next: ptr::null(),
             flags: Default::default(),
-            attachment_count: 1,
-            p_attachments: &color_attachment,
-            subpass_count: 1,
-            p_subpasses: &subpass_description,
-            dependency_count: 1,
-            p_dependencies: [dependency].as_ptr(),
-        };
-
-        let render_pass = unsafe {
-            device
-                .create_render_pass(&render_pass, None)
-                .expect("unable to create renderpass")
-        };
-
-        RenderPass { render_pass }
+            dynamic_state_count: self.states.len() as u32,
+            p_dynamic_states: self.states.as_ptr(),
+        }
     }
 }
 
-pub struct PipelineLayout {
-    pub layout: vk::PipelineLayout,
+/// How vertices are assembled into primitives.
+#[derive(Clone, Copy, Debug, Hash)]
+pub struct InputAssembly {
+    pub topology: vk::PrimitiveTopology,
+    pub primitive_restart: bool,
 }
 
-impl PipelineLayout {
-    pub fn empty(device: &Device<V1_0>) -> Self {
-        let layout_create_info = vk::PipelineLayoutCreateInfo {
-            s_type: vk::StructureType::PipelineLayoutCreateInfo,
-            p_next: ptr::null(),
-            flags: Default::default(),
-            set_layout_count: 0,
-            p_set_layouts: ptr::null(),
-            push_constant_range_count: 0,
-            p_push_constant_ranges: ptr::null(),
-        };
+impl InputAssembly {
+    pub fn new(topology: vk::PrimitiveTopology) -> Self {
+        InputAssembly {
+            topology,
+            primitive_restart: false,
+        }
+    }
 
-        let pipeline_layout = unsafe {
-            device
-                .create_pipeline_layout(&layout_create_info, None)
-                .unwrap()
-        };
+    pub fn triangle_list() -> Self {
+        InputAssembly::new(vk::PrimitiveTopology::TriangleList)
+    }
 
-        PipelineLayout {
-            layout: pipeline_layout,
+    pub fn line_list() -> Self {
+        InputAssembly::new(vk::PrimitiveTopology::LineList)
+    }
+
+    pub fn point_list() -> Self {
+        InputAssembly::new(vk::PrimitiveTopology::PointList)
+    }
+
+    /// Only meaningful for strip and fan topologies.
+    pub fn with_primitive_restart(mut self) -> Self {
+        self.primitive_restart = true;
+        self
+    }
+
+    pub fn create(&self) -> vk::PipelineInputAssemblyStateCreateInfo {
+        vk::PipelineInputAssemblyStateCreateInfo {
+            s_type: vk::StructureType::PipelineInputAssemblyStateCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            topology: self.topology,
+            primitive_restart_enable: self.primitive_restart as vk::Bool32,
         }
     }
 }
 
-#[derive(Debug)]
-pub enum ShaderType {
-    Vertex,
-    Fragment,
+#[derive(Clone, Copy, Debug)]
+pub struct DepthBias {
+    pub constant_factor: f32,
+    pub clamp: f32,
+    pub slope_factor: f32,
 }
 
-impl ShaderType {
-    pub fn to_vulkan(&self) -> vk::ShaderStageFlags {
-        match self {
-            ShaderType::Vertex => vk::SHADER_STAGE_VERTEX_BIT,
+#[derive(Clone, Copy, Debug)]
+pub struct Rasterizer {
+    pub polygon_mode: vk::PolygonMode,
+    pub cull_mode: vk::CullModeFlags,
+    pub front_face: vk::FrontFace,
+    pub line_width: f32,
+    pub depth_clamp: bool,
+    pub rasterizer_discard: bool,
+    pub depth_bias: Option<DepthBias>,
+}
+
+impl Hash for DepthBias {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.constant_factor.to_bits().hash(state);
+        self.clamp.to_bits().hash(state);
+        self.slope_factor.to_bits().hash(state);
+    }
+}
+
+impl Hash for Rasterizer {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.polygon_mode.hash(state);
+        self.cull_mode.hash(state);
+        self.front_face.hash(state);
+        self.line_width.to_bits().hash(state);
+        self.depth_clamp.hash(state);
+        self.rasterizer_discard.hash(state);
+        self.depth_bias.hash(state);
+    }
+}
+
+impl Rasterizer {
+    /// Filled polygons with back faces culled.
+    pub fn fill() -> Self {
+        Rasterizer {
+            polygon_mode: vk::PolygonMode::Fill,
+            cull_mode: vk::CULL_MODE_BACK_BIT,
+            front_face: vk::FrontFace::Clockwise,
+            line_width: 1.0,
+            depth_clamp: false,
+            rasterizer_discard: false,
+            depth_bias: None,
+        }
+    }
+
+    /// Filled polygons with both faces drawn.
+    pub fn no_cull() -> Self {
+        Rasterizer::fill().with_cull_mode(vk::CULL_MODE_NONE)
+    }
+
+    /// Polygon edges only, with both faces drawn.
+    ///
+    /// Requires the `fillModeNonSolid` device feature.
+    pub fn wireframe() -> Self {
+        Rasterizer {
+            polygon_mode: vk::PolygonMode::Line,
+            ..Rasterizer::no_cull()
+        }
+    }
+
+    pub fn with_cull_mode(mut self, cull_mode: vk::CullModeFlags) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn with_front_face(mut self, front_face: vk::FrontFace) -> Self {
+        self.front_face = front_face;
+        self
+    }
+
+    pub fn with_line_width(mut self, line_width: f32) -> Self {
+        self.line_width = line_width;
+        self
+    }
+
+    pub fn with_depth_clamp(mut self) -> Self {
+        self.depth_clamp = true;
+        self
+    }
+
+    pub fn with_depth_bias(mut self, depth_bias: DepthBias) -> Self {
+        self.depth_bias = Some(depth_bias);
+        self
+    }
+
+    pub fn create(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        let depth_bias = self.depth_bias.unwrap_or(DepthBias {
+            constant_factor: 0.0,
+            clamp: 0.0,
+            slope_factor: 0.0,
+        });
+
+        vk::PipelineRasterizationStateCreateInfo {
+            s_type: vk::StructureType::PipelineRasterizationStateCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            depth_clamp_enable: self.depth_clamp as vk::Bool32,
+            rasterizer_discard_enable: self.rasterizer_discard as vk::Bool32,
+            polygon_mode: self.polygon_mode,
+            line_width: self.line_width,
+            cull_mode: self.cull_mode,
+            front_face: self.front_face,
+            depth_bias_enable: self.depth_bias.is_some() as vk::Bool32,
+            depth_bias_constant_factor: depth_bias.constant_factor,
+            depth_bias_clamp: depth_bias.clamp,
+            depth_bias_slope_factor: depth_bias.slope_factor,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Multisample {
+    pub samples: vk::SampleCountFlags,
+    pub min_sample_shading: Option<f32>,
+    pub alpha_to_coverage: bool,
+    pub alpha_to_one: bool,
+}
+
+impl Hash for Multisample {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.samples.hash(state);
+        self.min_sample_shading.map(f32::to_bits).hash(state);
+        self.alpha_to_coverage.hash(state);
+        self.alpha_to_one.hash(state);
+    }
+}
+
+impl Multisample {
+    /// One sample per pixel.
+    pub fn disabled() -> Self {
+        Multisample::new(vk::SAMPLE_COUNT_1_BIT)
+    }
+
+    pub fn new(samples: vk::SampleCountFlags) -> Self {
+        Multisample {
+            samples,
+            min_sample_shading: None,
+            alpha_to_coverage: false,
+            alpha_to_one: false,
+        }
+    }
+
+    /// Requires the `sampleRateShading` device feature.
+    pub fn with_sample_shading(mut self, min_sample_shading: f32) -> Self {
+        self.min_sample_shading = Some(min_sample_shading);
+        self
+    }
+
+    pub fn with_alpha_to_coverage(mut self) -> Self {
+        self.alpha_to_coverage = true;
+        self
+    }
+
+    pub fn create(&self) -> vk::PipelineMultisampleStateCreateInfo {
+        vk::PipelineMultisampleStateCreateInfo {
+            s_type: vk::StructureType::PipelineMultisampleStateCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            sample_shading_enable: self.min_sample_shading.is_some() as vk::Bool32,
+            rasterization_samples: self.samples,
+            min_sample_shading: self.min_sample_shading.unwrap_or(1.0),
+            p_sample_mask: ptr::null(),
+            alpha_to_coverage_enable: self.alpha_to_coverage as vk::Bool32,
+            alpha_to_one_enable: self.alpha_to_one as vk::Bool32,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash)]
+pub struct DepthStencil {
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub compare_op: vk::CompareOp,
+}
+
+impl DepthStencil {
+    /// Closer fragments have smaller depth, the attachment is cleared to 1.
+    pub fn less() -> Self {
+        DepthStencil {
+            depth_test: true,
+            depth_write: true,
+            compare_op: vk::CompareOp::Less,
+        }
+    }
+
+    /// For reversed depth, closer fragments have larger depth and the
+    /// attachment is cleared to 0.
+    pub fn greater() -> Self {
+        DepthStencil {
+            compare_op: vk::CompareOp::Greater,
+            ..DepthStencil::less()
+        }
+    }
+
+    /// Ignore the depth attachment, for drawing into a render pass that has
+    /// one without using it.
+    pub fn disabled() -> Self {
+        DepthStencil {
+            depth_test: false,
+            depth_write: false,
+            compare_op: vk::CompareOp::Always,
+        }
+    }
+
+    /// Test against the depth attachment without writing to it.
+    pub fn read_only(mut self) -> Self {
+        self.depth_write = false;
+        self
+    }
+
+    pub fn create(&self) -> vk::PipelineDepthStencilStateCreateInfo {
+        let stencil_op = vk::StencilOpState {
+            fail_op: vk::StencilOp::Keep,
+            pass_op: vk::StencilOp::Keep,
+            depth_fail_op: vk::StencilOp::Keep,
+            compare_op: vk::CompareOp::Always,
+            compare_mask: 0,
+            write_mask: 0,
+            reference: 0,
+        };
+
+        vk::PipelineDepthStencilStateCreateInfo {
+            s_type: vk::StructureType::PipelineDepthStencilStateCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            depth_test_enable: self.depth_test as vk::Bool32,
+            depth_write_enable: self.depth_write as vk::Bool32,
+            depth_compare_op: self.compare_op,
+            depth_bounds_test_enable: 0,
+            stencil_test_enable: 0,
+            front: stencil_op,
+            back: stencil_op,
+            min_depth_bounds: 0.0,
+            max_depth_bounds: 1.0,
+        }
+    }
+}
+
+/// Blending for a single color attachment.
+#[derive(Clone, Copy, Debug)]
+pub struct BlendAttachment {
+    pub blend_enable: bool,
+    pub src_color_blend_factor: vk::BlendFactor,
+    pub dst_color_blend_factor: vk::BlendFactor,
+    pub color_blend_op: vk::BlendOp,
+    pub src_alpha_blend_factor: vk::BlendFactor,
+    pub dst_alpha_blend_factor: vk::BlendFactor,
+    pub alpha_blend_op: vk::BlendOp,
+    pub color_write_mask: vk::ColorComponentFlags,
+}
+
+impl BlendAttachment {
+    /// Source replaces destination.
+    pub fn opaque() -> Self {
+        BlendAttachment {
+            blend_enable: false,
+            src_color_blend_factor: vk::BlendFactor::One,
+            dst_color_blend_factor: vk::BlendFactor::Zero,
+            color_blend_op: vk::BlendOp::Add,
+            src_alpha_blend_factor: vk::BlendFactor::One,
+            dst_alpha_blend_factor: vk::BlendFactor::Zero,
+            alpha_blend_op: vk::BlendOp::Add,
+            color_write_mask: vk::ColorComponentFlags::all(),
+        }
+    }
+
+    /// Classic "over" blending with non-premultiplied alpha.
+    pub fn alpha_blend() -> Self {
+        BlendAttachment {
+            blend_enable: true,
+            src_color_blend_factor: vk::BlendFactor::SrcAlpha,
+            dst_color_blend_factor: vk::BlendFactor::OneMinusSrcAlpha,
+            src_alpha_blend_factor: vk::BlendFactor::One,
+            dst_alpha_blend_factor: vk::BlendFactor::OneMinusSrcAlpha,
+            ..BlendAttachment::opaque()
+        }
+    }
+
+    /// Source is added on top of destination, alpha is left untouched.
+    pub fn additive() -> Self {
+        BlendAttachment {
+            blend_enable: true,
+            src_color_blend_factor: vk::BlendFactor::One,
+            dst_color_blend_factor: vk::BlendFactor::One,
+            src_alpha_blend_factor: vk::BlendFactor::Zero,
+            dst_alpha_blend_factor: vk::BlendFactor::One,
+            ..BlendAttachment::opaque()
+        }
+    }
+
+    pub fn with_color_write_mask(mut self, color_write_mask: vk::ColorComponentFlags) -> Self {
+        self.color_write_mask = color_write_mask;
+        self
+    }
+
+    pub fn create(&self) -> vk::PipelineColorBlendAttachmentState {
+        vk::PipelineColorBlendAttachmentState {
+            blend_enable: self.blend_enable as vk::Bool32,
+            src_color_blend_factor: self.src_color_blend_factor,
+            dst_color_blend_factor: self.dst_color_blend_factor,
+            color_blend_op: self.color_blend_op,
+            src_alpha_blend_factor: self.src_alpha_blend_factor,
+            dst_alpha_blend_factor: self.dst_alpha_blend_factor,
+            alpha_blend_op: self.alpha_blend_op,
+            color_write_mask: self.color_write_mask,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ColorBlend {
+    attachments: Vec<vk::PipelineColorBlendAttachmentState>,
+    blend_constants: [f32; 4],
+}
+
+impl Hash for ColorBlend {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.attachments.hash(state);
+        for constant in &self.blend_constants {
+            constant.to_bits().hash(state);
+        }
+    }
+}
+
+impl ColorBlend {
+    /// One blend state per color attachment of the subpass, in order.
+    pub fn new(attachments: &[BlendAttachment]) -> Self {
+        ColorBlend {
+            attachments: attachments
+                .iter()
+                .map(|attachment| attachment.create())
+                .collect(),
+            blend_constants: [0.0, 0.0, 0.0, 0.0],
+        }
+    }
+
+    pub fn opaque() -> Self {
+        ColorBlend::new(&[BlendAttachment::opaque()])
+    }
+
+    pub fn alpha_blend() -> Self {
+        ColorBlend::new(&[BlendAttachment::alpha_blend()])
+    }
+
+    pub fn additive() -> Self {
+        ColorBlend::new(&[BlendAttachment::additive()])
+    }
+
+    /// Used by the `ConstantColor`/`ConstantAlpha` blend factors.
+    pub fn with_blend_constants(mut self, blend_constants: [f32; 4]) -> Self {
+        self.blend_constants = blend_constants;
+        self
+    }
+
+    pub fn create(&self) -> vk::PipelineColorBlendStateCreateInfo {
+        vk::PipelineColorBlendStateCreateInfo {
+            s_type: vk::StructureType::PipelineColorBlendStateCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            logic_op_enable: 0,
+            logic_op: vk::LogicOp::Copy,
+            attachment_count: self.attachments.len() as u32,
+            p_attachments: self.attachments.as_ptr(),
+            blend_constants: self.blend_constants,
+        }
+    }
+}
+
+/// Pipelines keep a copy of the render pass they were created for, the
+/// render pass itself is destroyed once by whoever created it.
+#[derive(Clone, Copy, Debug)]
+pub struct RenderPass {
+    pub render_pass: vk::RenderPass,
+    /// `Undefined` for render passes without a color attachment.
+    pub color_format: vk::Format,
+    /// Format of the depth attachment following the color attachment, if
+    /// there is one.
+    pub depth_format: Option<vk::Format>,
+    /// Samples of the color and depth attachments. With more than one the
+    /// color attachment is resolved into an attachment following them.
+    pub samples: vk::SampleCountFlags,
+}
+
+impl RenderPass {
+    /// A render pass with any number of attachments and subpasses, for what
+    /// the constructors below do not cover.
+    pub fn build() -> RenderPassBuilder {
+        RenderPassBuilder::new()
+    }
+
+    /// Render into a color attachment of `color_format` that is presented
+    /// afterwards.
+    pub fn new(
+        device: &Device<V1_0>,
+        color_format: vk::Format,
+        depth_format: Option<vk::Format>,
+    ) -> Self {
+        RenderPass::multisampled(device, color_format, depth_format, vk::SAMPLE_COUNT_1_BIT)
+    }
+
+    /// Render into multisampled color and depth attachments, then resolve
+    /// the color attachment into the presentable last attachment.
+    pub fn multisampled(
+        device: &Device<V1_0>,
+        color_format: vk::Format,
+        depth_format: Option<vk::Format>,
+        samples: vk::SampleCountFlags,
+    ) -> Self {
+        RenderPass::create(
+            device,
+            color_format,
+            depth_format,
+            samples,
+            vk::ImageLayout::PresentSrcKhr,
+        )
+    }
+
+    /// Like `multisampled`, but the color attachment, or the one it is
+    /// resolved into, is sampled by fragment shaders afterwards instead of
+    /// presented.
+    pub fn offscreen(
+        device: &Device<V1_0>,
+        color_format: vk::Format,
+        depth_format: Option<vk::Format>,
+        samples: vk::SampleCountFlags,
+    ) -> Self {
+        RenderPass::create(
+            device,
+            color_format,
+            depth_format,
+            samples,
+            vk::ImageLayout::ShaderReadOnlyOptimal,
+        )
+    }
+
+    /// Render into a single depth attachment that is sampled by fragment
+    /// shaders afterwards, like a shadow map.
+    pub fn depth_only(device: &Device<V1_0>, depth_format: vk::Format) -> Self {
+        let depth_attachment = vk::AttachmentDescription {
+            format: depth_format,
+            flags: vk::AttachmentDescriptionFlags::empty(),
+            samples: vk::SAMPLE_COUNT_1_BIT,
+            load_op: vk::AttachmentLoadOp::Clear,
+            store_op: vk::AttachmentStoreOp::Store,
+            stencil_load_op: vk::AttachmentLoadOp::DontCare,
+            stencil_store_op: vk::AttachmentStoreOp::DontCare,
+            initial_layout: vk::ImageLayout::Undefined,
+            final_layout: vk::ImageLayout::ShaderReadOnlyOptimal,
+        };
+
+        let depth_attachment_ref = vk::AttachmentReference {
+            attachment: 0,
+            layout: vk::ImageLayout::DepthStencilAttachmentOptimal,
+        };
+
+        let subpass_description = vk::SubpassDescription {
+            flags: Default::default(),
+            pipeline_bind_point: vk::PipelineBindPoint::Graphics,
+            color_attachment_count: 0,
+            p_color_attachments: ptr::null(),
+            input_attachment_count: 0,
+            p_input_attachments: ptr::null(),
+            p_resolve_attachments: ptr::null(),
+            p_depth_stencil_attachment: &depth_attachment_ref,
+            preserve_attachment_count: 0,
+            p_preserve_attachments: ptr::null(),
+        };
+
+        // Previous reads of the attachment have to finish before it is
+        // cleared, and the writes before it is read again.
+        let dependencies = [
+            vk::SubpassDependency {
+                dependency_flags: Default::default(),
+                src_subpass: vk::VK_SUBPASS_EXTERNAL,
+                dst_subpass: 0,
+                src_stage_mask: vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT,
+                src_access_mask: Default::default(),
+                dst_stage_mask: vk::PIPELINE_STAGE_EARLY_FRAGMENT_TESTS_BIT
+                    | vk::PIPELINE_STAGE_LATE_FRAGMENT_TESTS_BIT,
+                dst_access_mask: vk::ACCESS_DEPTH_STENCIL_ATTACHMENT_READ_BIT
+                    | vk::ACCESS_DEPTH_STENCIL_ATTACHMENT_WRITE_BIT,
+            },
+            vk::SubpassDependency {
+                dependency_flags: Default::default(),
+                src_subpass: 0,
+                dst_subpass: vk::VK_SUBPASS_EXTERNAL,
+                src_stage_mask: vk::PIPELINE_STAGE_LATE_FRAGMENT_TESTS_BIT,
+                src_access_mask: vk::ACCESS_DEPTH_STENCIL_ATTACHMENT_WRITE_BIT,
+                dst_stage_mask: vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT,
+                dst_access_mask: vk::ACCESS_SHADER_READ_BIT,
+            },
+        ];
+
+        let render_pass = vk::RenderPassCreateInfo {
+            s_type: vk::StructureType::RenderPassCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            attachment_count: 1,
+            p_attachments: &depth_attachment,
+            subpass_count: 1,
+            p_subpasses: &subpass_description,
+            dependency_count: dependencies.len() as u32,
+            p_dependencies: dependencies.as_ptr(),
+        };
+
+        let render_pass = unsafe {
+            device
+                .create_render_pass(&render_pass, None)
+                .expect("unable to create renderpass")
+        };
+
+        RenderPass {
+            render_pass,
+            color_format: vk::Format::Undefined,
+            depth_format: Some(depth_format),
+            samples: vk::SAMPLE_COUNT_1_BIT,
+        }
+    }
+
+    /// `final_layout` is the layout the single sampled color attachment is
+    /// left in.
+    fn create(
+        device: &Device<V1_0>,
+        color_format: vk::Format,
+        depth_format: Option<vk::Format>,
+        samples: vk::SampleCountFlags,
+        final_layout: vk::ImageLayout,
+    ) -> Self {
+        let resolve = samples != vk::SAMPLE_COUNT_1_BIT;
+        let sampled = final_layout == vk::ImageLayout::ShaderReadOnlyOptimal;
+
+        // A multisampled color attachment is only needed until it has been
+        // resolved.
+        let color_attachment = vk::AttachmentDescription {
+            format: color_format,
+            flags: vk::AttachmentDescriptionFlags::empty(),
+            samples,
+            load_op: vk::AttachmentLoadOp::Clear,
+            store_op: if resolve {
+                vk::AttachmentStoreOp::DontCare
+            } else {
+                vk::AttachmentStoreOp::Store
+            },
+            stencil_load_op: vk::AttachmentLoadOp::DontCare,
+            stencil_store_op: vk::AttachmentStoreOp::DontCare,
+            initial_layout: vk::ImageLayout::Undefined,
+            final_layout: if resolve {
+                vk::ImageLayout::ColorAttachmentOptimal
+            } else {
+                final_layout
+            },
+        };
+
+        let mut attachments = vec![color_attachment];
+        if let Some(depth_format) = depth_format {
+            // Only needed while the subpass runs, so its contents are
+            // discarded afterwards.
+            attachments.push(vk::AttachmentDescription {
+                format: depth_format,
+                flags: vk::AttachmentDescriptionFlags::empty(),
+                samples,
+                load_op: vk::AttachmentLoadOp::Clear,
+                store_op: vk::AttachmentStoreOp::DontCare,
+                stencil_load_op: vk::AttachmentLoadOp::DontCare,
+                stencil_store_op: vk::AttachmentStoreOp::DontCare,
+                initial_layout: vk::ImageLayout::Undefined,
+                final_layout: vk::ImageLayout::DepthStencilAttachmentOptimal,
+            });
+        }
+
+        let color_attachment_ref = vk::AttachmentReference {
+            attachment: 0,
+            layout: vk::ImageLayout::ColorAttachmentOptimal,
+        };
+
+        let depth_attachment_ref = vk::AttachmentReference {
+            attachment: 1,
+            layout: vk::ImageLayout::DepthStencilAttachmentOptimal,
+        };
+
+        let resolve_attachment_ref = vk::AttachmentReference {
+            attachment: attachments.len() as u32,
+            layout: vk::ImageLayout::ColorAttachmentOptimal,
+        };
+        if resolve {
+            attachments.push(vk::AttachmentDescription {
+                format: color_format,
+                flags: vk::AttachmentDescriptionFlags::empty(),
+                samples: vk::SAMPLE_COUNT_1_BIT,
+                load_op: vk::AttachmentLoadOp::DontCare,
+                store_op: vk::AttachmentStoreOp::Store,
+                stencil_load_op: vk::AttachmentLoadOp::DontCare,
+                stencil_store_op: vk::AttachmentStoreOp::DontCare,
+                initial_layout: vk::ImageLayout::Undefined,
+                final_layout,
+            });
+        }
+
+        let subpass_description = vk::SubpassDescription {
+            flags: Default::default(),
+            pipeline_bind_point: vk::PipelineBindPoint::Graphics,
+            color_attachment_count: 1,
+            p_color_attachments: &color_attachment_ref,
+            input_attachment_count: 0,
+            p_input_attachments: ptr::null(),
+            p_resolve_attachments: if resolve {
+                &resolve_attachment_ref
+            } else {
+                ptr::null()
+            },
+            p_depth_stencil_attachment: match depth_format {
+                Some(_) => &depth_attachment_ref,
+                None => ptr::null(),
+            },
+            preserve_attachment_count: 0,
+            p_preserve_attachments: ptr::null(),
+        };
+
+        // A sampled target has to wait for the previous frame's reads before
+        // it is overwritten.
+        let mut src_stage_mask = vk::PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT
+            | vk::PIPELINE_STAGE_EARLY_FRAGMENT_TESTS_BIT;
+        if sampled {
+            src_stage_mask |= vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT;
+        }
+
+        let dependency = vk::SubpassDependency {
+            dependency_flags: Default::default(),
+            src_subpass: vk::VK_SUBPASS_EXTERNAL,
+            dst_subpass: 0,
+            src_stage_mask,
+            src_access_mask: Default::default(),
+            dst_stage_mask: vk::PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT
+                | vk::PIPELINE_STAGE_EARLY_FRAGMENT_TESTS_BIT,
+            dst_access_mask: vk::ACCESS_COLOR_ATTACHMENT_READ_BIT
+                | vk::ACCESS_COLOR_ATTACHMENT_WRITE_BIT
+                | vk::ACCESS_DEPTH_STENCIL_ATTACHMENT_WRITE_BIT,
+        };
+        let mut dependencies = vec![dependency];
+        if sampled {
+            // The writes have to finish before the target is read.
+            dependencies.push(vk::SubpassDependency {
+                dependency_flags: Default::default(),
+                src_subpass: 0,
+                dst_subpass: vk::VK_SUBPASS_EXTERNAL,
+                src_stage_mask: vk::PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT,
+                src_access_mask: vk::ACCESS_COLOR_ATTACHMENT_WRITE_BIT,
+                dst_stage_mask: vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT,
+                dst_access_mask: vk::ACCESS_SHADER_READ_BIT,
+            });
+        }
+
+        let render_pass = vk::RenderPassCreateInfo {
+            s_type: vk::StructureType::RenderPassCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            attachment_count: attachments.len() as u32,
+            p_attachments: attachments.as_ptr(),
+            subpass_count: 1,
+            p_subpasses: &subpass_description,
+            dependency_count: dependencies.len() as u32,
+            p_dependencies: dependencies.as_ptr(),
+        };
+
+        let render_pass = unsafe {
+            device
+                .create_render_pass(&render_pass, None)
+                .expect("unable to create renderpass")
+        };
+
+        RenderPass {
+            render_pass,
+            color_format,
+            depth_format,
+            samples,
+        }
+    }
+
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        unsafe {
+            device.destroy_render_pass(self.render_pass, None);
+        }
+    }
+}
+
+/// Attachments, subpasses and dependencies of a render pass, in the order
+/// they are referenced by index.
+#[derive(Default)]
+pub struct RenderPassBuilder {
+    attachments: Vec<vk::AttachmentDescription>,
+    subpasses: Vec<SubpassDesc>,
+    dependencies: Vec<vk::SubpassDependency>,
+}
+
+impl RenderPassBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// A single sampled attachment whose previous contents are discarded,
+    /// stencil is neither loaded nor stored.
+    pub fn with_attachment(
+        mut self,
+        format: vk::Format,
+        load_op: vk::AttachmentLoadOp,
+        store_op: vk::AttachmentStoreOp,
+        final_layout: vk::ImageLayout,
+    ) -> Self {
+        self.attachments.push(vk::AttachmentDescription {
+            format,
+            flags: vk::AttachmentDescriptionFlags::empty(),
+            samples: vk::SAMPLE_COUNT_1_BIT,
+            load_op,
+            store_op,
+            stencil_load_op: vk::AttachmentLoadOp::DontCare,
+            stencil_store_op: vk::AttachmentStoreOp::DontCare,
+            initial_layout: vk::ImageLayout::Undefined,
+            final_layout,
+        });
+        self
+    }
+
+    pub fn with_subpass(mut self, subpass: SubpassDesc) -> Self {
+        self.subpasses.push(subpass);
+        self
+    }
+
+    pub fn with_dependency(mut self, dependency: vk::SubpassDependency) -> Self {
+        self.dependencies.push(dependency);
+        self
+    }
+
+    /// `color_format` of the render pass is that of the first color
+    /// attachment of the last subpass, `depth_format` that of the first
+    /// depth attachment used by any subpass.
+    ///
+    /// Fails with `ErrorInitializationFailed` without subpasses or when a
+    /// subpass refers to an attachment that doesn't exist.
+    pub fn create(self, device: &Device<V1_0>) -> Result<RenderPass, vk::Result> {
+        if self.subpasses.is_empty() {
+            return Err(vk::Result::ErrorInitializationFailed);
+        }
+
+        let format = |reference: &vk::AttachmentReference| {
+            self.attachments
+                .get(reference.attachment as usize)
+                .map(|attachment| attachment.format)
+                .ok_or(vk::Result::ErrorInitializationFailed)
+        };
+        let color_format = match self.subpasses[self.subpasses.len() - 1].colors.first() {
+            Some(reference) => format(reference)?,
+            None => vk::Format::Undefined,
+        };
+        let depth_format = match self
+            .subpasses
+            .iter()
+            .filter_map(|subpass| subpass.depth.as_ref())
+            .next()
+        {
+            Some(reference) => Some(format(reference)?),
+            None => None,
+        };
+
+        let subpass_descriptions = self
+            .subpasses
+            .iter()
+            .map(|subpass| vk::SubpassDescription {
+                flags: Default::default(),
+                pipeline_bind_point: vk::PipelineBindPoint::Graphics,
+                color_attachment_count: subpass.colors.len() as u32,
+                p_color_attachments: subpass.colors.as_ptr(),
+                input_attachment_count: subpass.inputs.len() as u32,
+                p_input_attachments: subpass.inputs.as_ptr(),
+                p_resolve_attachments: ptr::null(),
+                p_depth_stencil_attachment: match subpass.depth {
+                    Some(ref depth) => depth,
+                    None => ptr::null(),
+                },
+                preserve_attachment_count: 0,
+                p_preserve_attachments: ptr::null(),
+            }).collect::<Vec<_>>();
+
+        let render_pass_info = vk::RenderPassCreateInfo {
+            s_type: vk::StructureType::RenderPassCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            attachment_count: self.attachments.len() as u32,
+            p_attachments: self.attachments.as_ptr(),
+            subpass_count: subpass_descriptions.len() as u32,
+            p_subpasses: subpass_descriptions.as_ptr(),
+            dependency_count: self.dependencies.len() as u32,
+            p_dependencies: self.dependencies.as_ptr(),
+        };
+
+        let render_pass = unsafe { device.create_render_pass(&render_pass_info, None)? };
+
+        Ok(RenderPass {
+            render_pass,
+            color_format,
+            depth_format,
+            samples: vk::SAMPLE_COUNT_1_BIT,
+        })
+    }
+}
+
+/// The attachments a subpass of a `RenderPassBuilder` writes and reads, by
+/// their index into the attachments of the render pass.
+#[derive(Clone, Default)]
+pub struct SubpassDesc {
+    colors: Vec<vk::AttachmentReference>,
+    inputs: Vec<vk::AttachmentReference>,
+    depth: Option<vk::AttachmentReference>,
+}
+
+impl SubpassDesc {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Written by fragment shader output `location`s in the order they are
+    /// added.
+    pub fn with_color(mut self, attachment: u32) -> Self {
+        self.colors.push(vk::AttachmentReference {
+            attachment,
+            layout: vk::ImageLayout::ColorAttachmentOptimal,
+        });
+        self
+    }
+
+    /// Read with `subpassLoad` from `input_attachment_index`es in the order
+    /// they are added. `layout` is `ShaderReadOnlyOptimal` for color
+    /// attachments and `DepthStencilReadOnlyOptimal` for depth ones.
+    pub fn with_input(mut self, attachment: u32, layout: vk::ImageLayout) -> Self {
+        self.inputs.push(vk::AttachmentReference { attachment, layout });
+        self
+    }
+
+    pub fn with_depth(mut self, attachment: u32) -> Self {
+        self.depth = Some(vk::AttachmentReference {
+            attachment,
+            layout: vk::ImageLayout::DepthStencilAttachmentOptimal,
+        });
+        self
+    }
+}
+
+pub struct PipelineLayout {
+    pub layout: vk::PipelineLayout,
+    pub push_constant_ranges: Vec<vk::PushConstantRange>,
+}
+
+impl PipelineLayout {
+    pub fn build() -> PipelineLayoutBuilder {
+        PipelineLayoutBuilder::new()
+    }
+
+    pub fn empty(device: &Device<V1_0>) -> Self {
+        let layout_create_info = vk::PipelineLayoutCreateInfo {
+            s_type: vk::StructureType::PipelineLayoutCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            set_layout_count: 0,
+            p_set_layouts: ptr::null(),
+            push_constant_range_count: 0,
+            p_push_constant_ranges: ptr::null(),
+        };
+
+        let pipeline_layout = unsafe {
+            device
+                .create_pipeline_layout(&layout_create_info, None)
+                .unwrap()
+        };
+
+        PipelineLayout {
+            layout: pipeline_layout,
+            push_constant_ranges: Vec::new(),
+        }
+    }
+
+    /// Whether `size` bytes at `offset` are covered by a declared push
+    /// constant range that is visible to all of `stage_flags`.
+    pub fn covers_push_constants(
+        &self,
+        stage_flags: vk::ShaderStageFlags,
+        offset: u32,
+        size: u32,
+    ) -> bool {
+        self.push_constant_ranges.iter().any(|range| {
+            range.stage_flags.subset(stage_flags)
+                && offset >= range.offset
+                && offset + size <= range.offset + range.size
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum PipelineLayoutError {
+    /// A range with no bytes or no shader stages.
+    Empty,
+    /// Offset and size must both be a multiple of four.
+    Unaligned { offset: u32, size: u32 },
+    /// The range ends past the device's `maxPushConstantsSize`.
+    TooLarge { end: u32, max: u32 },
+    /// A shader stage may only appear in a single range.
+    DuplicateStage(vk::ShaderStageFlags),
+    /// More descriptor sets than the device's `maxBoundDescriptorSets`.
+    TooManySets { count: u32, max: u32 },
+}
+
+#[derive(Default)]
+pub struct PipelineLayoutBuilder {
+    set_layouts: Vec<vk::DescriptorSetLayout>,
+    push_constant_ranges: Vec<vk::PushConstantRange>,
+}
+
+impl PipelineLayoutBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add the layout of the next descriptor set, sets are numbered in the
+    /// order they are added.
+    pub fn with_descriptor_set_layout(mut self, set_layout: &DescriptorSetLayout) -> Self {
+        self.set_layouts.push(set_layout.layout);
+        self
+    }
+
+    pub fn with_push_constant_range(
+        mut self,
+        stage_flags: vk::ShaderStageFlags,
+        offset: u32,
+        size: u32,
+    ) -> Self {
+        self.push_constant_ranges.push(vk::PushConstantRange {
+            stage_flags,
+            offset,
+            size,
+        });
+        self
+    }
+
+    /// Declare a range sized for `T`, placed right after the ranges that
+    /// were declared before it.
+    ///
+    /// `T` is expected to be `#[repr(C)]` and to match the shader's
+    /// `push_constant` block.
+    pub fn with_push_constants<T: Copy>(self, stage_flags: vk::ShaderStageFlags) -> Self {
+        let offset = self
+            .push_constant_ranges
+            .iter()
+            .map(|range| range.offset + range.size)
+            .max()
+            .unwrap_or(0);
+        // Offsets have to stay 4 byte aligned.
+        let offset = (offset + 3) & !3;
+
+        self.with_push_constant_range(stage_flags, offset, mem::size_of::<T>() as u32)
+    }
+
+    /// Check the descriptor sets and push constant ranges against the device
+    /// limits.
+    pub fn validate(&self, limits: &vk::PhysicalDeviceLimits) -> Result<(), PipelineLayoutError> {
+        let count = self.set_layouts.len() as u32;
+        if count > limits.max_bound_descriptor_sets {
+            return Err(PipelineLayoutError::TooManySets {
+                count,
+                max: limits.max_bound_descriptor_sets,
+            });
+        }
+
+        let mut seen_stages = vk::ShaderStageFlags::empty();
+        for range in &self.push_constant_ranges {
+            if range.size == 0 || range.stage_flags.is_empty() {
+                return Err(PipelineLayoutError::Empty);
+            }
+
+            if range.offset % 4 != 0 || range.size % 4 != 0 {
+                return Err(PipelineLayoutError::Unaligned {
+                    offset: range.offset,
+                    size: range.size,
+                });
+            }
+
+            let end = range.offset + range.size;
+            if end > limits.max_push_constants_size {
+                return Err(PipelineLayoutError::TooLarge {
+                    end,
+                    max: limits.max_push_constants_size,
+                });
+            }
+
+            let duplicate = seen_stages & range.stage_flags;
+            if !duplicate.is_empty() {
+                return Err(PipelineLayoutError::DuplicateStage(duplicate));
+            }
+            seen_stages |= range.stage_flags;
+        }
+
+        Ok(())
+    }
+
+    /// Check the layout against the device limits and create it.
+    pub fn create(
+        self,
+        device: &Device<V1_0>,
+        limits: &vk::PhysicalDeviceLimits,
+    ) -> Result<PipelineLayout, PipelineLayoutError> {
+        self.validate(limits)?;
+
+        let layout_create_info = vk::PipelineLayoutCreateInfo {
+            s_type: vk::StructureType::PipelineLayoutCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            set_layout_count: self.set_layouts.len() as u32,
+            p_set_layouts: self.set_layouts.as_ptr(),
+            push_constant_range_count: self.push_constant_ranges.len() as u32,
+            p_push_constant_ranges: self.push_constant_ranges.as_ptr(),
+        };
+
+        let pipeline_layout = unsafe {
+            device
+                .create_pipeline_layout(&layout_create_info, None)
+                .unwrap()
+        };
+
+        Ok(PipelineLayout {
+            layout: pipeline_layout,
+            push_constant_ranges: self.push_constant_ranges,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ShaderType {
+    Vertex,
+    TessellationControl,
+    TessellationEvaluation,
+    Geometry,
+    Fragment,
+}
+
+impl ShaderType {
+    pub fn to_vulkan(&self) -> vk::ShaderStageFlags {
+        match self {
+            ShaderType::Vertex => vk::SHADER_STAGE_VERTEX_BIT,
+            ShaderType::TessellationControl => vk::SHADER_STAGE_TESSELLATION_CONTROL_BIT,
+            ShaderType::TessellationEvaluation => vk::SHADER_STAGE_TESSELLATION_EVALUATION_BIT,
+            ShaderType::Geometry => vk::SHADER_STAGE_GEOMETRY_BIT,
             ShaderType::Fragment => vk::SHADER_STAGE_FRAGMENT_BIT,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(rasterizer: Rasterizer) -> PipelineVariant {
+        PipelineVariant::new()
+            .with_input_assembly_state(InputAssembly::triangle_list())
+            .with_rasterizer(rasterizer)
+            .with_multisample(Multisample::disabled())
+            .with_color_blend(ColorBlend::opaque())
+            .with_depth_stencil(DepthStencil::less())
+    }
+
+    #[test]
+    fn cache_key_includes_fixed_function_state() {
+        let back = variant(Rasterizer::fill());
+        let none = variant(Rasterizer::no_cull());
+        assert_eq!(back.cache_key(&[]), variant(Rasterizer::fill()).cache_key(&[]));
+        assert_ne!(back.cache_key(&[]), none.cache_key(&[]));
+
+        let blended = back.clone().with_color_blend(ColorBlend::alpha_blend());
+        assert_ne!(back.cache_key(&[]), blended.cache_key(&[]));
+
+        let lines = back.clone().with_input_assembly_state(InputAssembly::line_list());
+        assert_ne!(back.cache_key(&[]), lines.cache_key(&[]));
+    }
+
+    #[test]
+    fn cache_key_includes_float_state() {
+        let mut wide = Rasterizer::fill();
+        wide.line_width = 2.0;
+        let back = variant(Rasterizer::fill());
+        assert_ne!(back.cache_key(&[]), variant(wide).cache_key(&[]));
+
+        let shaded = back
+            .clone()
+            .with_multisample(Multisample::disabled().with_sample_shading(0.5));
+        assert_ne!(back.cache_key(&[]), shaded.cache_key(&[]));
+    }
+
+    fn hash(specialization: &Specialization) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        specialization.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn specialization_hash_ignores_constant_order() {
+        let forward = Specialization::new()
+            .with_constant(0, 4u32)
+            .with_constant(1, true);
+        let reversed = Specialization::new()
+            .with_constant(1, true)
+            .with_constant(0, 4u32);
+        assert_eq!(hash(&forward), hash(&reversed));
+        assert_ne!(hash(&forward), hash(&forward.clone().with_constant(0, 8u32)));
+    }
+
+    fn limits() -> vk::PhysicalDeviceLimits {
+        // Every field is a plain number, flag or array of them.
+        let mut limits: vk::PhysicalDeviceLimits = unsafe { mem::zeroed() };
+        limits.max_push_constants_size = 128;
+        limits.max_bound_descriptor_sets = 4;
+        limits
+    }
+
+    fn layout_error(builder: PipelineLayoutBuilder) -> PipelineLayoutError {
+        builder.validate(&limits()).unwrap_err()
+    }
+
+    #[test]
+    fn layout_accepts_ranges_within_limits() {
+        let builder = PipelineLayoutBuilder::new()
+            .with_push_constant_range(vk::SHADER_STAGE_VERTEX_BIT, 0, 64)
+            .with_push_constant_range(vk::SHADER_STAGE_FRAGMENT_BIT, 64, 64);
+        assert!(builder.validate(&limits()).is_ok());
+    }
+
+    #[test]
+    fn layout_rejects_invalid_ranges() {
+        let vertex = vk::SHADER_STAGE_VERTEX_BIT;
+        match layout_error(PipelineLayoutBuilder::new().with_push_constant_range(vertex, 64, 80)) {
+            PipelineLayoutError::TooLarge { end: 144, max: 128 } => {}
+            err => panic!("{:?}", err),
+        }
+        match layout_error(PipelineLayoutBuilder::new().with_push_constant_range(vertex, 2, 8)) {
+            PipelineLayoutError::Unaligned { offset: 2, size: 8 } => {}
+            err => panic!("{:?}", err),
+        }
+        match layout_error(PipelineLayoutBuilder::new().with_push_constant_range(vertex, 0, 0)) {
+            PipelineLayoutError::Empty => {}
+            err => panic!("{:?}", err),
+        }
+        match layout_error(
+            PipelineLayoutBuilder::new()
+                .with_push_constant_range(vertex, 0, 16)
+                .with_push_constant_range(vertex, 16, 16),
+        ) {
+            PipelineLayoutError::DuplicateStage(_) => {}
+            err => panic!("{:?}", err),
+        }
+    }
+
+    #[test]
+    fn layout_rejects_too_many_sets() {
+        let mut builder = PipelineLayoutBuilder::new();
+        builder.set_layouts = vec![vk::DescriptorSetLayout::null(); 5];
+        match layout_error(builder) {
+            PipelineLayoutError::TooManySets { count: 5, max: 4 } => {}
+            err => panic!("{:?}", err),
+        }
+
+        let mut builder = PipelineLayoutBuilder::new();
+        builder.set_layouts = vec![vk::DescriptorSetLayout::null(); 4];
+        assert!(builder.validate(&limits()).is_ok());
+    }
+}
@@ -0,0 +1,648 @@
+use ash::version::{DeviceV1_0, V1_0};
+use ash::vk;
+use ash::{Device, Instance};
+use std::str::FromStr;
+
+use shader::Shader;
+
+use super::command_pool;
+use super::descriptor::{self, DescriptorPool, DescriptorSetLayout};
+use super::image::ImageError;
+use super::pipeline::{self, Pipeline, RenderPass};
+use super::render_target::RenderTarget;
+
+/// Format of the scene and of every intermediate target. Rendering into and
+/// sampling it is required by the spec.
+pub const HDR_FORMAT: vk::Format = vk::Format::R16g16b16a16Sfloat;
+
+/// Most bloom levels, the last one is `1 / 2^MAX_BLOOM_LEVELS` of the scene.
+pub const MAX_BLOOM_LEVELS: u32 = 8;
+
+const FULLSCREEN_VERT: &str = "assets/shaders/post/fullscreen.vert.spv";
+const BLOOM_PREFILTER_FRAG: &str = "assets/shaders/post/bloom_prefilter.frag.spv";
+const BLOOM_DOWNSAMPLE_FRAG: &str = "assets/shaders/post/bloom_downsample.frag.spv";
+const BLOOM_UPSAMPLE_FRAG: &str = "assets/shaders/post/bloom_upsample.frag.spv";
+const COMPOSITE_FRAG: &str = "assets/shaders/post/composite.frag.spv";
+const FXAA_FRAG: &str = "assets/shaders/post/fxaa.frag.spv";
+
+/// Curve that maps the HDR scene into the displayable range.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum TonemapOperator {
+    #[serde(rename = "reinhard")]
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve.
+    #[serde(rename = "aces")]
+    Aces,
+}
+
+impl FromStr for TonemapOperator {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "reinhard" => Ok(TonemapOperator::Reinhard),
+            "aces" => Ok(TonemapOperator::Aces),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TonemapSettings {
+    /// Clamps the scene to the displayable range when off.
+    pub enabled: bool,
+    pub operator: TonemapOperator,
+    /// Scales the scene before bloom and tonemapping.
+    pub exposure: f32,
+}
+
+impl TonemapSettings {
+    /// Go from Reinhard to ACES to off and back.
+    pub fn cycle(&mut self) {
+        match (self.enabled, self.operator) {
+            (false, _) => {
+                self.enabled = true;
+                self.operator = TonemapOperator::Reinhard;
+            }
+            (true, TonemapOperator::Reinhard) => self.operator = TonemapOperator::Aces,
+            (true, TonemapOperator::Aces) => self.enabled = false,
+        }
+    }
+}
+
+impl Default for TonemapSettings {
+    fn default() -> Self {
+        TonemapSettings {
+            enabled: true,
+            operator: TonemapOperator::Aces,
+            exposure: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BloomSettings {
+    pub enabled: bool,
+    /// Brightness above which the scene blooms, after exposure.
+    pub threshold: f32,
+    /// How much of the blurred chain is added back to the scene.
+    pub intensity: f32,
+    /// Levels of the downsample chain, each half the size of the one
+    /// before, up to `MAX_BLOOM_LEVELS`.
+    pub levels: u32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        BloomSettings {
+            enabled: true,
+            threshold: 1.0,
+            intensity: 0.05,
+            levels: 5,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FxaaSettings {
+    pub enabled: bool,
+}
+
+impl Default for FxaaSettings {
+    fn default() -> Self {
+        FxaaSettings { enabled: true }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VignetteSettings {
+    pub enabled: bool,
+    /// How dark the corners get, from 0 to 1.
+    pub strength: f32,
+    /// Distance from the center where the darkening starts, in UV units.
+    pub radius: f32,
+}
+
+impl Default for VignetteSettings {
+    fn default() -> Self {
+        VignetteSettings {
+            enabled: true,
+            strength: 0.25,
+            radius: 0.75,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GammaSettings {
+    /// Off by default, sRGB swapchains already encode what is written to
+    /// them.
+    pub enabled: bool,
+    pub value: f32,
+}
+
+impl Default for GammaSettings {
+    fn default() -> Self {
+        GammaSettings {
+            enabled: false,
+            value: 2.2,
+        }
+    }
+}
+
+/// The passes of a `PostStack`, in the order they run.
+///
+/// ```text
+/// [post.tonemap]
+/// operator = "reinhard"
+///
+/// [post.bloom]
+/// levels = 6
+///
+/// [post.fxaa]
+/// enabled = false
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PostSettings {
+    pub tonemap: TonemapSettings,
+    pub bloom: BloomSettings,
+    pub fxaa: FxaaSettings,
+    pub vignette: VignetteSettings,
+    pub gamma: GammaSettings,
+}
+
+impl PostSettings {
+    /// The push constants of every pass, with the disabled effects turned
+    /// into no-ops.
+    fn constants(&self, texel_size: [f32; 2]) -> PostConstants {
+        PostConstants {
+            texel_size,
+            bloom_threshold: self.bloom.threshold,
+            bloom_intensity: if self.bloom.enabled {
+                self.bloom.intensity
+            } else {
+                0.0
+            },
+            exposure: self.tonemap.exposure,
+            tonemap: match (self.tonemap.enabled, self.tonemap.operator) {
+                (false, _) => 0,
+                (true, TonemapOperator::Reinhard) => 1,
+                (true, TonemapOperator::Aces) => 2,
+            },
+            vignette_strength: if self.vignette.enabled {
+                self.vignette.strength
+            } else {
+                0.0
+            },
+            vignette_radius: self.vignette.radius,
+            inverse_gamma: if self.gamma.enabled {
+                1.0 / self.gamma.value
+            } else {
+                1.0
+            },
+        }
+    }
+}
+
+/// Matches `constants.glsl`.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+struct PostConstants {
+    texel_size: [f32; 2],
+    bloom_threshold: f32,
+    bloom_intensity: f32,
+    exposure: f32,
+    tonemap: u32,
+    vignette_strength: f32,
+    vignette_radius: f32,
+    inverse_gamma: f32,
+}
+
+/// Full-screen passes that take an HDR scene to the output: bloom, tone
+/// mapping, vignette and gamma in one composite pass, then FXAA.
+///
+/// `render_offscreen` runs the passes that render into `PostTargets`, and
+/// `render` draws the result inside the output render pass. Every pass
+/// reads `settings` when it is recorded, so effects can be switched on and
+/// off between frames.
+pub struct PostStack {
+    pub settings: PostSettings,
+    /// Two combined image samplers, the second only read by the bloom
+    /// upsample and the composite.
+    pub set_layout: DescriptorSetLayout,
+    /// Renders the bloom chain and the composite that FXAA reads.
+    pub pass: RenderPass,
+    prefilter: Pipeline,
+    downsample: Pipeline,
+    upsample: Pipeline,
+    /// Composite into the target FXAA reads.
+    composite_offscreen: Pipeline,
+    /// Composite straight into the output, with FXAA off.
+    composite: Pipeline,
+    fxaa: Pipeline,
+}
+
+impl PostStack {
+    /// Create the pipelines, drawing into `output_pass` in the end.
+    pub fn new(
+        device: &Device<V1_0>,
+        limits: &vk::PhysicalDeviceLimits,
+        output_pass: RenderPass,
+        settings: PostSettings,
+    ) -> Self {
+        let set_layout = DescriptorSetLayout::build()
+            .with_binding(
+                0,
+                vk::DescriptorType::CombinedImageSampler,
+                1,
+                vk::SHADER_STAGE_FRAGMENT_BIT,
+            ).with_binding(
+                1,
+                vk::DescriptorType::CombinedImageSampler,
+                1,
+                vk::SHADER_STAGE_FRAGMENT_BIT,
+            ).create(device)
+            .expect("Unable to create post-processing descriptor set layout");
+
+        let pass = RenderPass::offscreen(device, HDR_FORMAT, None, vk::SAMPLE_COUNT_1_BIT);
+
+        let (prefilter, downsample, upsample, composite_offscreen, composite, fxaa) = {
+            let create = |fragment: &str, render_pass: RenderPass| {
+                create_pipeline(device, limits, &set_layout, fragment, render_pass)
+            };
+            (
+                create(BLOOM_PREFILTER_FRAG, pass),
+                create(BLOOM_DOWNSAMPLE_FRAG, pass),
+                create(BLOOM_UPSAMPLE_FRAG, pass),
+                create(COMPOSITE_FRAG, pass),
+                create(COMPOSITE_FRAG, output_pass),
+                create(FXAA_FRAG, output_pass),
+            )
+        };
+
+        PostStack {
+            settings,
+            set_layout,
+            pass,
+            prefilter,
+            downsample,
+            upsample,
+            composite_offscreen,
+            composite,
+            fxaa,
+        }
+    }
+
+    /// Run the bloom chain and, with FXAA on, the composite. Has to be
+    /// recorded outside of any render pass.
+    pub fn render_offscreen(
+        &self,
+        device: &Device<V1_0>,
+        command_buffer: vk::CommandBuffer,
+        targets: &PostTargets,
+    ) {
+        if self.settings.bloom.enabled {
+            self.draw_into(
+                device,
+                command_buffer,
+                &self.prefilter,
+                &targets.bloom_down[0],
+                targets.prefilter_set,
+                targets.extent,
+            );
+
+            for (level, &set) in targets.downsample_sets.iter().enumerate() {
+                self.draw_into(
+                    device,
+                    command_buffer,
+                    &self.downsample,
+                    &targets.bloom_down[level + 1],
+                    set,
+                    targets.bloom_down[level].extent,
+                );
+            }
+
+            // From the smallest level back up, each one adding the level
+            // below to its own downsampled image.
+            for (level, &set) in targets.upsample_sets.iter().enumerate().rev() {
+                let source = match targets.bloom_up.get(level + 1) {
+                    Some(source) => source.extent,
+                    None => targets.bloom_down[level + 1].extent,
+                };
+                self.draw_into(
+                    device,
+                    command_buffer,
+                    &self.upsample,
+                    &targets.bloom_up[level],
+                    set,
+                    source,
+                );
+            }
+        } else {
+            // The composite samples the chain either way, so leave it black
+            // and in the layout it is sampled in.
+            let bloom_top = targets.bloom_top();
+            bloom_top.begin(device, command_buffer, [0.0; 4], 0.0);
+            bloom_top.end(device, command_buffer);
+        }
+
+        if self.settings.fxaa.enabled {
+            self.draw_into(
+                device,
+                command_buffer,
+                &self.composite_offscreen,
+                &targets.composite,
+                targets.composite_set,
+                targets.extent,
+            );
+        }
+    }
+
+    /// Draw the result inside the output render pass. The viewport has to
+    /// be set to cover the output.
+    pub fn render(
+        &self,
+        device: &Device<V1_0>,
+        command_buffer: vk::CommandBuffer,
+        targets: &PostTargets,
+    ) {
+        if self.settings.fxaa.enabled {
+            self.draw(
+                device,
+                command_buffer,
+                &self.fxaa,
+                targets.fxaa_set,
+                targets.composite.extent,
+            );
+        } else {
+            self.draw(
+                device,
+                command_buffer,
+                &self.composite,
+                targets.composite_set,
+                targets.extent,
+            );
+        }
+    }
+
+    /// Draw a full-screen triangle into all of `target`, reading an input
+    /// of `input_extent` through `set`.
+    fn draw_into(
+        &self,
+        device: &Device<V1_0>,
+        command_buffer: vk::CommandBuffer,
+        pipeline: &Pipeline,
+        target: &RenderTarget,
+        set: vk::DescriptorSet,
+        input_extent: vk::Extent2D,
+    ) {
+        target.begin(device, command_buffer, [0.0; 4], 0.0);
+
+        let viewport = pipeline::Viewport::new(target.extent);
+        unsafe {
+            device.cmd_set_viewport(command_buffer, 0, &[viewport.viewport]);
+            device.cmd_set_scissor(command_buffer, 0, &[viewport.scissor]);
+        }
+        self.draw(device, command_buffer, pipeline, set, input_extent);
+
+        target.end(device, command_buffer);
+    }
+
+    fn draw(
+        &self,
+        device: &Device<V1_0>,
+        command_buffer: vk::CommandBuffer,
+        pipeline: &Pipeline,
+        set: vk::DescriptorSet,
+        input_extent: vk::Extent2D,
+    ) {
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::Graphics,
+                pipeline.graphics_pipelines[0],
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::Graphics,
+                pipeline.layout.layout,
+                0,
+                &[set],
+                &[],
+            );
+        }
+
+        let texel_size = [
+            1.0 / input_extent.width as f32,
+            1.0 / input_extent.height as f32,
+        ];
+        command_pool::push_constants(
+            device,
+            command_buffer,
+            &pipeline.layout,
+            vk::SHADER_STAGE_FRAGMENT_BIT,
+            0,
+            &self.settings.constants(texel_size),
+        );
+
+        unsafe {
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+    }
+
+    /// Targets created with the stack have to be destroyed first.
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        for pipeline in &[
+            &self.prefilter,
+            &self.downsample,
+            &self.upsample,
+            &self.composite_offscreen,
+            &self.composite,
+            &self.fxaa,
+        ] {
+            pipeline.destroy(device);
+        }
+        self.pass.destroy(device);
+        self.set_layout.destroy(device);
+    }
+}
+
+/// The intermediate targets of a `PostStack` for a scene of one size, with
+/// the descriptor sets that read them.
+pub struct PostTargets {
+    /// Size of the scene.
+    pub extent: vk::Extent2D,
+    /// Half the size of the scene, then halved for every level after.
+    pub bloom_down: Vec<RenderTarget>,
+    /// One less than `bloom_down`, the smallest level has nothing to add.
+    pub bloom_up: Vec<RenderTarget>,
+    /// Read by FXAA, at the size of the scene.
+    pub composite: RenderTarget,
+    descriptor_pool: DescriptorPool,
+    prefilter_set: vk::DescriptorSet,
+    downsample_sets: Vec<vk::DescriptorSet>,
+    upsample_sets: Vec<vk::DescriptorSet>,
+    composite_set: vk::DescriptorSet,
+    fxaa_set: vk::DescriptorSet,
+}
+
+impl PostTargets {
+    /// Create targets for reading the scene through `scene_view` and
+    /// `scene_sampler`, with as many bloom levels as `stack.settings` asks
+    /// for.
+    pub fn new(
+        instance: &Instance<V1_0>,
+        pdevice: vk::PhysicalDevice,
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        stack: &PostStack,
+        scene_view: vk::ImageView,
+        scene_sampler: vk::Sampler,
+        extent: vk::Extent2D,
+    ) -> Result<PostTargets, ImageError> {
+        let levels = stack.settings.bloom.levels.clamp(1, MAX_BLOOM_LEVELS);
+        let create_target = |extent: vk::Extent2D| {
+            RenderTarget::new(
+                instance,
+                pdevice,
+                device,
+                memory_properties,
+                stack.pass,
+                extent,
+            )
+        };
+
+        let bloom_down = (0..levels)
+            .map(|level| create_target(bloom_extent(extent, level)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let bloom_up = (0..levels - 1)
+            .map(|level| create_target(bloom_extent(extent, level)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let composite = create_target(extent)?;
+
+        // Prefilter, downsamples, upsamples, composite and FXAA
+        let set_count = 2 * levels + 1;
+        let descriptor_pool = DescriptorPool::new(
+            device,
+            &[(vk::DescriptorType::CombinedImageSampler, 2 * set_count)],
+            set_count,
+        ).expect("Unable to create post-processing descriptor pool");
+        let mut sets = descriptor_pool
+            .allocate(device, &stack.set_layout, set_count as usize)
+            .expect("Unable to allocate post-processing descriptor sets")
+            .into_iter();
+
+        let mut write = |inputs: [(vk::ImageView, vk::Sampler); 2]| {
+            let set = sets.next().unwrap();
+            for (binding, &(view, sampler)) in inputs.iter().enumerate() {
+                descriptor::write_combined_image_sampler(
+                    device,
+                    set,
+                    binding as u32,
+                    view,
+                    sampler,
+                );
+            }
+            set
+        };
+        let input = |target: &RenderTarget| (target.color_view, target.sampler);
+        let scene = (scene_view, scene_sampler);
+
+        let prefilter_set = write([scene, scene]);
+        let downsample_sets = bloom_down
+            .windows(2)
+            .map(|pair| write([input(&pair[0]), input(&pair[0])]))
+            .collect::<Vec<_>>();
+        let upsample_sets = (0..bloom_up.len())
+            .map(|level| {
+                let source = bloom_up.get(level + 1).unwrap_or(&bloom_down[level + 1]);
+                write([input(source), input(&bloom_down[level])])
+            }).collect::<Vec<_>>();
+        let composite_set = {
+            let bloom_top = bloom_up.first().unwrap_or(&bloom_down[0]);
+            write([scene, input(bloom_top)])
+        };
+        let fxaa_set = write([input(&composite), input(&composite)]);
+
+        Ok(PostTargets {
+            extent,
+            bloom_down,
+            bloom_up,
+            composite,
+            descriptor_pool,
+            prefilter_set,
+            downsample_sets,
+            upsample_sets,
+            composite_set,
+            fxaa_set,
+        })
+    }
+
+    /// Bloom levels the targets were created with.
+    pub fn levels(&self) -> u32 {
+        self.bloom_down.len() as u32
+    }
+
+    /// The fully upsampled bloom the composite adds to the scene.
+    fn bloom_top(&self) -> &RenderTarget {
+        self.bloom_up.first().unwrap_or(&self.bloom_down[0])
+    }
+
+    /// The targets must not be in use by the device.
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        self.bloom_down
+            .iter()
+            .chain(self.bloom_up.iter())
+            .chain(Some(&self.composite))
+            .for_each(|target| target.destroy(device));
+        self.descriptor_pool.destroy(device);
+    }
+}
+
+/// Size of bloom `level`, at least a pixel.
+fn bloom_extent(extent: vk::Extent2D, level: u32) -> vk::Extent2D {
+    vk::Extent2D {
+        width: (extent.width >> (level + 1)).max(1),
+        height: (extent.height >> (level + 1)).max(1),
+    }
+}
+
+/// A full-screen triangle drawn with `fragment`, with the `PostConstants`
+/// push constants.
+fn create_pipeline(
+    device: &Device<V1_0>,
+    limits: &vk::PhysicalDeviceLimits,
+    set_layout: &DescriptorSetLayout,
+    fragment: &str,
+    render_pass: RenderPass,
+) -> Pipeline {
+    let vert_shader = Shader::load(device, FULLSCREEN_VERT, pipeline::ShaderType::Vertex);
+    let frag_shader = Shader::load(device, fragment, pipeline::ShaderType::Fragment);
+
+    let pipeline_layout = pipeline::PipelineLayout::build()
+        .with_descriptor_set_layout(set_layout)
+        .with_push_constants::<PostConstants>(vk::SHADER_STAGE_FRAGMENT_BIT)
+        .create(device, limits)
+        .expect("Unable to create post-processing pipeline layout");
+
+    Pipeline::build()
+        .with_shader_stage(vert_shader)
+        .with_shader_stage(frag_shader)
+        .with_vertex_input_state(pipeline::VertexInput::empty())
+        .with_input_assembly_state(pipeline::InputAssembly::triangle_list())
+        .with_dynamic_state(pipeline::DynamicState::viewport_and_scissor())
+        .with_rasterizer(pipeline::Rasterizer::no_cull())
+        .with_multisample(pipeline::Multisample::new(render_pass.samples))
+        .with_color_blend(pipeline::ColorBlend::opaque())
+        .with_depth_stencil(pipeline::DepthStencil::disabled())
+        .with_layout(pipeline_layout)
+        .with_render_pass(render_pass)
+        .create(device)
+        .unwrap()
+}
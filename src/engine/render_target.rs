@@ -0,0 +1,177 @@
+use ash::version::{DeviceV1_0, V1_0};
+use ash::vk;
+use ash::{Device, Instance};
+use std::ptr;
+
+use super::command_pool;
+use super::image::{Image, ImageDesc, ImageError};
+use super::pipeline::RenderPass;
+use super::texture::{self, SamplerDesc};
+
+/// Color and depth images to render into with a render pass from
+/// `RenderPass::offscreen`, and sample in a later pass.
+///
+/// The color image is left in the shader read only layout by the render
+/// pass. When the render pass is multisampled the color image is what it
+/// resolves into.
+pub struct RenderTarget {
+    /// Sampled by later passes.
+    pub color: Image,
+    pub color_view: vk::ImageView,
+    /// Multisampled color the render pass resolves into `color`.
+    pub multisampled_color: Option<(Image, vk::ImageView)>,
+    pub depth: Option<(Image, vk::ImageView)>,
+    pub framebuffer: vk::Framebuffer,
+    /// Clamps to the edge, so post-processing filters don't wrap around.
+    pub sampler: vk::Sampler,
+    pub extent: vk::Extent2D,
+    pub render_pass: RenderPass,
+}
+
+impl RenderTarget {
+    /// Create images of `extent` matching the attachments of `render_pass`.
+    pub fn new(
+        instance: &Instance<V1_0>,
+        pdevice: vk::PhysicalDevice,
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        render_pass: RenderPass,
+        extent: vk::Extent2D,
+    ) -> Result<RenderTarget, ImageError> {
+        let create_image = |desc: ImageDesc| -> Result<(Image, vk::ImageView), ImageError> {
+            let image = Image::create(instance, pdevice, device, memory_properties, &desc)?;
+            let view = image.create_view(device, &image.view_desc());
+            Ok((image, view))
+        };
+
+        let resolve = render_pass.samples != vk::SAMPLE_COUNT_1_BIT;
+
+        // Transfer source so the result can be copied out, like the golden
+        // image checks do
+        let (color, color_view) = create_image(
+            ImageDesc::new_2d(render_pass.color_format, extent.width, extent.height).with_usage(
+                vk::IMAGE_USAGE_COLOR_ATTACHMENT_BIT
+                    | vk::IMAGE_USAGE_SAMPLED_BIT
+                    | vk::IMAGE_USAGE_TRANSFER_SRC_BIT,
+            ),
+        )?;
+
+        let multisampled_color = if resolve {
+            Some(create_image(
+                ImageDesc::new_2d(render_pass.color_format, extent.width, extent.height)
+                    .with_usage(
+                        vk::IMAGE_USAGE_COLOR_ATTACHMENT_BIT
+                            | vk::IMAGE_USAGE_TRANSIENT_ATTACHMENT_BIT,
+                    ).with_samples(render_pass.samples),
+            )?)
+        } else {
+            None
+        };
+
+        let depth = match render_pass.depth_format {
+            Some(depth_format) => Some(create_image(
+                ImageDesc::new_2d(depth_format, extent.width, extent.height)
+                    .with_usage(vk::IMAGE_USAGE_DEPTH_STENCIL_ATTACHMENT_BIT)
+                    .with_samples(render_pass.samples),
+            )?),
+            None => None,
+        };
+
+        // Same order as the attachments of the render pass: color, depth,
+        // then the resolve attachment.
+        let attachments = match multisampled_color.as_ref() {
+            Some(&(_, multisampled_view)) => Some(multisampled_view)
+                .into_iter()
+                .chain(depth.as_ref().map(|&(_, view)| view))
+                .chain(Some(color_view))
+                .collect::<Vec<_>>(),
+            None => Some(color_view)
+                .into_iter()
+                .chain(depth.as_ref().map(|&(_, view)| view))
+                .collect::<Vec<_>>(),
+        };
+
+        let framebuffer_info = vk::FramebufferCreateInfo {
+            s_type: vk::StructureType::FramebufferCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            render_pass: render_pass.render_pass,
+            attachment_count: attachments.len() as u32,
+            p_attachments: attachments.as_ptr(),
+            width: extent.width,
+            height: extent.height,
+            layers: 1,
+        };
+
+        let framebuffer = unsafe {
+            device
+                .create_framebuffer(&framebuffer_info, None)
+                .expect("Unable to create render target framebuffer")
+        };
+
+        let sampler = texture::create_sampler(
+            device,
+            &SamplerDesc {
+                address_mode_u: vk::SamplerAddressMode::ClampToEdge,
+                address_mode_v: vk::SamplerAddressMode::ClampToEdge,
+                ..SamplerDesc::default()
+            },
+        );
+
+        Ok(RenderTarget {
+            color,
+            color_view,
+            multisampled_color,
+            depth,
+            framebuffer,
+            sampler,
+            extent,
+            render_pass,
+        })
+    }
+
+    /// Begin the render pass on the target, clearing it like the main render
+    /// pass is cleared.
+    pub fn begin(
+        &self,
+        device: &Device<V1_0>,
+        command_buffer: vk::CommandBuffer,
+        clear_color: [f32; 4],
+        clear_depth: f32,
+    ) {
+        command_pool::begin_render_pass(
+            device,
+            command_buffer,
+            &self.render_pass,
+            self.framebuffer,
+            self.extent,
+            clear_color,
+            clear_depth,
+        );
+    }
+
+    /// End the render pass, after which `color` can be sampled.
+    pub fn end(&self, device: &Device<V1_0>, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            device.cmd_end_render_pass(command_buffer);
+        }
+    }
+
+    /// Destroy the images and the framebuffer, the render pass is left to
+    /// whoever created it. The target must not be in use by the device.
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        unsafe {
+            device.destroy_sampler(self.sampler, None);
+            device.destroy_framebuffer(self.framebuffer, None);
+            device.destroy_image_view(self.color_view, None);
+        }
+        self.color.destroy(device);
+
+        for &(ref image, view) in self.multisampled_color.iter().chain(self.depth.iter()) {
+            unsafe {
+                device.destroy_image_view(view, None);
+            }
+            image.destroy(device);
+        }
+    }
+}
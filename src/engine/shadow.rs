@@ -0,0 +1,266 @@
+use ash::version::{DeviceV1_0, V1_0};
+use ash::vk;
+use ash::{Device, Instance};
+use std::ptr;
+
+use super::command_pool;
+use super::image::{Image, ImageDesc, ImageError, ImageKind};
+use super::mesh;
+use super::model::{DepthPushConstants, DrawItem, Matrix, Model};
+use super::pipeline::{self, DepthBias, Pipeline, PipelineVariant, RenderPass};
+use super::texture::{self, SamplerDesc};
+use shader::Shader;
+
+const SHADOW_VERT: &str = "assets/shaders/shadow.vert.spv";
+
+/// Index into `Pipeline::graphics_pipelines` of the pipeline for
+/// orthographic shadow maps, like the cascades of a directional light.
+pub const ORTHOGRAPHIC_PIPELINE: usize = 0;
+/// The depth bias variant for perspective shadow maps, like those of spot
+/// lights.
+pub const PERSPECTIVE_PIPELINE: usize = 1;
+
+/// Depth bias of orthographic shadow maps, where depth is linear.
+const ORTHOGRAPHIC_DEPTH_BIAS: DepthBias = DepthBias {
+    constant_factor: 1.25,
+    clamp: 0.0,
+    slope_factor: 1.75,
+};
+
+/// Depth bias of perspective shadow maps, which store most of their depth
+/// precision close to the light.
+const PERSPECTIVE_DEPTH_BIAS: DepthBias = DepthBias {
+    constant_factor: 2.0,
+    clamp: 0.0,
+    slope_factor: 2.5,
+};
+
+/// An array of square shadow maps, one per layer, rendered with a depth only
+/// pass and sampled with depth comparison.
+pub struct ShadowMaps {
+    pub render_pass: RenderPass,
+    pub pipeline: Pipeline,
+    pub image: Image,
+    /// Sees every layer, for sampling.
+    pub view: vk::ImageView,
+    /// One per layer, for rendering.
+    pub layer_views: Vec<vk::ImageView>,
+    pub framebuffers: Vec<vk::Framebuffer>,
+    /// Compares against the stored depth, what lies outside the maps is lit.
+    pub sampler: vk::Sampler,
+    pub resolution: u32,
+}
+
+impl ShadowMaps {
+    pub fn new(
+        instance: &Instance<V1_0>,
+        pdevice: vk::PhysicalDevice,
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        limits: &vk::PhysicalDeviceLimits,
+        format: vk::Format,
+        resolution: u32,
+        layers: u32,
+    ) -> Result<ShadowMaps, ImageError> {
+        let image = Image::create(
+            instance,
+            pdevice,
+            device,
+            memory_properties,
+            &ImageDesc::new(
+                ImageKind::Type2d {
+                    width: resolution,
+                    height: resolution,
+                    layers,
+                },
+                format,
+            ).with_usage(
+                vk::IMAGE_USAGE_DEPTH_STENCIL_ATTACHMENT_BIT | vk::IMAGE_USAGE_SAMPLED_BIT,
+            ),
+        )?;
+        let view = image.create_view(
+            device,
+            &image
+                .view_desc()
+                .with_view_type(vk::ImageViewType::Type2dArray),
+        );
+        let layer_views = (0..layers)
+            .map(|layer| {
+                image.create_view(
+                    device,
+                    &image
+                        .view_desc()
+                        .with_view_type(vk::ImageViewType::Type2d)
+                        .with_layers(layer, 1),
+                )
+            }).collect::<Vec<_>>();
+
+        let render_pass = RenderPass::depth_only(device, format);
+        let framebuffers = layer_views
+            .iter()
+            .map(|view| {
+                let framebuffer_info = vk::FramebufferCreateInfo {
+                    s_type: vk::StructureType::FramebufferCreateInfo,
+                    p_next: ptr::null(),
+                    flags: Default::default(),
+                    render_pass: render_pass.render_pass,
+                    attachment_count: 1,
+                    p_attachments: view,
+                    width: resolution,
+                    height: resolution,
+                    layers: 1,
+                };
+
+                unsafe {
+                    device
+                        .create_framebuffer(&framebuffer_info, None)
+                        .expect("Unable to create shadow map framebuffer")
+                }
+            }).collect::<Vec<_>>();
+
+        let sampler = texture::create_sampler(
+            device,
+            &SamplerDesc {
+                mag_filter: vk::Filter::Nearest,
+                min_filter: vk::Filter::Nearest,
+                address_mode_u: vk::SamplerAddressMode::ClampToBorder,
+                address_mode_v: vk::SamplerAddressMode::ClampToBorder,
+                compare_op: Some(vk::CompareOp::LessOrEqual),
+                ..SamplerDesc::default()
+            },
+        );
+
+        Ok(ShadowMaps {
+            render_pass,
+            pipeline: create_pipeline(device, limits, render_pass),
+            image,
+            view,
+            layer_views,
+            framebuffers,
+            sampler,
+            resolution,
+        })
+    }
+
+    /// Render the geometry of `draws` into `layer` as seen through
+    /// `view_projection`, which maps to Vulkan clip space with depth going
+    /// from 0 close to the light to 1 far away.
+    pub fn render(
+        &self,
+        device: &Device<V1_0>,
+        command_buffer: vk::CommandBuffer,
+        layer: usize,
+        perspective: bool,
+        view_projection: Matrix,
+        model: &Model,
+        draws: &[DrawItem],
+    ) {
+        let pipeline = if perspective {
+            PERSPECTIVE_PIPELINE
+        } else {
+            ORTHOGRAPHIC_PIPELINE
+        };
+
+        self.begin(device, command_buffer, layer);
+
+        let viewport = pipeline::Viewport::new(self.extent());
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::Graphics,
+                self.pipeline.graphics_pipelines[pipeline],
+            );
+            device.cmd_set_viewport(command_buffer, 0, &[viewport.viewport]);
+            device.cmd_set_scissor(command_buffer, 0, &[viewport.scissor]);
+        }
+
+        model.draw_depth(
+            device,
+            command_buffer,
+            &self.pipeline.layout,
+            view_projection,
+            draws,
+        );
+
+        unsafe {
+            device.cmd_end_render_pass(command_buffer);
+        }
+    }
+
+    /// Clear `layer` so that nothing in it is shadowed, for layers no light
+    /// uses that are sampled all the same.
+    pub fn clear(&self, device: &Device<V1_0>, command_buffer: vk::CommandBuffer, layer: usize) {
+        self.begin(device, command_buffer, layer);
+        unsafe {
+            device.cmd_end_render_pass(command_buffer);
+        }
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        vk::Extent2D {
+            width: self.resolution,
+            height: self.resolution,
+        }
+    }
+
+    fn begin(&self, device: &Device<V1_0>, command_buffer: vk::CommandBuffer, layer: usize) {
+        command_pool::begin_render_pass(
+            device,
+            command_buffer,
+            &self.render_pass,
+            self.framebuffers[layer],
+            self.extent(),
+            [0.0; 4],
+            1.0,
+        );
+    }
+
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        unsafe {
+            device.destroy_sampler(self.sampler, None);
+            for (&framebuffer, &view) in self.framebuffers.iter().zip(self.layer_views.iter()) {
+                device.destroy_framebuffer(framebuffer, None);
+                device.destroy_image_view(view, None);
+            }
+            device.destroy_image_view(self.view, None);
+        }
+        self.image.destroy(device);
+        self.pipeline.destroy(device);
+        self.render_pass.destroy(device);
+    }
+}
+
+/// A depth only pipeline with a depth bias variant per kind of projection.
+fn create_pipeline(
+    device: &Device<V1_0>,
+    limits: &vk::PhysicalDeviceLimits,
+    render_pass: RenderPass,
+) -> Pipeline {
+    let layout = pipeline::PipelineLayout::build()
+        .with_push_constants::<DepthPushConstants>(vk::SHADER_STAGE_VERTEX_BIT)
+        .create(device, limits)
+        .expect("Unable to create shadow pipeline layout");
+
+    // Double sided materials cast shadows from both faces, so nothing is
+    // culled.
+    Pipeline::build()
+        .with_shader_stage(Shader::load(
+            device,
+            SHADOW_VERT,
+            pipeline::ShaderType::Vertex,
+        )).with_vertex_input_state(mesh::Vertex::vertex_input())
+        .with_input_assembly_state(pipeline::InputAssembly::triangle_list())
+        .with_dynamic_state(pipeline::DynamicState::viewport_and_scissor())
+        .with_rasterizer(pipeline::Rasterizer::no_cull().with_depth_bias(ORTHOGRAPHIC_DEPTH_BIAS))
+        .with_multisample(pipeline::Multisample::disabled())
+        .with_color_blend(pipeline::ColorBlend::new(&[]))
+        .with_depth_stencil(pipeline::DepthStencil::less())
+        .with_variant(
+            PipelineVariant::new().with_rasterizer(
+                pipeline::Rasterizer::no_cull().with_depth_bias(PERSPECTIVE_DEPTH_BIAS),
+            ),
+        ).with_layout(layout)
+        .with_render_pass(render_pass)
+        .create(device)
+        .expect("Unable to create shadow pipeline")
+}
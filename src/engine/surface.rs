@@ -1,15 +1,20 @@
 use ash::extensions::Surface;
-use ash::version::{EntryV1_0, InstanceV1_0};
+use ash::version::{EntryV1_0, InstanceV1_0, V1_0};
 use ash::vk;
+use ash::{Entry, Instance};
+use std::ffi::CString;
+use std::mem;
 use std::ptr;
+use std::str::FromStr;
 use winit::Window;
 
 #[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
 pub fn create_surface<E: EntryV1_0, I: InstanceV1_0>(
     entry: &E,
     instance: &I,
-    window: &winit::Window,
+    window: &Window,
 ) -> Result<vk::SurfaceKHR, vk::Result> {
+    use ash::extensions::XlibSurface;
     use winit::os::unix::WindowExt;
     let x11_display = window.get_xlib_display().unwrap();
     let x11_window = window.get_xlib_window().unwrap();
@@ -102,34 +107,247 @@ pub fn create_surface<E: EntryV1_0, I: InstanceV1_0>(
     }
 }
 
-/// Fetch the optimal surface format from the present queue
-pub fn select_surface_format(
-    present_queue: vk::Queue,
+/// Name of the instance extension that adds the HDR color spaces.
+pub const SWAPCHAIN_COLORSPACE_EXTENSION: &str = "VK_EXT_swapchain_colorspace";
+
+/// A swapchain color space.
+///
+/// ash only knows about sRGB, so the ones from `VK_EXT_swapchain_colorspace`
+/// are kept as their raw values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSpace {
+    /// `VK_COLOR_SPACE_SRGB_NONLINEAR_KHR`, what every surface supports.
+    SrgbNonlinear,
+    /// `VK_COLOR_SPACE_EXTENDED_SRGB_LINEAR_EXT`, scRGB: linear sRGB
+    /// primaries with values outside of 0 to 1 for a wider gamut and range.
+    ExtendedSrgbLinear,
+    /// `VK_COLOR_SPACE_HDR10_ST2084_EXT`, BT.2020 primaries with the PQ
+    /// transfer function.
+    Hdr10St2084,
+    Other(i32),
+}
+
+const SRGB_NONLINEAR: i32 = 0;
+const EXTENDED_SRGB_LINEAR: i32 = 1000104002;
+const HDR10_ST2084: i32 = 1000104008;
+
+impl ColorSpace {
+    pub fn from_raw(raw: i32) -> Self {
+        match raw {
+            SRGB_NONLINEAR => ColorSpace::SrgbNonlinear,
+            EXTENDED_SRGB_LINEAR => ColorSpace::ExtendedSrgbLinear,
+            HDR10_ST2084 => ColorSpace::Hdr10St2084,
+            other => ColorSpace::Other(other),
+        }
+    }
+
+    pub fn as_raw(&self) -> i32 {
+        match *self {
+            ColorSpace::SrgbNonlinear => SRGB_NONLINEAR,
+            ColorSpace::ExtendedSrgbLinear => EXTENDED_SRGB_LINEAR,
+            ColorSpace::Hdr10St2084 => HDR10_ST2084,
+            ColorSpace::Other(raw) => raw,
+        }
+    }
+
+    pub fn is_hdr(&self) -> bool {
+        matches!(*self, ColorSpace::ExtendedSrgbLinear | ColorSpace::Hdr10St2084)
+    }
+}
+
+/// The format and color space of the swapchain images.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SurfaceFormat {
+    pub format: vk::Format,
+    pub color_space: ColorSpace,
+}
+
+/// Layout of `VkSurfaceFormatKHR` with raw values, the driver may report
+/// formats and color spaces ash has no variants for.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct RawSurfaceFormat {
+    format: i32,
+    color_space: i32,
+}
+
+/// Swapchain formats read back from the driver, others are skipped.
+const KNOWN_FORMATS: &[vk::Format] = &[
+    vk::Format::Undefined,
+    vk::Format::B8g8r8a8Unorm,
+    vk::Format::B8g8r8a8Srgb,
+    vk::Format::R8g8b8a8Unorm,
+    vk::Format::R8g8b8a8Srgb,
+    vk::Format::A8b8g8r8UnormPack32,
+    vk::Format::A8b8g8r8SrgbPack32,
+    vk::Format::A2b10g10r10UnormPack32,
+    vk::Format::A2r10g10b10UnormPack32,
+    vk::Format::R16g16b16a16Sfloat,
+];
+
+fn format_from_raw(raw: i32) -> Option<vk::Format> {
+    KNOWN_FORMATS
+        .iter()
+        .cloned()
+        .find(|&format| format as i32 == raw)
+}
+
+type GetPhysicalDeviceSurfaceFormats = unsafe extern "system" fn(
+    vk::PhysicalDevice,
+    vk::SurfaceKHR,
+    *mut u32,
+    *mut RawSurfaceFormat,
+) -> vk::Result;
+
+/// Which color space to prefer for the swapchain.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum ColorSpacePreference {
+    /// 8 bit sRGB.
+    #[serde(rename = "srgb")]
+    Srgb,
+    /// 10 bit HDR10, the shaders have to output PQ encoded BT.2020.
+    #[serde(rename = "hdr10")]
+    Hdr10,
+    /// 16 bit float scRGB, the shaders have to output linear values, with
+    /// 1.0 as SDR white.
+    #[serde(rename = "scrgb")]
+    ScRgb,
+}
+
+impl ColorSpacePreference {
+    /// Whether `VK_EXT_swapchain_colorspace` is needed for this preference.
+    pub fn is_hdr(&self) -> bool {
+        *self != ColorSpacePreference::Srgb
+    }
+
+    /// Formats in order of preference.
+    fn ranking(&self) -> &'static [(vk::Format, ColorSpace)] {
+        match *self {
+            ColorSpacePreference::Srgb => SRGB_FORMATS,
+            ColorSpacePreference::Hdr10 => &[
+                (vk::Format::A2b10g10r10UnormPack32, ColorSpace::Hdr10St2084),
+                (vk::Format::A2r10g10b10UnormPack32, ColorSpace::Hdr10St2084),
+                (vk::Format::R16g16b16a16Sfloat, ColorSpace::Hdr10St2084),
+            ],
+            ColorSpacePreference::ScRgb => &[(
+                vk::Format::R16g16b16a16Sfloat,
+                ColorSpace::ExtendedSrgbLinear,
+            )],
+        }
+    }
+}
+
+impl FromStr for ColorSpacePreference {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "srgb" => Ok(ColorSpacePreference::Srgb),
+            "hdr10" => Ok(ColorSpacePreference::Hdr10),
+            "scrgb" => Ok(ColorSpacePreference::ScRgb),
+            _ => Err(()),
+        }
+    }
+}
+
+/// sRGB formats, so the hardware does the gamma encoding of linear shader
+/// output.
+const SRGB_FORMATS: &[(vk::Format, ColorSpace)] = &[
+    (vk::Format::B8g8r8a8Srgb, ColorSpace::SrgbNonlinear),
+    (vk::Format::R8g8b8a8Srgb, ColorSpace::SrgbNonlinear),
+    (vk::Format::A8b8g8r8SrgbPack32, ColorSpace::SrgbNonlinear),
+];
+
+/// The surface formats with a format in `KNOWN_FORMATS`.
+///
+/// `vkGetPhysicalDeviceSurfaceFormatsKHR` is loaded by hand, ash would
+/// read formats and color spaces it does not know into its enums.
+pub fn surface_formats(
+    entry: &Entry<V1_0>,
+    instance: &Instance<V1_0>,
     pdevice: vk::PhysicalDevice,
-    surface_loader: &Surface,
     surface: vk::SurfaceKHR,
-) -> Result<vk::SurfaceFormatKHR, ()> {
-    // Fetch the surface formats
-    let surface_formats = surface_loader
-        .get_physical_device_surface_formats_khr(pdevice, surface)
-        .unwrap();
+) -> Result<Vec<SurfaceFormat>, vk::Result> {
+    let name = CString::new("vkGetPhysicalDeviceSurfaceFormatsKHR").unwrap();
+
+    unsafe {
+        let get_formats: Option<GetPhysicalDeviceSurfaceFormats> =
+            mem::transmute(entry.get_instance_proc_addr(instance.handle(), name.as_ptr()));
+        let get_formats = get_formats.ok_or(vk::Result::ErrorExtensionNotPresent)?;
+
+        let mut count = 0;
+        match get_formats(pdevice, surface, &mut count, ptr::null_mut()) {
+            vk::Result::Success => {}
+            error => return Err(error),
+        }
+
+        let mut formats = Vec::with_capacity(count as usize);
+        match get_formats(pdevice, surface, &mut count, formats.as_mut_ptr()) {
+            vk::Result::Success | vk::Result::Incomplete => formats.set_len(count as usize),
+            error => return Err(error),
+        }
 
-    let surface_format = surface_formats
+        Ok(formats
+            .iter()
+            .filter_map(|raw: &RawSurfaceFormat| {
+                format_from_raw(raw.format).map(|format| SurfaceFormat {
+                    format,
+                    color_space: ColorSpace::from_raw(raw.color_space),
+                })
+            }).collect())
+    }
+}
+
+/// Pick the best of `formats` for `preference`.
+///
+/// Falls back to an sRGB format when the preferred color space is not
+/// available, then to any format in the sRGB color space, then to the first
+/// one.
+pub fn select_surface_format(
+    formats: &[SurfaceFormat],
+    preference: ColorSpacePreference,
+) -> Result<SurfaceFormat, vk::Result> {
+    // A single undefined format means the surface takes any format.
+    if formats.len() == 1 && formats[0].format == vk::Format::Undefined {
+        let (format, color_space) = preference.ranking()[0];
+        return Ok(SurfaceFormat {
+            format,
+            color_space,
+        });
+    }
+
+    let ranking = preference
+        .ranking()
         .iter()
-        .map(|sfmt| match sfmt.format {
-            vk::Format::Undefined => vk::SurfaceFormatKHR {
-                format: vk::Format::B8g8r8Unorm,
-                color_space: sfmt.color_space,
-            },
-            _ => sfmt.clone(),
-        }).nth(0)
-        .expect("Unable to find suitable surface format.");
+        .chain(SRGB_FORMATS.iter())
+        .collect::<Vec<_>>();
+
+    let ranked = ranking.iter().filter_map(|&&(format, color_space)| {
+        formats
+            .iter()
+            .find(|candidate| candidate.format == format && candidate.color_space == color_space)
+    });
 
-    Ok(surface_format)
+    ranked
+        .chain(
+            formats
+                .iter()
+                .filter(|candidate| candidate.color_space == ColorSpace::SrgbNonlinear),
+        ).chain(formats.iter())
+        .cloned()
+        .next()
+        .ok_or(vk::Result::ErrorFormatNotSupported)
 }
 
-pub fn select_desired_image_count(surface_capabilities: &vk::SurfaceCapabilitiesKHR) -> u32 {
-    let mut desired_image_count = surface_capabilities.min_image_count + 1;
+/// One more image than the surface minimum, or `preferred` when given,
+/// within the limits of the surface.
+pub fn select_desired_image_count(
+    surface_capabilities: &vk::SurfaceCapabilitiesKHR,
+    preferred: Option<u32>,
+) -> u32 {
+    let mut desired_image_count = preferred
+        .unwrap_or(surface_capabilities.min_image_count + 1)
+        .max(surface_capabilities.min_image_count);
     if surface_capabilities.max_image_count > 0
         && desired_image_count > surface_capabilities.max_image_count
     {
@@ -139,21 +357,253 @@ pub fn select_desired_image_count(surface_capabilities: &vk::SurfaceCapabilities
     desired_image_count
 }
 
+/// Use the extent the surface reports, or the window size when the surface
+/// lets the swapchain decide.
+pub fn select_surface_extent(
+    surface_capabilities: &vk::SurfaceCapabilitiesKHR,
+    width: u32,
+    height: u32,
+) -> vk::Extent2D {
+    match surface_capabilities.current_extent.width {
+        u32::MAX => vk::Extent2D {
+            width: width
+                .max(surface_capabilities.min_image_extent.width)
+                .min(surface_capabilities.max_image_extent.width),
+            height: height
+                .max(surface_capabilities.min_image_extent.height)
+                .min(surface_capabilities.max_image_extent.height),
+        },
+        _ => surface_capabilities.current_extent,
+    }
+}
+
+/// Prefer the identity transform when the surface supports it.
+pub fn select_pre_transform(
+    surface_capabilities: &vk::SurfaceCapabilitiesKHR,
+) -> vk::SurfaceTransformFlagsKHR {
+    if surface_capabilities
+        .supported_transforms
+        .subset(vk::SURFACE_TRANSFORM_IDENTITY_BIT_KHR)
+    {
+        vk::SURFACE_TRANSFORM_IDENTITY_BIT_KHR
+    } else {
+        surface_capabilities.current_transform
+    }
+}
+
+/// Which present mode to ask for, each policy falls back to FIFO, which
+/// every surface supports.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum PresentModePolicy {
+    /// Wait for the vertical blank, never tears.
+    #[serde(rename = "on")]
+    Vsync,
+    /// Wait for the vertical blank unless a frame is late, late frames tear
+    /// instead of waiting for the next one.
+    #[serde(rename = "adaptive")]
+    Adaptive,
+    /// Replace the queued image with newer ones, never tears and does not
+    /// hold rendering back to the refresh rate.
+    #[serde(rename = "mailbox")]
+    LowLatency,
+    /// Present right away, tearing is possible.
+    #[serde(rename = "off")]
+    Uncapped,
+}
+
+impl PresentModePolicy {
+    /// Present modes to try, in order of preference.
+    pub fn fallbacks(&self) -> &'static [vk::PresentModeKHR] {
+        match *self {
+            PresentModePolicy::Vsync => &[vk::PresentModeKHR::Fifo],
+            PresentModePolicy::Adaptive => {
+                &[vk::PresentModeKHR::FifoRelaxed, vk::PresentModeKHR::Fifo]
+            }
+            PresentModePolicy::LowLatency => {
+                &[vk::PresentModeKHR::Mailbox, vk::PresentModeKHR::Fifo]
+            }
+            PresentModePolicy::Uncapped => &[
+                vk::PresentModeKHR::Immediate,
+                vk::PresentModeKHR::Mailbox,
+                vk::PresentModeKHR::Fifo,
+            ],
+        }
+    }
+
+    /// The policy after this one, for cycling through all of them.
+    pub fn next(&self) -> Self {
+        match *self {
+            PresentModePolicy::Vsync => PresentModePolicy::Adaptive,
+            PresentModePolicy::Adaptive => PresentModePolicy::LowLatency,
+            PresentModePolicy::LowLatency => PresentModePolicy::Uncapped,
+            PresentModePolicy::Uncapped => PresentModePolicy::Vsync,
+        }
+    }
+}
+
+impl FromStr for PresentModePolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "on" => Ok(PresentModePolicy::Vsync),
+            "adaptive" => Ok(PresentModePolicy::Adaptive),
+            "mailbox" => Ok(PresentModePolicy::LowLatency),
+            "off" => Ok(PresentModePolicy::Uncapped),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The first present mode of `policy` the surface supports.
 pub fn select_present_mode(
     surface_loader: &Surface,
     pdevice: vk::PhysicalDevice,
     surface: vk::SurfaceKHR,
-) -> Result<vk::PresentModeKHR, ()> {
+    policy: PresentModePolicy,
+) -> Result<vk::PresentModeKHR, vk::Result> {
     // Get the physical devices surface preset
     let present_modes = surface_loader
-        .get_physical_device_surface_present_modes_khr(pdevice, surface)
-        .unwrap();
+        .get_physical_device_surface_present_modes_khr(pdevice, surface)?;
 
-    let present_mode = present_modes
+    let present_mode = policy
+        .fallbacks()
         .iter()
         .cloned()
-        .find(|&mode| mode == vk::PresentModeKHR::Mailbox)
+        .find(|mode| present_modes.contains(mode))
         .unwrap_or(vk::PresentModeKHR::Fifo);
 
     Ok(present_mode)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNORM: vk::Format = vk::Format::B8g8r8a8Unorm;
+    const SRGB: vk::Format = vk::Format::B8g8r8a8Srgb;
+    const RGBA_SRGB: vk::Format = vk::Format::R8g8b8a8Srgb;
+    const A2B10: vk::Format = vk::Format::A2b10g10r10UnormPack32;
+    const A2R10: vk::Format = vk::Format::A2r10g10b10UnormPack32;
+    const F16: vk::Format = vk::Format::R16g16b16a16Sfloat;
+
+    fn formats(formats: &[(vk::Format, ColorSpace)]) -> Vec<SurfaceFormat> {
+        formats
+            .iter()
+            .map(|&(format, color_space)| SurfaceFormat {
+                format,
+                color_space,
+            }).collect()
+    }
+
+    #[test]
+    fn select_surface_formats() {
+        use self::ColorSpace::*;
+        use self::ColorSpacePreference::*;
+
+        type Case = (
+            &'static [(vk::Format, ColorSpace)],
+            ColorSpacePreference,
+            (vk::Format, ColorSpace),
+        );
+
+        let table: &[Case] = &[
+            // Any format goes, the first of the ranking is taken.
+            (
+                &[(vk::Format::Undefined, SrgbNonlinear)],
+                Srgb,
+                (SRGB, SrgbNonlinear),
+            ),
+            (
+                &[(vk::Format::Undefined, SrgbNonlinear)],
+                Hdr10,
+                (A2B10, Hdr10St2084),
+            ),
+            // sRGB formats beat UNORM ones in the same color space.
+            (
+                &[(UNORM, SrgbNonlinear), (SRGB, SrgbNonlinear)],
+                Srgb,
+                (SRGB, SrgbNonlinear),
+            ),
+            (
+                &[(UNORM, SrgbNonlinear), (RGBA_SRGB, SrgbNonlinear)],
+                Srgb,
+                (RGBA_SRGB, SrgbNonlinear),
+            ),
+            // HDR is only picked when asked for.
+            (
+                &[(F16, ExtendedSrgbLinear), (SRGB, SrgbNonlinear)],
+                Srgb,
+                (SRGB, SrgbNonlinear),
+            ),
+            (
+                &[(SRGB, SrgbNonlinear), (F16, ExtendedSrgbLinear)],
+                ScRgb,
+                (F16, ExtendedSrgbLinear),
+            ),
+            (
+                &[
+                    (SRGB, SrgbNonlinear),
+                    (A2R10, Hdr10St2084),
+                    (A2B10, Hdr10St2084),
+                ],
+                Hdr10,
+                (A2B10, Hdr10St2084),
+            ),
+            (
+                &[(SRGB, SrgbNonlinear), (A2R10, Hdr10St2084)],
+                Hdr10,
+                (A2R10, Hdr10St2084),
+            ),
+            // HDR10 does not settle for scRGB, it falls back to sRGB.
+            (
+                &[(F16, ExtendedSrgbLinear), (SRGB, SrgbNonlinear)],
+                Hdr10,
+                (SRGB, SrgbNonlinear),
+            ),
+            // Then any format in the sRGB color space, then the first one.
+            (
+                &[(F16, ExtendedSrgbLinear), (UNORM, SrgbNonlinear)],
+                ScRgb,
+                (F16, ExtendedSrgbLinear),
+            ),
+            (
+                &[(F16, Other(7)), (UNORM, SrgbNonlinear)],
+                Hdr10,
+                (UNORM, SrgbNonlinear),
+            ),
+            (&[(F16, Other(7)), (UNORM, Other(8))], Srgb, (F16, Other(7))),
+        ];
+
+        for &(available, preference, (format, color_space)) in table {
+            assert_eq!(
+                select_surface_format(&formats(available), preference),
+                Ok(SurfaceFormat {
+                    format,
+                    color_space,
+                }),
+                "{:?} from {:?}",
+                preference,
+                available
+            );
+        }
+    }
+
+    #[test]
+    fn select_surface_format_needs_a_format() {
+        assert_eq!(
+            select_surface_format(&[], ColorSpacePreference::Srgb),
+            Err(vk::Result::ErrorFormatNotSupported)
+        );
+    }
+
+    #[test]
+    fn color_spaces_round_trip() {
+        for &raw in &[SRGB_NONLINEAR, EXTENDED_SRGB_LINEAR, HDR10_ST2084, 42] {
+            assert_eq!(ColorSpace::from_raw(raw).as_raw(), raw);
+        }
+        assert_eq!(ColorSpace::from_raw(42), ColorSpace::Other(42));
+        assert_eq!(format_from_raw(F16 as i32), Some(F16));
+        assert_eq!(format_from_raw(-1), None);
+    }
+}
@@ -1,42 +1,110 @@
-use ash::extensions::Swapchain;
+use ash::version::{EntryV1_0, V1_0};
 use ash::vk;
+use ash::{Device, Entry, Instance};
+use std::ffi::CString;
+use std::mem;
 use std::ptr;
 
+use super::full_screen_exclusive::{FullScreenExclusive, SurfaceFullScreenExclusiveInfo};
+use super::surface::SurfaceFormat;
+
+/// Layout of `VkSwapchainCreateInfoKHR` with the color space as its raw
+/// value, ash has no variants for the ones from `VK_EXT_swapchain_colorspace`.
+#[repr(C)]
+pub struct RawSwapchainCreateInfo {
+    s_type: vk::StructureType,
+    p_next: *const vk::c_void,
+    flags: vk::SwapchainCreateFlagsKHR,
+    surface: vk::SurfaceKHR,
+    min_image_count: u32,
+    image_format: vk::Format,
+    image_color_space: i32,
+    image_extent: vk::Extent2D,
+    image_array_layers: u32,
+    image_usage: vk::ImageUsageFlags,
+    image_sharing_mode: vk::SharingMode,
+    queue_family_index_count: u32,
+    p_queue_family_indices: *const u32,
+    pre_transform: vk::SurfaceTransformFlagsKHR,
+    composite_alpha: vk::CompositeAlphaFlagsKHR,
+    present_mode: vk::PresentModeKHR,
+    clipped: vk::Bool32,
+    old_swapchain: vk::SwapchainKHR,
+}
+
+pub type CreateSwapchain = unsafe extern "system" fn(
+    vk::Device,
+    *const RawSwapchainCreateInfo,
+    *const vk::AllocationCallbacks,
+    *mut vk::SwapchainKHR,
+) -> vk::Result;
+
+/// Load `vkCreateSwapchainKHR` by hand, ash's version only takes its own
+/// create info.
+pub fn load_create_swapchain(
+    entry: &Entry<V1_0>,
+    instance: &Instance<V1_0>,
+) -> Result<CreateSwapchain, vk::Result> {
+    let name = CString::new("vkCreateSwapchainKHR").unwrap();
+
+    unsafe {
+        let create_swapchain: Option<CreateSwapchain> =
+            mem::transmute(entry.get_instance_proc_addr(instance.handle(), name.as_ptr()));
+        create_swapchain.ok_or(vk::Result::ErrorExtensionNotPresent)
+    }
+}
+
+/// `full_screen_exclusive` is only passed on when the device has
+/// `VK_EXT_full_screen_exclusive` enabled.
 pub fn create_swapchain(
-    swapchain_loader: &Swapchain,
+    create_swapchain: CreateSwapchain,
+    device: &Device<V1_0>,
     surface: vk::SurfaceKHR,
     desired_image_count: u32,
-    surface_format: &vk::SurfaceFormatKHR,
+    surface_format: &SurfaceFormat,
     surface_resolution: vk::Extent2D,
     pre_transform: vk::SurfaceTransformFlagsKHR,
     present_mode: vk::PresentModeKHR,
-) -> Result<vk::SwapchainKHR, ()> {
-    let swapchain_create_info = vk::SwapchainCreateInfoKHR {
+    old_swapchain: vk::SwapchainKHR,
+    full_screen_exclusive: Option<FullScreenExclusive>,
+) -> Result<vk::SwapchainKHR, vk::Result> {
+    let full_screen_exclusive_info =
+        full_screen_exclusive.map(SurfaceFullScreenExclusiveInfo::new);
+
+    let swapchain_create_info = RawSwapchainCreateInfo {
         s_type: vk::StructureType::SwapchainCreateInfoKhr,
-        p_next: ptr::null(),
+        p_next: match full_screen_exclusive_info {
+            Some(ref info) => info as *const SurfaceFullScreenExclusiveInfo as *const vk::c_void,
+            None => ptr::null(),
+        },
         flags: Default::default(),
-        surface: surface,
+        surface,
         min_image_count: desired_image_count,
-        image_color_space: surface_format.color_space,
+        image_color_space: surface_format.color_space.as_raw(),
         image_format: surface_format.format,
-        image_extent: surface_resolution.clone(),
+        image_extent: surface_resolution,
         image_usage: vk::IMAGE_USAGE_COLOR_ATTACHMENT_BIT,
         image_array_layers: 1,
         image_sharing_mode: vk::SharingMode::Exclusive,
         queue_family_index_count: 0,
         p_queue_family_indices: ptr::null(),
-        pre_transform: pre_transform,
+        pre_transform,
         composite_alpha: vk::COMPOSITE_ALPHA_OPAQUE_BIT_KHR,
         present_mode,
         clipped: 1,
-        old_swapchain: vk::SwapchainKHR::null(),
-    };
-
-    let swapchain = unsafe {
-        swapchain_loader
-            .create_swapchain_khr(&swapchain_create_info, None)
-            .unwrap()
+        old_swapchain,
     };
 
-    Ok(swapchain)
+    let mut swapchain = vk::SwapchainKHR::null();
+    match unsafe {
+        create_swapchain(
+            device.handle(),
+            &swapchain_create_info,
+            ptr::null(),
+            &mut swapchain,
+        )
+    } {
+        vk::Result::Success => Ok(swapchain),
+        error => Err(error),
+    }
 }
@@ -0,0 +1,249 @@
+use ash::version::{DeviceV1_0, V1_0};
+use ash::vk;
+use ash::Device;
+use std::ptr;
+
+use super::barrier::{AccessType, Barrier};
+use super::buffer::Buffer;
+use super::command_pool::CommandPool;
+use super::image::Image;
+use super::memory;
+
+/// Filtering and addressing used when sampling a texture.
+#[derive(Clone, Copy, Debug)]
+pub struct SamplerDesc {
+    pub mag_filter: vk::Filter,
+    pub min_filter: vk::Filter,
+    pub address_mode_u: vk::SamplerAddressMode,
+    pub address_mode_v: vk::SamplerAddressMode,
+    /// Highest mip level that is sampled, 0 samples only the first one.
+    pub max_lod: f32,
+    /// Compare texels against a reference instead of returning them, for
+    /// sampling depth images like shadow maps.
+    pub compare_op: Option<vk::CompareOp>,
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        SamplerDesc {
+            mag_filter: vk::Filter::Linear,
+            min_filter: vk::Filter::Linear,
+            address_mode_u: vk::SamplerAddressMode::Repeat,
+            address_mode_v: vk::SamplerAddressMode::Repeat,
+            max_lod: 0.0,
+            compare_op: None,
+        }
+    }
+}
+
+/// A sampled 2D image with a single mip level.
+pub struct Texture {
+    pub image: vk::Image,
+    pub memory: vk::DeviceMemory,
+    pub view: vk::ImageView,
+    pub sampler: vk::Sampler,
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+}
+
+impl Texture {
+    /// Upload tightly packed 8 bit RGBA pixels through a staging buffer.
+    ///
+    /// `format` picks how the bytes are interpreted, usually
+    /// `R8g8b8a8Srgb` for colors and `R8g8b8a8Unorm` for anything else.
+    pub fn from_rgba8(
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        command_pool: &CommandPool,
+        queue: vk::Queue,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        format: vk::Format,
+        sampler_desc: SamplerDesc,
+    ) -> Result<Texture, vk::Result> {
+        assert_eq!(pixels.len(), (width * height * 4) as usize);
+
+        let staging = Buffer::with_data(
+            device,
+            memory_properties,
+            vk::BUFFER_USAGE_TRANSFER_SRC_BIT,
+            pixels,
+        )?;
+
+        let extent = vk::Extent2D { width, height };
+        let image_info = vk::ImageCreateInfo {
+            s_type: vk::StructureType::ImageCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            image_type: vk::ImageType::Type2d,
+            format,
+            extent: vk::Extent3D {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_levels: 1,
+            array_layers: 1,
+            samples: vk::SAMPLE_COUNT_1_BIT,
+            tiling: vk::ImageTiling::Optimal,
+            usage: vk::IMAGE_USAGE_TRANSFER_DST_BIT | vk::IMAGE_USAGE_SAMPLED_BIT,
+            sharing_mode: vk::SharingMode::Exclusive,
+            queue_family_index_count: 0,
+            p_queue_family_indices: ptr::null(),
+            initial_layout: vk::ImageLayout::Undefined,
+        };
+
+        let image = unsafe {
+            device
+                .create_image(&image_info, None)
+                .expect("Unable to create image")
+        };
+
+        let memory_requirements = device.get_image_memory_requirements(image);
+        let memory_type_index = match memory::find_memory_type_index(
+            &memory_requirements,
+            memory_properties,
+            vk::MEMORY_PROPERTY_DEVICE_LOCAL_BIT,
+        ) {
+            Some(index) => index,
+            None => {
+                unsafe { device.destroy_image(image, None) };
+                staging.destroy(device);
+                return Err(vk::Result::ErrorOutOfDeviceMemory);
+            }
+        };
+
+        let allocate_info = vk::MemoryAllocateInfo {
+            s_type: vk::StructureType::MemoryAllocateInfo,
+            p_next: ptr::null(),
+            allocation_size: memory_requirements.size,
+            memory_type_index,
+        };
+
+        let memory = unsafe {
+            let memory = device
+                .allocate_memory(&allocate_info, None)
+                .expect("Unable to allocate image memory");
+            device
+                .bind_image_memory(image, memory, 0)
+                .expect("Unable to bind image memory");
+            memory
+        };
+
+        let subresource_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::IMAGE_ASPECT_COLOR_BIT,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+
+        let mut tracked = Image::new(image, format, image_info.extent, subresource_range.clone());
+
+        command_pool.one_time_submit(device, queue, |command_buffer| {
+            let region = vk::BufferImageCopy {
+                buffer_offset: 0,
+                buffer_row_length: 0,
+                buffer_image_height: 0,
+                image_subresource: vk::ImageSubresourceLayers {
+                    aspect_mask: vk::IMAGE_ASPECT_COLOR_BIT,
+                    mip_level: 0,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                },
+                image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+                image_extent: image_info.extent,
+            };
+
+            Barrier::new()
+                .with_image(&mut tracked, &[AccessType::TransferDst])
+                .record(device, command_buffer);
+            unsafe {
+                device.cmd_copy_buffer_to_image(
+                    command_buffer,
+                    staging.buffer,
+                    image,
+                    tracked.layout(),
+                    &[region],
+                );
+            }
+            Barrier::new()
+                .with_image(&mut tracked, &[AccessType::FragmentShaderSampledRead])
+                .record(device, command_buffer);
+        });
+
+        staging.destroy(device);
+
+        let view_info = vk::ImageViewCreateInfo {
+            s_type: vk::StructureType::ImageViewCreateInfo,
+            p_next: ptr::null(),
+            flags: Default::default(),
+            view_type: vk::ImageViewType::Type2d,
+            format,
+            components: vk::ComponentMapping {
+                r: vk::ComponentSwizzle::R,
+                g: vk::ComponentSwizzle::G,
+                b: vk::ComponentSwizzle::B,
+                a: vk::ComponentSwizzle::A,
+            },
+            subresource_range,
+            image,
+        };
+
+        let view = unsafe {
+            device
+                .create_image_view(&view_info, None)
+                .expect("Failed to create image view!")
+        };
+
+        let sampler = create_sampler(device, &sampler_desc);
+
+        Ok(Texture {
+            image,
+            memory,
+            view,
+            sampler,
+            format,
+            extent,
+        })
+    }
+
+    pub fn destroy(&self, device: &Device<V1_0>) {
+        unsafe {
+            device.destroy_sampler(self.sampler, None);
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
+
+pub fn create_sampler(device: &Device<V1_0>, sampler_desc: &SamplerDesc) -> vk::Sampler {
+    let sampler_info = vk::SamplerCreateInfo {
+        s_type: vk::StructureType::SamplerCreateInfo,
+        p_next: ptr::null(),
+        flags: Default::default(),
+        mag_filter: sampler_desc.mag_filter,
+        min_filter: sampler_desc.min_filter,
+        mipmap_mode: vk::SamplerMipmapMode::Linear,
+        address_mode_u: sampler_desc.address_mode_u,
+        address_mode_v: sampler_desc.address_mode_v,
+        address_mode_w: vk::SamplerAddressMode::Repeat,
+        mip_lod_bias: 0.0,
+        anisotropy_enable: 0,
+        max_anisotropy: 1.0,
+        compare_enable: sampler_desc.compare_op.is_some() as vk::Bool32,
+        compare_op: sampler_desc.compare_op.unwrap_or(vk::CompareOp::Never),
+        min_lod: 0.0,
+        max_lod: sampler_desc.max_lod,
+        border_color: vk::BorderColor::FloatOpaqueWhite,
+        unnormalized_coordinates: 0,
+    };
+
+    unsafe {
+        device
+            .create_sampler(&sampler_info, None)
+            .expect("Unable to create sampler")
+    }
+}
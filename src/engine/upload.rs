@@ -0,0 +1,847 @@
+use ash::version::{DeviceV1_0, V1_0};
+use ash::vk;
+use ash::Device;
+use std::collections::VecDeque;
+use std::mem;
+use std::ptr;
+use std::slice;
+
+use super::buffer::Buffer;
+
+/// Size of the staging ring the runner creates.
+pub const DEFAULT_STAGING_SIZE: vk::DeviceSize = 16 * 1024 * 1024;
+
+/// Staging offsets are aligned to this, which covers the texel size of every
+/// uncompressed format and the 4 bytes buffer to image copies need.
+const STAGING_ALIGNMENT: vk::DeviceSize = 16;
+
+/// A queue and the family it is from.
+#[derive(Clone, Copy, Debug)]
+pub struct Queue {
+    pub queue: vk::Queue,
+    pub family_index: u32,
+}
+
+/// Identifies a batch of uploads, later batches have larger tickets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UploadTicket(u64);
+
+/// Where an upload ends up being used, the copy is made visible to these
+/// stages and accesses.
+#[derive(Clone, Copy, Debug)]
+pub struct UploadDst {
+    pub stage: vk::PipelineStageFlags,
+    pub access: vk::AccessFlags,
+}
+
+impl UploadDst {
+    pub fn vertex_input() -> Self {
+        UploadDst {
+            stage: vk::PIPELINE_STAGE_VERTEX_INPUT_BIT,
+            access: vk::ACCESS_VERTEX_ATTRIBUTE_READ_BIT | vk::ACCESS_INDEX_READ_BIT,
+        }
+    }
+
+    pub fn uniform_read() -> Self {
+        UploadDst {
+            stage: vk::PIPELINE_STAGE_VERTEX_SHADER_BIT | vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT,
+            access: vk::ACCESS_UNIFORM_READ_BIT,
+        }
+    }
+
+    pub fn fragment_shader_read() -> Self {
+        UploadDst {
+            stage: vk::PIPELINE_STAGE_FRAGMENT_SHADER_BIT,
+            access: vk::ACCESS_SHADER_READ_BIT,
+        }
+    }
+}
+
+/// Hands out offsets into a fixed size buffer in order, and takes them back
+/// in the same order.
+///
+/// `head` and `tail` count every byte ever handed out, the offset is where
+/// they point to in the buffer.
+struct RingAllocator {
+    capacity: vk::DeviceSize,
+    head: vk::DeviceSize,
+    tail: vk::DeviceSize,
+}
+
+impl RingAllocator {
+    fn new(capacity: vk::DeviceSize) -> Self {
+        RingAllocator {
+            capacity,
+            head: 0,
+            tail: 0,
+        }
+    }
+
+    /// The offset of `size` free bytes, skipping the end of the buffer when
+    /// they would not fit in before it.
+    fn allocate(
+        &mut self,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+    ) -> Option<vk::DeviceSize> {
+        // Nothing is in flight, start over so the whole buffer is free.
+        if self.head == self.tail {
+            self.head = 0;
+            self.tail = 0;
+        }
+
+        let offset = self.head % self.capacity;
+        let mut aligned = offset.div_ceil(alignment) * alignment;
+        if aligned + size > self.capacity {
+            aligned = self.capacity;
+        }
+
+        let padding = aligned - offset;
+        if self.head + padding + size - self.tail > self.capacity {
+            return None;
+        }
+
+        self.head += padding + size;
+        Some(aligned % self.capacity)
+    }
+
+    /// Free everything handed out before `head` was at `end`.
+    fn free_until(&mut self, end: vk::DeviceSize) {
+        self.tail = end;
+    }
+}
+
+/// The command buffers of the batch that is still being recorded.
+struct Recording {
+    /// Copies, on the transfer queue if there is one.
+    transfer: vk::CommandBuffer,
+    /// Acquires what the transfer queue released on the graphics queue,
+    /// only used with a transfer queue.
+    acquire: Option<vk::CommandBuffer>,
+    /// Staging buffers for uploads larger than the ring.
+    temporaries: Vec<Buffer>,
+}
+
+/// A submitted batch.
+struct Batch {
+    ticket: UploadTicket,
+    fence: vk::Fence,
+    /// Orders the acquire after the copies on the transfer queue.
+    semaphore: Option<vk::Semaphore>,
+    transfer: vk::CommandBuffer,
+    acquire: Option<vk::CommandBuffer>,
+    ring_end: vk::DeviceSize,
+    temporaries: Vec<Buffer>,
+}
+
+/// Copies data into device local buffers and images through a staging ring.
+///
+/// Copies are recorded into a batch that is submitted with `flush`, on the
+/// transfer queue when the device has one. Resources written there are
+/// released to the graphics queue and acquired there before the batch
+/// completes, so they are ready for the graphics queue afterwards.
+///
+/// On devices where all memory is device local, buffers are created host
+/// visible and written directly instead.
+pub struct Uploader {
+    staging: Buffer,
+    mapped: *mut u8,
+    ring: RingAllocator,
+    graphics: Queue,
+    transfer: Option<Queue>,
+    /// Pool for the copies, from the transfer family if there is one.
+    transfer_pool: vk::CommandPool,
+    /// Pool for the acquire barriers, with a transfer queue.
+    graphics_pool: Option<vk::CommandPool>,
+    unified_memory: bool,
+    recording: Option<Recording>,
+    in_flight: VecDeque<Batch>,
+    next_ticket: u64,
+}
+
+impl Uploader {
+    /// `transfer` should be a queue from another family than `graphics`,
+    /// without one everything is copied on the graphics queue.
+    pub fn new(
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        graphics: Queue,
+        transfer: Option<Queue>,
+        staging_size: vk::DeviceSize,
+    ) -> Result<Uploader, vk::Result> {
+        let staging = Buffer::new(
+            device,
+            memory_properties,
+            staging_size,
+            vk::BUFFER_USAGE_TRANSFER_SRC_BIT,
+            vk::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vk::MEMORY_PROPERTY_HOST_COHERENT_BIT,
+        )?;
+
+        // Kept mapped for as long as the uploader lives.
+        let mapped = unsafe {
+            device.map_memory(staging.memory, 0, staging_size, vk::MemoryMapFlags::empty())?
+        } as *mut u8;
+
+        let transfer_pool = create_command_pool(
+            device,
+            transfer.unwrap_or(graphics).family_index,
+        );
+        let graphics_pool = transfer.map(|_| create_command_pool(device, graphics.family_index));
+
+        Ok(Uploader {
+            staging,
+            mapped,
+            ring: RingAllocator::new(staging_size),
+            graphics,
+            transfer,
+            transfer_pool,
+            graphics_pool,
+            unified_memory: is_unified_memory(memory_properties),
+            recording: None,
+            in_flight: VecDeque::new(),
+            next_ticket: 1,
+        })
+    }
+
+    /// Whether all memory of the device is device local, like on integrated
+    /// GPUs.
+    pub fn is_unified_memory(&self) -> bool {
+        self.unified_memory
+    }
+
+    /// Create a device local buffer holding a copy of `data`.
+    ///
+    /// With unified memory the buffer is host visible and written right
+    /// away, otherwise it is ready once the current batch is.
+    pub fn create_buffer<T: Copy>(
+        &mut self,
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        usage: vk::BufferUsageFlags,
+        data: &[T],
+        dst: UploadDst,
+    ) -> Result<Buffer, vk::Result> {
+        let size = mem::size_of_val(data) as vk::DeviceSize;
+
+        if self.unified_memory {
+            if let Ok(buffer) = Buffer::new(
+                device,
+                memory_properties,
+                size,
+                usage,
+                vk::MEMORY_PROPERTY_DEVICE_LOCAL_BIT
+                    | vk::MEMORY_PROPERTY_HOST_VISIBLE_BIT
+                    | vk::MEMORY_PROPERTY_HOST_COHERENT_BIT,
+            ) {
+                buffer.write(device, 0, data);
+                return Ok(buffer);
+            }
+        }
+
+        let buffer = Buffer::new(
+            device,
+            memory_properties,
+            size,
+            usage | vk::BUFFER_USAGE_TRANSFER_DST_BIT,
+            vk::MEMORY_PROPERTY_DEVICE_LOCAL_BIT,
+        )?;
+        self.upload_buffer(device, memory_properties, &buffer, 0, data, dst)?;
+
+        Ok(buffer)
+    }
+
+    /// Copy `data` into `buffer` at `offset`, the buffer needs
+    /// `BUFFER_USAGE_TRANSFER_DST_BIT`.
+    pub fn upload_buffer<T: Copy>(
+        &mut self,
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        buffer: &Buffer,
+        offset: vk::DeviceSize,
+        data: &[T],
+        dst: UploadDst,
+    ) -> Result<(), vk::Result> {
+        let size = mem::size_of_val(data) as vk::DeviceSize;
+        assert!(offset + size <= buffer.size, "upload past the end of the buffer");
+        if size == 0 {
+            return Ok(());
+        }
+
+        let bytes = unsafe { slice::from_raw_parts(data.as_ptr() as *const u8, size as usize) };
+        let (staging, staging_offset) = self.stage(device, memory_properties, bytes)?;
+
+        let (src_family, dst_family) = self.ownership_transfer();
+        let release = vk::BufferMemoryBarrier {
+            s_type: vk::StructureType::BufferMemoryBarrier,
+            p_next: ptr::null(),
+            src_access_mask: vk::ACCESS_TRANSFER_WRITE_BIT,
+            dst_access_mask: if self.transfer.is_some() {
+                vk::AccessFlags::empty()
+            } else {
+                dst.access
+            },
+            src_queue_family_index: src_family,
+            dst_queue_family_index: dst_family,
+            buffer: buffer.buffer,
+            offset,
+            size,
+        };
+        let acquire = vk::BufferMemoryBarrier {
+            src_access_mask: vk::AccessFlags::empty(),
+            dst_access_mask: dst.access,
+            ..release
+        };
+
+        let recording = self.recording(device);
+        unsafe {
+            device.cmd_copy_buffer(
+                recording.transfer,
+                staging,
+                buffer.buffer,
+                &[vk::BufferCopy {
+                    src_offset: staging_offset,
+                    dst_offset: offset,
+                    size,
+                }],
+            );
+        }
+        record_barriers(device, recording, dst, &[release], &[acquire], &[], &[]);
+
+        Ok(())
+    }
+
+    /// Copy tightly packed texels into `subresource` of `image` and move it
+    /// to `final_layout`. The image needs `IMAGE_USAGE_TRANSFER_DST_BIT`.
+    ///
+    /// The previous contents of the subresource are discarded.
+    pub fn upload_image(
+        &mut self,
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        image: vk::Image,
+        subresource: vk::ImageSubresourceLayers,
+        extent: vk::Extent3D,
+        data: &[u8],
+        final_layout: vk::ImageLayout,
+        dst: UploadDst,
+    ) -> Result<(), vk::Result> {
+        let (staging, staging_offset) = self.stage(device, memory_properties, data)?;
+
+        let subresource_range = vk::ImageSubresourceRange {
+            aspect_mask: subresource.aspect_mask,
+            base_mip_level: subresource.mip_level,
+            level_count: 1,
+            base_array_layer: subresource.base_array_layer,
+            layer_count: subresource.layer_count,
+        };
+
+        let to_transfer = vk::ImageMemoryBarrier {
+            s_type: vk::StructureType::ImageMemoryBarrier,
+            p_next: ptr::null(),
+            src_access_mask: vk::AccessFlags::empty(),
+            dst_access_mask: vk::ACCESS_TRANSFER_WRITE_BIT,
+            old_layout: vk::ImageLayout::Undefined,
+            new_layout: vk::ImageLayout::TransferDstOptimal,
+            src_queue_family_index: vk::VK_QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::VK_QUEUE_FAMILY_IGNORED,
+            image,
+            subresource_range: subresource_range.clone(),
+        };
+
+        // The layout transition happens once, as part of both the release
+        // and the acquire.
+        let (src_family, dst_family) = self.ownership_transfer();
+        let release = vk::ImageMemoryBarrier {
+            src_access_mask: vk::ACCESS_TRANSFER_WRITE_BIT,
+            dst_access_mask: if self.transfer.is_some() {
+                vk::AccessFlags::empty()
+            } else {
+                dst.access
+            },
+            old_layout: vk::ImageLayout::TransferDstOptimal,
+            new_layout: final_layout,
+            src_queue_family_index: src_family,
+            dst_queue_family_index: dst_family,
+            subresource_range: subresource_range.clone(),
+            ..to_transfer
+        };
+        let acquire = vk::ImageMemoryBarrier {
+            src_access_mask: vk::AccessFlags::empty(),
+            dst_access_mask: dst.access,
+            subresource_range,
+            ..release
+        };
+
+        let region = vk::BufferImageCopy {
+            buffer_offset: staging_offset,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: subresource,
+            image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+            image_extent: extent,
+        };
+
+        let recording = self.recording(device);
+        unsafe {
+            device.cmd_pipeline_barrier(
+                recording.transfer,
+                vk::PIPELINE_STAGE_TOP_OF_PIPE_BIT,
+                vk::PIPELINE_STAGE_TRANSFER_BIT,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_transfer],
+            );
+            device.cmd_copy_buffer_to_image(
+                recording.transfer,
+                staging,
+                image,
+                vk::ImageLayout::TransferDstOptimal,
+                &[region],
+            );
+        }
+        record_barriers(device, recording, dst, &[], &[], &[release], &[acquire]);
+
+        Ok(())
+    }
+
+    /// Submit the uploads recorded so far, signaling `signal_semaphores`
+    /// once they are done and ready for the graphics queue.
+    ///
+    /// Without anything recorded the ticket of the last batch is returned,
+    /// and the semaphores are not signaled.
+    pub fn flush(
+        &mut self,
+        device: &Device<V1_0>,
+        signal_semaphores: &[vk::Semaphore],
+    ) -> UploadTicket {
+        let recording = match self.recording.take() {
+            Some(recording) => recording,
+            None => return UploadTicket(self.next_ticket - 1),
+        };
+
+        let ticket = UploadTicket(self.next_ticket);
+        self.next_ticket += 1;
+
+        let fence_info = vk::FenceCreateInfo {
+            s_type: vk::StructureType::FenceCreateInfo,
+            p_next: ptr::null(),
+            flags: vk::FenceCreateFlags::empty(),
+        };
+        let fence = unsafe { device.create_fence(&fence_info, None).unwrap() };
+
+        let semaphore = recording.acquire.map(|_| {
+            let semaphore_info = vk::SemaphoreCreateInfo {
+                s_type: vk::StructureType::SemaphoreCreateInfo,
+                p_next: ptr::null(),
+                flags: Default::default(),
+            };
+            unsafe { device.create_semaphore(&semaphore_info, None).unwrap() }
+        });
+
+        unsafe {
+            device
+                .end_command_buffer(recording.transfer)
+                .expect("Unable to record upload commands");
+
+            match (recording.acquire, semaphore, self.transfer) {
+                (Some(acquire), Some(semaphore), Some(transfer)) => {
+                    device
+                        .end_command_buffer(acquire)
+                        .expect("Unable to record upload commands");
+                    submit(
+                        device,
+                        transfer.queue,
+                        recording.transfer,
+                        &[],
+                        &[semaphore],
+                        vk::Fence::null(),
+                    );
+                    submit(
+                        device,
+                        self.graphics.queue,
+                        acquire,
+                        &[semaphore],
+                        signal_semaphores,
+                        fence,
+                    );
+                }
+                _ => submit(
+                    device,
+                    self.graphics.queue,
+                    recording.transfer,
+                    &[],
+                    signal_semaphores,
+                    fence,
+                ),
+            }
+        }
+
+        self.in_flight.push_back(Batch {
+            ticket,
+            fence,
+            semaphore,
+            transfer: recording.transfer,
+            acquire: recording.acquire,
+            ring_end: self.ring.head,
+            temporaries: recording.temporaries,
+        });
+
+        ticket
+    }
+
+    /// Whether the batch of `ticket` is done.
+    pub fn is_complete(&mut self, device: &Device<V1_0>, ticket: UploadTicket) -> bool {
+        self.collect(device);
+        ticket.0 < self.next_ticket && self
+            .in_flight
+            .front()
+            .is_none_or(|batch| batch.ticket > ticket)
+    }
+
+    /// Block until the batch of `ticket` is done, flushing it first if it is
+    /// still being recorded.
+    pub fn wait(&mut self, device: &Device<V1_0>, ticket: UploadTicket) {
+        if ticket.0 >= self.next_ticket {
+            self.flush(device, &[]);
+        }
+
+        let fences = self
+            .in_flight
+            .iter()
+            .take_while(|batch| batch.ticket <= ticket)
+            .map(|batch| batch.fence)
+            .collect::<Vec<_>>();
+        if !fences.is_empty() {
+            unsafe {
+                device
+                    .wait_for_fences(&fences, true, u64::MAX)
+                    .expect("Unable to wait for uploads");
+            }
+        }
+
+        self.collect(device);
+    }
+
+    /// The ticket the uploads recorded since the last `flush` will get.
+    pub fn pending_ticket(&self) -> UploadTicket {
+        UploadTicket(self.next_ticket)
+    }
+
+    /// Free the staging space and command buffers of finished batches.
+    pub fn collect(&mut self, device: &Device<V1_0>) {
+        while let Some(done) = self.in_flight.front().map(|batch| unsafe {
+            device.get_fence_status(batch.fence).is_ok()
+        }) {
+            if !done {
+                break;
+            }
+
+            let batch = self.in_flight.pop_front().unwrap();
+            self.ring.free_until(batch.ring_end);
+            self.destroy_batch(device, batch);
+        }
+    }
+
+    /// Wait for every batch and destroy the uploader. Uploads that have not
+    /// been flushed are dropped.
+    pub fn destroy(&mut self, device: &Device<V1_0>) {
+        device.device_wait_idle().unwrap();
+        self.collect(device);
+
+        unsafe {
+            if let Some(recording) = self.recording.take() {
+                recording
+                    .temporaries
+                    .iter()
+                    .for_each(|buffer| buffer.destroy(device));
+            }
+
+            device.destroy_command_pool(self.transfer_pool, None);
+            if let Some(graphics_pool) = self.graphics_pool {
+                device.destroy_command_pool(graphics_pool, None);
+            }
+
+            device.unmap_memory(self.staging.memory);
+        }
+        self.staging.destroy(device);
+    }
+
+    /// Copy `data` into staging memory, waiting for earlier batches when the
+    /// ring is full. Data larger than the ring gets a staging buffer of its
+    /// own.
+    fn stage(
+        &mut self,
+        device: &Device<V1_0>,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        data: &[u8],
+    ) -> Result<(vk::Buffer, vk::DeviceSize), vk::Result> {
+        let size = data.len() as vk::DeviceSize;
+
+        if size > self.ring.capacity {
+            let buffer = Buffer::with_data(
+                device,
+                memory_properties,
+                vk::BUFFER_USAGE_TRANSFER_SRC_BIT,
+                data,
+            )?;
+            let handle = buffer.buffer;
+            self.recording(device).temporaries.push(buffer);
+            return Ok((handle, 0));
+        }
+
+        loop {
+            if let Some(offset) = self.ring.allocate(size, STAGING_ALIGNMENT) {
+                unsafe {
+                    ptr::copy_nonoverlapping(
+                        data.as_ptr(),
+                        self.mapped.offset(offset as isize),
+                        data.len(),
+                    );
+                }
+                return Ok((self.staging.buffer, offset));
+            }
+
+            // Out of space, what is recorded so far goes out so its staging
+            // space can be waited on like the rest.
+            if self.recording.is_some() {
+                self.flush(device, &[]);
+            }
+            let oldest = self
+                .in_flight
+                .front()
+                .map(|batch| batch.ticket)
+                .ok_or(vk::Result::ErrorOutOfDeviceMemory)?;
+            self.wait(device, oldest);
+        }
+    }
+
+    /// The batch being recorded, started if there is none.
+    fn recording(&mut self, device: &Device<V1_0>) -> &mut Recording {
+        if self.recording.is_none() {
+            self.recording = Some(Recording {
+                transfer: begin_command_buffer(device, self.transfer_pool),
+                acquire: self
+                    .graphics_pool
+                    .map(|graphics_pool| begin_command_buffer(device, graphics_pool)),
+                temporaries: Vec::new(),
+            });
+        }
+
+        self.recording.as_mut().unwrap()
+    }
+
+    /// The families to pass ownership between, ignored without a transfer
+    /// queue.
+    fn ownership_transfer(&self) -> (u32, u32) {
+        match self.transfer {
+            Some(transfer) => (transfer.family_index, self.graphics.family_index),
+            None => (vk::VK_QUEUE_FAMILY_IGNORED, vk::VK_QUEUE_FAMILY_IGNORED),
+        }
+    }
+
+    fn destroy_batch(&self, device: &Device<V1_0>, batch: Batch) {
+        unsafe {
+            device.destroy_fence(batch.fence, None);
+            if let Some(semaphore) = batch.semaphore {
+                device.destroy_semaphore(semaphore, None);
+            }
+            device.free_command_buffers(self.transfer_pool, &[batch.transfer]);
+            if let (Some(acquire), Some(graphics_pool)) = (batch.acquire, self.graphics_pool) {
+                device.free_command_buffers(graphics_pool, &[acquire]);
+            }
+        }
+        batch
+            .temporaries
+            .iter()
+            .for_each(|buffer| buffer.destroy(device));
+    }
+}
+
+/// Whether every memory heap is device local, so device local memory is as
+/// close to the host as any other.
+pub fn is_unified_memory(memory_properties: &vk::PhysicalDeviceMemoryProperties) -> bool {
+    memory_properties.memory_heaps[..memory_properties.memory_heap_count as usize]
+        .iter()
+        .all(|heap| heap.flags.subset(vk::MEMORY_HEAP_DEVICE_LOCAL_BIT))
+        && memory_properties.memory_types[..memory_properties.memory_type_count as usize]
+            .iter()
+            .any(|memory_type| {
+                memory_type.property_flags.subset(
+                    vk::MEMORY_PROPERTY_DEVICE_LOCAL_BIT
+                        | vk::MEMORY_PROPERTY_HOST_VISIBLE_BIT
+                        | vk::MEMORY_PROPERTY_HOST_COHERENT_BIT,
+                )
+            })
+}
+
+/// Make the copies visible to `dst`. With a transfer queue the barriers are
+/// the release on the transfer queue and the acquire on the graphics queue.
+fn record_barriers(
+    device: &Device<V1_0>,
+    recording: &Recording,
+    dst: UploadDst,
+    buffer_releases: &[vk::BufferMemoryBarrier],
+    buffer_acquires: &[vk::BufferMemoryBarrier],
+    image_releases: &[vk::ImageMemoryBarrier],
+    image_acquires: &[vk::ImageMemoryBarrier],
+) {
+    unsafe {
+        match recording.acquire {
+            Some(acquire) => {
+                device.cmd_pipeline_barrier(
+                    recording.transfer,
+                    vk::PIPELINE_STAGE_TRANSFER_BIT,
+                    vk::PIPELINE_STAGE_BOTTOM_OF_PIPE_BIT,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    buffer_releases,
+                    image_releases,
+                );
+                device.cmd_pipeline_barrier(
+                    acquire,
+                    vk::PIPELINE_STAGE_TOP_OF_PIPE_BIT,
+                    dst.stage,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    buffer_acquires,
+                    image_acquires,
+                );
+            }
+            None => device.cmd_pipeline_barrier(
+                recording.transfer,
+                vk::PIPELINE_STAGE_TRANSFER_BIT,
+                dst.stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                buffer_releases,
+                image_releases,
+            ),
+        }
+    }
+}
+
+fn create_command_pool(device: &Device<V1_0>, queue_family_index: u32) -> vk::CommandPool {
+    let command_pool_info = vk::CommandPoolCreateInfo {
+        s_type: vk::StructureType::CommandPoolCreateInfo,
+        p_next: ptr::null(),
+        flags: vk::COMMAND_POOL_CREATE_TRANSIENT_BIT,
+        queue_family_index,
+    };
+
+    unsafe {
+        device
+            .create_command_pool(&command_pool_info, None)
+            .expect("Unable to create upload command pool")
+    }
+}
+
+fn begin_command_buffer(
+    device: &Device<V1_0>,
+    command_pool: vk::CommandPool,
+) -> vk::CommandBuffer {
+    let command_buffer_alloc_info = vk::CommandBufferAllocateInfo {
+        s_type: vk::StructureType::CommandBufferAllocateInfo,
+        p_next: ptr::null(),
+        level: vk::CommandBufferLevel::Primary,
+        command_pool,
+        command_buffer_count: 1,
+    };
+
+    let begin_info = vk::CommandBufferBeginInfo {
+        s_type: vk::StructureType::CommandBufferBeginInfo,
+        p_next: ptr::null(),
+        flags: vk::COMMAND_BUFFER_USAGE_ONE_TIME_SUBMIT_BIT,
+        p_inheritance_info: ptr::null(),
+    };
+
+    unsafe {
+        let command_buffer = device
+            .allocate_command_buffers(&command_buffer_alloc_info)
+            .expect("Unable to allocate upload command buffer")[0];
+        device
+            .begin_command_buffer(command_buffer, &begin_info)
+            .expect("Unable to begin upload command buffer");
+        command_buffer
+    }
+}
+
+unsafe fn submit(
+    device: &Device<V1_0>,
+    queue: vk::Queue,
+    command_buffer: vk::CommandBuffer,
+    wait_semaphores: &[vk::Semaphore],
+    signal_semaphores: &[vk::Semaphore],
+    fence: vk::Fence,
+) {
+    // The acquire barriers pick their own stages, so waiting has to cover
+    // all of them.
+    let wait_stages = vec![vk::PIPELINE_STAGE_ALL_COMMANDS_BIT; wait_semaphores.len()];
+
+    let submit_info = vk::SubmitInfo {
+        s_type: vk::StructureType::SubmitInfo,
+        p_next: ptr::null(),
+        wait_semaphore_count: wait_semaphores.len() as u32,
+        p_wait_semaphores: wait_semaphores.as_ptr(),
+        p_wait_dst_stage_mask: wait_stages.as_ptr(),
+        command_buffer_count: 1,
+        p_command_buffers: &command_buffer,
+        signal_semaphore_count: signal_semaphores.len() as u32,
+        p_signal_semaphores: signal_semaphores.as_ptr(),
+    };
+
+    device
+        .queue_submit(queue, &[submit_info], fence)
+        .expect("Unable to submit uploads");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_allocates_in_order_when_empty() {
+        let mut ring = RingAllocator::new(100);
+        assert_eq!(ring.allocate(10, 1), Some(0));
+        assert_eq!(ring.allocate(20, 1), Some(10));
+        assert_eq!(ring.head, 30);
+    }
+
+    #[test]
+    fn ring_aligns_offsets() {
+        let mut ring = RingAllocator::new(100);
+        assert_eq!(ring.allocate(3, 1), Some(0));
+        assert_eq!(ring.allocate(8, 16), Some(16));
+        assert_eq!(ring.allocate(1, 4), Some(24));
+    }
+
+    #[test]
+    fn ring_wraps_past_the_end() {
+        let mut ring = RingAllocator::new(100);
+        assert_eq!(ring.allocate(60, 1), Some(0));
+        assert_eq!(ring.allocate(20, 1), Some(60));
+        ring.free_until(60);
+        assert_eq!(ring.allocate(30, 1), Some(0));
+        assert_eq!(ring.head, 130);
+    }
+
+    #[test]
+    fn ring_refuses_when_full() {
+        let mut ring = RingAllocator::new(100);
+        assert_eq!(ring.allocate(60, 1), Some(0));
+        assert_eq!(ring.allocate(50, 1), None);
+        ring.free_until(60);
+        assert_eq!(ring.allocate(50, 1), Some(0));
+        assert_eq!(ring.allocate(60, 1), None);
+    }
+
+    #[test]
+    fn ring_starts_over_when_everything_is_freed() {
+        let mut ring = RingAllocator::new(100);
+        assert_eq!(ring.allocate(90, 1), Some(0));
+        ring.free_until(90);
+        assert_eq!(ring.allocate(95, 1), Some(0));
+        assert_eq!(ring.head, 95);
+        assert_eq!(ring.tail, 0);
+    }
+}
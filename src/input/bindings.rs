@@ -0,0 +1,298 @@
+use gilrs;
+use gilrs::{Axis as GamepadAxis, Button as GamepadButton};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use winit::{MouseButton, VirtualKeyCode};
+
+/// Which half of a gamepad axis drives a binding.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+/// A physical input an action can be bound to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    GamepadButton(gilrs::Button),
+    GamepadAxis(gilrs::Axis, AxisDirection),
+}
+
+#[derive(Debug)]
+pub enum BindingsError {
+    Io(PathBuf, io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for BindingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BindingsError::Io(ref path, ref error) => {
+                write!(f, "unable to read {}: {}", path.display(), error)
+            }
+            BindingsError::Parse { line, ref message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+/// Named actions and the inputs that trigger them.
+///
+/// The file format has one action per line, followed by a comma separated
+/// list of bindings:
+///
+/// ```text
+/// # Comments start with a hash
+/// move_forward = Key:W, Key:Up, Axis:LeftStickY+
+/// look = Mouse:Right
+/// jump = Key:Space, Button:South
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Bindings {
+    actions: HashMap<String, Vec<Binding>>,
+}
+
+impl Bindings {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Bindings, BindingsError> {
+        let path = path.as_ref();
+        let source =
+            fs::read_to_string(path).map_err(|error| BindingsError::Io(path.to_owned(), error))?;
+        Bindings::parse(&source)
+    }
+
+    /// Lines for actions that were already bound replace the earlier
+    /// bindings.
+    pub fn parse(source: &str) -> Result<Bindings, BindingsError> {
+        let mut bindings = Bindings::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = match line.find('#') {
+                Some(comment) => &line[..comment],
+                None => line,
+            }.trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let action = parts.next().unwrap().trim();
+            let list = parts.next().ok_or_else(|| BindingsError::Parse {
+                line: line_number,
+                message: "expected `action = bindings`".to_owned(),
+            })?;
+
+            if action.is_empty() {
+                return Err(BindingsError::Parse {
+                    line: line_number,
+                    message: "missing action name".to_owned(),
+                });
+            }
+
+            let action_bindings = list
+                .split(',')
+                .map(str::trim)
+                .filter(|binding| !binding.is_empty())
+                .map(|binding| {
+                    parse_binding(binding).ok_or_else(|| BindingsError::Parse {
+                        line: line_number,
+                        message: format!("unknown binding `{}`", binding),
+                    })
+                }).collect::<Result<Vec<_>, _>>()?;
+
+            bindings.actions.insert(action.to_owned(), action_bindings);
+        }
+
+        Ok(bindings)
+    }
+
+    /// Add another input to `action`.
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let action_bindings = self
+            .actions
+            .entry(action.to_owned())
+            .or_default();
+        if !action_bindings.contains(&binding) {
+            action_bindings.push(binding);
+        }
+    }
+
+    /// Replace every input of `action`.
+    pub fn rebind(&mut self, action: &str, bindings: Vec<Binding>) {
+        self.actions.insert(action.to_owned(), bindings);
+    }
+
+    pub fn unbind(&mut self, action: &str) {
+        self.actions.remove(action);
+    }
+
+    /// The inputs bound to `action`, empty for unknown actions.
+    pub fn get(&self, action: &str) -> &[Binding] {
+        self.actions
+            .get(action)
+            .map(|bindings| &bindings[..])
+            .unwrap_or(&[])
+    }
+}
+
+fn parse_binding(binding: &str) -> Option<Binding> {
+    let mut parts = binding.splitn(2, ':');
+    let kind = parts.next()?.trim();
+    let name = parts.next()?.trim();
+
+    match kind {
+        "Key" => parse_key(name).map(Binding::Key),
+        "Mouse" => match name {
+            "Left" => Some(MouseButton::Left),
+            "Right" => Some(MouseButton::Right),
+            "Middle" => Some(MouseButton::Middle),
+            other => other.parse().ok().map(MouseButton::Other),
+        }.map(Binding::Mouse),
+        "Button" => parse_button(name).map(Binding::GamepadButton),
+        "Axis" => {
+            let (axis, direction) = if let Some(axis) = name.strip_suffix('+') {
+                (axis, AxisDirection::Positive)
+            } else if let Some(axis) = name.strip_suffix('-') {
+                (axis, AxisDirection::Negative)
+            } else {
+                return None;
+            };
+            parse_axis(axis).map(|axis| Binding::GamepadAxis(axis, direction))
+        }
+        _ => None,
+    }
+}
+
+/// Look up enum variants by their name, for the variants that are listed.
+macro_rules! name_table {
+    ($name:ident, $ty:ident, [$($variant:ident),*]) => {
+        fn $name(name: &str) -> Option<$ty> {
+            match name {
+                $(stringify!($variant) => Some($ty::$variant),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+name_table!(
+    parse_key,
+    VirtualKeyCode,
+    [
+        Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0, A, B, C, D, E, F, G, H, I, J,
+        K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, Escape, F1, F2, F3, F4, F5, F6, F7, F8,
+        F9, F10, F11, F12, Insert, Home, Delete, End, PageDown, PageUp, Left, Up, Right, Down,
+        Back, Return, Space, Tab, LShift, RShift, LControl, RControl, LAlt, RAlt, Numpad0,
+        Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9, Add,
+        Subtract, Minus, Equals, Comma, Period, Slash, Backslash, Semicolon, Apostrophe, Grave,
+        LBracket, RBracket
+    ]
+);
+
+name_table!(
+    parse_button,
+    GamepadButton,
+    [
+        South, East, North, West, C, Z, LeftTrigger, LeftTrigger2, RightTrigger, RightTrigger2,
+        Select, Start, Mode, LeftThumb, RightThumb, DPadUp, DPadDown, DPadLeft, DPadRight
+    ]
+);
+
+name_table!(
+    parse_axis,
+    GamepadAxis,
+    [LeftStickX, LeftStickY, LeftZ, RightStickX, RightStickY, RightZ, DPadX, DPadY]
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_line(source: &str) -> usize {
+        match Bindings::parse(source) {
+            Err(BindingsError::Parse { line, .. }) => line,
+            result => panic!("expected a parse error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn keys_and_mouse_buttons() {
+        let bindings = Bindings::parse(
+            "
+# Movement
+move_forward = Key:W, Key:Up  # trailing comment
+fire = Mouse:Left, Mouse:4, Key:LControl
+menu = Key:Escape,
+",
+        ).unwrap();
+
+        assert_eq!(
+            bindings.get("move_forward"),
+            &[
+                Binding::Key(VirtualKeyCode::W),
+                Binding::Key(VirtualKeyCode::Up),
+            ]
+        );
+        assert_eq!(
+            bindings.get("fire"),
+            &[
+                Binding::Mouse(MouseButton::Left),
+                Binding::Mouse(MouseButton::Other(4)),
+                Binding::Key(VirtualKeyCode::LControl),
+            ]
+        );
+        assert_eq!(bindings.get("menu"), &[Binding::Key(VirtualKeyCode::Escape)]);
+        assert!(bindings.get("unknown").is_empty());
+    }
+
+    #[test]
+    fn gamepad_buttons_and_axes() {
+        let bindings = Bindings::parse(
+            "jump = Button:South, Button:DPadUp\nmove = Axis:LeftStickY+, Axis:LeftStickY-\n",
+        ).unwrap();
+
+        assert_eq!(
+            bindings.get("jump"),
+            &[
+                Binding::GamepadButton(gilrs::Button::South),
+                Binding::GamepadButton(gilrs::Button::DPadUp),
+            ]
+        );
+        assert_eq!(
+            bindings.get("move"),
+            &[
+                Binding::GamepadAxis(gilrs::Axis::LeftStickY, AxisDirection::Positive),
+                Binding::GamepadAxis(gilrs::Axis::LeftStickY, AxisDirection::Negative),
+            ]
+        );
+    }
+
+    #[test]
+    fn later_lines_replace_earlier_ones() {
+        let bindings = Bindings::parse("jump = Key:Space\njump = Key:J\nnothing =\n").unwrap();
+        assert_eq!(bindings.get("jump"), &[Binding::Key(VirtualKeyCode::J)]);
+        assert!(bindings.get("nothing").is_empty());
+    }
+
+    #[test]
+    fn invalid_entries_are_errors() {
+        assert_eq!(error_line("jump Key:Space"), 1);
+        assert_eq!(error_line("\n= Key:Space"), 2);
+        assert_eq!(error_line("jump = Key:Hyperspace"), 1);
+        assert_eq!(error_line("jump = Space"), 1);
+        assert_eq!(error_line("jump = Pedal:Left"), 1);
+        assert_eq!(error_line("fire = Mouse:Thumb"), 1);
+        assert_eq!(error_line("jump = Button:Select\njump = Button:Any"), 2);
+        assert_eq!(error_line("move = Axis:LeftStickY"), 1);
+        assert_eq!(error_line("move = Axis:Throttle+"), 1);
+    }
+}
@@ -0,0 +1,502 @@
+pub mod bindings;
+
+use gilrs::{self, Gilrs};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use winit::dpi::LogicalPosition;
+use winit::{ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+
+use self::bindings::{AxisDirection, Binding, Bindings};
+
+/// Axis values closer to zero than this are treated as zero, sticks rarely
+/// rest exactly at the center.
+const AXIS_DEAD_ZONE: f32 = 0.15;
+
+/// How far an axis has to be pushed before a binding to it counts as held.
+const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+/// Buttons that are down, and the ones that changed since the last frame.
+///
+/// Presses and releases are kept until the end of the frame, so a button
+/// tapped between two frames counts as both pressed and released.
+struct ButtonState<T: Hash + Eq + Copy> {
+    held: HashSet<T>,
+    /// `held` at the end of the previous frame.
+    held_before: HashSet<T>,
+    pressed: HashSet<T>,
+    released: HashSet<T>,
+}
+
+impl<T: Hash + Eq + Copy> ButtonState<T> {
+    fn new() -> Self {
+        ButtonState {
+            held: HashSet::new(),
+            held_before: HashSet::new(),
+            pressed: HashSet::new(),
+            released: HashSet::new(),
+        }
+    }
+
+    fn press(&mut self, button: T) {
+        // Key repeat sends presses for buttons that are already down.
+        if self.held.insert(button) {
+            self.pressed.insert(button);
+        }
+    }
+
+    fn release(&mut self, button: T) {
+        if self.held.remove(&button) {
+            self.released.insert(button);
+        }
+    }
+
+    /// Whether the button was down at the end of the previous frame.
+    fn held_before(&self, button: T) -> bool {
+        self.held_before.contains(&button)
+    }
+
+    fn release_all(&mut self) {
+        let held = self.held.drain().collect::<Vec<_>>();
+        self.released.extend(held);
+    }
+
+    fn end_frame(&mut self) {
+        self.held_before = self.held.clone();
+        self.pressed.clear();
+        self.released.clear();
+    }
+}
+
+/// Keyboard, mouse and gamepad state, gathered from window events and
+/// gamepad polling over the course of a frame.
+///
+/// Call `end_frame` after the frame has been handled, "pressed" and
+/// "released" refer to changes since the previous call.
+pub struct Input {
+    bindings: Bindings,
+    keys: ButtonState<VirtualKeyCode>,
+    mouse_buttons: ButtonState<MouseButton>,
+    gamepad_buttons: ButtonState<gilrs::Button>,
+    axes: HashMap<gilrs::Axis, f32>,
+    previous_axes: HashMap<gilrs::Axis, f32>,
+    cursor_position: Option<LogicalPosition>,
+    cursor_delta: (f64, f64),
+    wheel_delta: (f32, f32),
+    /// `None` when gamepads are not supported on this platform.
+    gilrs: Option<Gilrs>,
+}
+
+impl Input {
+    /// Gamepads are left out when they are not supported, see
+    /// `gamepads_available`.
+    pub fn new(bindings: Bindings) -> Self {
+        Input::with_gilrs(bindings, Gilrs::new().ok())
+    }
+
+    fn with_gilrs(bindings: Bindings, gilrs: Option<Gilrs>) -> Self {
+        Input {
+            bindings,
+            keys: ButtonState::new(),
+            mouse_buttons: ButtonState::new(),
+            gamepad_buttons: ButtonState::new(),
+            axes: HashMap::new(),
+            previous_axes: HashMap::new(),
+            cursor_position: None,
+            cursor_delta: (0.0, 0.0),
+            wheel_delta: (0.0, 0.0),
+            gilrs,
+        }
+    }
+
+    pub fn gamepads_available(&self) -> bool {
+        self.gilrs.is_some()
+    }
+
+    pub fn bindings(&self) -> &Bindings {
+        &self.bindings
+    }
+
+    /// Rebinding takes effect for the next query.
+    pub fn bindings_mut(&mut self) -> &mut Bindings {
+        &mut self.bindings
+    }
+
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        match *event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => match state {
+                ElementState::Pressed => self.keys.press(key),
+                ElementState::Released => self.keys.release(key),
+            },
+            WindowEvent::MouseInput { state, button, .. } => match state {
+                ElementState::Pressed => self.mouse_buttons.press(button),
+                ElementState::Released => self.mouse_buttons.release(button),
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                if let Some(last_position) = self.cursor_position {
+                    self.cursor_delta.0 += position.x - last_position.x;
+                    self.cursor_delta.1 += position.y - last_position.y;
+                }
+                self.cursor_position = Some(position);
+            }
+            WindowEvent::CursorLeft { .. } => self.cursor_position = None,
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (x, y) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => (x, y),
+                    // Roughly one line for every 20 logical pixels.
+                    MouseScrollDelta::PixelDelta(position) => {
+                        (position.x as f32 / 20.0, position.y as f32 / 20.0)
+                    }
+                };
+                self.wheel_delta.0 += x;
+                self.wheel_delta.1 += y;
+            }
+            // Releases are not delivered to an unfocused window, so nothing
+            // can be assumed to still be down.
+            WindowEvent::Focused(false) => {
+                self.keys.release_all();
+                self.mouse_buttons.release_all();
+                self.cursor_position = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Drain pending gamepad events, buttons and axes of all connected
+    /// gamepads are merged.
+    pub fn poll_gamepads(&mut self) {
+        let gilrs = match self.gilrs {
+            Some(ref mut gilrs) => gilrs,
+            None => return,
+        };
+
+        while let Some(gilrs::Event { event, .. }) = gilrs.next_event() {
+            match event {
+                gilrs::EventType::ButtonPressed(button, _) => self.gamepad_buttons.press(button),
+                gilrs::EventType::ButtonReleased(button, _) => {
+                    self.gamepad_buttons.release(button)
+                }
+                gilrs::EventType::AxisChanged(axis, value, _) => {
+                    let value = if value.abs() < AXIS_DEAD_ZONE {
+                        0.0
+                    } else {
+                        value
+                    };
+                    self.axes.insert(axis, value);
+                }
+                gilrs::EventType::Disconnected => {
+                    self.gamepad_buttons.release_all();
+                    self.axes.clear();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Forget this frame's changes, call once per frame after everything has
+    /// queried the input.
+    pub fn end_frame(&mut self) {
+        self.keys.end_frame();
+        self.mouse_buttons.end_frame();
+        self.gamepad_buttons.end_frame();
+        self.previous_axes = self.axes.clone();
+        self.cursor_delta = (0.0, 0.0);
+        self.wheel_delta = (0.0, 0.0);
+    }
+
+    pub fn key_held(&self, key: VirtualKeyCode) -> bool {
+        self.keys.held.contains(&key)
+    }
+
+    pub fn key_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.keys.pressed.contains(&key)
+    }
+
+    pub fn key_released(&self, key: VirtualKeyCode) -> bool {
+        self.keys.released.contains(&key)
+    }
+
+    pub fn mouse_held(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.held.contains(&button)
+    }
+
+    pub fn mouse_pressed(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.pressed.contains(&button)
+    }
+
+    pub fn mouse_released(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.released.contains(&button)
+    }
+
+    pub fn gamepad_held(&self, button: gilrs::Button) -> bool {
+        self.gamepad_buttons.held.contains(&button)
+    }
+
+    pub fn gamepad_pressed(&self, button: gilrs::Button) -> bool {
+        self.gamepad_buttons.pressed.contains(&button)
+    }
+
+    pub fn gamepad_released(&self, button: gilrs::Button) -> bool {
+        self.gamepad_buttons.released.contains(&button)
+    }
+
+    /// From -1 to 1, zero inside the dead zone.
+    pub fn gamepad_axis(&self, axis: gilrs::Axis) -> f32 {
+        self.axes.get(&axis).cloned().unwrap_or(0.0)
+    }
+
+    /// `None` while the cursor is outside of the window.
+    pub fn cursor_position(&self) -> Option<LogicalPosition> {
+        self.cursor_position
+    }
+
+    /// How far the cursor moved this frame, in logical pixels.
+    pub fn cursor_delta(&self) -> (f64, f64) {
+        self.cursor_delta
+    }
+
+    /// Lines scrolled this frame, positive `y` scrolls up.
+    pub fn wheel_delta(&self) -> (f32, f32) {
+        self.wheel_delta
+    }
+
+    /// Whether any input bound to `action` is down.
+    pub fn action_held(&self, action: &str) -> bool {
+        self.bindings
+            .get(action)
+            .iter()
+            .any(|&binding| self.binding_held(binding, &self.axes))
+    }
+
+    /// Whether `action` went down this frame, also when it was let go of
+    /// again before the frame ended.
+    pub fn action_pressed(&self, action: &str) -> bool {
+        let bindings = self.bindings.get(action);
+        bindings.iter().any(|&binding| self.binding_pressed(binding))
+            && !bindings
+                .iter()
+                .any(|&binding| self.binding_held_before(binding))
+    }
+
+    /// Whether `action` was let go of this frame.
+    pub fn action_released(&self, action: &str) -> bool {
+        !self.action_held(action) && self
+            .bindings
+            .get(action)
+            .iter()
+            .any(|&binding| self.binding_released(binding))
+    }
+
+    /// From 0 to 1, analog for gamepad axes and either end for buttons.
+    ///
+    /// The strongest of the bound inputs wins.
+    pub fn action_value(&self, action: &str) -> f32 {
+        self.bindings
+            .get(action)
+            .iter()
+            .map(|&binding| match binding {
+                Binding::GamepadAxis(axis, direction) => {
+                    axis_value(&self.axes, axis, direction)
+                }
+                binding => {
+                    if self.binding_held(binding, &self.axes) {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                }
+            }).fold(0.0, f32::max)
+    }
+
+    fn binding_held(&self, binding: Binding, axes: &HashMap<gilrs::Axis, f32>) -> bool {
+        match binding {
+            Binding::Key(key) => self.key_held(key),
+            Binding::Mouse(button) => self.mouse_held(button),
+            Binding::GamepadButton(button) => self.gamepad_held(button),
+            Binding::GamepadAxis(axis, direction) => {
+                axis_value(axes, axis, direction) >= AXIS_PRESS_THRESHOLD
+            }
+        }
+    }
+
+    /// Whether the binding went down this frame.
+    fn binding_pressed(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.key_pressed(key),
+            Binding::Mouse(button) => self.mouse_pressed(button),
+            Binding::GamepadButton(button) => self.gamepad_pressed(button),
+            Binding::GamepadAxis(..) => {
+                self.binding_held(binding, &self.axes) && !self.binding_held_before(binding)
+            }
+        }
+    }
+
+    /// Whether the binding was let go of this frame.
+    fn binding_released(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.key_released(key),
+            Binding::Mouse(button) => self.mouse_released(button),
+            Binding::GamepadButton(button) => self.gamepad_released(button),
+            Binding::GamepadAxis(..) => {
+                !self.binding_held(binding, &self.axes) && self.binding_held_before(binding)
+            }
+        }
+    }
+
+    /// Whether the binding was down at the end of the previous frame.
+    fn binding_held_before(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.keys.held_before(key),
+            Binding::Mouse(button) => self.mouse_buttons.held_before(button),
+            Binding::GamepadButton(button) => self.gamepad_buttons.held_before(button),
+            Binding::GamepadAxis(..) => self.binding_held(binding, &self.previous_axes),
+        }
+    }
+}
+
+fn axis_value(axes: &HashMap<gilrs::Axis, f32>, axis: gilrs::Axis, direction: AxisDirection) -> f32 {
+    let value = axes.get(&axis).cloned().unwrap_or(0.0);
+    match direction {
+        AxisDirection::Positive => value.max(0.0),
+        AxisDirection::Negative => (-value).max(0.0),
+    }
+}
+
+/// Stand-ins for window events, which can't be created outside of winit, so
+/// code reading the input can be tested.
+#[cfg(test)]
+impl Input {
+    pub(crate) fn without_gamepads(bindings: Bindings) -> Self {
+        Input::with_gilrs(bindings, None)
+    }
+
+    pub(crate) fn press_key(&mut self, key: VirtualKeyCode) {
+        self.keys.press(key);
+    }
+
+    pub(crate) fn press_mouse(&mut self, button: MouseButton) {
+        self.mouse_buttons.press(button);
+    }
+
+    pub(crate) fn move_cursor(&mut self, x: f64, y: f64) {
+        self.cursor_delta.0 += x;
+        self.cursor_delta.1 += y;
+    }
+
+    pub(crate) fn scroll(&mut self, lines: f32) {
+        self.wheel_delta.1 += lines;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input() -> Input {
+        let mut bindings = Bindings::new();
+        bindings.bind("jump", Binding::Key(VirtualKeyCode::Space));
+        bindings.bind("jump", Binding::GamepadButton(gilrs::Button::South));
+        bindings.bind(
+            "move_right",
+            Binding::GamepadAxis(gilrs::Axis::LeftStickX, AxisDirection::Positive),
+        );
+        Input::with_gilrs(bindings, None)
+    }
+
+    #[test]
+    fn button_state_tracks_changes_per_frame() {
+        let mut state = ButtonState::new();
+        state.press(1);
+        state.press(1);
+        assert!(state.held.contains(&1) && state.pressed.contains(&1));
+        assert!(!state.held_before(1));
+
+        state.end_frame();
+        assert!(state.held.contains(&1) && state.pressed.is_empty());
+        assert!(state.held_before(1));
+
+        state.release(1);
+        state.release(1);
+        assert!(!state.held.contains(&1) && state.released.contains(&1));
+        assert!(state.held_before(1));
+
+        state.end_frame();
+        assert!(state.released.is_empty() && !state.held_before(1));
+    }
+
+    #[test]
+    fn release_all_releases_held_buttons() {
+        let mut state = ButtonState::new();
+        state.press(1);
+        state.press(2);
+        state.end_frame();
+        state.release_all();
+        assert!(state.held.is_empty());
+        assert_eq!(state.released.len(), 2);
+    }
+
+    #[test]
+    fn action_pressed_held_and_released() {
+        let mut input = input();
+        input.keys.press(VirtualKeyCode::Space);
+        assert!(input.action_pressed("jump") && input.action_held("jump"));
+
+        input.end_frame();
+        assert!(!input.action_pressed("jump") && input.action_held("jump"));
+
+        input.keys.release(VirtualKeyCode::Space);
+        assert!(input.action_released("jump") && !input.action_held("jump"));
+
+        input.end_frame();
+        assert!(!input.action_released("jump"));
+    }
+
+    #[test]
+    fn taps_within_a_frame_are_pressed() {
+        let mut input = input();
+        input.keys.press(VirtualKeyCode::Space);
+        input.keys.release(VirtualKeyCode::Space);
+        assert!(!input.action_held("jump"));
+        assert!(input.action_pressed("jump"));
+        assert!(input.action_released("jump"));
+
+        input.end_frame();
+        assert!(!input.action_pressed("jump") && !input.action_released("jump"));
+    }
+
+    #[test]
+    fn second_binding_does_not_press_a_held_action() {
+        let mut input = input();
+        input.keys.press(VirtualKeyCode::Space);
+        input.end_frame();
+
+        input.gamepad_buttons.press(gilrs::Button::South);
+        assert!(!input.action_pressed("jump"));
+        input.keys.release(VirtualKeyCode::Space);
+        assert!(!input.action_released("jump"));
+    }
+
+    #[test]
+    fn axis_bindings_press_past_the_threshold() {
+        let mut input = input();
+        input.axes.insert(gilrs::Axis::LeftStickX, 0.3);
+        assert!(!input.action_held("move_right"));
+        assert!((input.action_value("move_right") - 0.3).abs() < 1e-6);
+
+        input.axes.insert(gilrs::Axis::LeftStickX, 0.8);
+        assert!(input.action_pressed("move_right"));
+        input.end_frame();
+        assert!(!input.action_pressed("move_right") && input.action_held("move_right"));
+
+        input.axes.insert(gilrs::Axis::LeftStickX, -0.8);
+        assert!(input.action_released("move_right"));
+        assert_eq!(input.action_value("move_right"), 0.0);
+    }
+}
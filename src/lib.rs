@@ -0,0 +1,34 @@
+// Vulkan setup takes a lot of parameters, and patterns are written as
+// `&(ref a, ref b)` throughout instead of relying on binding modes.
+#![allow(clippy::too_many_arguments, clippy::needless_borrowed_reference)]
+
+#[macro_use]
+extern crate ash;
+extern crate cgmath;
+extern crate gilrs;
+extern crate gltf;
+extern crate image;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate toml;
+extern crate winit;
+
+#[cfg(target_os = "windows")]
+extern crate winapi;
+
+#[cfg(target_os = "macos")]
+extern crate cocoa;
+#[cfg(target_os = "macos")]
+extern crate metal_rs as metal;
+#[cfg(target_os = "macos")]
+extern crate objc;
+
+pub mod app;
+pub mod assets;
+pub mod config;
+pub mod engine;
+pub mod input;
+pub mod scene;
+pub mod shader;
+pub mod time;
@@ -1,25 +1,62 @@
-#[macro_use]
+#![allow(clippy::needless_borrowed_reference)]
+
 extern crate ash;
+extern crate ash_toy_engine;
+extern crate cgmath;
 extern crate winit;
 
-#[cfg(target_os = "windows")]
-extern crate winapi;
+mod viewer;
+
+use ash_toy_engine::app::{self, Settings};
+use ash_toy_engine::config::Config;
+use ash_toy_engine::input::bindings::Bindings;
+use std::env;
+use std::process;
+use viewer::Viewer;
+
+/// Rate of the fixed timestep updates.
+const UPDATES_PER_SECOND: u32 = 60;
+
+/// Upper limit on rendered frames per second, `None` renders as fast as the
+/// present mode allows.
+const FRAME_CAP: Option<u32> = None;
 
-#[cfg(target_os = "macos")]
-extern crate cocoa;
-#[cfg(target_os = "macos")]
-extern crate metal_rs as metal;
-#[cfg(target_os = "macos")]
-extern crate objc;
+/// Engine settings, read at startup unless another file is given with
+/// `--config` or `ASH_CONFIG`.
+const CONFIG_PATH: &str = "assets/config.toml";
 
-mod application;
-mod engine;
-mod shader;
+/// Input bindings, read at startup.
+const BINDINGS_PATH: &str = "assets/input.cfg";
 
-use application::Application;
+/// Used when the bindings at `BINDINGS_PATH` are missing or invalid.
+const DEFAULT_BINDINGS: &str = include_str!("../assets/input.cfg");
 
 fn main() {
-    let mut app = Application::new();
+    let (config, args) = Config::load_with_overrides(CONFIG_PATH, env::args().skip(1).collect())
+        .unwrap_or_else(|error| {
+            eprintln!("Invalid configuration, {}", error);
+            process::exit(1);
+        });
+
+    // An optional path to a .gltf, .glb or .obj file to show instead of the
+    // triangle
+    let model_path = args.into_iter().next();
+
+    let bindings = Bindings::load(BINDINGS_PATH).unwrap_or_else(|error| {
+        println!("Using the default input bindings, {}", error);
+        Bindings::parse(DEFAULT_BINDINGS).expect("Invalid default input bindings")
+    });
+
+    let post_settings = config.post.clone();
+    let shading = config.graphics.shading;
+    let settings = Settings::default()
+        .with_config(config)
+        .with_updates_per_second(UPDATES_PER_SECOND)
+        .with_frame_cap(FRAME_CAP)
+        .with_bindings(bindings);
 
-    app.run().unwrap();
+    if let Err(error) = app::run(settings, Viewer::new(model_path, post_settings, shading)) {
+        eprintln!("Unable to run, {}", error);
+        process::exit(1);
+    }
 }
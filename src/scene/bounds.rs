@@ -0,0 +1,161 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Vector3};
+
+/// An axis aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Self {
+        Aabb { min, max }
+    }
+
+    /// The smallest box containing all of `points`, `None` when there are
+    /// none.
+    pub fn from_points<I: IntoIterator<Item = Point3<f32>>>(points: I) -> Option<Self> {
+        points.into_iter().fold(None, |aabb: Option<Aabb>, point| {
+            Some(match aabb {
+                Some(aabb) => aabb.expand(point),
+                None => Aabb::new(point, point),
+            })
+        })
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    pub fn half_extents(&self) -> Vector3<f32> {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn expand(&self, point: Point3<f32>) -> Aabb {
+        Aabb {
+            min: Point3::new(
+                self.min.x.min(point.x),
+                self.min.y.min(point.y),
+                self.min.z.min(point.z),
+            ),
+            max: Point3::new(
+                self.max.x.max(point.x),
+                self.max.y.max(point.y),
+                self.max.z.max(point.z),
+            ),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        self.expand(other.min).expand(other.max)
+    }
+
+    /// The box around this box after it has been transformed by `matrix`.
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Aabb {
+        let center = matrix * self.center().to_homogeneous();
+        let center = Point3::new(center.x, center.y, center.z);
+        let extents = self.half_extents();
+
+        // Each world axis extent is the sum of the local extents projected
+        // onto it.
+        let mut world_extents = Vector3::new(0.0, 0.0, 0.0);
+        for row in 0..3 {
+            world_extents[row] = matrix.x[row].abs() * extents.x
+                + matrix.y[row].abs() * extents.y
+                + matrix.z[row].abs() * extents.z;
+        }
+
+        Aabb {
+            min: center - world_extents,
+            max: center + world_extents,
+        }
+    }
+}
+
+/// A sphere around a set of points, cheaper to test than a box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn from_aabb(aabb: &Aabb) -> Self {
+        BoundingSphere {
+            center: aabb.center(),
+            radius: aabb.half_extents().magnitude(),
+        }
+    }
+
+    /// The sphere after it has been transformed by `matrix`, growing the
+    /// radius by the largest scale of the matrix.
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> BoundingSphere {
+        let center = matrix * self.center.to_homogeneous();
+        let scale = matrix
+            .x
+            .truncate()
+            .magnitude()
+            .max(matrix.y.truncate().magnitude())
+            .max(matrix.z.truncate().magnitude());
+
+        BoundingSphere {
+            center: Point3::from_vec(center.truncate()),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Vector3};
+
+    fn assert_close(a: Point3<f32>, b: Point3<f32>) {
+        assert!((a - b).magnitude() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    fn skewed_box() -> Aabb {
+        Aabb::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 2.0, 3.0))
+    }
+
+    #[test]
+    fn transform_translates_and_scales() {
+        let matrix = Matrix4::from_translation(Vector3::new(10.0, 0.0, -5.0))
+            * Matrix4::from_nonuniform_scale(2.0, 1.0, 0.5);
+        let aabb = skewed_box().transform(&matrix);
+        assert_close(aabb.min, Point3::new(8.0, -1.0, -5.5));
+        assert_close(aabb.max, Point3::new(12.0, 2.0, -3.5));
+    }
+
+    #[test]
+    fn transform_rotates_into_the_enclosing_box() {
+        let aabb = skewed_box().transform(&Matrix4::from_angle_y(Deg(90.0)));
+        // A quarter turn around Y maps x to -z and z to x.
+        assert_close(aabb.min, Point3::new(-1.0, -1.0, -1.0));
+        assert_close(aabb.max, Point3::new(3.0, 2.0, 1.0));
+
+        let cube = Aabb::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+        let aabb = cube.transform(&Matrix4::from_angle_z(Deg(45.0)));
+        let reach = 2.0f32.sqrt();
+        assert_close(aabb.min, Point3::new(-reach, -reach, -1.0));
+        assert_close(aabb.max, Point3::new(reach, reach, 1.0));
+    }
+
+    #[test]
+    fn from_points_and_union() {
+        assert_eq!(Aabb::from_points(Vec::new()), None);
+
+        let aabb = Aabb::from_points(vec![
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(-1.0, 2.0, 0.0),
+            Point3::new(0.0, 0.0, 3.0),
+        ]).unwrap();
+        assert_eq!(aabb.min, Point3::new(-1.0, 0.0, 0.0));
+        assert_eq!(aabb.max, Point3::new(1.0, 2.0, 3.0));
+
+        let other = Aabb::new(Point3::new(5.0, 5.0, 5.0), Point3::new(6.0, 6.0, 6.0));
+        let union = aabb.union(&other);
+        assert_eq!(union.min, aabb.min);
+        assert_eq!(union.max, other.max);
+    }
+}
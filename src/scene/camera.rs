@@ -0,0 +1,246 @@
+use cgmath::{Angle, InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Vector3, Vector4};
+
+use engine::model::Matrix;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    Perspective {
+        /// Vertical field of view.
+        fov_y: Rad<f32>,
+        near: f32,
+        far: f32,
+    },
+    Orthographic {
+        /// Height of the view volume in world units, the width follows from
+        /// the aspect ratio.
+        height: f32,
+        near: f32,
+        far: f32,
+    },
+}
+
+/// Camera data as laid out in the `Camera` uniform block of the shaders.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CameraUniform {
+    pub view: Matrix,
+    pub projection: Matrix,
+    pub view_projection: Matrix,
+    /// World space position, `w` is unused.
+    pub position: [f32; 4],
+    /// Clip space back to world space, to reconstruct positions from depth.
+    pub inverse_view_projection: Matrix,
+}
+
+/// A right handed, Y up camera looking down `-Z` when yaw and pitch are zero.
+///
+/// Projections map to Vulkan clip space: Y points down and depth goes from
+/// 0 at the near plane to 1 at the far plane, or the other way around with
+/// `reversed_z`.
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    pub position: Point3<f32>,
+    /// Rotation around the Y axis, positive turns right.
+    pub yaw: Rad<f32>,
+    /// Rotation around the camera's X axis, positive looks up.
+    pub pitch: Rad<f32>,
+    pub projection: Projection,
+    /// Width divided by height of the viewport.
+    pub aspect_ratio: f32,
+    /// Map the near plane to depth 1 and the far plane to 0, which spreads
+    /// floating point depth precision more evenly.
+    pub reversed_z: bool,
+}
+
+impl Camera {
+    pub fn perspective(fov_y: Rad<f32>, aspect_ratio: f32, near: f32, far: f32) -> Self {
+        Camera {
+            position: Point3::new(0.0, 0.0, 0.0),
+            yaw: Rad(0.0),
+            pitch: Rad(0.0),
+            projection: Projection::Perspective { fov_y, near, far },
+            aspect_ratio,
+            reversed_z: false,
+        }
+    }
+
+    pub fn orthographic(height: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+        Camera {
+            projection: Projection::Orthographic { height, near, far },
+            ..Camera::perspective(Rad(0.0), aspect_ratio, near, far)
+        }
+    }
+
+    pub fn with_reversed_z(mut self) -> Self {
+        self.reversed_z = true;
+        self
+    }
+
+    /// The depth the depth attachment has to be cleared to.
+    pub fn clear_depth(&self) -> f32 {
+        if self.reversed_z {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    /// Unit vector the camera looks along.
+    pub fn forward(&self) -> Vector3<f32> {
+        Vector3::new(
+            self.yaw.sin() * self.pitch.cos(),
+            self.pitch.sin(),
+            -self.yaw.cos() * self.pitch.cos(),
+        )
+    }
+
+    /// Unit vector pointing to the right of the camera, always horizontal.
+    pub fn right(&self) -> Vector3<f32> {
+        Vector3::new(self.yaw.cos(), 0.0, self.yaw.sin())
+    }
+
+    /// Turn the camera towards `target`.
+    pub fn look_at(&mut self, target: Point3<f32>) {
+        let direction = target - self.position;
+        if direction.magnitude2() == 0.0 {
+            return;
+        }
+
+        let direction = direction.normalize();
+        self.yaw = Rad(direction.x.atan2(-direction.z));
+        self.pitch = Rad(direction.y.asin());
+    }
+
+    /// The camera `alpha` of the way from `self` to `other`, used to render
+    /// in between two updates.
+    pub fn interpolate(&self, other: &Camera, alpha: f32) -> Camera {
+        Camera {
+            position: self.position + (other.position - self.position) * alpha,
+            yaw: self.yaw + (other.yaw - self.yaw) * alpha,
+            pitch: self.pitch + (other.pitch - self.pitch) * alpha,
+            ..*other
+        }
+    }
+
+    pub fn view(&self) -> Matrix4<f32> {
+        Matrix4::look_at_dir(self.position, self.forward(), Vector3::unit_y())
+    }
+
+    pub fn projection(&self) -> Matrix4<f32> {
+        match self.projection {
+            Projection::Perspective { fov_y, near, far } => {
+                let focal_length = 1.0 / (fov_y / 2.0).tan();
+                let (depth_scale, depth_offset) = if self.reversed_z {
+                    (near / (far - near), far * near / (far - near))
+                } else {
+                    (far / (near - far), far * near / (near - far))
+                };
+
+                Matrix4::from_cols(
+                    Vector4::new(focal_length / self.aspect_ratio, 0.0, 0.0, 0.0),
+                    Vector4::new(0.0, -focal_length, 0.0, 0.0),
+                    Vector4::new(0.0, 0.0, depth_scale, -1.0),
+                    Vector4::new(0.0, 0.0, depth_offset, 0.0),
+                )
+            }
+            Projection::Orthographic { height, near, far } => {
+                let width = height * self.aspect_ratio;
+                let (depth_scale, depth_offset) = if self.reversed_z {
+                    (1.0 / (far - near), far / (far - near))
+                } else {
+                    (1.0 / (near - far), near / (near - far))
+                };
+
+                Matrix4::from_cols(
+                    Vector4::new(2.0 / width, 0.0, 0.0, 0.0),
+                    Vector4::new(0.0, -2.0 / height, 0.0, 0.0),
+                    Vector4::new(0.0, 0.0, depth_scale, 0.0),
+                    Vector4::new(0.0, 0.0, depth_offset, 1.0),
+                )
+            }
+        }
+    }
+
+    pub fn view_projection(&self) -> Matrix4<f32> {
+        self.projection() * self.view()
+    }
+
+    pub fn uniform(&self) -> CameraUniform {
+        let view = self.view();
+        let projection = self.projection();
+        let view_projection = projection * view;
+
+        CameraUniform {
+            view: view.into(),
+            projection: projection.into(),
+            view_projection: view_projection.into(),
+            position: self.position.to_homogeneous().into(),
+            inverse_view_projection: view_projection
+                .invert()
+                .unwrap_or_else(Matrix4::identity)
+                .into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Deg;
+
+    /// Normalized device coordinates of a world space point.
+    fn project(camera: &Camera, point: [f32; 3]) -> [f32; 3] {
+        let clip = camera.view_projection() * Point3::from(point).to_homogeneous();
+        [clip.x / clip.w, clip.y / clip.w, clip.z / clip.w]
+    }
+
+    fn assert_near(actual: [f32; 3], expected: [f32; 3]) {
+        for (a, e) in actual.iter().zip(&expected) {
+            assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn perspective_maps_the_frustum_to_vulkan_clip_space() {
+        let camera = Camera::perspective(Deg(90.0).into(), 2.0, 1.0, 100.0);
+        assert_near(project(&camera, [0.0, 0.0, -1.0]), [0.0, 0.0, 0.0]);
+        assert_near(project(&camera, [0.0, 0.0, -100.0]), [0.0, 0.0, 1.0]);
+        // Up is -Y in Vulkan, the aspect ratio narrows X.
+        assert_near(project(&camera, [2.0, 1.0, -1.0]), [1.0, -1.0, 0.0]);
+        assert_near(project(&camera, [-4.0, -2.0, -2.0]), [-1.0, 1.0, 0.5050505]);
+    }
+
+    #[test]
+    fn reversed_perspective_maps_near_to_one() {
+        let camera = Camera::perspective(Deg(90.0).into(), 1.0, 1.0, 100.0).with_reversed_z();
+        assert_near(project(&camera, [0.0, 0.0, -1.0]), [0.0, 0.0, 1.0]);
+        assert_near(project(&camera, [0.0, 0.0, -100.0]), [0.0, 0.0, 0.0]);
+        assert_near(project(&camera, [1.0, 1.0, -1.0]), [1.0, -1.0, 1.0]);
+        assert_eq!(camera.clear_depth(), 0.0);
+    }
+
+    #[test]
+    fn orthographic_maps_the_box_to_vulkan_clip_space() {
+        let camera = Camera::orthographic(2.0, 2.0, 1.0, 11.0);
+        assert_near(project(&camera, [2.0, 1.0, -1.0]), [1.0, -1.0, 0.0]);
+        assert_near(project(&camera, [-2.0, -1.0, -11.0]), [-1.0, 1.0, 1.0]);
+        assert_near(project(&camera, [0.0, 0.0, -6.0]), [0.0, 0.0, 0.5]);
+
+        let reversed = camera.with_reversed_z();
+        assert_near(project(&reversed, [0.0, 0.0, -1.0]), [0.0, 0.0, 1.0]);
+        assert_near(project(&reversed, [0.0, 0.0, -11.0]), [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn view_follows_position_and_orientation() {
+        let mut camera = Camera::perspective(Deg(90.0).into(), 1.0, 1.0, 100.0);
+        camera.position = Point3::new(5.0, 0.0, 0.0);
+        camera.look_at(Point3::new(5.0, 0.0, -10.0));
+        assert_near(project(&camera, [5.0, 0.0, -1.0]), [0.0, 0.0, 0.0]);
+
+        // Looking at the origin from +X puts -Z on the right.
+        camera.look_at(Point3::new(0.0, 0.0, 0.0));
+        assert_near(project(&camera, [4.0, 0.0, -1.0]), [1.0, 0.0, 0.0]);
+        assert_near(camera.right().into(), [0.0, 0.0, -1.0]);
+    }
+}
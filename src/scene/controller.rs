@@ -0,0 +1,210 @@
+use cgmath::{InnerSpace, Point3, Rad, Vector3};
+use std::f32::consts::FRAC_PI_2;
+
+use input::Input;
+
+use super::camera::Camera;
+
+/// Keeps pitch just short of straight up or down, where yaw stops having a
+/// meaning.
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+/// Radians per second with a gamepad stick pushed all the way.
+const STICK_TURN_RATE: f32 = 2.0;
+
+/// Moves a camera in response to input actions.
+pub trait CameraController {
+    /// Apply this frame's input, `delta` is the time since the last update in
+    /// seconds.
+    fn update(&mut self, camera: &mut Camera, input: &Input, delta: f32);
+}
+
+fn axis(input: &Input, positive: &str, negative: &str) -> f32 {
+    input.action_value(positive) - input.action_value(negative)
+}
+
+/// Turn from dragging the cursor while `drag_action` is held, or from the
+/// turn actions bound to a stick.
+fn turn(camera: &mut Camera, input: &Input, drag_action: &str, sensitivity: f32, delta: f32) {
+    let (mut yaw, mut pitch) = if input.action_held(drag_action) {
+        let (x, y) = input.cursor_delta();
+        (x as f32 * sensitivity, -y as f32 * sensitivity)
+    } else {
+        (0.0, 0.0)
+    };
+
+    yaw += axis(input, "turn_right", "turn_left") * STICK_TURN_RATE * delta;
+    pitch += axis(input, "turn_up", "turn_down") * STICK_TURN_RATE * delta;
+
+    camera.yaw += Rad(yaw);
+    camera.pitch = Rad((camera.pitch.0 + pitch).clamp(-MAX_PITCH, MAX_PITCH));
+}
+
+/// Free flight: the move actions fly around, `move_fast` speeds up and
+/// dragging with `look` held looks around.
+pub struct FlyController {
+    /// Units per second.
+    pub speed: f32,
+    /// Radians per logical pixel of cursor movement.
+    pub sensitivity: f32,
+}
+
+impl FlyController {
+    pub fn new(speed: f32) -> Self {
+        FlyController {
+            speed,
+            sensitivity: 0.005,
+        }
+    }
+}
+
+impl CameraController for FlyController {
+    fn update(&mut self, camera: &mut Camera, input: &Input, delta: f32) {
+        turn(camera, input, "look", self.sensitivity, delta);
+
+        let direction = camera.forward() * axis(input, "move_forward", "move_backward")
+            + camera.right() * axis(input, "move_right", "move_left")
+            + Vector3::unit_y() * axis(input, "move_up", "move_down");
+
+        // Analog sticks move slower when pushed less, so only normalize
+        // diagonal movement that would otherwise be faster.
+        let direction = if direction.magnitude2() > 1.0 {
+            direction.normalize()
+        } else {
+            direction
+        };
+
+        let speed = if input.action_held("move_fast") {
+            self.speed * 4.0
+        } else {
+            self.speed
+        };
+        camera.position += direction * speed * delta;
+    }
+}
+
+/// Circles around a target point: dragging with `orbit` held rotates, the
+/// mouse wheel and the zoom actions move closer or further away.
+pub struct OrbitController {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    /// Radians per logical pixel of cursor movement.
+    pub sensitivity: f32,
+    /// Fraction of the distance moved per wheel line.
+    pub zoom_speed: f32,
+}
+
+impl OrbitController {
+    pub fn new(target: Point3<f32>, distance: f32) -> Self {
+        OrbitController {
+            target,
+            distance,
+            sensitivity: 0.005,
+            zoom_speed: 0.1,
+        }
+    }
+}
+
+impl CameraController for OrbitController {
+    fn update(&mut self, camera: &mut Camera, input: &Input, delta: f32) {
+        turn(camera, input, "orbit", self.sensitivity, delta);
+
+        // The zoom actions count as ten wheel lines per second.
+        let zoom = input.wheel_delta().1 + axis(input, "zoom_in", "zoom_out") * 10.0 * delta;
+        self.distance *= (1.0 - self.zoom_speed).powf(zoom);
+
+        camera.position = self.target - camera.forward() * self.distance;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input::bindings::Bindings;
+    use winit::{MouseButton, VirtualKeyCode};
+
+    fn input() -> Input {
+        let bindings = Bindings::parse(
+            "move_forward = Key:W\n\
+             move_fast = Key:LShift\n\
+             look = Mouse:Right\n\
+             orbit = Mouse:Left\n",
+        ).unwrap();
+        Input::without_gamepads(bindings)
+    }
+
+    fn camera() -> Camera {
+        Camera::perspective(Rad(1.0), 1.0, 0.1, 100.0)
+    }
+
+    fn assert_near(actual: Point3<f32>, expected: [f32; 3]) {
+        let error = actual - Point3::from(expected);
+        assert!(error.magnitude() < 1e-4, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn fly_moves_along_forward() {
+        let mut controller = FlyController::new(2.0);
+        let mut camera = camera();
+        let mut input = input();
+
+        controller.update(&mut camera, &input, 0.5);
+        assert_near(camera.position, [0.0, 0.0, 0.0]);
+
+        input.press_key(VirtualKeyCode::W);
+        controller.update(&mut camera, &input, 0.5);
+        assert_near(camera.position, [0.0, 0.0, -1.0]);
+
+        input.press_key(VirtualKeyCode::LShift);
+        controller.update(&mut camera, &input, 0.5);
+        assert_near(camera.position, [0.0, 0.0, -5.0]);
+    }
+
+    #[test]
+    fn fly_turns_only_while_look_is_held() {
+        let mut controller = FlyController::new(1.0);
+        let mut camera = camera();
+        let mut input = input();
+
+        input.move_cursor(100.0, 0.0);
+        controller.update(&mut camera, &input, 0.1);
+        assert_eq!(camera.yaw, Rad(0.0));
+
+        input.press_mouse(MouseButton::Right);
+        controller.update(&mut camera, &input, 0.1);
+        assert!((camera.yaw.0 - 0.5).abs() < 1e-6);
+
+        // Moving the cursor up looks up, but never past straight up.
+        input.end_frame();
+        input.move_cursor(0.0, -10_000.0);
+        controller.update(&mut camera, &input, 0.1);
+        assert_eq!(camera.pitch, Rad(MAX_PITCH));
+    }
+
+    #[test]
+    fn orbit_keeps_the_distance_to_the_target() {
+        let mut controller = OrbitController::new(Point3::new(1.0, 0.0, 0.0), 10.0);
+        let mut camera = camera();
+        let mut input = input();
+
+        controller.update(&mut camera, &input, 0.1);
+        assert_near(camera.position, [1.0, 0.0, 10.0]);
+
+        input.press_mouse(MouseButton::Left);
+        input.move_cursor(FRAC_PI_2 as f64 / 0.005, 0.0);
+        controller.update(&mut camera, &input, 0.1);
+        assert_near(camera.position, [-9.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn orbit_zooms_with_the_wheel() {
+        let mut controller = OrbitController::new(Point3::new(0.0, 0.0, 0.0), 10.0);
+        let mut camera = camera();
+        let mut input = input();
+
+        input.scroll(1.0);
+        controller.update(&mut camera, &input, 0.1);
+        assert!((controller.distance - 9.0).abs() < 1e-5);
+        assert_near(camera.position, [0.0, 0.0, 9.0]);
+    }
+}
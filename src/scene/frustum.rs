@@ -0,0 +1,176 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix, Matrix4, Point3, Vector3, Vector4};
+
+use super::bounds::{Aabb, BoundingSphere};
+
+/// Where a bounding volume lies relative to a frustum.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Containment {
+    Outside,
+    Intersecting,
+    Inside,
+}
+
+/// A plane with its normal pointing into the inside half space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    /// Build a plane from `ax + by + cz + d = 0`, normalizing it.
+    pub fn from_coefficients(coefficients: Vector4<f32>) -> Self {
+        let normal = coefficients.truncate();
+        let length = normal.magnitude();
+
+        Plane {
+            normal: normal / length,
+            distance: coefficients.w / length,
+        }
+    }
+
+    /// Positive on the inside, negative on the outside.
+    pub fn signed_distance(&self, point: Point3<f32>) -> f32 {
+        self.normal.dot(point.to_vec()) + self.distance
+    }
+}
+
+/// The six planes bounding what a camera can see.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far, in that order.
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Extract the planes of a view projection matrix that maps depth to
+    /// Vulkan's `0..1` range.
+    ///
+    /// Works for reversed depth too, near and far just swap roles.
+    pub fn from_matrix(view_projection: &Matrix4<f32>) -> Self {
+        let row = |i| view_projection.row(i);
+
+        Frustum {
+            planes: [
+                Plane::from_coefficients(row(3) + row(0)),
+                Plane::from_coefficients(row(3) - row(0)),
+                Plane::from_coefficients(row(3) + row(1)),
+                Plane::from_coefficients(row(3) - row(1)),
+                Plane::from_coefficients(row(2)),
+                Plane::from_coefficients(row(3) - row(2)),
+            ],
+        }
+    }
+
+    pub fn contains_point(&self, point: Point3<f32>) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(point) >= 0.0)
+    }
+
+    pub fn contains_sphere(&self, sphere: &BoundingSphere) -> Containment {
+        let mut containment = Containment::Inside;
+        for plane in &self.planes {
+            let distance = plane.signed_distance(sphere.center);
+            if distance < -sphere.radius {
+                return Containment::Outside;
+            }
+            if distance < sphere.radius {
+                containment = Containment::Intersecting;
+            }
+        }
+        containment
+    }
+
+    pub fn contains_aabb(&self, aabb: &Aabb) -> Containment {
+        let center = aabb.center();
+        let extents = aabb.half_extents();
+
+        let mut containment = Containment::Inside;
+        for plane in &self.planes {
+            // How far the box reaches along the plane normal.
+            let radius = extents.x * plane.normal.x.abs()
+                + extents.y * plane.normal.y.abs()
+                + extents.z * plane.normal.z.abs();
+            let distance = plane.signed_distance(center);
+
+            if distance < -radius {
+                return Containment::Outside;
+            }
+            if distance < radius {
+                containment = Containment::Intersecting;
+            }
+        }
+        containment
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Deg;
+    use scene::camera::Camera;
+
+    fn cube(center: Point3<f32>, half_extent: f32) -> Aabb {
+        let extents = Vector3::new(half_extent, half_extent, half_extent);
+        Aabb::new(center - extents, center + extents)
+    }
+
+    /// Culling results for a box in front, one behind, one past the far
+    /// plane, one off to the side and one crossing the left plane.
+    fn check(camera: &Camera, side: f32) {
+        let frustum = Frustum::from_matrix(&camera.view_projection());
+
+        let front = cube(Point3::new(0.0, 0.0, -10.0), 1.0);
+        assert_eq!(frustum.contains_aabb(&front), Containment::Inside);
+        assert!(frustum.contains_point(front.center()));
+
+        let behind = cube(Point3::new(0.0, 0.0, 10.0), 1.0);
+        assert_eq!(frustum.contains_aabb(&behind), Containment::Outside);
+        assert!(!frustum.contains_point(behind.center()));
+
+        let past_far = cube(Point3::new(0.0, 0.0, -200.0), 1.0);
+        assert_eq!(frustum.contains_aabb(&past_far), Containment::Outside);
+
+        let aside = cube(Point3::new(side + 5.0, 0.0, -10.0), 1.0);
+        assert_eq!(frustum.contains_aabb(&aside), Containment::Outside);
+
+        let crossing = cube(Point3::new(-side, 0.0, -10.0), 1.0);
+        assert_eq!(frustum.contains_aabb(&crossing), Containment::Intersecting);
+
+        let sphere = BoundingSphere::from_aabb(&front);
+        assert_eq!(frustum.contains_sphere(&sphere), Containment::Inside);
+    }
+
+    #[test]
+    fn perspective_frustum() {
+        // With a 90 degree field of view the sides are at |x| = -z.
+        let camera = Camera::perspective(Deg(90.0).into(), 1.0, 0.1, 100.0);
+        check(&camera, 10.0);
+    }
+
+    #[test]
+    fn reversed_z_perspective_frustum() {
+        let camera = Camera::perspective(Deg(90.0).into(), 1.0, 0.1, 100.0).with_reversed_z();
+        check(&camera, 10.0);
+    }
+
+    #[test]
+    fn orthographic_frustum() {
+        let camera = Camera::orthographic(10.0, 1.0, 0.1, 100.0);
+        check(&camera, 5.0);
+
+        let camera = Camera::orthographic(10.0, 1.0, 0.1, 100.0).with_reversed_z();
+        check(&camera, 5.0);
+    }
+
+    #[test]
+    fn planes_point_inside() {
+        let camera = Camera::perspective(Deg(60.0).into(), 1.5, 0.5, 50.0);
+        let frustum = Frustum::from_matrix(&camera.view_projection());
+        for plane in &frustum.planes {
+            assert!((plane.normal.magnitude() - 1.0).abs() < 1e-5);
+            assert!(plane.signed_distance(Point3::new(0.0, 0.0, -5.0)) > 0.0);
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use ash::version::V1_0;
+use ash::vk;
+use ash::Device;
+use cgmath::{InnerSpace, Point3, Rad, Vector3};
+use std::mem;
+
+use engine::buffer::Buffer;
+
+use super::shadow;
+
+/// Most lights a light buffer holds, the rest are left out.
+pub const MAX_LIGHTS: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    /// Infinitely far away, like the sun.
+    Directional,
+    Point,
+    /// A point light limited to a cone, fading out between the inner and
+    /// the outer angle from its direction.
+    Spot {
+        inner_cone: Rad<f32>,
+        outer_cone: Rad<f32>,
+    },
+}
+
+/// A punctual light, following glTF's `KHR_lights_punctual`: point and spot
+/// intensities are in candela, directional ones in lux.
+#[derive(Clone, Copy, Debug)]
+pub struct Light {
+    pub kind: LightKind,
+    /// Unused by directional lights.
+    pub position: Point3<f32>,
+    /// Where the light points, unused by point lights.
+    pub direction: Vector3<f32>,
+    /// Linear RGB.
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// Distance at which point and spot lights fade out completely,
+    /// unlimited when `None`.
+    pub range: Option<f32>,
+    /// Only directional and spot lights cast shadows, see
+    /// `shadow::shadow_layers`.
+    pub cast_shadows: bool,
+}
+
+impl Light {
+    pub fn directional(direction: Vector3<f32>, color: [f32; 3], intensity: f32) -> Self {
+        Light {
+            kind: LightKind::Directional,
+            position: Point3::new(0.0, 0.0, 0.0),
+            direction: direction.normalize(),
+            color,
+            intensity,
+            range: None,
+            cast_shadows: false,
+        }
+    }
+
+    pub fn point(position: Point3<f32>, color: [f32; 3], intensity: f32) -> Self {
+        Light {
+            kind: LightKind::Point,
+            position,
+            direction: Vector3::new(0.0, -1.0, 0.0),
+            color,
+            intensity,
+            range: None,
+            cast_shadows: false,
+        }
+    }
+
+    pub fn spot(
+        position: Point3<f32>,
+        direction: Vector3<f32>,
+        inner_cone: Rad<f32>,
+        outer_cone: Rad<f32>,
+        color: [f32; 3],
+        intensity: f32,
+    ) -> Self {
+        Light {
+            kind: LightKind::Spot {
+                inner_cone,
+                outer_cone,
+            },
+            direction: direction.normalize(),
+            ..Light::point(position, color, intensity)
+        }
+    }
+
+    pub fn with_range(mut self, range: f32) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    pub fn with_shadows(mut self) -> Self {
+        self.cast_shadows = true;
+        self
+    }
+
+    /// The light as the shaders see it, with its shadows starting at
+    /// `shadow_layer` of the shadow maps if it has any.
+    pub fn data(&self, shadow_layer: Option<usize>) -> LightData {
+        let (kind, spot_scale_offset) = match self.kind {
+            LightKind::Directional => (0, [0.0, 1.0]),
+            LightKind::Point => (1, [0.0, 1.0]),
+            LightKind::Spot {
+                inner_cone,
+                outer_cone,
+            } => {
+                let cos_outer = outer_cone.0.cos();
+                let scale = 1.0 / (inner_cone.0.cos() - cos_outer).max(0.001);
+                (2, [scale, -cos_outer * scale])
+            }
+        };
+
+        LightData {
+            position_range: [
+                self.position.x,
+                self.position.y,
+                self.position.z,
+                self.range.unwrap_or(0.0),
+            ],
+            direction: [self.direction.x, self.direction.y, self.direction.z, 0.0],
+            color: [
+                self.color[0] * self.intensity,
+                self.color[1] * self.intensity,
+                self.color[2] * self.intensity,
+                0.0,
+            ],
+            spot_scale_offset,
+            kind,
+            shadow_layer: shadow_layer.map_or(-1, |layer| layer as i32),
+        }
+    }
+}
+
+/// A light as laid out in the `Lights` storage buffer of the shaders.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct LightData {
+    /// World space position, `w` is the range or 0 when unlimited.
+    pub position_range: [f32; 4],
+    pub direction: [f32; 4],
+    /// Color times intensity.
+    pub color: [f32; 4],
+    /// Turns the cosine of the angle to the direction into the cone
+    /// attenuation of spot lights.
+    pub spot_scale_offset: [f32; 2],
+    /// 0 for directional, 1 for point and 2 for spot lights.
+    pub kind: u32,
+    /// First layer of the light's shadow maps, -1 without shadows.
+    pub shadow_layer: i32,
+}
+
+/// What comes before the lights in the `Lights` storage buffer.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct LightsHeader {
+    count: u32,
+    /// Scales the image based ambient light.
+    ambient_intensity: f32,
+    _padding: [u32; 2],
+}
+
+/// Size of a storage buffer holding `MAX_LIGHTS` lights.
+pub fn buffer_size() -> vk::DeviceSize {
+    (mem::size_of::<LightsHeader>() + MAX_LIGHTS * mem::size_of::<LightData>()) as vk::DeviceSize
+}
+
+/// Write `lights` into a host visible buffer of `buffer_size`, leaving out
+/// any past `MAX_LIGHTS`.
+pub fn write_lights(
+    device: &Device<V1_0>,
+    buffer: &Buffer,
+    lights: &[Light],
+    ambient_intensity: f32,
+) {
+    let lights = &lights[..lights.len().min(MAX_LIGHTS)];
+    let header = LightsHeader {
+        count: lights.len() as u32,
+        ambient_intensity,
+        _padding: [0; 2],
+    };
+    let data = lights
+        .iter()
+        .zip(shadow::shadow_layers(lights))
+        .map(|(light, layer)| light.data(layer))
+        .collect::<Vec<_>>();
+
+    buffer.write(device, 0, &[header]);
+    buffer.write(
+        device,
+        mem::size_of::<LightsHeader>() as vk::DeviceSize,
+        &data,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Byte offset of `field` inside `base`.
+    fn offset<T, F>(base: &T, field: &F) -> usize {
+        field as *const F as usize - base as *const T as usize
+    }
+
+    #[test]
+    fn light_data_matches_std430() {
+        let data = Light::point(Point3::new(1.0, 2.0, 3.0), [1.0, 0.5, 0.0], 2.0).data(Some(3));
+        assert_eq!(mem::size_of::<LightData>(), 64);
+        assert_eq!(offset(&data, &data.position_range), 0);
+        assert_eq!(offset(&data, &data.direction), 16);
+        assert_eq!(offset(&data, &data.color), 32);
+        assert_eq!(offset(&data, &data.spot_scale_offset), 48);
+        assert_eq!(offset(&data, &data.kind), 56);
+        assert_eq!(offset(&data, &data.shadow_layer), 60);
+
+        assert_eq!(data.position_range, [1.0, 2.0, 3.0, 0.0]);
+        assert_eq!(data.color, [2.0, 1.0, 0.0, 0.0]);
+        assert_eq!((data.kind, data.shadow_layer), (1, 3));
+    }
+
+    #[test]
+    fn lights_start_after_a_16_byte_header() {
+        // The `Light` array is aligned to its `vec4` members.
+        assert_eq!(mem::size_of::<LightsHeader>(), 16);
+        assert_eq!(buffer_size(), 16 + MAX_LIGHTS as vk::DeviceSize * 64);
+    }
+}
@@ -0,0 +1,364 @@
+pub mod bounds;
+pub mod camera;
+pub mod controller;
+pub mod frustum;
+pub mod light;
+pub mod shadow;
+pub mod transform;
+
+use cgmath::{Matrix4, Point3, SquareMatrix};
+
+use engine::model::{DrawItem, Model};
+
+use self::bounds::Aabb;
+use self::frustum::{Containment, Frustum};
+use self::light::Light;
+use self::transform::Transform;
+
+/// Handle to a node of a `Scene`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+pub struct SceneNode {
+    pub name: Option<String>,
+    pub mesh: Option<usize>,
+    /// Transform relative to the parent node.
+    local_transform: Matrix4<f32>,
+    /// Bounds of the mesh in the node's own space.
+    mesh_bounds: Option<Aabb>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    world_transform: Matrix4<f32>,
+    /// Bounds of the mesh in world space.
+    world_mesh_bounds: Option<Aabb>,
+    /// Bounds of this node and all of its descendants in world space.
+    world_bounds: Option<Aabb>,
+}
+
+impl SceneNode {
+    pub fn local_transform(&self) -> Matrix4<f32> {
+        self.local_transform
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    /// Only up to date after `Scene::update_transforms`.
+    pub fn world_transform(&self) -> Matrix4<f32> {
+        self.world_transform
+    }
+
+    /// Only up to date after `Scene::update_transforms`.
+    pub fn world_bounds(&self) -> Option<Aabb> {
+        self.world_bounds
+    }
+}
+
+/// A hierarchy of nodes placing meshes in the world.
+///
+/// Parents always come before their children in `nodes`, which lets
+/// transforms propagate in a single forward pass and bounds in a single
+/// backward pass.
+pub struct Scene {
+    nodes: Vec<SceneNode>,
+    roots: Vec<NodeId>,
+    lights: Vec<Light>,
+    dirty: bool,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene {
+            nodes: Vec::new(),
+            roots: Vec::new(),
+            lights: Vec::new(),
+            dirty: false,
+        }
+    }
+
+    /// Mirror the node hierarchy of `model`, with mesh indices referring to
+    /// `model.meshes`.
+    pub fn from_model(model: &Model) -> Self {
+        let mut scene = Scene::new();
+
+        let mut stack = model
+            .root_nodes
+            .iter()
+            .rev()
+            .map(|&node| (node, None))
+            .collect::<Vec<_>>();
+
+        while let Some((index, parent)) = stack.pop() {
+            let node = &model.nodes[index];
+            let id = scene.add_node(parent, Matrix4::from(node.local_transform));
+            scene.nodes[id.0].name = node.name.clone();
+
+            if let Some(mesh) = node.mesh {
+                let bounds = model.meshes[mesh]
+                    .bounds()
+                    .map(|(min, max)| Aabb::new(Point3::from(min), Point3::from(max)));
+                scene.set_mesh(id, mesh, bounds);
+            }
+
+            stack.extend(node.children.iter().rev().map(|&child| (child, Some(id))));
+        }
+
+        scene.update_transforms();
+        scene
+    }
+
+    /// Add a node below `parent`, or as a root node without one.
+    pub fn add_node(&mut self, parent: Option<NodeId>, local_transform: Matrix4<f32>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+
+        self.nodes.push(SceneNode {
+            name: None,
+            mesh: None,
+            local_transform,
+            mesh_bounds: None,
+            parent,
+            children: Vec::new(),
+            world_transform: Matrix4::identity(),
+            world_mesh_bounds: None,
+            world_bounds: None,
+        });
+
+        match parent {
+            Some(parent) => self.nodes[parent.0].children.push(id),
+            None => self.roots.push(id),
+        }
+
+        self.dirty = true;
+        id
+    }
+
+    /// Attach a mesh to the node, meshes without bounds are never drawn.
+    pub fn set_mesh(&mut self, id: NodeId, mesh: usize, bounds: Option<Aabb>) {
+        let node = &mut self.nodes[id.0];
+        node.mesh = Some(mesh);
+        node.mesh_bounds = bounds;
+        self.dirty = true;
+    }
+
+    pub fn set_local_transform(&mut self, id: NodeId, local_transform: Matrix4<f32>) {
+        self.nodes[id.0].local_transform = local_transform;
+        self.dirty = true;
+    }
+
+    pub fn set_transform(&mut self, id: NodeId, transform: &Transform) {
+        self.set_local_transform(id, transform.matrix());
+    }
+
+    pub fn node(&self, id: NodeId) -> &SceneNode {
+        &self.nodes[id.0]
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn add_light(&mut self, light: Light) -> usize {
+        self.lights.push(light);
+        self.lights.len() - 1
+    }
+
+    pub fn light_mut(&mut self, index: usize) -> &mut Light {
+        &mut self.lights[index]
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    /// Bounds of the whole scene in world space.
+    ///
+    /// Only up to date after `update_transforms`.
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.roots
+            .iter()
+            .filter_map(|root| self.nodes[root.0].world_bounds)
+            .fold(None, |bounds, root_bounds| match bounds {
+                Some(bounds) => Some(root_bounds.union(&bounds)),
+                None => Some(root_bounds),
+            })
+    }
+
+    /// Recompute world transforms and bounds after nodes changed.
+    pub fn update_transforms(&mut self) {
+        if !self.dirty {
+            return;
+        }
+
+        for index in 0..self.nodes.len() {
+            let parent_transform = match self.nodes[index].parent {
+                Some(parent) => self.nodes[parent.0].world_transform,
+                None => Matrix4::identity(),
+            };
+
+            let node = &mut self.nodes[index];
+            node.world_transform = parent_transform * node.local_transform;
+            node.world_mesh_bounds = node
+                .mesh_bounds
+                .map(|bounds| bounds.transform(&node.world_transform));
+            node.world_bounds = node.world_mesh_bounds;
+        }
+
+        for index in (0..self.nodes.len()).rev() {
+            let bounds = match self.nodes[index].world_bounds {
+                Some(bounds) => bounds,
+                None => continue,
+            };
+
+            if let Some(parent) = self.nodes[index].parent {
+                let parent = &mut self.nodes[parent.0];
+                parent.world_bounds = Some(match parent.world_bounds {
+                    Some(parent_bounds) => parent_bounds.union(&bounds),
+                    None => bounds,
+                });
+            }
+        }
+
+        self.dirty = false;
+    }
+
+    /// The meshes visible through `view_projection`, with their world
+    /// transforms.
+    ///
+    /// Whole subtrees are skipped when their bounds are outside the frustum
+    /// and accepted without further tests when inside it.
+    pub fn draw_list(&self, view_projection: &Matrix4<f32>) -> Vec<DrawItem> {
+        debug_assert!(!self.dirty, "Scene changed without updating transforms");
+
+        let frustum = Frustum::from_matrix(view_projection);
+        let mut draws = Vec::new();
+
+        let mut stack = self
+            .roots
+            .iter()
+            .rev()
+            .map(|&id| (id, Containment::Intersecting))
+            .collect::<Vec<_>>();
+
+        while let Some((id, parent_containment)) = stack.pop() {
+            let node = &self.nodes[id.0];
+
+            let containment = match (parent_containment, node.world_bounds) {
+                (_, None) => continue,
+                (Containment::Inside, _) => Containment::Inside,
+                (_, Some(bounds)) => frustum.contains_aabb(&bounds),
+            };
+
+            if containment == Containment::Outside {
+                continue;
+            }
+
+            if let (Some(mesh), Some(mesh_bounds)) = (node.mesh, node.world_mesh_bounds) {
+                if containment == Containment::Inside
+                    || frustum.contains_aabb(&mesh_bounds) != Containment::Outside
+                {
+                    draws.push(DrawItem {
+                        mesh,
+                        transform: node.world_transform.into(),
+                    });
+                }
+            }
+
+            stack.extend(node.children.iter().rev().map(|&child| (child, containment)));
+        }
+
+        draws
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Scene::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Vector3};
+    use scene::camera::Camera;
+
+    fn unit_box() -> Option<Aabb> {
+        Some(Aabb::new(
+            Point3::new(-1.0, -1.0, -1.0),
+            Point3::new(1.0, 1.0, 1.0),
+        ))
+    }
+
+    fn translation(x: f32, y: f32, z: f32) -> Matrix4<f32> {
+        Matrix4::from_translation(Vector3::new(x, y, z))
+    }
+
+    fn drawn_meshes(scene: &Scene, camera: &Camera) -> Vec<usize> {
+        let mut meshes = scene
+            .draw_list(&camera.view_projection())
+            .iter()
+            .map(|item| item.mesh)
+            .collect::<Vec<_>>();
+        meshes.sort();
+        meshes
+    }
+
+    #[test]
+    fn transforms_propagate_to_children() {
+        let mut scene = Scene::new();
+        let root = scene.add_node(None, translation(1.0, 0.0, 0.0));
+        let child = scene.add_node(Some(root), Matrix4::from_scale(2.0));
+        let grandchild = scene.add_node(Some(child), translation(0.0, 1.0, 0.0));
+        scene.set_mesh(grandchild, 0, unit_box());
+        scene.update_transforms();
+
+        let world = scene.node(grandchild).world_transform();
+        assert_eq!(world, translation(1.0, 2.0, 0.0) * Matrix4::from_scale(2.0));
+        assert_eq!(scene.node(child).children(), &[grandchild]);
+        assert_eq!(scene.node(grandchild).parent(), Some(child));
+
+        // Bounds grow up the hierarchy, nodes without meshes included.
+        let bounds = Aabb::new(Point3::new(-1.0, 0.0, -2.0), Point3::new(3.0, 4.0, 2.0));
+        assert_eq!(scene.node(grandchild).world_bounds(), Some(bounds));
+        assert_eq!(scene.node(root).world_bounds(), Some(bounds));
+        assert_eq!(scene.bounds(), Some(bounds));
+
+        // Moving the root moves everything below it.
+        scene.set_local_transform(root, translation(0.0, 0.0, 5.0));
+        scene.update_transforms();
+        let world = scene.node(grandchild).world_transform();
+        assert_eq!(world, translation(0.0, 2.0, 5.0) * Matrix4::from_scale(2.0));
+    }
+
+    #[test]
+    fn draw_list_culls_nodes_outside_the_frustum() {
+        let mut scene = Scene::new();
+        let front = scene.add_node(None, translation(0.0, 0.0, -10.0));
+        scene.set_mesh(front, 0, unit_box());
+        let behind = scene.add_node(None, translation(0.0, 0.0, 10.0));
+        scene.set_mesh(behind, 1, unit_box());
+
+        // A child in view below a parent that is not.
+        let parent = scene.add_node(None, translation(0.0, 0.0, 10.0));
+        scene.set_mesh(parent, 2, unit_box());
+        let child = scene.add_node(Some(parent), translation(0.0, 0.0, -20.0));
+        scene.set_mesh(child, 3, unit_box());
+
+        // A mesh without bounds is never drawn.
+        let unbounded = scene.add_node(None, translation(0.0, 0.0, -10.0));
+        scene.set_mesh(unbounded, 4, None);
+        scene.update_transforms();
+
+        let camera = Camera::perspective(Deg(90.0).into(), 1.0, 0.1, 100.0);
+        assert_eq!(drawn_meshes(&scene, &camera), vec![0, 3]);
+
+        let mut turned = camera;
+        turned.yaw = Deg(180.0).into();
+        assert_eq!(drawn_meshes(&scene, &turned), vec![1, 2]);
+    }
+}
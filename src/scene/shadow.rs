@@ -0,0 +1,357 @@
+use cgmath::{
+    Angle, Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Vector3, Vector4,
+};
+
+use engine::model::{Matrix, IDENTITY};
+
+use super::bounds::Aabb;
+use super::camera::{Camera, Projection};
+use super::light::{Light, LightKind};
+
+/// Cascades of the shadow maps of a directional light.
+pub const CASCADES: usize = 4;
+/// Spot lights casting shadows beyond this many cast none.
+pub const MAX_SPOT_SHADOWS: usize = 4;
+/// Layers of the shadow maps, the cascades followed by the spot lights.
+pub const SHADOW_LAYERS: usize = CASCADES + MAX_SPOT_SHADOWS;
+
+/// Blends evenly spaced cascade splits, at 0, with logarithmically spaced
+/// ones, at 1.
+const CASCADE_SPLIT_LAMBDA: f32 = 0.75;
+
+/// Shadow data as laid out in the `Shadows` uniform block of the shaders.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ShadowUniform {
+    /// World to shadow map clip space of every layer.
+    pub view_projections: [Matrix; SHADOW_LAYERS],
+    /// View space depth at which each cascade ends.
+    pub cascade_splits: [f32; CASCADES],
+    /// One texel of the shadow maps in texture coordinates, for filtering.
+    pub texel_size: f32,
+    pub _padding: [f32; 3],
+}
+
+/// A layer of the shadow maps to render.
+#[derive(Clone, Copy, Debug)]
+pub struct ShadowView {
+    pub layer: usize,
+    pub view_projection: Matrix4<f32>,
+    /// Spot light maps use a perspective projection, cascades an
+    /// orthographic one.
+    pub perspective: bool,
+}
+
+/// The shadow maps of a frame and how the shaders find them.
+#[derive(Clone, Debug)]
+pub struct Shadows {
+    pub views: Vec<ShadowView>,
+    pub uniform: ShadowUniform,
+}
+
+impl Shadows {
+    /// Fit shadow maps `resolution` texels wide for `lights` around what
+    /// `camera` sees of `bounds`, the bounds of everything casting shadows.
+    ///
+    /// Cascades cover the view up to the far side of `bounds`, as nothing
+    /// beyond it can be shadowed.
+    pub fn new(camera: &Camera, lights: &[Light], bounds: &Aabb, resolution: u32) -> Self {
+        let mut uniform = ShadowUniform {
+            view_projections: [IDENTITY; SHADOW_LAYERS],
+            cascade_splits: [0.0; CASCADES],
+            texel_size: 1.0 / resolution as f32,
+            _padding: [0.0; 3],
+        };
+        let mut views = Vec::new();
+
+        let (near, far) = match camera.projection {
+            Projection::Perspective { near, far, .. }
+            | Projection::Orthographic { near, far, .. } => (near, far),
+        };
+        let scene_radius = bounds.half_extents().magnitude();
+        let shadow_distance = ((bounds.center() - camera.position).magnitude() + scene_radius)
+            .min(far)
+            .max(near * 2.0);
+        let splits = cascade_splits(near, shadow_distance);
+        uniform.cascade_splits = splits;
+
+        for (light, layer) in lights.iter().zip(shadow_layers(lights)) {
+            let layer = match layer {
+                Some(layer) => layer,
+                None => continue,
+            };
+
+            match light.kind {
+                LightKind::Directional => {
+                    let mut slice_near = near;
+                    for (cascade, &slice_far) in splits.iter().enumerate() {
+                        let corners = slice_corners(camera, slice_near, slice_far);
+                        let view_projection =
+                            cascade_view_projection(&corners, light.direction, bounds, resolution);
+                        views.push(ShadowView {
+                            layer: layer + cascade,
+                            view_projection,
+                            perspective: false,
+                        });
+                        slice_near = slice_far;
+                    }
+                }
+                LightKind::Spot { outer_cone, .. } => {
+                    let view_projection = spot_view_projection(light, outer_cone, bounds);
+                    views.push(ShadowView {
+                        layer,
+                        view_projection,
+                        perspective: true,
+                    });
+                }
+                LightKind::Point => {}
+            }
+        }
+
+        for view in &views {
+            uniform.view_projections[view.layer] = view.view_projection.into();
+        }
+
+        Shadows { views, uniform }
+    }
+
+    /// Whether a light renders into `layer`, unused layers are left cleared.
+    pub fn uses_layer(&self, layer: usize) -> bool {
+        self.views.iter().any(|view| view.layer == layer)
+    }
+}
+
+/// The first layer of the shadow maps of every light, for those that cast
+/// shadows and got some.
+///
+/// The first directional light casting shadows gets the cascades and spot
+/// lights get a layer each after them, while there are layers left. Point
+/// lights cast none.
+pub fn shadow_layers(lights: &[Light]) -> Vec<Option<usize>> {
+    let mut directional = false;
+    let mut spots = 0;
+
+    lights
+        .iter()
+        .map(|light| {
+            if !light.cast_shadows {
+                return None;
+            }
+
+            match light.kind {
+                LightKind::Directional if !directional => {
+                    directional = true;
+                    Some(0)
+                }
+                LightKind::Spot { .. } if spots < MAX_SPOT_SHADOWS => {
+                    spots += 1;
+                    Some(CASCADES + spots - 1)
+                }
+                _ => None,
+            }
+        }).collect()
+}
+
+/// Where each cascade ends between `near` and `far`.
+fn cascade_splits(near: f32, far: f32) -> [f32; CASCADES] {
+    let mut splits = [far; CASCADES];
+    for (index, split) in splits.iter_mut().enumerate() {
+        let fraction = (index + 1) as f32 / CASCADES as f32;
+        let logarithmic = near * (far / near).powf(fraction);
+        let uniform = near + (far - near) * fraction;
+        *split = CASCADE_SPLIT_LAMBDA * logarithmic + (1.0 - CASCADE_SPLIT_LAMBDA) * uniform;
+    }
+    splits
+}
+
+/// The world space corners of the part of the view between the view space
+/// depths `near` and `far`.
+fn slice_corners(camera: &Camera, near: f32, far: f32) -> Vec<Point3<f32>> {
+    let to_world = camera.view().invert().unwrap_or_else(Matrix4::identity);
+
+    let mut corners = Vec::with_capacity(8);
+    for &depth in &[near, far] {
+        let half_height = match camera.projection {
+            Projection::Perspective { fov_y, .. } => depth * (fov_y / 2.0).tan(),
+            Projection::Orthographic { height, .. } => height / 2.0,
+        };
+        let half_width = half_height * camera.aspect_ratio;
+
+        for &(x, y) in &[(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+            let corner = Point3::new(x * half_width, y * half_height, -depth);
+            corners.push(transform_point(&to_world, corner));
+        }
+    }
+    corners
+}
+
+/// An orthographic projection along `direction` around a sphere bounding
+/// `corners`, reaching back to include every caster in `bounds`.
+///
+/// The sphere keeps the size of the map from changing as the camera turns,
+/// and snapping its center to whole texels keeps shadow edges from
+/// flickering as the camera moves.
+fn cascade_view_projection(
+    corners: &[Point3<f32>],
+    direction: Vector3<f32>,
+    bounds: &Aabb,
+    resolution: u32,
+) -> Matrix4<f32> {
+    let center = Point3::centroid(corners);
+    let radius = corners
+        .iter()
+        .map(|&corner| (corner - center).magnitude())
+        .fold(0.0, f32::max);
+
+    let view = Matrix4::look_at_dir(Point3::origin(), direction, up_vector(direction));
+    let texel = 2.0 * radius / resolution as f32;
+    let light_center = transform_point(&view, center);
+    let x = (light_center.x / texel).floor() * texel;
+    let y = (light_center.y / texel).floor() * texel;
+
+    // The light looks down -Z, the largest Z is closest to it.
+    let (min_z, max_z) = aabb_corners(bounds)
+        .iter()
+        .map(|&corner| transform_point(&view, corner).z)
+        .fold(
+            (light_center.z - radius, light_center.z + radius),
+            |(min_z, max_z), z| (min_z.min(z), max_z.max(z)),
+        );
+
+    orthographic(x - radius, x + radius, y - radius, y + radius, -max_z, -min_z) * view
+}
+
+/// A perspective projection covering the cone of a spot light, out to its
+/// range or to the far side of `bounds` without one.
+fn spot_view_projection(light: &Light, outer_cone: Rad<f32>, bounds: &Aabb) -> Matrix4<f32> {
+    let far = light
+        .range
+        .unwrap_or_else(|| {
+            aabb_corners(bounds)
+                .iter()
+                .map(|&corner| (corner - light.position).magnitude())
+                .fold(0.0, f32::max)
+        }).max(0.01);
+    let fov_y = Rad((outer_cone.0 * 2.0).min(Rad::from(Deg(170.0)).0));
+
+    let projection = Camera::perspective(fov_y, 1.0, far * 0.005, far).projection();
+    let view = Matrix4::look_at_dir(
+        light.position,
+        light.direction,
+        up_vector(light.direction),
+    );
+    projection * view
+}
+
+/// An up vector that is not parallel to `direction`.
+fn up_vector(direction: Vector3<f32>) -> Vector3<f32> {
+    if direction.normalize().y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    }
+}
+
+/// Maps the box to Vulkan clip space like `Camera::projection` does, with
+/// `near` and `far` as distances along -Z.
+fn orthographic(
+    left: f32,
+    right: f32,
+    bottom: f32,
+    top: f32,
+    near: f32,
+    far: f32,
+) -> Matrix4<f32> {
+    Matrix4::from_cols(
+        Vector4::new(2.0 / (right - left), 0.0, 0.0, 0.0),
+        Vector4::new(0.0, -2.0 / (top - bottom), 0.0, 0.0),
+        Vector4::new(0.0, 0.0, 1.0 / (near - far), 0.0),
+        Vector4::new(
+            -(right + left) / (right - left),
+            (top + bottom) / (top - bottom),
+            near / (near - far),
+            1.0,
+        ),
+    )
+}
+
+fn aabb_corners(aabb: &Aabb) -> [Point3<f32>; 8] {
+    let (min, max) = (aabb.min, aabb.max);
+    [
+        Point3::new(min.x, min.y, min.z),
+        Point3::new(max.x, min.y, min.z),
+        Point3::new(min.x, max.y, min.z),
+        Point3::new(max.x, max.y, min.z),
+        Point3::new(min.x, min.y, max.z),
+        Point3::new(max.x, min.y, max.z),
+        Point3::new(min.x, max.y, max.z),
+        Point3::new(max.x, max.y, max.z),
+    ]
+}
+
+fn transform_point(matrix: &Matrix4<f32>, point: Point3<f32>) -> Point3<f32> {
+    let point = matrix * point.to_homogeneous();
+    Point3::new(point.x, point.y, point.z) / point.w
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spot() -> Light {
+        Light::spot(
+            Point3::new(0.0, 2.0, 0.0),
+            Vector3::new(0.0, -1.0, 0.0),
+            Rad(0.3),
+            Rad(0.5),
+            [1.0; 3],
+            1.0,
+        )
+    }
+
+    #[test]
+    fn cascade_splits_increase_up_to_far() {
+        let splits = cascade_splits(0.1, 100.0);
+        for pair in splits.windows(2) {
+            assert!(pair[0] < pair[1], "{:?}", splits);
+        }
+        assert!(splits[0] > 0.1);
+        assert!((splits[CASCADES - 1] - 100.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn shadow_layers_follow_the_cascades() {
+        let directional = Light::directional(Vector3::new(0.0, -1.0, 0.0), [1.0; 3], 1.0);
+        let mut lights = vec![
+            spot(),
+            directional.with_shadows(),
+            Light::point(Point3::new(0.0, 1.0, 0.0), [1.0; 3], 1.0).with_shadows(),
+            directional.with_shadows(),
+        ];
+        for _ in 0..MAX_SPOT_SHADOWS + 1 {
+            lights.push(spot().with_shadows());
+        }
+
+        let mut expected = vec![None, Some(0), None, None];
+        for spot in 0..MAX_SPOT_SHADOWS {
+            expected.push(Some(CASCADES + spot));
+        }
+        expected.push(None);
+        assert_eq!(shadow_layers(&lights), expected);
+    }
+
+    #[test]
+    fn orthographic_maps_near_and_far_to_depth_range() {
+        let projection = orthographic(-2.0, 2.0, -1.0, 1.0, 1.0, 10.0);
+        let depth = |z: f32| {
+            let clip = projection * Vector4::new(0.0, 0.0, z, 1.0);
+            clip.z / clip.w
+        };
+        assert!(depth(-1.0).abs() < 1e-6);
+        assert!((depth(-10.0) - 1.0).abs() < 1e-6);
+        assert!((depth(-5.5) - 0.5).abs() < 1e-6);
+
+        let corner = projection * Vector4::new(2.0, 1.0, -1.0, 1.0);
+        assert!((corner.x - 1.0).abs() < 1e-6 && (corner.y + 1.0).abs() < 1e-6);
+    }
+}
@@ -1,28 +1,39 @@
 use ash::version::{DeviceV1_0, V1_0};
 use ash::vk;
 use ash::Device;
+use std::collections::hash_map::DefaultHasher;
 use std::fs::File;
+use std::hash::{Hash, Hasher};
 use std::io::Read;
 use std::path::Path;
 use std::ptr;
 
 use engine::pipeline::ShaderType;
 
+const ENTRY_POINT: &[u8] = b"main\0";
+
 pub struct Shader {
     pub module: vk::ShaderModule,
     pub shader_type: ShaderType,
+    /// Hash of the SPIR-V the module was created from.
+    pub code_hash: u64,
 }
 
 impl Shader {
     pub fn load<P: AsRef<Path>>(device: &Device<V1_0>, path: P, shader_type: ShaderType) -> Self {
-        let file = File::open(path).unwrap();
-        let bytes = file.bytes().filter_map(|byte| byte.ok()).collect();
+        let mut bytes = Vec::new();
+        File::open(path).unwrap().read_to_end(&mut bytes).unwrap();
+
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        let code_hash = hasher.finish();
 
         let module = Shader::create_module(device, bytes);
 
         Shader {
             module,
             shader_type,
+            code_hash,
         }
     }
 
@@ -42,15 +53,120 @@ impl Shader {
         }
     }
 
-    pub fn create_stage(&self) -> vk::PipelineShaderStageCreateInfo {
+    /// The returned stage points at `specialization`, which has to outlive it.
+    pub fn create_stage(
+        &self,
+        specialization: Option<&vk::SpecializationInfo>,
+    ) -> vk::PipelineShaderStageCreateInfo {
         vk::PipelineShaderStageCreateInfo {
             s_type: vk::StructureType::PipelineShaderStageCreateInfo,
             p_next: ptr::null(),
             flags: Default::default(),
             stage: self.shader_type.to_vulkan(),
-            p_specialization_info: ptr::null(),
-            p_name: "main".as_ptr() as *const i8,
+            p_specialization_info: specialization
+                .map(|info| info as *const _)
+                .unwrap_or(ptr::null()),
+            p_name: ENTRY_POINT.as_ptr() as *const i8,
             module: self.module,
         }
     }
 }
+
+/// A value that can be bound to a `layout(constant_id = N) const` in a shader.
+pub trait SpecializationConstant: Copy {
+    fn to_bytes(self) -> [u8; 4];
+}
+
+impl SpecializationConstant for bool {
+    fn to_bytes(self) -> [u8; 4] {
+        (self as vk::Bool32).to_bytes()
+    }
+}
+
+impl SpecializationConstant for u32 {
+    fn to_bytes(self) -> [u8; 4] {
+        self.to_ne_bytes()
+    }
+}
+
+impl SpecializationConstant for i32 {
+    fn to_bytes(self) -> [u8; 4] {
+        self.to_ne_bytes()
+    }
+}
+
+impl SpecializationConstant for f32 {
+    fn to_bytes(self) -> [u8; 4] {
+        self.to_bits().to_bytes()
+    }
+}
+
+/// Specialization constant values for a single shader stage.
+///
+/// Lets one SPIR-V module be turned into several pipelines, for example with
+/// a different light count or with features toggled off.
+#[derive(Clone, Debug, Default)]
+pub struct Specialization {
+    entries: Vec<vk::SpecializationMapEntry>,
+    data: Vec<u8>,
+}
+
+impl Specialization {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set the value of `constant_id`, replacing any earlier value for it.
+    pub fn with_constant<T: SpecializationConstant>(mut self, constant_id: u32, value: T) -> Self {
+        let bytes = value.to_bytes();
+
+        match self
+            .entries
+            .iter()
+            .find(|entry| entry.constant_id == constant_id)
+        {
+            Some(entry) => {
+                let offset = entry.offset as usize;
+                self.data[offset..offset + bytes.len()].copy_from_slice(&bytes);
+            }
+            None => {
+                self.entries.push(vk::SpecializationMapEntry {
+                    constant_id,
+                    offset: self.data.len() as u32,
+                    size: bytes.len(),
+                });
+                self.data.extend_from_slice(&bytes);
+            }
+        }
+
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The returned info points into `self`, which has to outlive it.
+    pub fn info(&self) -> vk::SpecializationInfo {
+        vk::SpecializationInfo {
+            map_entry_count: self.entries.len() as u32,
+            p_map_entries: self.entries.as_ptr(),
+            data_size: self.data.len(),
+            p_data: self.data.as_ptr() as *const vk::c_void,
+        }
+    }
+}
+
+/// Hashes the values by constant ID, the order the constants were set in
+/// doesn't change the hash.
+impl Hash for Specialization {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by_key(|entry| entry.constant_id);
+        for entry in entries {
+            let offset = entry.offset as usize;
+            entry.constant_id.hash(state);
+            self.data[offset..offset + entry.size].hash(state);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Frames taking longer than this only advance the simulation by this much,
+/// so a stall does not have to be caught up with a burst of updates.
+const MAX_FRAME_TIME: f32 = 0.25;
+
+fn seconds(duration: Duration) -> f32 {
+    duration.as_secs() as f32 + duration.subsec_nanos() as f32 * 1e-9
+}
+
+/// Timing information handed to update and render code.
+#[derive(Clone, Copy, Debug)]
+pub struct Time {
+    /// Seconds simulated by every fixed update.
+    pub fixed_delta: f32,
+    /// Seconds between the start of the previous frame and this one.
+    pub frame_delta: f32,
+    /// Seconds since the timer started.
+    pub elapsed: f32,
+    /// How far rendering is between the last two fixed updates, from 0 to 1.
+    pub alpha: f32,
+    /// Frames started so far, including the current one.
+    pub frame_count: u64,
+    /// Fixed updates run so far.
+    pub update_count: u64,
+}
+
+/// Drives a loop that renders as often as it can, or up to a frame cap,
+/// while updating on a fixed timestep.
+///
+/// ```text
+/// loop {
+///     let updates = timer.begin_frame();
+///     for _ in 0..updates {
+///         update(timer.time().fixed_delta);
+///     }
+///     render(timer.time().alpha);
+///     timer.end_frame();
+/// }
+/// ```
+pub struct FrameTimer {
+    frame_cap: Option<Duration>,
+    start: Instant,
+    frame_start: Instant,
+    accumulator: f32,
+    time: Time,
+}
+
+impl FrameTimer {
+    pub fn new(updates_per_second: u32) -> Self {
+        assert!(updates_per_second > 0, "at least one update per second");
+
+        let fixed_timestep = Duration::new(0, 1_000_000_000 / updates_per_second);
+        let now = Instant::now();
+
+        FrameTimer {
+            frame_cap: None,
+            start: now,
+            frame_start: now,
+            accumulator: 0.0,
+            time: Time {
+                fixed_delta: seconds(fixed_timestep),
+                frame_delta: 0.0,
+                elapsed: 0.0,
+                alpha: 0.0,
+                frame_count: 0,
+                update_count: 0,
+            },
+        }
+    }
+
+    /// Sleep at the end of frames that finish early so no more than
+    /// `frames_per_second` are rendered, `None` renders as fast as possible.
+    pub fn with_frame_cap(mut self, frames_per_second: Option<u32>) -> Self {
+        self.set_frame_cap(frames_per_second);
+        self
+    }
+
+    pub fn set_frame_cap(&mut self, frames_per_second: Option<u32>) {
+        self.frame_cap = frames_per_second
+            .filter(|&fps| fps > 0)
+            .map(|fps| Duration::new(0, 1_000_000_000 / fps));
+    }
+
+    pub fn time(&self) -> &Time {
+        &self.time
+    }
+
+    /// Start a new frame and return how many fixed updates it has to run.
+    pub fn begin_frame(&mut self) -> u32 {
+        self.begin_frame_at(Instant::now())
+    }
+
+    fn begin_frame_at(&mut self, now: Instant) -> u32 {
+        let frame_delta = seconds(now.duration_since(self.frame_start));
+        self.frame_start = now;
+
+        self.time.frame_delta = frame_delta;
+        self.time.elapsed = seconds(now.duration_since(self.start));
+        self.time.frame_count += 1;
+
+        self.accumulator += frame_delta.min(MAX_FRAME_TIME);
+
+        let fixed_delta = self.time.fixed_delta;
+        let mut updates = 0;
+        while self.accumulator >= fixed_delta {
+            self.accumulator -= fixed_delta;
+            updates += 1;
+        }
+
+        self.time.update_count += updates as u64;
+        self.time.alpha = self.accumulator / fixed_delta;
+
+        updates
+    }
+
+    /// Finish a frame that had nothing to render by sleeping until the next
+    /// fixed update is due, instead of spinning through empty frames.
+    pub fn wait_for_update(&self) {
+        let until_update = self.time.fixed_delta - self.accumulator;
+        let frame_time = seconds(self.frame_start.elapsed());
+        if frame_time < until_update {
+            // Below a second, since the fixed timestep is at most one.
+            let nanos = ((until_update - frame_time) * 1e9).ceil() as u32;
+            thread::sleep(Duration::new(0, nanos));
+        }
+    }
+
+    /// Finish the frame, waiting out the rest of it when there is a frame
+    /// cap.
+    pub fn end_frame(&self) {
+        if let Some(frame_cap) = self.frame_cap {
+            let frame_time = self.frame_start.elapsed();
+            if frame_time < frame_cap {
+                thread::sleep(frame_cap - frame_time);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn after(timer: &FrameTimer, millis: u64) -> Instant {
+        timer.start + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn frames_accumulate_into_fixed_updates() {
+        let mut timer = FrameTimer::new(100);
+        assert_eq!(timer.begin_frame_at(after(&timer, 5)), 0);
+        assert_eq!(timer.begin_frame_at(after(&timer, 12)), 1);
+        assert_eq!(timer.begin_frame_at(after(&timer, 45)), 3);
+
+        let time = timer.time();
+        assert_eq!(time.frame_count, 3);
+        assert_eq!(time.update_count, 4);
+        assert!((time.frame_delta - 0.033).abs() < 1e-4);
+        assert!((time.elapsed - 0.045).abs() < 1e-4);
+    }
+
+    #[test]
+    fn alpha_is_the_fraction_of_the_next_update() {
+        let mut timer = FrameTimer::new(100);
+        timer.begin_frame_at(after(&timer, 2));
+        assert!((timer.time().alpha - 0.2).abs() < 1e-3);
+
+        timer.begin_frame_at(after(&timer, 17));
+        assert!((timer.time().alpha - 0.7).abs() < 1e-3);
+    }
+
+    #[test]
+    fn long_frames_are_clamped() {
+        // A timestep that adds up without rounding errors.
+        let mut timer = FrameTimer::new(64);
+        let updates = timer.begin_frame_at(after(&timer, 10_000));
+        assert_eq!(updates, (MAX_FRAME_TIME * 64.0) as u32);
+        assert_eq!(timer.time().alpha, 0.0);
+        // The elapsed time is not clamped, only the simulated time.
+        assert!((timer.time().elapsed - 10.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn waiting_reaches_the_next_update() {
+        let mut timer = FrameTimer::new(50);
+        assert_eq!(timer.begin_frame(), 0);
+        timer.wait_for_update();
+        assert!(timer.begin_frame() >= 1);
+    }
+}
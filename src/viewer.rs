@@ -0,0 +1,824 @@
+use ash::version::{DeviceV1_0, V1_0};
+use ash::vk;
+use ash::Device;
+use cgmath::{Deg, InnerSpace, Point3, Rad, Vector3};
+use std::mem;
+use winit::WindowEvent;
+
+use ash_toy_engine::app::{App, Context, Frame, TargetId};
+use ash_toy_engine::assets;
+use ash_toy_engine::config::WindowConfig;
+use ash_toy_engine::engine::buffer::Buffer;
+use ash_toy_engine::engine::deferred::{DeferredRenderer, GBuffer, Shading};
+use ash_toy_engine::engine::depth;
+use ash_toy_engine::engine::descriptor::{self, DescriptorPool, DescriptorSetLayout};
+use ash_toy_engine::engine::environment::{Environment, SkyDesc};
+use ash_toy_engine::engine::mesh;
+use ash_toy_engine::engine::model::{MeshPushConstants, Model};
+use ash_toy_engine::engine::pbr::{self, PbrMaterials};
+use ash_toy_engine::engine::pipeline::{self, Pipeline, RenderPass};
+use ash_toy_engine::engine::post::{self, PostSettings, PostStack, PostTargets};
+use ash_toy_engine::engine::render_target::RenderTarget;
+use ash_toy_engine::engine::shadow::ShadowMaps;
+use ash_toy_engine::input::Input;
+use ash_toy_engine::scene::camera::{Camera, CameraUniform, Projection};
+use ash_toy_engine::scene::controller::{CameraController, FlyController, OrbitController};
+use ash_toy_engine::scene::light::{self, Light};
+use ash_toy_engine::scene::shadow::{self, ShadowUniform, Shadows};
+use ash_toy_engine::scene::Scene;
+use ash_toy_engine::shader::Shader;
+use ash_toy_engine::time::Time;
+
+/// The main window and the preview.
+const WINDOWS: usize = 2;
+
+/// Texels along a side of the environment cube map.
+const ENVIRONMENT_SIZE: u32 = 128;
+
+/// Scales the light coming from the environment.
+const AMBIENT_INTENSITY: f32 = 1.0;
+
+/// Texels along a side of every shadow map.
+const SHADOW_RESOLUTION: u32 = 1024;
+
+/// Shows a glTF or OBJ model with an orbit and a fly camera, or the built-in
+/// triangle when there is no model.
+///
+/// The scene is rendered into an HDR target per window, which the post
+/// processing stack takes to the window. Models are shaded forward or
+/// deferred, the triangle always forward.
+pub struct Viewer {
+    model_path: Option<String>,
+    camera: Camera,
+    /// The camera before the last update, for interpolation.
+    previous_camera: Camera,
+    controller: Box<dyn CameraController>,
+    flying: bool,
+    scene: Scene,
+    /// The second window, while it is open.
+    preview: Option<TargetId>,
+    /// Handed to the post-processing stack in `init`.
+    post_settings: PostSettings,
+    shading: Shading,
+    resources: Option<Resources>,
+}
+
+/// Draws the scene into the targets of the windows.
+enum SceneRenderer {
+    /// Into a `RenderTarget` of `pass`, lighting every fragment as it is
+    /// drawn.
+    Forward {
+        pass: RenderPass,
+        pipeline: Pipeline,
+    },
+    Deferred(DeferredRenderer),
+}
+
+/// What a window's scene is rendered into, matching the `SceneRenderer`.
+enum SceneTarget {
+    Forward(RenderTarget),
+    Deferred(GBuffer),
+}
+
+impl SceneTarget {
+    fn extent(&self) -> vk::Extent2D {
+        match *self {
+            SceneTarget::Forward(ref target) => target.extent,
+            SceneTarget::Deferred(ref gbuffer) => gbuffer.extent,
+        }
+    }
+
+    /// The HDR scene, for the post-processing stack.
+    fn view(&self) -> vk::ImageView {
+        match *self {
+            SceneTarget::Forward(ref target) => target.color_view,
+            SceneTarget::Deferred(ref gbuffer) => gbuffer.output_view,
+        }
+    }
+
+    fn sampler(&self) -> vk::Sampler {
+        match *self {
+            SceneTarget::Forward(ref target) => target.sampler,
+            SceneTarget::Deferred(ref gbuffer) => gbuffer.sampler,
+        }
+    }
+
+    fn destroy(&self, device: &Device<V1_0>) {
+        match *self {
+            SceneTarget::Forward(ref target) => target.destroy(device),
+            SceneTarget::Deferred(ref gbuffer) => gbuffer.destroy(device),
+        }
+    }
+}
+
+/// Everything created on the device in `init`.
+struct Resources {
+    renderer: SceneRenderer,
+    post: PostStack,
+    frame_set_layout: DescriptorSetLayout,
+    material_set_layout: DescriptorSetLayout,
+    descriptor_pool: DescriptorPool,
+    /// One camera, light and shadow buffer and set per frame in flight, for
+    /// the main window followed by the preview.
+    camera_buffers: Vec<Buffer>,
+    light_buffers: Vec<Buffer>,
+    shadow_buffers: Vec<Buffer>,
+    frame_sets: Vec<vk::DescriptorSet>,
+    environment: Environment,
+    /// Shared by the windows, each renders them from its own camera before
+    /// drawing the scene.
+    shadow_maps: ShadowMaps,
+    /// The scene of every window and the post-processing targets reading
+    /// it, created at the size of the window when it is first drawn.
+    targets: Vec<Option<(SceneTarget, PostTargets)>>,
+    model: Option<Model>,
+    materials: Option<PbrMaterials>,
+}
+
+impl Viewer {
+    pub fn new(model_path: Option<String>, post_settings: PostSettings, shading: Shading) -> Self {
+        // Reversed depth keeps precision far away from the camera.
+        let camera = Camera::perspective(Deg(60.0).into(), 1.0, 0.1, 1000.0).with_reversed_z();
+
+        Viewer {
+            model_path,
+            camera,
+            previous_camera: camera,
+            controller: Box::new(OrbitController::new(Point3::new(0.0, 0.0, 0.0), 1.0)),
+            flying: false,
+            scene: Scene::new(),
+            preview: None,
+            post_settings,
+            shading,
+            resources: None,
+        }
+    }
+
+    /// Index of `target` into the per window resources.
+    fn window_index(&self, target: TargetId) -> Option<usize> {
+        if target == TargetId::MAIN {
+            Some(0)
+        } else if Some(target) == self.preview {
+            Some(1)
+        } else {
+            None
+        }
+    }
+
+    /// Flip between orbiting the scene and flying freely, keeping the camera
+    /// where it is.
+    fn switch_controller(&mut self, flying: bool) {
+        let (target, radius) = scene_focus(&self.scene);
+
+        self.controller = if flying {
+            Box::new(FlyController::new(radius))
+        } else {
+            let distance = (self.camera.position - target)
+                .magnitude()
+                .max(radius * 0.1);
+            self.camera.look_at(target);
+            Box::new(OrbitController::new(target, distance))
+        };
+    }
+}
+
+impl App for Viewer {
+    fn init(&mut self, ctx: &mut Context) {
+        let device = &ctx.device;
+
+        let frame_set_layout =
+            pbr::frame_set_layout(device).expect("Unable to create frame descriptor set layout");
+        let material_set_layout = pbr::material_set_layout(device)
+            .expect("Unable to create material descriptor set layout");
+
+        let renderer = match (&self.model_path, self.shading) {
+            (&Some(_), Shading::Deferred) => {
+                let depth_format = depth::select_gbuffer_depth_format(&ctx.instance, ctx.pdevice)
+                    .expect("No supported G-buffer depth format");
+                SceneRenderer::Deferred(DeferredRenderer::new(
+                    device,
+                    &ctx.device_properties.limits,
+                    depth_format,
+                    &frame_set_layout,
+                    &material_set_layout,
+                    self.camera.reversed_z,
+                ))
+            }
+            (model_path, _) => {
+                let pass = RenderPass::offscreen(
+                    device,
+                    post::HDR_FORMAT,
+                    ctx.render_pass.depth_format,
+                    ctx.samples,
+                );
+                let pipeline = match *model_path {
+                    Some(_) => create_mesh_pipeline(
+                        ctx,
+                        pass,
+                        &frame_set_layout,
+                        &material_set_layout,
+                        &self.camera,
+                    ),
+                    None => create_triangle_pipeline(ctx, pass),
+                };
+                SceneRenderer::Forward { pass, pipeline }
+            }
+        };
+
+        let post = PostStack::new(
+            device,
+            &ctx.device_properties.limits,
+            ctx.render_pass,
+            self.post_settings.clone(),
+        );
+
+        let model = self.model_path.as_ref().map(|path| {
+            assets::load_model(
+                device,
+                &ctx.memory_properties,
+                &ctx.command_pool,
+                ctx.queue,
+                path,
+            ).expect("Unable to load model")
+        });
+
+        let materials = model.as_ref().map(|model| {
+            PbrMaterials::new(
+                device,
+                &ctx.memory_properties,
+                &ctx.command_pool,
+                ctx.queue,
+                model,
+                &material_set_layout,
+            ).expect("Unable to create materials")
+        });
+
+        let sky = SkyDesc::default();
+        let environment = Environment::sky(
+            &ctx.instance,
+            ctx.pdevice,
+            device,
+            &ctx.memory_properties,
+            &mut ctx.uploader,
+            &sky,
+            ENVIRONMENT_SIZE,
+        ).unwrap_or_else(|error| panic!("Unable to create the environment, {}", error));
+
+        self.scene = model
+            .as_ref()
+            .map(Scene::from_model)
+            .unwrap_or_default();
+
+        let shadow_format = depth::select_shadow_format(&ctx.instance, ctx.pdevice)
+            .expect("No supported shadow map format");
+        let shadow_maps = ShadowMaps::new(
+            &ctx.instance,
+            ctx.pdevice,
+            device,
+            &ctx.memory_properties,
+            &ctx.device_properties.limits,
+            shadow_format,
+            SHADOW_RESOLUTION,
+            shadow::SHADOW_LAYERS as u32,
+        ).unwrap_or_else(|error| panic!("Unable to create the shadow maps, {}", error));
+
+        let camera_set_count = WINDOWS * ctx.frames_in_flight;
+        let descriptor_pool = DescriptorPool::new(
+            device,
+            &[
+                (vk::DescriptorType::UniformBuffer, 2 * camera_set_count as u32),
+                (vk::DescriptorType::StorageBuffer, camera_set_count as u32),
+                (vk::DescriptorType::CombinedImageSampler, 2 * camera_set_count as u32),
+            ],
+            camera_set_count as u32,
+        ).expect("Unable to create descriptor pool");
+
+        let camera_buffers = (0..camera_set_count)
+            .map(|_| {
+                Buffer::new(
+                    device,
+                    &ctx.memory_properties,
+                    mem::size_of::<CameraUniform>() as vk::DeviceSize,
+                    vk::BUFFER_USAGE_UNIFORM_BUFFER_BIT,
+                    vk::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vk::MEMORY_PROPERTY_HOST_COHERENT_BIT,
+                ).expect("Unable to create camera buffer")
+            }).collect::<Vec<_>>();
+
+        let light_buffers = (0..camera_set_count)
+            .map(|_| {
+                Buffer::new(
+                    device,
+                    &ctx.memory_properties,
+                    light::buffer_size(),
+                    vk::BUFFER_USAGE_STORAGE_BUFFER_BIT,
+                    vk::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vk::MEMORY_PROPERTY_HOST_COHERENT_BIT,
+                ).expect("Unable to create light buffer")
+            }).collect::<Vec<_>>();
+
+        let shadow_buffers = (0..camera_set_count)
+            .map(|_| {
+                Buffer::new(
+                    device,
+                    &ctx.memory_properties,
+                    mem::size_of::<ShadowUniform>() as vk::DeviceSize,
+                    vk::BUFFER_USAGE_UNIFORM_BUFFER_BIT,
+                    vk::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vk::MEMORY_PROPERTY_HOST_COHERENT_BIT,
+                ).expect("Unable to create shadow buffer")
+            }).collect::<Vec<_>>();
+
+        let frame_sets = descriptor_pool
+            .allocate(device, &frame_set_layout, camera_buffers.len())
+            .expect("Unable to allocate frame descriptor sets");
+
+        for (index, &set) in frame_sets.iter().enumerate() {
+            descriptor::write_uniform_buffer(device, set, 0, &camera_buffers[index]);
+            descriptor::write_storage_buffer(device, set, 1, &light_buffers[index]);
+            descriptor::write_combined_image_sampler(
+                device,
+                set,
+                2,
+                environment.view,
+                environment.sampler,
+            );
+            descriptor::write_uniform_buffer(device, set, 3, &shadow_buffers[index]);
+            descriptor::write_combined_image_sampler(
+                device,
+                set,
+                4,
+                shadow_maps.view,
+                shadow_maps.sampler,
+            );
+        }
+
+        // Start out looking at the whole scene, with the depth range scaled
+        // to its size.
+        let (target, radius) = scene_focus(&self.scene);
+        add_default_lights(&mut self.scene, &sky, target, radius);
+        if let Projection::Perspective {
+            ref mut near,
+            ref mut far,
+            ..
+        } = self.camera.projection
+        {
+            *near = radius * 0.01;
+            *far = radius * 1000.0;
+        }
+        self.camera.aspect_ratio = ctx.aspect_ratio();
+        self.camera.position = target + Vector3::new(0.0, 0.0, radius * 2.5);
+        self.previous_camera = self.camera;
+        self.controller = Box::new(OrbitController::new(target, radius * 2.5));
+
+        ctx.clear_depth = self.camera.clear_depth();
+
+        self.resources = Some(Resources {
+            renderer,
+            post,
+            frame_set_layout,
+            material_set_layout,
+            descriptor_pool,
+            camera_buffers,
+            light_buffers,
+            shadow_buffers,
+            frame_sets,
+            environment,
+            shadow_maps,
+            targets: (0..WINDOWS).map(|_| None).collect(),
+            model,
+            materials,
+        });
+    }
+
+    fn update(&mut self, ctx: &mut Context, input: &Input, time: &Time) {
+        self.previous_camera = self.camera;
+
+        if input.action_pressed("cycle_present_mode") {
+            let window = ctx.main_window_mut();
+            let policy = window.present_policy.next();
+            window.set_present_policy(policy);
+        }
+
+        if input.action_pressed("toggle_preview") {
+            self.preview = match self.preview.take() {
+                Some(preview) => {
+                    ctx.close_window(preview);
+                    None
+                }
+                None => Some(ctx.open_window(WindowConfig {
+                    title: "Preview".to_owned(),
+                    width: 480,
+                    height: 360,
+                    ..WindowConfig::default()
+                })),
+            };
+        }
+
+        if let Some(ref mut resources) = self.resources {
+            let settings = &mut resources.post.settings;
+            if input.action_pressed("cycle_tonemap") {
+                settings.tonemap.cycle();
+                match settings.tonemap.enabled {
+                    true => println!("Tonemapping: {:?}", settings.tonemap.operator),
+                    false => println!("Tonemapping: off"),
+                }
+            }
+            if input.action_pressed("toggle_bloom") {
+                settings.bloom.enabled = !settings.bloom.enabled;
+                println!("Bloom: {}", settings.bloom.enabled);
+            }
+            if input.action_pressed("toggle_fxaa") {
+                settings.fxaa.enabled = !settings.fxaa.enabled;
+                println!("FXAA: {}", settings.fxaa.enabled);
+            }
+            if input.action_pressed("toggle_vignette") {
+                settings.vignette.enabled = !settings.vignette.enabled;
+                println!("Vignette: {}", settings.vignette.enabled);
+            }
+            if input.action_pressed("toggle_gamma") {
+                settings.gamma.enabled = !settings.gamma.enabled;
+                println!("Gamma correction: {}", settings.gamma.enabled);
+            }
+        }
+
+        if input.action_pressed("toggle_camera") {
+            let flying = !self.flying;
+            self.flying = flying;
+            self.switch_controller(flying);
+        }
+
+        self.controller
+            .update(&mut self.camera, input, time.fixed_delta);
+    }
+
+    /// Draw the scene into the window's target and run the offscreen post
+    /// passes on it. The camera is drawn `alpha` of the way between its
+    /// state before and after the last update.
+    fn render_offscreen(&mut self, ctx: &Context, frame: &Frame) {
+        let window = match self.window_index(frame.target) {
+            Some(window) => window,
+            None => return,
+        };
+        let resources = match self.resources {
+            Some(ref mut resources) => resources,
+            None => return,
+        };
+
+        let device = &ctx.device;
+        let command_buffer = frame.command_buffer;
+
+        // The runner waits for the device after every frame, so a target
+        // of the wrong size is no longer in use by the time it is replaced.
+        let outdated = match resources.targets[window] {
+            Some((ref target, ref post_targets)) => {
+                target.extent().width != frame.extent.width
+                    || target.extent().height != frame.extent.height
+                    || post_targets.levels() != resources.post.settings.bloom.levels
+            }
+            None => true,
+        };
+        if outdated {
+            if let Some((target, post_targets)) = resources.targets[window].take() {
+                post_targets.destroy(device);
+                target.destroy(device);
+            }
+
+            let target = match resources.renderer {
+                SceneRenderer::Forward { pass, .. } => SceneTarget::Forward(
+                    RenderTarget::new(
+                        &ctx.instance,
+                        ctx.pdevice,
+                        device,
+                        &ctx.memory_properties,
+                        pass,
+                        frame.extent,
+                    ).unwrap_or_else(|error| {
+                        panic!("Unable to create the scene target, {}", error)
+                    }),
+                ),
+                SceneRenderer::Deferred(ref renderer) => SceneTarget::Deferred(
+                    GBuffer::new(
+                        &ctx.instance,
+                        ctx.pdevice,
+                        device,
+                        &ctx.memory_properties,
+                        renderer,
+                        frame.extent,
+                    ).unwrap_or_else(|error| panic!("Unable to create the G-buffer, {}", error)),
+                ),
+            };
+            let post_targets = PostTargets::new(
+                &ctx.instance,
+                ctx.pdevice,
+                device,
+                &ctx.memory_properties,
+                &resources.post,
+                target.view(),
+                target.sampler(),
+                frame.extent,
+            ).unwrap_or_else(|error| {
+                panic!("Unable to create the post-processing targets, {}", error)
+            });
+            resources.targets[window] = Some((target, post_targets));
+        }
+        let (ref target, ref post_targets) = *resources.targets[window].as_ref().unwrap();
+
+        // Each window writes its own uniform buffers, the first window's
+        // frame may still be in flight while the next one is recorded.
+        let camera_index = window * ctx.frames_in_flight + frame.frame_index;
+
+        let mut camera = self
+            .previous_camera
+            .interpolate(&self.camera, frame.time.alpha);
+        camera.aspect_ratio = frame.aspect_ratio();
+        resources.camera_buffers[camera_index].write(device, 0, &[camera.uniform()]);
+        light::write_lights(
+            device,
+            &resources.light_buffers[camera_index],
+            self.scene.lights(),
+            AMBIENT_INTENSITY,
+        );
+
+        if let (&Some(ref model), Some(bounds)) = (&resources.model, self.scene.bounds()) {
+            let shadows = Shadows::new(
+                &camera,
+                self.scene.lights(),
+                &bounds,
+                resources.shadow_maps.resolution,
+            );
+            resources.shadow_buffers[camera_index].write(device, 0, &[shadows.uniform]);
+
+            for view in &shadows.views {
+                let draws = self.scene.draw_list(&view.view_projection);
+                resources.shadow_maps.render(
+                    device,
+                    command_buffer,
+                    view.layer,
+                    view.perspective,
+                    view.view_projection.into(),
+                    model,
+                    &draws,
+                );
+            }
+            for layer in (0..shadow::SHADOW_LAYERS).filter(|&layer| !shadows.uses_layer(layer)) {
+                resources
+                    .shadow_maps
+                    .clear(device, command_buffer, layer);
+            }
+        }
+
+        let frame_set = resources.frame_sets[camera_index];
+        match (&resources.renderer, target) {
+            (&SceneRenderer::Forward { ref pipeline, .. }, &SceneTarget::Forward(ref target)) => {
+                target.begin(device, command_buffer, ctx.clear_color, ctx.clear_depth);
+
+                unsafe {
+                    device.cmd_bind_pipeline(
+                        command_buffer,
+                        vk::PipelineBindPoint::Graphics,
+                        pipeline.graphics_pipelines[0],
+                    );
+                }
+
+                if pipeline.has_dynamic_viewport() {
+                    frame.set_viewport(device);
+                }
+
+                match (&resources.model, &resources.materials) {
+                    (&Some(ref model), &Some(ref materials)) => {
+                        unsafe {
+                            device.cmd_bind_descriptor_sets(
+                                command_buffer,
+                                vk::PipelineBindPoint::Graphics,
+                                pipeline.layout.layout,
+                                pbr::FRAME_SET,
+                                &[frame_set],
+                                &[],
+                            );
+                        }
+
+                        let draws = self.scene.draw_list(&camera.view_projection());
+                        model.draw(device, command_buffer, &pipeline.layout, materials, &draws);
+                    }
+                    _ => unsafe { device.cmd_draw(command_buffer, 3, 1, 0, 0) },
+                }
+
+                target.end(device, command_buffer);
+            }
+            (&SceneRenderer::Deferred(ref renderer), &SceneTarget::Deferred(ref gbuffer)) => {
+                // Deferred shading is only picked with a model.
+                if let (&Some(ref model), &Some(ref materials)) =
+                    (&resources.model, &resources.materials)
+                {
+                    let draws = self.scene.draw_list(&camera.view_projection());
+                    renderer.render(
+                        device,
+                        command_buffer,
+                        gbuffer,
+                        frame_set,
+                        model,
+                        materials,
+                        &draws,
+                        ctx.clear_color,
+                        ctx.clear_depth,
+                    );
+                }
+            }
+            _ => unreachable!("scene targets are created for the renderer"),
+        }
+
+        resources
+            .post
+            .render_offscreen(device, command_buffer, post_targets);
+    }
+
+    /// Draw the result of the post-processing stack into the window.
+    fn render(&mut self, ctx: &Context, frame: &Frame) {
+        let window = match self.window_index(frame.target) {
+            Some(window) => window,
+            None => return,
+        };
+        let resources = match self.resources {
+            Some(ref resources) => resources,
+            None => return,
+        };
+
+        if let Some((_, ref post_targets)) = resources.targets[window] {
+            frame.set_viewport(&ctx.device);
+            resources
+                .post
+                .render(&ctx.device, frame.command_buffer, post_targets);
+        }
+    }
+
+    /// Forget the preview once its window has been closed.
+    fn on_event(&mut self, _ctx: &mut Context, target: TargetId, event: &WindowEvent) {
+        if let WindowEvent::CloseRequested = *event {
+            if Some(target) == self.preview {
+                self.preview = None;
+            }
+        }
+    }
+
+    fn shutdown(&mut self, ctx: &mut Context) {
+        let device = &ctx.device;
+
+        if let Some(resources) = self.resources.take() {
+            if let Some(ref materials) = resources.materials {
+                materials.destroy(device);
+            }
+            if let Some(ref model) = resources.model {
+                model.destroy(device);
+            }
+
+            resources
+                .camera_buffers
+                .iter()
+                .chain(resources.light_buffers.iter())
+                .chain(resources.shadow_buffers.iter())
+                .for_each(|buffer| buffer.destroy(device));
+            resources
+                .targets
+                .iter()
+                .flat_map(|targets| targets.iter())
+                .for_each(|&(ref target, ref post_targets)| {
+                    post_targets.destroy(device);
+                    target.destroy(device);
+                });
+            match resources.renderer {
+                SceneRenderer::Forward { pass, ref pipeline } => {
+                    pipeline.destroy(device);
+                    pass.destroy(device);
+                }
+                SceneRenderer::Deferred(ref renderer) => renderer.destroy(device),
+            }
+            resources.descriptor_pool.destroy(device);
+            resources.environment.destroy(device);
+            resources.shadow_maps.destroy(device);
+            resources.frame_set_layout.destroy(device);
+            resources.material_set_layout.destroy(device);
+            resources.post.destroy(device);
+        }
+    }
+}
+
+/// The center of the scene and the radius of a sphere around it.
+fn scene_focus(scene: &Scene) -> (Point3<f32>, f32) {
+    match scene.bounds() {
+        Some(bounds) => (
+            bounds.center(),
+            bounds.half_extents().magnitude().max(0.01),
+        ),
+        None => (Point3::new(0.0, 0.0, 0.0), 1.0),
+    }
+}
+
+/// A sun matching the sky, and a point and a spot light near the scene,
+/// bright enough for its size.
+fn add_default_lights(scene: &mut Scene, sky: &SkyDesc, target: Point3<f32>, radius: f32) {
+    let sun = Vector3::from(sky.sun_direction);
+    scene.add_light(Light::directional(-sun, [1.0, 0.95, 0.85], 3.0).with_shadows());
+
+    let intensity = 4.0 * radius * radius;
+    scene.add_light(
+        Light::point(
+            target + Vector3::new(-radius, radius, radius) * 0.8,
+            [1.0, 0.6, 0.3],
+            intensity,
+        ).with_range(radius * 4.0),
+    );
+    scene.add_light(
+        Light::spot(
+            target + Vector3::new(radius, radius * 1.5, 0.0),
+            Vector3::new(-1.0, -1.5, 0.0),
+            Rad(0.3),
+            Rad(0.5),
+            [0.4, 0.6, 1.0],
+            intensity * 2.0,
+        ).with_range(radius * 6.0)
+        .with_shadows(),
+    );
+}
+
+fn create_triangle_pipeline(ctx: &Context, render_pass: RenderPass) -> Pipeline {
+    let device = &ctx.device;
+
+    let vert_shader = Shader::load(
+        device,
+        "assets/shaders/vert.spv",
+        pipeline::ShaderType::Vertex,
+    );
+    let frag_shader = Shader::load(
+        device,
+        "assets/shaders/frag.spv",
+        pipeline::ShaderType::Fragment,
+    );
+
+    let pipeline_layout = pipeline::PipelineLayout::empty(device);
+
+    // Viewport and scissor are set while recording, so the pipeline
+    // survives swapchain recreation.
+    Pipeline::build()
+        .with_shader_stage(vert_shader)
+        .with_shader_stage(frag_shader)
+        .with_vertex_input_state(pipeline::VertexInput::empty())
+        .with_input_assembly_state(pipeline::InputAssembly::triangle_list())
+        .with_dynamic_state(pipeline::DynamicState::viewport_and_scissor())
+        .with_rasterizer(pipeline::Rasterizer::fill())
+        .with_multisample(pipeline::Multisample::new(ctx.samples))
+        .with_color_blend(pipeline::ColorBlend::opaque())
+        .with_depth_stencil(pipeline::DepthStencil::disabled())
+        .with_layout(pipeline_layout)
+        .with_render_pass(render_pass)
+        .create(device)
+        .unwrap()
+}
+
+fn create_mesh_pipeline(
+    ctx: &Context,
+    render_pass: RenderPass,
+    frame_set_layout: &DescriptorSetLayout,
+    material_set_layout: &DescriptorSetLayout,
+    camera: &Camera,
+) -> Pipeline {
+    let device = &ctx.device;
+
+    let vert_shader = Shader::load(
+        device,
+        "assets/shaders/mesh.vert.spv",
+        pipeline::ShaderType::Vertex,
+    );
+    let frag_shader = Shader::load(
+        device,
+        "assets/shaders/mesh.frag.spv",
+        pipeline::ShaderType::Fragment,
+    );
+
+    let pipeline_layout = pipeline::PipelineLayout::build()
+        .with_descriptor_set_layout(frame_set_layout)
+        .with_descriptor_set_layout(material_set_layout)
+        .with_push_constants::<MeshPushConstants>(vk::SHADER_STAGE_VERTEX_BIT)
+        .create(device, &ctx.device_properties.limits)
+        .expect("Unable to create mesh pipeline layout");
+
+    let depth_stencil = if camera.reversed_z {
+        pipeline::DepthStencil::greater()
+    } else {
+        pipeline::DepthStencil::less()
+    };
+
+    // Materials may be double sided, so draw both faces until pipelines
+    // are picked per material.
+    Pipeline::build()
+        .with_shader_stage(vert_shader)
+        .with_shader_stage(frag_shader)
+        .with_vertex_input_state(mesh::Vertex::vertex_input())
+        .with_input_assembly_state(pipeline::InputAssembly::triangle_list())
+        .with_dynamic_state(pipeline::DynamicState::viewport_and_scissor())
+        .with_rasterizer(pipeline::Rasterizer::no_cull())
+        .with_multisample(pipeline::Multisample::new(ctx.samples))
+        .with_color_blend(pipeline::ColorBlend::opaque())
+        .with_depth_stencil(depth_stencil)
+        .with_layout(pipeline_layout)
+        .with_render_pass(render_pass)
+        .create(device)
+        .unwrap()
+}
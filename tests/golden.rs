@@ -0,0 +1,61 @@
+//! Runs the golden image comparison of the post-processing stack.
+//!
+//! It needs a Vulkan driver and the images in `assets/golden`, written with
+//! `cargo run --bin golden -- --update`, so it only runs when asked for:
+//!
+//! ```text
+//! cargo test --test golden -- --ignored
+//! ```
+//!
+//! The images are rendered with lavapipe, so the comparison is run with a
+//! software driver as well. Its ICD manifest is looked up in the usual
+//! places, `GOLDEN_ICD` points at another one, like SwiftShader's
+//! `vk_swiftshader_icd.json`. An explicit `VK_ICD_FILENAMES` is left alone.
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Where distributions install the ICD manifests of the Vulkan loader.
+const ICD_DIRS: &[&str] = &[
+    "/usr/share/vulkan/icd.d",
+    "/usr/local/share/vulkan/icd.d",
+    "/etc/vulkan/icd.d",
+];
+
+/// File name prefixes of the manifests of software drivers.
+const SOFTWARE_ICDS: &[&str] = &["lvp_icd", "vk_swiftshader_icd"];
+
+fn software_icd() -> Option<PathBuf> {
+    if let Some(icd) = env::var_os("GOLDEN_ICD") {
+        return Some(PathBuf::from(icd));
+    }
+
+    ICD_DIRS
+        .iter()
+        .filter_map(|dir| Path::new(dir).read_dir().ok())
+        .flat_map(|entries| entries.filter_map(|entry| entry.ok()))
+        .map(|entry| entry.path())
+        .find(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            SOFTWARE_ICDS.iter().any(|prefix| name.starts_with(prefix))
+        })
+}
+
+#[test]
+#[ignore]
+fn post_processing_matches_golden_images() {
+    let mut command = Command::new(env!("CARGO"));
+    command.args(["run", "--bin", "golden"]);
+
+    if env::var_os("VK_ICD_FILENAMES").is_none() {
+        match software_icd() {
+            Some(icd) => {
+                command.env("VK_ICD_FILENAMES", icd);
+            }
+            None => eprintln!("No software Vulkan driver found, using the installed drivers"),
+        }
+    }
+
+    let status = command.status().expect("Unable to run the golden binary");
+    assert!(status.success(), "golden images differ, see target/golden");
+}